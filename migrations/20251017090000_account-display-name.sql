@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE "account"
+    ADD COLUMN IF NOT EXISTS display_name TEXT
+    CONSTRAINT account_display_name_length CHECK (char_length(display_name) BETWEEN 1 AND 64);
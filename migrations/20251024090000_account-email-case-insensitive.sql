@@ -0,0 +1,2 @@
+-- Emails are unique regardless of their case, rows imported with mixed-case emails included
+CREATE UNIQUE INDEX IF NOT EXISTS account_email_lower_key ON "account" (lower(email));
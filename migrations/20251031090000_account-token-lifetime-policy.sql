@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE "account"
+    ADD COLUMN IF NOT EXISTS max_token_lifetime_secs INTEGER
+    CONSTRAINT account_max_token_lifetime_secs_positive CHECK (max_token_lifetime_secs > 0);
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE "account_verification_ticket"
+    ADD COLUMN IF NOT EXISTS failed_attempts INTEGER NOT NULL DEFAULT 0;
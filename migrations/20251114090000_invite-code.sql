@@ -0,0 +1,14 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS "invite_code" (
+    id              UUID        NOT NULL    PRIMARY KEY DEFAULT uuid_generate_v4 (),
+    code            TEXT        NOT NULL    UNIQUE,
+    used_by         UUID,
+    used_at         TIMESTAMPTZ,
+    created_at      TIMESTAMPTZ NOT NULL    DEFAULT CURRENT_TIMESTAMP,
+    updated_at      TIMESTAMPTZ NOT NULL    DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TRIGGER update_invite_code_moddatetime
+BEFORE UPDATE ON "invite_code"
+FOR EACH ROW
+EXECUTE FUNCTION moddatetime('updated_at');
@@ -0,0 +1,19 @@
+-- Active access tokens of an account have distinct names, the name of a revoked access token can be reused
+-- Duplicated names of active access tokens are suffixed with the beginning of the ID, except the oldest one
+UPDATE "access_token"
+SET "name" = left("name", 31) || '-' || left("id"::text, 8)
+WHERE "id" IN (
+    SELECT "id"
+    FROM (
+        SELECT
+            "id",
+            row_number() OVER (PARTITION BY "account_id", "name" ORDER BY "created_at", "id") AS "rank"
+        FROM "access_token"
+        WHERE "revoked_at" IS NULL
+    ) AS "ranked_access_token"
+    WHERE "rank" > 1
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS "access_token_account_id_name_active_key"
+ON "access_token" ("account_id", "name")
+WHERE "revoked_at" IS NULL;
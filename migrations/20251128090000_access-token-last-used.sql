@@ -0,0 +1,7 @@
+-- Recording the use of an access token is not an update, `updated_at` only follows the other columns
+DROP TRIGGER IF EXISTS update_token_moddatetime ON "access_token";
+
+CREATE TRIGGER update_token_moddatetime
+BEFORE UPDATE OF "account_id", "name", "mac", "created_at", "expires_at", "revoked_at" ON "access_token"
+FOR EACH ROW
+EXECUTE FUNCTION moddatetime("updated_at");
@@ -0,0 +1,3 @@
+-- Keyed hash of the lowercase email, set instead of a plaintext email if the emails are protected, see `EMAIL_PROTECTION_KEY`
+ALTER TABLE "account" ADD COLUMN IF NOT EXISTS "email_lookup" TEXT;
+CREATE UNIQUE INDEX IF NOT EXISTS account_email_lookup_key ON "account" ("email_lookup");
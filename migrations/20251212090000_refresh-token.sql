@@ -0,0 +1,28 @@
+-- Date of the password authentication an access token derives from, it differs from the creation date for the access tokens issued by a refresh
+ALTER TABLE "access_token" ADD COLUMN IF NOT EXISTS "authenticated_at" TIMESTAMPTZ;
+UPDATE "access_token" SET "authenticated_at" = "created_at" WHERE "authenticated_at" IS NULL;
+ALTER TABLE "access_token"
+    ALTER COLUMN "authenticated_at" SET DEFAULT CURRENT_TIMESTAMP,
+    ALTER COLUMN "authenticated_at" SET NOT NULL;
+
+-- Refresh tokens of a family descend from the same login, each refresh rotates the refresh token of the family
+CREATE TABLE IF NOT EXISTS "refresh_token" (
+    id                  UUID            NOT NULL    PRIMARY KEY DEFAULT uuid_generate_v4 (),
+    account_id          UUID            NOT NULL,
+    family_id           UUID            NOT NULL,
+    access_token_id     UUID            NOT NULL,
+    mac                 bytea           NOT NULL    UNIQUE CHECK (length(mac) = 32),
+    authenticated_at    TIMESTAMPTZ     NOT NULL,
+    created_at          TIMESTAMPTZ     NOT NULL    DEFAULT CURRENT_TIMESTAMP,
+    updated_at          TIMESTAMPTZ     NOT NULL    DEFAULT CURRENT_TIMESTAMP,
+    expires_at          TIMESTAMPTZ     NOT NULL,
+    rotated_at          TIMESTAMPTZ,
+    revoked_at          TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS "refresh_token_family_id_idx" ON "refresh_token" ("family_id");
+
+CREATE TRIGGER update_refresh_token_moddatetime
+BEFORE UPDATE ON "refresh_token"
+FOR EACH ROW
+EXECUTE FUNCTION moddatetime("updated_at");
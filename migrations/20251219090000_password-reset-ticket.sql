@@ -0,0 +1,18 @@
+CREATE TYPE password_reset_ticket_status AS ENUM ('active', 'cancelled', 'confirmed');
+
+-- An account has at most one active password reset ticket, a new request cancels the previous ticket and a reset confirms it
+CREATE TABLE IF NOT EXISTS "password_reset_ticket" (
+    id              UUID                            NOT NULL    PRIMARY KEY DEFAULT uuid_generate_v4 (),
+    account_id      UUID                            NOT NULL,
+    cyphertext      TEXT                            NOT NULL,
+    status          password_reset_ticket_status    NOT NULL    DEFAULT 'active',
+    created_at      TIMESTAMPTZ                     NOT NULL    DEFAULT CURRENT_TIMESTAMP,
+    updated_at      TIMESTAMPTZ                     NOT NULL    DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS "password_reset_ticket_account_id_idx" ON "password_reset_ticket" ("account_id");
+
+CREATE TRIGGER update_password_reset_ticket_moddatetime
+BEFORE UPDATE ON "password_reset_ticket"
+FOR EACH ROW
+EXECUTE FUNCTION moddatetime("updated_at");
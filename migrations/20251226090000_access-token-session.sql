@@ -0,0 +1,3 @@
+-- Access tokens issued by a login or a refresh are sessions, they do not count towards the limit of active access tokens of the account
+ALTER TABLE "access_token" ADD COLUMN IF NOT EXISTS "session" BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE "access_token" SET "session" = TRUE WHERE "id" IN (SELECT "access_token_id" FROM "refresh_token");
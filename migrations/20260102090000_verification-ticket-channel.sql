@@ -0,0 +1,6 @@
+CREATE TYPE verification_channel AS ENUM ('email', 'sms');
+
+-- Channel through which the secret of a verification ticket is sent, a resend goes through the same channel
+ALTER TABLE "account_verification_ticket" ADD COLUMN IF NOT EXISTS "channel" verification_channel NOT NULL DEFAULT 'email';
+-- Phone number to which the secret is sent, only set for the SMS channel
+ALTER TABLE "account_verification_ticket" ADD COLUMN IF NOT EXISTS "phone_number" TEXT;
@@ -0,0 +1,4 @@
+-- Accounts locked until this date can not authenticate, the lock is lifted once the date is passed
+ALTER TABLE "account" ADD COLUMN IF NOT EXISTS "locked_until" TIMESTAMPTZ;
+-- Deactivated accounts can not authenticate anymore
+ALTER TABLE "account" ADD COLUMN IF NOT EXISTS "deactivated_at" TIMESTAMPTZ;
@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::routes::accounts::AccountRepository;
+
+/// Interval between two cleanups of the stale verification tickets
+pub const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Default maximum number of rows deleted by a single statement of the cleanup, see `CLEANUP_BATCH_SIZE`
+pub const DEFAULT_CLEANUP_BATCH_SIZE: u32 = 1000;
+
+/// Delete the stale verification tickets, i.e. unconfirmed and expired, batch after batch until none is left
+///
+/// Returns the number of deleted tickets. Confirmed tickets are kept, see [AccountRepository::get_last_confirmed_verification_ticket].
+///
+/// # Arguments
+/// * `account_repository` - repository deleting the batches,
+/// * `stale_before` - unconfirmed tickets created before this date are stale,
+/// * `batch_size` - maximum number of tickets deleted by a single statement
+pub async fn cleanup_verification_tickets(
+    account_repository: &impl AccountRepository,
+    stale_before: DateTime<Utc>,
+    batch_size: u32,
+) -> Result<u64, anyhow::Error> {
+    let mut deleted = 0;
+    loop {
+        let batch_deleted = account_repository
+            .delete_stale_verification_tickets(stale_before, batch_size)
+            .await?;
+        deleted += batch_deleted;
+        if batch_deleted < u64::from(batch_size) {
+            return Ok(deleted);
+        }
+    }
+}
+
+/// Spawn a task deleting the stale verification tickets at every interval, the first cleanup is run immediately
+///
+/// # Arguments
+/// * `account_repository` - repository deleting the tickets,
+/// * `interval` - interval between two cleanups,
+/// * `ticket_retention` - age after which an unconfirmed ticket is stale, i.e. the ticket lifetime and the clock skew tolerance, see [crate::Config::verification_ticket_lifetime],
+/// * `batch_size` - maximum number of tickets deleted by a single statement
+pub fn spawn_cleanup(
+    account_repository: impl AccountRepository + 'static,
+    interval: Duration,
+    ticket_retention: Duration,
+    batch_size: u32,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            // A retention beyond the representable dates never expires a ticket
+            let Some(stale_before) = TimeDelta::from_std(ticket_retention)
+                .ok()
+                .and_then(|retention| Utc::now().checked_sub_signed(retention))
+            else {
+                continue;
+            };
+            match cleanup_verification_tickets(&account_repository, stale_before, batch_size).await
+            {
+                Ok(0) => {}
+                Ok(deleted) => info!("deleted {deleted} stale verification tickets"),
+                Err(e) => warn!("cleanup of the stale verification tickets failed: {e:?}"),
+            }
+        }
+    })
+}
@@ -0,0 +1,115 @@
+use std::{sync::RwLock, time::Duration};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+/// Default tolerance for the clock skew between the clients and the server, see `CLOCK_SKEW_TOLERANCE_SECS`
+pub const DEFAULT_CLOCK_SKEW_TOLERANCE: Duration = Duration::from_secs(30);
+
+/// Source of the current date of the time-based behaviors, e.g. the expiration of the verification tickets
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Clock running with the system clock but which can be advanced, only meant for tests, see `TEST_CLOCK`
+#[derive(Debug, Default)]
+pub struct TestClock {
+    offset: RwLock<TimeDelta>,
+}
+
+impl TestClock {
+    /// Advance the clock, the clock can not go back in time
+    ///
+    /// # Arguments
+    /// * `delta` - duration by which the clock is advanced, negative durations are ignored
+    pub fn advance(&self, delta: TimeDelta) -> DateTime<Utc> {
+        let mut offset = match self.offset.write() {
+            Ok(v) => v,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *offset += delta.max(TimeDelta::zero());
+        Utc::now() + *offset
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> DateTime<Utc> {
+        let offset = match self.offset.read() {
+            Ok(v) => *v,
+            Err(poisoned) => *poisoned.into_inner(),
+        };
+        Utc::now() + offset
+    }
+}
+
+/// True once the current date reaches the expiration date extended by the clock skew tolerance
+///
+/// Every expiration, e.g. of an access token or of a verification ticket, is checked with this helper so that
+/// a client whose clock lags behind the server is not rejected moments before the expiration it expects.
+///
+/// # Arguments
+/// * `expires_at` - expiration date,
+/// * `now` - current date, see [Clock],
+/// * `clock_skew_tolerance` - grace period after the expiration date, zero disables it
+pub fn is_expired(
+    expires_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+    clock_skew_tolerance: Duration,
+) -> bool {
+    TimeDelta::from_std(clock_skew_tolerance)
+        .ok()
+        .and_then(|tolerance| expires_at.checked_add_signed(tolerance))
+        .is_some_and(|deadline| now >= deadline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_test_clock_only_advances() {
+        let clock = TestClock::default();
+
+        let advanced = clock.advance(TimeDelta::minutes(16));
+        assert!(advanced >= Utc::now() + TimeDelta::minutes(15));
+        assert!(clock.now() >= advanced);
+
+        clock.advance(TimeDelta::minutes(-30));
+        assert!(clock.now() >= advanced);
+    }
+
+    #[test]
+    fn test_is_expired_at_the_boundary() {
+        let expires_at = Utc::now();
+
+        // Without tolerance, the expiration date is the deadline
+        assert!(!is_expired(
+            expires_at,
+            expires_at - TimeDelta::milliseconds(1),
+            Duration::ZERO
+        ));
+        assert!(is_expired(expires_at, expires_at, Duration::ZERO));
+
+        // With tolerance, the deadline is pushed back by the tolerance
+        let tolerance = Duration::from_secs(30);
+        assert!(!is_expired(expires_at, expires_at, tolerance));
+        assert!(!is_expired(
+            expires_at,
+            expires_at + TimeDelta::seconds(30) - TimeDelta::milliseconds(1),
+            tolerance
+        ));
+        assert!(is_expired(
+            expires_at,
+            expires_at + TimeDelta::seconds(30),
+            tolerance
+        ));
+    }
+}
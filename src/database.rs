@@ -0,0 +1,377 @@
+use std::{str::FromStr, time::Duration};
+
+use anyhow::anyhow;
+use sqlx::{
+    Executor, Pool, Postgres,
+    error::ErrorKind,
+    migrate::MigrateError,
+    postgres::{PgConnectOptions, PgPoolOptions},
+};
+use thiserror::Error;
+
+use super::{Config, MigrationConfig};
+
+/// Maximum number of connections of the database pool
+pub const DATABASE_MAX_CONNECTIONS: u32 = 5;
+/// Timeout when acquiring a connection from the database pool
+pub const DATABASE_ACQUIRE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Options of the database pool derived from the configuration
+///
+/// Every connection of the pool is set up with a `statement_timeout` so that a runaway query can not hold a connection indefinitely,
+/// and with the `UTC` time zone so that timestamps defaulted or formatted by Postgres do not depend on the server locale.
+pub fn pool_options(config: &Config) -> PgPoolOptions {
+    let statement_timeout_ms = config.database_statement_timeout.as_millis();
+    PgPoolOptions::new()
+        .max_connections(config.database_max_connections)
+        .acquire_timeout(config.database_acquire_timeout)
+        .after_connect(move |conn, _meta| {
+            Box::pin(async move {
+                conn.execute(format!("SET statement_timeout = {statement_timeout_ms}").as_str())
+                    .await?;
+                conn.execute("SET TIME ZONE 'UTC'").await?;
+                Ok(())
+            })
+        })
+}
+
+/// Name of the application reported to Postgres, e.g. `soko@0.1.0/instance-1`, visible in `pg_stat_activity`
+pub fn application_name(config: &Config) -> String {
+    format!("soko@{}/{}", env!("CARGO_PKG_VERSION"), config.instance_id)
+}
+
+/// Options of the database connections derived from the configuration
+pub fn connect_options(config: &Config) -> Result<PgConnectOptions, sqlx::Error> {
+    Ok(
+        PgConnectOptions::from_str(config.database_url.extract_inner())?
+            .application_name(&application_name(config)),
+    )
+}
+
+/// Options of the connections of `soko migrate`, see [MigrationConfig]
+pub fn migration_connect_options(
+    config: &MigrationConfig,
+) -> Result<PgConnectOptions, sqlx::Error> {
+    Ok(
+        PgConnectOptions::from_str(config.database_url.extract_inner())?
+            .application_name(&format!("soko@{}/migrate", env!("CARGO_PKG_VERSION"))),
+    )
+}
+
+/// Errors of the database setup at startup, each one exits the process with its own code, see [StartupError::exit_code]
+#[derive(Error, Debug)]
+pub enum StartupError {
+    #[error("failed to connect to the database: {0}")]
+    Connection(#[source] sqlx::Error),
+    #[error(
+        "database migrations are in a dirty state, migration {version} is partially applied and must be fixed manually"
+    )]
+    DirtyMigration { version: i64 },
+    #[error("failed to run database migrations: {0}")]
+    Migration(#[source] MigrateError),
+}
+
+impl StartupError {
+    /// Exit code of the process, `1` is left to the other startup errors, e.g. an invalid configuration
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            StartupError::Connection(_) => 2,
+            StartupError::Migration(_) => 3,
+            StartupError::DirtyMigration { .. } => 4,
+        }
+    }
+}
+
+/// Run the pending migrations of `./migrations`, see [classify_migrate_error] for the errors
+pub async fn run_migrations(pool: &Pool<Postgres>) -> Result<(), StartupError> {
+    sqlx::migrate!("./migrations")
+        .run(pool)
+        .await
+        .map_err(classify_migrate_error)
+}
+
+/// Classify a [MigrateError] into a [StartupError]
+///
+/// Migrations failing because the database is unreachable are classified as connection errors.
+pub fn classify_migrate_error(e: MigrateError) -> StartupError {
+    match e {
+        MigrateError::Dirty(version) => StartupError::DirtyMigration { version },
+        MigrateError::Execute(e) if is_connection_error(&e) => StartupError::Connection(e),
+        MigrateError::ExecuteMigration(e, _) if is_connection_error(&e) => {
+            StartupError::Connection(e)
+        }
+        e => StartupError::Migration(e),
+    }
+}
+
+fn is_connection_error(e: &sqlx::Error) -> bool {
+    matches!(
+        e,
+        sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+    )
+}
+
+/// Transaction shared by several repository operations, see [begin_transaction] and [commit_transaction]
+///
+/// Repository methods suffixed with `_in_transaction` run within the given transaction, they neither commit it nor roll it back.
+pub type DatabaseTransaction = sqlx::Transaction<'static, Postgres>;
+
+/// Begin a transaction, it is rolled back when dropped without being committed
+pub async fn begin_transaction(
+    pool: &Pool<Postgres>,
+) -> Result<DatabaseTransaction, RepositoryError> {
+    pool.begin()
+        .await
+        .map_err(|e| map_sqlx_error("failed to start transaction", e))
+}
+
+/// Commit a transaction
+pub async fn commit_transaction(transaction: DatabaseTransaction) -> Result<(), RepositoryError> {
+    transaction
+        .commit()
+        .await
+        .map_err(|e| map_sqlx_error("failed to commit transaction", e))
+}
+
+/// Errors of the database adapters, classified from [sqlx::Error] by [map_sqlx_error]
+///
+/// Repositories convert them into the errors of their domain.
+#[derive(Error, Debug)]
+pub enum RepositoryError {
+    #[error("{op}: row not found")]
+    NotFound { op: String },
+    #[error("{op}: unique constraint violation on {constraint:?}")]
+    UniqueViolation {
+        op: String,
+        constraint: Option<String>,
+    },
+    #[error("{op}: check constraint violation on {constraint:?}")]
+    CheckViolation {
+        op: String,
+        constraint: String,
+        field: &'static str,
+    },
+    #[error("{op}: timed out while acquiring a connection")]
+    PoolTimeout { op: String },
+    #[error(transparent)]
+    Unknown(#[from] anyhow::Error),
+}
+
+/// Check constraints of the database mirroring a validation of the application, with the field of the request they validate
+///
+/// Violations of other check constraints are classified as unknown errors.
+const CHECK_CONSTRAINT_FIELDS: [(&str, &str); 1] = [("account_display_name_length", "displayName")];
+
+/// Classify a [sqlx::Error] into a [RepositoryError]
+///
+/// # Arguments
+/// * `op` - description of the failed operation, used as context
+/// * `e` - error returned by sqlx
+pub fn map_sqlx_error(op: &str, e: sqlx::Error) -> RepositoryError {
+    let op = op.to_string();
+    match e {
+        sqlx::Error::RowNotFound => RepositoryError::NotFound { op },
+        sqlx::Error::PoolTimedOut => RepositoryError::PoolTimeout { op },
+        sqlx::Error::Database(ref database_error)
+            if database_error.kind() == ErrorKind::UniqueViolation =>
+        {
+            RepositoryError::UniqueViolation {
+                op,
+                constraint: database_error.constraint().map(|v| v.to_string()),
+            }
+        }
+        sqlx::Error::Database(ref database_error)
+            if database_error.kind() == ErrorKind::CheckViolation =>
+        {
+            let known_constraint = database_error.constraint().and_then(|constraint| {
+                CHECK_CONSTRAINT_FIELDS
+                    .iter()
+                    .find(|(name, _)| *name == constraint)
+            });
+            match known_constraint {
+                Some((constraint, field)) => RepositoryError::CheckViolation {
+                    op,
+                    constraint: constraint.to_string(),
+                    field,
+                },
+                None => RepositoryError::Unknown(anyhow!(e).context(op)),
+            }
+        }
+        e => RepositoryError::Unknown(anyhow!(e).context(op)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, error::Error as StdError, fmt::Display};
+
+    use sqlx::error::DatabaseError;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct FakeDatabaseError {
+        code: &'static str,
+        constraint: Option<&'static str>,
+    }
+
+    impl Display for FakeDatabaseError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "fake database error {}", self.code)
+        }
+    }
+
+    impl StdError for FakeDatabaseError {}
+
+    impl DatabaseError for FakeDatabaseError {
+        fn message(&self) -> &str {
+            "fake database error"
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some(Cow::Borrowed(self.code))
+        }
+
+        fn constraint(&self) -> Option<&str> {
+            self.constraint
+        }
+
+        fn as_error(&self) -> &(dyn StdError + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn StdError + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn StdError + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> ErrorKind {
+            match self.code {
+                "23505" => ErrorKind::UniqueViolation,
+                "23514" => ErrorKind::CheckViolation,
+                _ => ErrorKind::Other,
+            }
+        }
+    }
+
+    #[test]
+    fn test_map_row_not_found() {
+        let err = map_sqlx_error("select account", sqlx::Error::RowNotFound);
+        assert!(matches!(err, RepositoryError::NotFound { op } if op == "select account"));
+    }
+
+    #[test]
+    fn test_map_pool_timeout() {
+        let err = map_sqlx_error("select account", sqlx::Error::PoolTimedOut);
+        assert!(matches!(err, RepositoryError::PoolTimeout { .. }));
+    }
+
+    #[test]
+    fn test_map_unique_violation() {
+        let err = map_sqlx_error(
+            "insert account",
+            sqlx::Error::Database(Box::new(FakeDatabaseError {
+                code: "23505",
+                constraint: Some("account_email_key"),
+            })),
+        );
+        assert!(matches!(
+            err,
+            RepositoryError::UniqueViolation { constraint: Some(constraint), .. } if constraint == "account_email_key"
+        ));
+    }
+
+    #[test]
+    fn test_map_check_violation() {
+        let err = map_sqlx_error(
+            "update account",
+            sqlx::Error::Database(Box::new(FakeDatabaseError {
+                code: "23514",
+                constraint: Some("account_display_name_length"),
+            })),
+        );
+        assert!(matches!(
+            err,
+            RepositoryError::CheckViolation { constraint, field: "displayName", .. } if constraint == "account_display_name_length"
+        ));
+    }
+
+    #[test]
+    fn test_map_other_database_error() {
+        let err = map_sqlx_error(
+            "insert account",
+            sqlx::Error::Database(Box::new(FakeDatabaseError {
+                code: "23514",
+                constraint: Some("access_token_mac_check"),
+            })),
+        );
+        match err {
+            RepositoryError::Unknown(e) => assert_eq!(e.to_string(), "insert account"),
+            _ => panic!("Invalid error, expected `Unknown` variant, got {err}"),
+        }
+    }
+
+    #[test]
+    fn test_map_unknown_error() {
+        let err = map_sqlx_error("insert account", sqlx::Error::PoolClosed);
+        assert!(matches!(err, RepositoryError::Unknown(_)));
+    }
+
+    #[test]
+    fn test_classify_dirty_migration() {
+        let err = classify_migrate_error(MigrateError::Dirty(20251114090000));
+        assert!(matches!(
+            err,
+            StartupError::DirtyMigration {
+                version: 20251114090000
+            }
+        ));
+    }
+
+    #[test]
+    fn test_classify_unreachable_database_as_connection_error() {
+        let err = classify_migrate_error(MigrateError::Execute(sqlx::Error::PoolTimedOut));
+        assert!(matches!(err, StartupError::Connection(_)));
+
+        let err = classify_migrate_error(MigrateError::ExecuteMigration(
+            sqlx::Error::Io(std::io::ErrorKind::ConnectionRefused.into()),
+            20251114090000,
+        ));
+        assert!(matches!(err, StartupError::Connection(_)));
+    }
+
+    #[test]
+    fn test_classify_migration_error() {
+        let err = classify_migrate_error(MigrateError::VersionMismatch(20251114090000));
+        assert!(matches!(err, StartupError::Migration(_)));
+
+        let err = classify_migrate_error(MigrateError::ExecuteMigration(
+            sqlx::Error::Database(Box::new(FakeDatabaseError {
+                code: "42P07",
+                constraint: None,
+            })),
+            20251114090000,
+        ));
+        assert!(matches!(err, StartupError::Migration(_)));
+    }
+
+    #[test]
+    fn test_startup_errors_have_distinct_exit_codes() {
+        let exit_codes = [
+            StartupError::Connection(sqlx::Error::PoolTimedOut).exit_code(),
+            StartupError::Migration(MigrateError::VersionMissing(1)).exit_code(),
+            StartupError::DirtyMigration { version: 1 }.exit_code(),
+        ];
+        for (i, exit_code) in exit_codes.iter().enumerate() {
+            assert_ne!(*exit_code, 0);
+            assert_ne!(*exit_code, 1);
+            assert!(!exit_codes[i + 1..].contains(exit_code));
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::{sync::Arc, time::Duration};
+
+use thiserror::Error;
+use tokio::sync::Semaphore;
+
+/// Default maximum number of concurrent Argon2 operations, each one allocates 19 MiB with the default Argon2 parameters
+pub const DEFAULT_MAX_CONCURRENT_HASHES: usize = 8;
+/// Maximum duration an Argon2 operation waits for the previous ones before the request is rejected
+pub const HASHING_QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Bound on the number of concurrent Argon2 operations, e.g. password hashing or verification secret derivation
+///
+/// A burst of signups would otherwise multiply the memory cost of Argon2 until the process runs out of memory.
+/// Operations beyond the bound are queued, they are rejected if they can not start within the queue timeout.
+#[derive(Clone, Debug)]
+pub struct HashingLimiter {
+    semaphore: Arc<Semaphore>,
+    queue_timeout: Duration,
+}
+
+/// Errors that may occur while running a hashing operation
+#[derive(Error, Debug)]
+pub enum HashingError {
+    #[error("too many concurrent hashing operations, the operation could not start in time")]
+    Saturated,
+    #[error(transparent)]
+    Unknown(#[from] anyhow::Error),
+}
+
+impl HashingLimiter {
+    /// # Arguments
+    /// * `max_concurrent_hashes` - maximum number of operations running at the same time,
+    /// * `queue_timeout` - maximum duration an operation waits for a slot
+    pub fn new(max_concurrent_hashes: usize, queue_timeout: Duration) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(max_concurrent_hashes)),
+            queue_timeout,
+        }
+    }
+
+    /// True if no slot is available, further operations are queued
+    pub fn is_saturated(&self) -> bool {
+        self.semaphore.available_permits() == 0
+    }
+
+    /// Run a hashing operation on the blocking thread pool once a slot is available
+    ///
+    /// # Arguments
+    /// * `operation` - CPU and memory heavy operation, e.g. a domain request derivation hashing a password
+    ///
+    /// # Errors
+    /// * `HashingError::Saturated` - no slot became available within the queue timeout
+    /// * `HashingError::Unknown` - the operation panicked
+    pub async fn run<T, F>(&self, operation: F) -> Result<T, HashingError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit =
+            tokio::time::timeout(self.queue_timeout, self.semaphore.clone().acquire_owned())
+                .await
+                .map_err(|_| HashingError::Saturated)?
+                .map_err(|e| anyhow::anyhow!(e).context("hashing semaphore closed"))?;
+
+        tokio::task::spawn_blocking(move || {
+            let output = operation();
+            drop(permit);
+            output
+        })
+        .await
+        .map_err(|e| {
+            anyhow::anyhow!(e)
+                .context("hashing operation failed")
+                .into()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_hashing_operations_are_serialized_with_a_limit_of_one() {
+        let limiter = HashingLimiter::new(1, Duration::from_secs(5));
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+
+        let operations: Vec<_> = (0..4)
+            .map(|_| {
+                let limiter = limiter.clone();
+                let running = running.clone();
+                let max_running = max_running.clone();
+                tokio::spawn(async move {
+                    limiter
+                        .run(move || {
+                            let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                            max_running.fetch_max(now_running, Ordering::SeqCst);
+                            std::thread::sleep(Duration::from_millis(50));
+                            running.fetch_sub(1, Ordering::SeqCst);
+                        })
+                        .await
+                })
+            })
+            .collect();
+        for operation in operations {
+            operation.await.unwrap().unwrap();
+        }
+
+        assert_eq!(max_running.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_saturated_hashing_operations_are_rejected() {
+        let limiter = HashingLimiter::new(1, Duration::from_millis(10));
+
+        let busy_limiter = limiter.clone();
+        let busy = tokio::spawn(async move {
+            busy_limiter
+                .run(|| std::thread::sleep(Duration::from_millis(200)))
+                .await
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let result = limiter.run(|| ()).await;
+        assert!(matches!(result, Err(HashingError::Saturated)));
+
+        busy.await.unwrap().unwrap();
+        limiter.run(|| ()).await.unwrap();
+    }
+}
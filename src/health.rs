@@ -0,0 +1,195 @@
+use std::{
+    future::Future,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::database::map_sqlx_error;
+
+/// Interval between two health checks of the database
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// Maximum duration of the probe of a subsystem, a slower subsystem is reported down
+pub const SUBSYSTEM_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Readiness of the application to serve requests, shared between the health checks and the readiness route
+///
+/// The application is not ready until the first successful health check.
+#[derive(Clone, Debug, Default)]
+pub struct Readiness(Arc<AtomicBool>);
+
+impl Readiness {
+    pub fn is_ready(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn set(&self, ready: bool) {
+        let was_ready = self.0.swap(ready, Ordering::Relaxed);
+        match (was_ready, ready) {
+            (false, true) => info!("application is ready"),
+            (true, false) => warn!("application is not ready anymore"),
+            _ => {}
+        }
+    }
+}
+
+#[async_trait]
+pub trait HealthRepository: Send + Sync {
+    /// Check that the database is reachable
+    ///
+    /// # Errors
+    /// * `anyhow::Error` - the database can not be queried
+    async fn ping(&self) -> Result<(), anyhow::Error>;
+}
+
+pub struct PostgresHealthRepository {
+    pool: Pool<Postgres>,
+}
+
+impl From<Pool<Postgres>> for PostgresHealthRepository {
+    fn from(value: Pool<Postgres>) -> Self {
+        PostgresHealthRepository { pool: value }
+    }
+}
+
+#[async_trait]
+impl HealthRepository for PostgresHealthRepository {
+    async fn ping(&self) -> Result<(), anyhow::Error> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| map_sqlx_error("failed to ping database", e))?;
+        Ok(())
+    }
+}
+
+/// Run a single health check and update the readiness accordingly
+pub async fn check_health(health_repository: &impl HealthRepository, readiness: &Readiness) {
+    match health_repository.ping().await {
+        Ok(()) => readiness.set(true),
+        Err(e) => {
+            warn!("health check failed: {e:?}");
+            readiness.set(false);
+        }
+    }
+}
+
+/// Spawn a task running a health check at every interval, the first check is run immediately
+///
+/// # Arguments
+/// * `health_repository` - repository used to check the database,
+/// * `readiness` - readiness updated with the result of every check,
+/// * `interval` - interval between two checks
+pub fn spawn_health_checks(
+    health_repository: impl HealthRepository + 'static,
+    readiness: Readiness,
+    interval: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            check_health(&health_repository, &readiness).await;
+        }
+    })
+}
+
+/// Health of a subsystem of the application, e.g. the database or the mailing provider
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubsystemHealth {
+    pub ok: bool,
+    /// The application is unhealthy if a required subsystem is down
+    pub required: bool,
+}
+
+/// Probe a subsystem, the error of a failed probe is logged but not exposed
+///
+/// # Arguments
+/// * `name` - name of the subsystem, used in the logs,
+/// * `required` - whether the application is unhealthy if the subsystem is down,
+/// * `probe` - check of the subsystem, it fails if it does not complete within [SUBSYSTEM_PROBE_TIMEOUT]
+pub async fn probe_subsystem(
+    name: &str,
+    required: bool,
+    probe: impl Future<Output = Result<(), anyhow::Error>>,
+) -> SubsystemHealth {
+    let result = match tokio::time::timeout(SUBSYSTEM_PROBE_TIMEOUT, probe).await {
+        Ok(result) => result,
+        Err(_) => Err(anyhow::anyhow!(
+            "probe timed out after {SUBSYSTEM_PROBE_TIMEOUT:?}"
+        )),
+    };
+    if let Err(e) = &result {
+        warn!("subsystem {name} is down: {e:?}");
+    }
+    SubsystemHealth {
+        ok: result.is_ok(),
+        required,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeHealthRepository {
+        healthy: AtomicBool,
+    }
+
+    #[async_trait]
+    impl HealthRepository for FakeHealthRepository {
+        async fn ping(&self) -> Result<(), anyhow::Error> {
+            if self.healthy.load(Ordering::Relaxed) {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!("database unreachable"))
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_readiness_follows_health_checks() {
+        let readiness = Readiness::default();
+        let health_repository = FakeHealthRepository {
+            healthy: AtomicBool::new(false),
+        };
+        assert!(!readiness.is_ready());
+
+        check_health(&health_repository, &readiness).await;
+        assert!(!readiness.is_ready());
+
+        health_repository.healthy.store(true, Ordering::Relaxed);
+        check_health(&health_repository, &readiness).await;
+        assert!(readiness.is_ready());
+
+        health_repository.healthy.store(false, Ordering::Relaxed);
+        check_health(&health_repository, &readiness).await;
+        assert!(!readiness.is_ready());
+
+        health_repository.healthy.store(true, Ordering::Relaxed);
+        check_health(&health_repository, &readiness).await;
+        assert!(readiness.is_ready());
+    }
+
+    #[tokio::test]
+    async fn test_probe_subsystem() {
+        let health = probe_subsystem("database", true, async { Ok(()) }).await;
+        assert!(health.ok);
+        assert!(health.required);
+
+        let health = probe_subsystem("mailing", false, async {
+            Err(anyhow::anyhow!("mailing provider unreachable"))
+        })
+        .await;
+        assert!(!health.ok);
+        assert!(!health.required);
+    }
+}
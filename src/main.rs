@@ -1,38 +1,81 @@
 use std::time::Duration;
 
-use axum::{
-    body::Body,
-    extract::{MatchedPath, Request},
-    http::{HeaderName, Response},
-};
+use axum::{body::Body, extract::Request, http::Response};
 use dotenvy::dotenv;
 use soko::{
-    Config,
+    Config, MigrationConfig,
+    cleanup::{CLEANUP_INTERVAL, spawn_cleanup},
+    database::{
+        StartupError, connect_options, migration_connect_options, pool_options, run_migrations,
+    },
+    health::{HEALTH_CHECK_INTERVAL, PostgresHealthRepository, Readiness, spawn_health_checks},
+    observability::{
+        LogFormat, fmt_layer, install_prometheus_recorder, log_response, otlp_tracer_provider,
+        request_id_layers, request_span,
+    },
     routes::{
-        accounts::PostgresAccountRepository, app_router, tokens::PostgresAccessTokenRepository,
+        accounts::{EmailProtection, PostgresAccountRepository},
+        app_router,
+        tokens::PostgresAccessTokenRepository,
+    },
+    server::{load_rustls_config, serve},
+    third_party::{
+        HttpWebhookNotifier, MailingService, SmtpMailingService, ToBeImplementedMailingService,
+        ToBeImplementedSmsService,
     },
-    third_party::ToBeImplementedMailingService,
 };
 use sqlx::postgres::PgPoolOptions;
 use tokio::signal;
-use tower_http::{
-    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
-    timeout::TimeoutLayer,
-    trace::TraceLayer,
-};
-use tracing::{Span, error, info, info_span, level_filters::LevelFilter};
+use tower_http::{timeout::TimeoutLayer, trace::TraceLayer};
+use tracing::{Span, error, info, level_filters::LevelFilter, warn};
 use tracing_subscriber::{Layer, layer::SubscriberExt, util::SubscriberInitExt};
 
-const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Command run by the binary
+enum Command {
+    /// `soko`, serve the routes, the migrations are run first unless `SKIP_MIGRATIONS` is enabled
+    Serve,
+    /// `soko migrate`, only run the migrations and exit, e.g. as a separate step of a deployment
+    Migrate,
+    /// `soko protect-emails`, encrypt the emails stored in plaintext with `EMAIL_PROTECTION_KEY` and exit, see [EmailProtection]
+    ProtectEmails,
+}
+
+/// Maximum number of emails protected by a single transaction of `soko protect-emails`
+const EMAIL_PROTECTION_BATCH_SIZE: u32 = 1000;
+
+impl Command {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, anyhow::Error> {
+        let args: Vec<String> = args.collect();
+        match args
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            [] => Ok(Self::Serve),
+            ["migrate"] | ["--migrate"] => Ok(Self::Migrate),
+            ["protect-emails"] => Ok(Self::ProtectEmails),
+            _ => Err(anyhow::anyhow!(
+                "Unexpected arguments {args:?}, usage: soko [migrate|protect-emails]"
+            )),
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
+    let command = Command::parse(std::env::args().skip(1))?;
+
     if let Err(err) = dotenv()
         && !err.not_found()
     {
         return Err(anyhow::anyhow!("Error while loading .env file: {err}"));
     }
 
+    if let Command::Migrate = command {
+        return migrate().await;
+    }
+
     let config = match Config::parse_environment() {
         Ok(c) => c,
         Err(e) => {
@@ -42,92 +85,195 @@ async fn main() -> Result<(), anyhow::Error> {
         }
     };
 
+    // The spans are exported only if a collector is configured, the registry is otherwise the same
+    let tracer_provider = config
+        .otel_exporter_otlp_endpoint
+        .as_deref()
+        .map(|endpoint| otlp_tracer_provider(endpoint, &config.instance_id))
+        .transpose()
+        .map_err(|e| anyhow::anyhow!("Failed to build the OTLP exporter: {e}"))?;
     tracing_subscriber::registry()
         .with(
-            tracing_subscriber::fmt::layer()
+            fmt_layer(config.log_format, std::io::stdout)
                 .with_filter(Into::<LevelFilter>::into(config.log_level)),
         )
+        .with(tracer_provider.as_ref().map(|tracer_provider| {
+            tracer_provider
+                .layer()
+                .with_filter(Into::<LevelFilter>::into(config.log_level))
+        }))
         .init();
 
-    let pool = match PgPoolOptions::new()
-        .max_connections(5)
-        .acquire_timeout(Duration::from_secs(5))
-        .connect(config.database_url.extract_inner())
-        .await
-    {
-        Ok(c) => c,
+    info!("Starting with configuration: {config:?}");
+    if let Some(endpoint) = &config.otel_exporter_otlp_endpoint {
+        info!("Spans are exported to the OTLP collector {endpoint}");
+    }
+    if !config.verification_autoverify_domains.is_empty() {
+        warn!(
+            "Email verification is bypassed for the domains {:?}, this must not be enabled in production",
+            config.verification_autoverify_domains
+        );
+    }
+    if config.log_error_bodies && !cfg!(debug_assertions) {
+        warn!("Logging of error response bodies is only available in debug builds, it is ignored");
+    }
+    if config.test_clock {
+        warn!(
+            "The clock can be advanced through the admin routes, this must not be enabled in production"
+        );
+    }
+    if config.dev_return_verification_secret {
+        warn!(
+            "Verification secrets are returned in signup responses, this must not be enabled in production"
+        );
+    }
+
+    let connect_options = match connect_options(&config) {
+        Ok(v) => v,
         Err(e) => {
-            let err = format!("Failed to establish connection to database {e}");
+            let err = format!("Invalid database URL {e}");
             error!(err);
             return Err(anyhow::anyhow!(err));
         }
     };
 
-    if let Err(e) = sqlx::migrate!("./migrations").run(&pool).await {
-        let err = format!("Failed to run database migrations: {e}");
-        error!(err);
-        return Err(anyhow::anyhow!(err));
+    let pool = match pool_options(&config).connect_with(connect_options).await {
+        Ok(c) => c,
+        Err(e) => exit_on_startup_error(StartupError::Connection(e)),
     };
 
-    info!("Successfully ran migrations");
+    if config.skip_migrations {
+        warn!(
+            "Database migrations are skipped, they must be run separately, e.g. with `soko migrate`"
+        );
+    } else {
+        if let Err(e) = run_migrations(&pool).await {
+            exit_on_startup_error(e);
+        }
+        info!("Successfully ran migrations");
+    }
 
-    let x_request_id = HeaderName::from_static(REQUEST_ID_HEADER);
+    let account_repository = PostgresAccountRepository::from(pool.clone()).with_email_protection(
+        config
+            .email_protection_key
+            .as_ref()
+            .map(EmailProtection::new),
+    );
 
-    let account_repository = PostgresAccountRepository::from(pool.clone());
-    let access_token_repository = PostgresAccessTokenRepository::from(pool);
-    let mailing_service = ToBeImplementedMailingService;
+    if let Command::ProtectEmails = command {
+        if config.email_protection_key.is_none() {
+            return Err(anyhow::anyhow!(
+                "EMAIL_PROTECTION_KEY must be set to protect the emails"
+            ));
+        }
+        let protected = account_repository
+            .protect_plaintext_emails(EMAIL_PROTECTION_BATCH_SIZE)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to protect the plaintext emails: {e:?}"))?;
+        info!("Successfully protected {protected} plaintext emails");
+        return Ok(());
+    }
+
+    // The accounts created before the protection could neither be found nor kept unique by their email
+    if config.email_protection_key.is_some() {
+        let plaintext_emails = account_repository.count_plaintext_emails().await?;
+        if plaintext_emails > 0 {
+            return Err(anyhow::anyhow!(
+                "{plaintext_emails} accounts store their email in plaintext, protect them with `soko protect-emails` before serving with EMAIL_PROTECTION_KEY"
+            ));
+        }
+    }
+    let access_token_repository = PostgresAccessTokenRepository::from(pool.clone());
+    let mailing_service: Box<dyn MailingService> = match &config.smtp {
+        Some(smtp) => {
+            info!(
+                "Emails are sent through the SMTP relay {}:{}",
+                smtp.host, smtp.port
+            );
+            Box::new(SmtpMailingService::try_from(smtp)?)
+        }
+        None => Box::new(ToBeImplementedMailingService),
+    };
+    let sms_service = ToBeImplementedSmsService;
+    let webhook_notifier = HttpWebhookNotifier::from(config.verification_webhook.clone());
+
+    // Without a recorder, the metrics are discarded
+    let metrics_handle = if config.metrics_enabled {
+        let handle = install_prometheus_recorder()?;
+        info!("Metrics are exported to the admin on `GET /metrics`");
+        if config.admin_api_key.is_none() {
+            warn!("No admin API key is configured, `GET /metrics` rejects every request");
+        }
+        Some(handle)
+    } else {
+        None
+    };
+
+    let readiness = Readiness::default();
+    spawn_health_checks(
+        PostgresHealthRepository::from(pool.clone()),
+        readiness.clone(),
+        HEALTH_CHECK_INTERVAL,
+    );
+
+    spawn_cleanup(
+        PostgresAccountRepository::from(pool.clone()),
+        CLEANUP_INTERVAL,
+        config
+            .verification_ticket_lifetime
+            .saturating_add(config.clock_skew_tolerance),
+        config.cleanup_batch_size,
+    );
 
     let app = app_router(
         &config,
         account_repository,
         access_token_repository,
         mailing_service,
+        sms_service,
+        webhook_notifier,
+        PostgresHealthRepository::from(pool),
+        readiness,
+        metrics_handle,
     )
-    .layer((
-        // Set `x-request-id` header for every request
-        SetRequestIdLayer::new(x_request_id.clone(), MakeRequestUuid),
-        // Log request and response
-        TraceLayer::new_for_http()
-            .make_span_with(|request: &Request<_>| {
-                let matched_path = request
-                    .extensions()
-                    .get::<MatchedPath>()
-                    .map(MatchedPath::as_str);
-
-                let request_id = request.headers().get(REQUEST_ID_HEADER);
-
-                match request_id {
-                    Some(v) => info_span!(
-                        "http_request",
-                        method = ?request.method(),
-                        matched_path,
-                        request_id = ?v
-                    ),
-                    None => {
-                        error!("Failed to extract `request_id` header");
-                        info_span!(
-                            "http_request",
-                            method = ?request.method(),
-                            matched_path,
+    // Set the request ID header for every request and propagate it to the response, see `REQUEST_ID_HEADER`
+    .layer(request_id_layers(
+        config.request_id_header.as_ref(),
+        (
+            // Log request and response
+            TraceLayer::new_for_http()
+                .make_span_with({
+                    let request_id_header = config.request_id_header.clone();
+                    let logged_headers = config.log_headers.clone();
+                    let redacted_headers = config.log_redacted_headers.clone();
+                    move |request: &Request<_>| {
+                        request_span(
+                            request,
+                            request_id_header.as_ref(),
+                            &logged_headers,
+                            &redacted_headers,
                         )
                     }
-                }
-            })
-            .on_response(
-                |response: &Response<Body>, latency: Duration, _span: &Span| {
-                    if response.status().is_server_error() {
-                        error!("response: {} {latency:?}", response.status())
-                    } else {
-                        info!("response: {} {latency:?}", response.status())
-                    }
-                },
-            ),
-        // Timeout requests at 10 seconds
-        TimeoutLayer::new(Duration::from_secs(10)),
-        // Propagate the `x-request-id` header to responses
-        PropagateRequestIdLayer::new(x_request_id),
+                })
+                .on_response(
+                    |response: &Response<Body>, latency: Duration, _span: &Span| {
+                        log_response(response, latency)
+                    },
+                ),
+            // Timeout requests, 10 seconds by default
+            TimeoutLayer::new(config.request_timeout),
+        ),
     ));
 
+    // An invalid certificate or private key fails the startup before anything is served
+    let tls = match config.tls.as_ref().map(load_rustls_config).transpose() {
+        Ok(v) => v,
+        Err(e) => {
+            error!("{e:?}");
+            return Err(e);
+        }
+    };
+
     let addr = format!("0.0.0.0:{}", config.port);
     let listener = tokio::net::TcpListener::bind(&addr).await.map_err(|err| {
         let err = format!("Error while binding the TCP listener to address {addr}: {err}");
@@ -136,22 +282,73 @@ async fn main() -> Result<(), anyhow::Error> {
         anyhow::anyhow!(err)
     })?;
 
-    info!("Successfully bind the TCP listener to address {addr}\n");
+    let scheme = if tls.is_some() { "HTTPS" } else { "HTTP" };
+    info!("Successfully bind the TCP listener to address {addr}, serving {scheme}\n");
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
+    let served = serve(listener, app, tls, shutdown_signal())
         .await
         .map_err(|err| {
             let err = format!("Error while serving the routes: {err}");
             error!(err);
             anyhow::anyhow!(err)
-        })?;
+        });
+
+    // The in-flight requests have been drained, the spans of the last batch are flushed before exiting
+    if let Some(tracer_provider) = tracer_provider {
+        match tokio::task::spawn_blocking(move || tracer_provider.shutdown()).await {
+            Ok(Ok(())) => info!("Flushed the spans to the OTLP collector"),
+            Ok(Err(e)) => warn!("Failed to flush the spans to the OTLP collector: {e}"),
+            Err(e) => warn!("Failed to flush the spans to the OTLP collector: {e}"),
+        }
+    }
+    served?;
 
     info!("App has been gracefully shutdown");
 
     Ok(())
 }
 
+/// `soko migrate`, only `DATABASE_URL` is parsed so that the migrations do not require the rest of the configuration, see [MigrationConfig]
+async fn migrate() -> Result<(), anyhow::Error> {
+    let config = MigrationConfig::parse_environment().map_err(|e| {
+        anyhow::anyhow!("Failed to parse environment variables for configuration: {e}")
+    })?;
+
+    tracing_subscriber::registry()
+        .with(fmt_layer(LogFormat::Pretty, std::io::stdout).with_filter(LevelFilter::INFO))
+        .init();
+
+    let connect_options = match migration_connect_options(&config) {
+        Ok(v) => v,
+        Err(e) => {
+            let err = format!("Invalid database URL {e}");
+            error!(err);
+            return Err(anyhow::anyhow!(err));
+        }
+    };
+    // A single connection runs the migrations
+    let pool = match PgPoolOptions::new()
+        .max_connections(1)
+        .connect_with(connect_options)
+        .await
+    {
+        Ok(c) => c,
+        Err(e) => exit_on_startup_error(StartupError::Connection(e)),
+    };
+
+    if let Err(e) = run_migrations(&pool).await {
+        exit_on_startup_error(e);
+    }
+    info!("Successfully ran migrations");
+    Ok(())
+}
+
+/// Log a database startup error and exit with its code, see [StartupError::exit_code]
+fn exit_on_startup_error(e: StartupError) -> ! {
+    error!("{e}");
+    std::process::exit(e.exit_code());
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
@@ -3,20 +3,24 @@ use serde::{Deserialize, Serialize, de::Visitor};
 use sqlx::{Database, Decode, Encode};
 use std::fmt::Debug;
 use validator::ValidateEmail;
+use zeroize::Zeroize;
 
 // #######################################################
 // #################### OPAQUE STRING ####################
 // #######################################################
 
+/// Secret value, redacted from the logs and overwritten with zeros when dropped
+///
+/// Every clone owns its own copy of the secret, dropping one clone leaves the others untouched.
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct Opaque<T>(T)
 where
-    T: Clone + Serialize;
+    T: Clone + Serialize + Zeroize;
 
 impl<T> Opaque<T>
 where
-    T: Clone + Serialize,
+    T: Clone + Serialize + Zeroize,
 {
     pub fn new(v: T) -> Self {
         Self(v)
@@ -31,7 +35,7 @@ where
 
 impl<T> std::fmt::Display for Opaque<T>
 where
-    T: Clone + Serialize,
+    T: Clone + Serialize + Zeroize,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "*********")
@@ -40,13 +44,22 @@ where
 
 impl<T> Debug for Opaque<T>
 where
-    T: Clone + Serialize,
+    T: Clone + Serialize + Zeroize,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "*********")
     }
 }
 
+impl<T> Drop for Opaque<T>
+where
+    T: Clone + Serialize + Zeroize,
+{
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
 // ###############################################
 // #################### EMAIL ####################
 // ###############################################
@@ -115,6 +128,22 @@ impl Email {
     pub fn as_str(&self) -> &str {
         &self.0
     }
+
+    /// Local part of the email address, i.e. the part before the last `@`
+    pub fn local_part(&self) -> &str {
+        self.0
+            .rsplit_once('@')
+            .map(|(local_part, _)| local_part)
+            .unwrap_or(&self.0)
+    }
+
+    /// Domain of the email address, i.e. the part after the last `@`
+    pub fn domain(&self) -> &str {
+        self.0
+            .rsplit_once('@')
+            .map(|(_, domain)| domain)
+            .unwrap_or_default()
+    }
 }
 
 impl Serialize for Email {
@@ -218,3 +247,18 @@ impl std::fmt::Display for Email {
         write!(f, "{}", self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opaque_clone_outlives_the_original() {
+        let secret = Opaque::new("super-secret".to_string());
+        let cloned = secret.clone();
+        drop(secret);
+
+        assert_eq!(cloned.extract_inner(), "super-secret");
+        assert_eq!(format!("{cloned:?}"), "*********");
+    }
+}
@@ -0,0 +1,678 @@
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{MatchedPath, Request},
+    http::{HeaderMap, HeaderName},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use metrics::{Label, counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use opentelemetry::{KeyValue, trace::TracerProvider};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    Resource,
+    error::OTelSdkResult,
+    trace::{SdkTracerProvider, SpanData},
+};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tower::{
+    layer::util::Identity,
+    util::{Either, option_layer},
+};
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tracing::{Span, error, info, info_span, warn};
+use tracing_subscriber::{Layer, fmt::MakeWriter, registry::LookupSpan};
+
+/// Counter of signups, labelled by `outcome`
+pub const SIGNUP_COUNTER: &str = "soko_signups_total";
+/// Counter of email verifications, labelled by `outcome`
+pub const VERIFY_EMAIL_COUNTER: &str = "soko_verifications_total";
+/// Counter of access token creations, labelled by `outcome`
+pub const TOKEN_CREATION_COUNTER: &str = "soko_tokens_created_total";
+/// Counter of invalid passwords presented for an existing account, a spike is a sign of brute force
+pub const FAILED_PASSWORD_COUNTER: &str = "soko_failed_password_total";
+/// Counter of the emails sent through the mailing service, labelled by `outcome`
+pub const EMAIL_SEND_COUNTER: &str = "soko_email_send_total";
+/// Histogram of the duration in seconds of the emails sent through the mailing service, labelled by `outcome`
+pub const EMAIL_SEND_DURATION_HISTOGRAM: &str = "soko_email_send_duration_seconds";
+
+/// Counter of the HTTP requests, labelled by `method`, `path` and `status`
+pub const HTTP_REQUESTS_COUNTER: &str = "soko_http_requests_total";
+/// Gauge of the HTTP requests being served, labelled by `method` and `path`
+pub const HTTP_REQUESTS_IN_FLIGHT_GAUGE: &str = "soko_http_requests_in_flight";
+/// Histogram of the duration in seconds of the HTTP requests, labelled by `method`, `path` and `status`
+pub const HTTP_REQUEST_DURATION_HISTOGRAM: &str = "soko_http_request_duration_seconds";
+
+/// Buckets in seconds of the duration histograms exported to Prometheus, the hash-heavy requests take hundreds of milliseconds
+const DURATION_BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+/// Interval between two drains of the histograms of the Prometheus recorder
+const PROMETHEUS_UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+pub const OUTCOME_LABEL: &str = "outcome";
+pub const METHOD_LABEL: &str = "method";
+pub const PATH_LABEL: &str = "path";
+pub const STATUS_LABEL: &str = "status";
+pub const SUCCESS_OUTCOME: &str = "success";
+pub const FAILURE_OUTCOME: &str = "failure";
+
+/// Increment the counter `name` with the outcome of the result
+pub fn record_outcome<T, E>(name: &'static str, result: &Result<T, E>) {
+    counter!(name, OUTCOME_LABEL => outcome(result)).increment(1);
+}
+
+/// Record the duration of an operation in the histogram `name` with the outcome of its result
+pub fn record_duration<T, E>(name: &'static str, result: &Result<T, E>, duration: Duration) {
+    histogram!(name, OUTCOME_LABEL => outcome(result)).record(duration.as_secs_f64());
+}
+
+fn outcome<T, E>(result: &Result<T, E>) -> &'static str {
+    if result.is_ok() {
+        SUCCESS_OUTCOME
+    } else {
+        FAILURE_OUTCOME
+    }
+}
+
+/// Install the Prometheus recorder as the global recorder of the metrics, the returned handle renders them
+///
+/// The histograms are drained by a task spawned on the current runtime.
+///
+/// # Errors
+/// * `anyhow::Error` - a global recorder is already installed
+pub fn install_prometheus_recorder() -> Result<PrometheusHandle, anyhow::Error> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), &DURATION_BUCKETS)?
+        .install_recorder()?;
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            let mut interval = tokio::time::interval(PROMETHEUS_UPKEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                handle.run_upkeep();
+            }
+        }
+    });
+    Ok(handle)
+}
+
+/// Decrements the in-flight gauge of a request once it is dropped, including when the request is cancelled, e.g. on timeout
+struct InFlightGuard(metrics::Gauge);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.decrement(1.0);
+    }
+}
+
+/// Middleware recording the count, the in-flight number and the duration of the requests, keyed by their matched path
+///
+/// The requests matching no route are not recorded, their paths are unbounded.
+pub async fn track_http_metrics(request: Request, next: Next) -> Response {
+    let Some(path) = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|v| v.as_str().to_string())
+    else {
+        return next.run(request).await;
+    };
+    let mut labels = vec![
+        Label::new(METHOD_LABEL, request.method().to_string()),
+        Label::new(PATH_LABEL, path),
+    ];
+
+    let in_flight = gauge!(HTTP_REQUESTS_IN_FLIGHT_GAUGE, labels.clone());
+    in_flight.increment(1.0);
+    let _guard = InFlightGuard(in_flight);
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+    labels.push(Label::new(
+        STATUS_LABEL,
+        response.status().as_u16().to_string(),
+    ));
+    counter!(HTTP_REQUESTS_COUNTER, labels.clone()).increment(1);
+    histogram!(HTTP_REQUEST_DURATION_HISTOGRAM, labels).record(start.elapsed().as_secs_f64());
+    response
+}
+
+/// Format of the logs, see [fmt_layer]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Pretty,
+    /// One JSON object per line, for the log aggregators
+    Json,
+}
+
+/// Layer writing the logs in the given format
+///
+/// In JSON, every line carries the fields of the current span, e.g. the `method`, `matched_path` and `request_id` of the request, see [request_span].
+///
+/// # Arguments
+/// * `format` - format of the logs,
+/// * `writer` - destination of the logs, e.g. `std::io::stdout`
+pub fn fmt_layer<S, W>(format: LogFormat, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    match format {
+        LogFormat::Pretty => tracing_subscriber::fmt::layer().with_writer(writer).boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_writer(writer)
+            .boxed(),
+    }
+}
+
+/// Name of the service reported with the exported spans
+const OTEL_SERVICE_NAME: &str = "soko";
+
+/// Provider of the tracer exporting the spans to an OTLP/HTTP collector, the spans are exported in batches
+///
+/// The provider must be shut down before the process exits, the spans of the last batch are lost otherwise.
+///
+/// # Arguments
+/// * `endpoint` - base URL of the collector, the spans are sent to `<endpoint>/v1/traces`,
+/// * `instance_id` - identifier of the running instance, reported as `service.instance.id`
+///
+/// # Errors
+/// * `anyhow::Error` - the exporter can not be built, e.g. the endpoint is not a valid URL
+pub fn otlp_tracer_provider(
+    endpoint: &str,
+    instance_id: &str,
+) -> Result<OtlpTracerProvider, anyhow::Error> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{endpoint}/v1/traces"))
+        .build()?;
+    let failed_spans = Arc::new(AtomicU64::new(0));
+    let resource = Resource::builder()
+        .with_service_name(OTEL_SERVICE_NAME)
+        .with_attribute(KeyValue::new(
+            "service.instance.id",
+            instance_id.to_string(),
+        ))
+        .build();
+    Ok(OtlpTracerProvider {
+        tracer_provider: SdkTracerProvider::builder()
+            .with_batch_exporter(CountingSpanExporter {
+                inner: exporter,
+                failed_spans: failed_spans.clone(),
+            })
+            .with_resource(resource)
+            .build(),
+        failed_spans,
+    })
+}
+
+/// Tracer provider of [otlp_tracer_provider], it counts the spans whose export failed, e.g. while the collector was unreachable
+#[derive(Debug)]
+pub struct OtlpTracerProvider {
+    tracer_provider: SdkTracerProvider,
+    failed_spans: Arc<AtomicU64>,
+}
+
+impl OtlpTracerProvider {
+    /// Layer exporting the spans to the collector, see [otlp_layer]
+    pub fn layer<S>(
+        &self,
+    ) -> tracing_opentelemetry::OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>
+    where
+        S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+    {
+        otlp_layer(&self.tracer_provider)
+    }
+
+    /// Export the spans of the last batch and shut the exporter down, it blocks until the export is done
+    ///
+    /// # Errors
+    /// * `anyhow::Error` - the last batch or a previous batch could not be exported
+    pub fn shutdown(&self) -> Result<(), anyhow::Error> {
+        let shutdown = self.tracer_provider.shutdown();
+        let failed_spans = self.failed_spans.load(Ordering::Relaxed);
+        match shutdown {
+            Err(e) => Err(anyhow::anyhow!(
+                "{e}, {failed_spans} spans could not be exported"
+            )),
+            Ok(()) if failed_spans > 0 => Err(anyhow::anyhow!(
+                "{failed_spans} spans could not be exported"
+            )),
+            Ok(()) => Ok(()),
+        }
+    }
+}
+
+/// Exporter counting the spans of the batches whose export failed
+#[derive(Debug)]
+struct CountingSpanExporter<E> {
+    inner: E,
+    failed_spans: Arc<AtomicU64>,
+}
+
+impl<E: opentelemetry_sdk::trace::SpanExporter> opentelemetry_sdk::trace::SpanExporter
+    for CountingSpanExporter<E>
+{
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let batch_size = batch.len() as u64;
+        let result = self.inner.export(batch).await;
+        if result.is_err() {
+            self.failed_spans.fetch_add(batch_size, Ordering::Relaxed);
+        }
+        result
+    }
+
+    fn shutdown_with_timeout(&mut self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn force_flush(&mut self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+/// Layer exporting the spans through the tracer provider, the fields of the spans are exported as attributes
+pub fn otlp_layer<S>(
+    tracer_provider: &SdkTracerProvider,
+) -> tracing_opentelemetry::OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer(OTEL_SERVICE_NAME))
+}
+
+/// Default header carrying the ID of a request, it is set if absent, see `REQUEST_ID_HEADER`
+pub const DEFAULT_REQUEST_ID_HEADER: &str = "x-request-id";
+/// Headers recorded in the request spans unless `LOG_HEADERS` is set, the spans may leave the host, see [otlp_layer]
+pub const DEFAULT_LOGGED_HEADERS: [&str; 3] = ["accept", "content-length", "content-type"];
+/// Headers whose values are always redacted from the logs, more headers can be added with `LOG_REDACTED_HEADERS`
+pub const DEFAULT_REDACTED_HEADERS: [&str; 3] = ["authorization", "cookie", "x-api-key"];
+
+/// Headers of a request as they are logged, only the logged headers are kept and the values of the redacted headers are replaced
+///
+/// # Arguments
+/// * `headers` - headers of the request,
+/// * `logged_headers` - lowercase names of the headers which are logged, the other headers are left out,
+/// * `redacted_headers` - lowercase names of the headers whose values are redacted, in addition to [DEFAULT_REDACTED_HEADERS]
+pub fn redact_headers(
+    headers: &HeaderMap,
+    logged_headers: &[String],
+    redacted_headers: &[String],
+) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter(|(name, _)| logged_headers.iter().any(|v| v == name.as_str()))
+        .map(|(name, value)| {
+            let redacted = DEFAULT_REDACTED_HEADERS.contains(&name.as_str())
+                || redacted_headers.iter().any(|v| v == name.as_str());
+            let value = if redacted {
+                "******".to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+/// Layers setting the request ID header of the requests without one and propagating it to the responses
+///
+/// The inner layers, e.g. the trace layer, see the request ID set by the outer layer. Both layers are skipped if request IDs are disabled.
+///
+/// # Arguments
+/// * `request_id_header` - header carrying the request ID, `None` disables request IDs,
+/// * `inner` - layers wrapped by the request ID layers
+pub fn request_id_layers<L>(
+    request_id_header: Option<&HeaderName>,
+    inner: L,
+) -> (
+    Either<SetRequestIdLayer<MakeRequestUuid>, Identity>,
+    L,
+    Either<PropagateRequestIdLayer, Identity>,
+) {
+    (
+        option_layer(
+            request_id_header.map(|header| SetRequestIdLayer::new(header.clone(), MakeRequestUuid)),
+        ),
+        inner,
+        option_layer(request_id_header.map(|header| PropagateRequestIdLayer::new(header.clone()))),
+    )
+}
+
+/// Span of an HTTP request, to be used by the `make_span_with` of the trace layer
+///
+/// Only the logged headers are recorded, with the values of the redacted headers replaced, see [redact_headers]. The request ID is recorded as a plain string,
+/// it is an attribute of the span once exported, see [otlp_layer].
+///
+/// # Arguments
+/// * `request` - HTTP request,
+/// * `request_id_header` - header carrying the request ID, `None` if request IDs are disabled,
+/// * `logged_headers` - lowercase names of the headers which are recorded,
+/// * `redacted_headers` - lowercase names of the headers whose values are redacted
+pub fn request_span<B>(
+    request: &Request<B>,
+    request_id_header: Option<&HeaderName>,
+    logged_headers: &[String],
+    redacted_headers: &[String],
+) -> Span {
+    let matched_path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str);
+    let headers = redact_headers(request.headers(), logged_headers, redacted_headers);
+
+    let Some(request_id_header) = request_id_header else {
+        return info_span!(
+            "http_request",
+            method = ?request.method(),
+            matched_path,
+            headers = ?headers,
+        );
+    };
+    match request.headers().get(request_id_header) {
+        Some(v) => info_span!(
+            "http_request",
+            method = ?request.method(),
+            matched_path,
+            request_id = %String::from_utf8_lossy(v.as_bytes()),
+            headers = ?headers,
+        ),
+        None => {
+            error!("Failed to extract `{request_id_header}` header");
+            info_span!(
+                "http_request",
+                method = ?request.method(),
+                matched_path,
+                headers = ?headers,
+            )
+        }
+    }
+}
+
+/// Maximum size of a logged response body, larger bodies are not buffered
+const MAX_LOGGED_BODY_BYTES: u64 = 4096;
+/// JSON fields whose values are redacted from the logged response bodies, matched exactly on the field name
+const REDACTED_BODY_FIELDS: [&str; 8] = [
+    "password",
+    "currentPassword",
+    "newPassword",
+    "secret",
+    "verificationSecret",
+    "token",
+    "accessToken",
+    "refreshToken",
+];
+
+/// Body of an error response, buffered by [buffer_error_body] to be logged by [log_response]
+#[derive(Clone)]
+pub struct ErrorBody(Bytes);
+
+/// Middleware buffering the bodies of the error responses in an [ErrorBody] extension, for the trace layer to log them, see [log_response]
+///
+/// Only the bodies of a known size up to [MAX_LOGGED_BODY_BYTES] are buffered.
+pub async fn buffer_error_body(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let status = response.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return response;
+    }
+    let body_size = response.body().size_hint().exact();
+    if body_size.is_none_or(|size| size > MAX_LOGGED_BODY_BYTES) {
+        warn!("{status} response body of unknown or too large size {body_size:?} is not logged");
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, MAX_LOGGED_BODY_BYTES as usize).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("failed to buffer {status} response body: {e}");
+            return (status, "Internal server error").into_response();
+        }
+    };
+    parts.extensions.insert(ErrorBody(bytes.clone()));
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Log a response, to be used by the `on_response` of the trace layer
+///
+/// The buffered body of an error response is logged with the values of its secret JSON fields redacted, see [buffer_error_body].
+///
+/// # Arguments
+/// * `response` - HTTP response,
+/// * `latency` - duration of the request
+pub fn log_response<B>(response: &Response<B>, latency: Duration) {
+    let status = response.status();
+    if status.is_server_error() {
+        error!("response: {status} {latency:?}")
+    } else {
+        info!("response: {status} {latency:?}")
+    }
+
+    let Some(ErrorBody(bytes)) = response.extensions().get::<ErrorBody>() else {
+        return;
+    };
+    let logged_body = match serde_json::from_slice::<serde_json::Value>(bytes) {
+        Ok(mut v) => {
+            redact_body_fields(&mut v);
+            v.to_string()
+        }
+        Err(_) => String::from_utf8_lossy(bytes).into_owned(),
+    };
+    warn!("{status} response body: {logged_body}");
+}
+
+fn redact_body_fields(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (field, v) in map.iter_mut() {
+                if REDACTED_BODY_FIELDS.contains(&field.as_str()) {
+                    *v = serde_json::Value::String("******".to_string());
+                } else {
+                    redact_body_fields(v);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact_body_fields),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::header::{AUTHORIZATION, CONTENT_TYPE, COOKIE, USER_AGENT};
+    use opentelemetry_sdk::trace::InMemorySpanExporter;
+    use tracing::info;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+    use crate::test_support::CapturedLogs;
+
+    #[test]
+    fn test_request_span_redacts_sensitive_headers() {
+        let request = Request::get("/tokens/verify")
+            .header(AUTHORIZATION, "Bearer soko__secret-token")
+            .header(COOKIE, "session=secret-cookie")
+            .header("x-tenant-secret", "secret-tenant")
+            .header(USER_AGENT, "integration-tests")
+            .header("x-forwarded-for", "203.0.113.7")
+            .header(DEFAULT_REQUEST_ID_HEADER, "request-id")
+            .body(())
+            .unwrap();
+        // Even explicitly logged, the sensitive headers are redacted
+        let logged_headers = vec![
+            "authorization".to_string(),
+            "cookie".to_string(),
+            "x-tenant-secret".to_string(),
+            "user-agent".to_string(),
+        ];
+        let redacted_headers = vec!["x-tenant-secret".to_string()];
+
+        let logs = CapturedLogs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(logs.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            let span = request_span(
+                &request,
+                Some(&HeaderName::from_static(DEFAULT_REQUEST_ID_HEADER)),
+                &logged_headers,
+                &redacted_headers,
+            );
+            let _entered = span.enter();
+            info!("request handled");
+        });
+
+        let logs = logs.contents();
+        assert!(logs.contains("integration-tests"), "{logs}");
+        assert!(logs.contains("authorization"), "{logs}");
+        for value in ["soko__secret-token", "secret-cookie", "secret-tenant"] {
+            assert!(!logs.contains(value), "{logs}");
+        }
+        // The headers which are not logged are left out
+        assert!(!logs.contains("x-forwarded-for"), "{logs}");
+        assert!(!logs.contains("203.0.113.7"), "{logs}");
+    }
+
+    #[test]
+    fn test_request_span_exports_the_request_id() {
+        let request = Request::get("/tokens/verify")
+            .header(DEFAULT_REQUEST_ID_HEADER, "request-id")
+            .header(USER_AGENT, "integration-tests")
+            .header(CONTENT_TYPE, "application/json")
+            .body(())
+            .unwrap();
+
+        let exporter = InMemorySpanExporter::default();
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry().with(otlp_layer(&tracer_provider));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = request_span(
+                &request,
+                Some(&HeaderName::from_static(DEFAULT_REQUEST_ID_HEADER)),
+                &["content-type".to_string()],
+                &[],
+            );
+            let _entered = span.enter();
+            info!("request handled");
+        });
+
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].name, "http_request");
+        let request_id = spans[0]
+            .attributes
+            .iter()
+            .find(|v| v.key.as_str() == "request_id")
+            .map(|v| v.value.as_str().into_owned());
+        assert_eq!(request_id.as_deref(), Some("request-id"));
+        // Only the logged headers leave the host
+        let headers = spans[0]
+            .attributes
+            .iter()
+            .find(|v| v.key.as_str() == "headers")
+            .map(|v| v.value.as_str().into_owned())
+            .unwrap();
+        assert!(headers.contains("application/json"), "{headers}");
+        assert!(!headers.contains("integration-tests"), "{headers}");
+    }
+
+    #[test]
+    fn test_otlp_tracer_provider_reports_the_failed_exports() {
+        // Nothing listens on the discard port
+        let tracer_provider = otlp_tracer_provider("http://127.0.0.1:9", "tests").unwrap();
+        let subscriber = tracing_subscriber::registry().with(tracer_provider.layer());
+        tracing::subscriber::with_default(subscriber, || {
+            let _entered = info_span!("http_request").entered();
+        });
+
+        let err = tracer_provider.shutdown().unwrap_err();
+        assert!(
+            err.to_string().contains("1 spans could not be exported"),
+            "{err}"
+        );
+    }
+
+    /// Logs of an event emitted within the span of a request, through a subscriber with the given format
+    fn log_request(format: LogFormat) -> String {
+        let request = Request::get("/tokens/verify")
+            .header(DEFAULT_REQUEST_ID_HEADER, "request-id")
+            .body(())
+            .unwrap();
+
+        let logs = CapturedLogs::default();
+        let subscriber = tracing_subscriber::registry().with(fmt_layer(format, logs.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = request_span(
+                &request,
+                Some(&HeaderName::from_static(DEFAULT_REQUEST_ID_HEADER)),
+                &[],
+                &[],
+            );
+            let _entered = span.enter();
+            info!("request handled");
+        });
+
+        logs.contents()
+    }
+
+    #[test]
+    fn test_fmt_layer_pretty() {
+        let logs = log_request(LogFormat::Pretty);
+        assert!(logs.contains("request handled"), "{logs}");
+        assert!(serde_json::from_str::<serde_json::Value>(logs.trim()).is_err());
+    }
+
+    #[test]
+    fn test_fmt_layer_json() {
+        let logs = log_request(LogFormat::Json);
+        let line: serde_json::Value = serde_json::from_str(logs.trim()).unwrap();
+        assert_eq!(line["fields"]["message"], "request handled", "{logs}");
+        assert_eq!(line["span"]["name"], "http_request", "{logs}");
+        assert_eq!(line["span"]["method"], "GET", "{logs}");
+        assert_eq!(line["span"]["request_id"], "request-id", "{logs}");
+    }
+
+    #[test]
+    fn test_redact_body_fields_by_field_name() {
+        let mut body = serde_json::json!({
+            "message": "secret: length, password: weakpassword",
+            "fields": {
+                "secret": [{ "code": "length" }],
+                "newPassword": [{ "code": "weakpassword" }]
+            },
+            "tokens": [{ "accessToken": "soko__secret-token", "tokenName": "default" }]
+        });
+        redact_body_fields(&mut body);
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "message": "secret: length, password: weakpassword",
+                "fields": {
+                    "secret": "******",
+                    "newPassword": "******"
+                },
+                "tokens": [{ "accessToken": "******", "tokenName": "default" }]
+            })
+        );
+    }
+}
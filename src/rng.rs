@@ -0,0 +1,9 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+/// Create the random number generator used to generate secrets, seeded from the operating system
+///
+/// Functions generating secrets have a `_with_rng` variant accepting any [rand::CryptoRng], a seeded generator can be injected there in tests.
+pub fn new_rng() -> ChaCha20Rng {
+    ChaCha20Rng::from_os_rng()
+}
@@ -1,12 +1,21 @@
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
 use chrono::{DateTime, TimeDelta, Utc};
+use rand::RngCore;
 use sqlx::{prelude::FromRow, types::uuid};
+use std::time::Duration;
 use thiserror::Error;
 use tracing::warn;
 
-use crate::newtypes::Email;
+use crate::{
+    clock::is_expired,
+    newtypes::Email,
+    rng::new_rng,
+    routes::{newtypes::PasswordHashAlgorithm, tokens::MAX_LIFETIME},
+};
 
 use super::{
-    SignupBody, VerifyAccountBody, verification_secret_strategy::VerificationSecretStrategy,
+    ChangePasswordBody, ResetPasswordBody, SignupBody, UpdateProfileBody, VerificationChannel,
+    VerifyAccountBody, verification_secret_strategy::VerificationSecretStrategy,
 };
 
 #[derive(FromRow, Clone, Debug)]
@@ -15,18 +24,79 @@ pub struct Account {
     pub email: Email,
     pub password_hash: String,
     pub verified: bool,
+    pub display_name: Option<String>,
+    /// Maximum lifetime of the access tokens of the account set by an admin, the global maximum applies if absent
+    pub max_token_lifetime_secs: Option<i32>,
+    /// Date until which the account can not authenticate, see [AccountState::Locked]
+    pub locked_until: Option<DateTime<Utc>>,
+    /// Date of the deactivation of the account, see [AccountState::Deactivated]
+    pub deactivated_at: Option<DateTime<Utc>>,
     // This field is automatically set at creation at the database level
     pub created_at: DateTime<Utc>,
     // This field is automatically updated at the database level
     pub updated_at: DateTime<Utc>,
 }
 
+/// State of an account in its lifecycle, derived from the columns of the account, see [Account::state]
+///
+/// The lifecycle checks, e.g. the guard of the access token creation, rely on the state instead of the columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountState {
+    /// Signed up, the verification secret has not been confirmed yet
+    Unverified,
+    /// Verified, the account can create and use access tokens
+    Active,
+    /// Locked until a date, the account can not authenticate until then
+    Locked,
+    /// Deactivated, the account can not authenticate anymore
+    Deactivated,
+}
+
+impl Account {
+    /// State of the account, derived from its columns
+    ///
+    /// A deactivation prevails over a lock, and a lock prevails over the verification.
+    ///
+    /// # Arguments
+    /// * `now` - current date, a lock ending before it is lifted
+    pub fn state(&self, now: DateTime<Utc>) -> AccountState {
+        if self.deactivated_at.is_some() {
+            AccountState::Deactivated
+        } else if self
+            .locked_until
+            .is_some_and(|locked_until| locked_until > now)
+        {
+            AccountState::Locked
+        } else if self.verified {
+            AccountState::Active
+        } else {
+            AccountState::Unverified
+        }
+    }
+
+    /// Strong entity tag of the account, derived from its last update date with microsecond precision, e.g. `"1760000000123456"`
+    pub fn etag(&self) -> String {
+        format!("\"{}\"", self.updated_at.timestamp_micros())
+    }
+
+    /// Maximum lifetime in seconds of the access tokens of the account, the account policy can only shorten the global [MAX_LIFETIME]
+    pub fn max_token_lifetime(&self) -> u32 {
+        self.max_token_lifetime_secs
+            .and_then(|v| u32::try_from(v).ok())
+            .map_or(MAX_LIFETIME, |v| v.min(MAX_LIFETIME))
+    }
+}
+
 #[derive(FromRow, Clone, Debug)]
 pub struct AccountVerificationTicket {
     pub id: uuid::Uuid,
     pub account_id: uuid::Uuid,
     pub cyphertext: String,
     pub status: AccountVerificationTicketStatus,
+    /// Channel through which the verification secret has been sent
+    pub channel: VerificationChannel,
+    /// Phone number to which the verification secret has been sent, only set for the SMS channel
+    pub phone_number: Option<String>,
     // This field is automatically set at creation at the database level
     pub created_at: DateTime<Utc>,
     // This field is automatically updated at the database level
@@ -44,6 +114,52 @@ pub enum AccountVerificationTicketStatus {
     Confirmed,
 }
 
+#[cfg(test)]
+mod account_state_tests {
+    use fake::{Fake, Faker};
+
+    use super::*;
+
+    #[test]
+    fn test_account_state() {
+        let mut account: Account = Faker.fake();
+        let now = Utc::now();
+        let past = now - TimeDelta::hours(1);
+        let future = now + TimeDelta::hours(1);
+        for (verified, locked_until, deactivated_at, expected_state) in [
+            (false, None, None, AccountState::Unverified),
+            (true, None, None, AccountState::Active),
+            (true, Some(future), None, AccountState::Locked),
+            (false, Some(future), None, AccountState::Locked),
+            // An ended lock is lifted
+            (true, Some(past), None, AccountState::Active),
+            (false, Some(past), None, AccountState::Unverified),
+            (true, None, Some(past), AccountState::Deactivated),
+            (false, None, Some(past), AccountState::Deactivated),
+            (true, Some(future), Some(past), AccountState::Deactivated),
+        ] {
+            account.verified = verified;
+            account.locked_until = locked_until;
+            account.deactivated_at = deactivated_at;
+            assert_eq!(
+                account.state(now),
+                expected_state,
+                "verified: {verified}, locked until: {locked_until:?}, deactivated at: {deactivated_at:?}"
+            );
+        }
+
+        // The account policy and the profile do not change the state
+        account.locked_until = None;
+        account.deactivated_at = None;
+        account.max_token_lifetime_secs = Some(60);
+        account.display_name = Some("Jane".to_string());
+        account.verified = true;
+        assert_eq!(account.state(now), AccountState::Active);
+        account.verified = false;
+        assert_eq!(account.state(now), AccountState::Unverified);
+    }
+}
+
 // ###############################################
 // ################## RETRIEVAL ##################
 // ###############################################
@@ -69,6 +185,14 @@ pub struct SignupRequest {
     pub password_hash: String,
     pub verification_plaintext: String,
     pub verification_cyphertext: String,
+    /// If true, the account is created verified, no verification ticket is created and no email is sent
+    pub auto_verified: bool,
+    /// Channel through which the verification secret is sent, persisted on the verification ticket
+    pub channel: VerificationChannel,
+    /// Phone number to which the verification secret is sent, only set for the SMS channel
+    pub phone_number: Option<String>,
+    /// Invite code consumed by the signup, only set if invite codes are required
+    pub invite_code: Option<String>,
 }
 
 /// Errors in the construction of the [SignupRequest]
@@ -76,14 +200,57 @@ pub struct SignupRequest {
 pub enum SignupRequestError {
     #[error("A verified account already exist for the email: {email}")]
     AccountAlreadyVerified { email: Email },
+    #[error("a phone number in E.164 format is required for the SMS verification channel")]
+    InvalidPhoneNumber,
+    #[error("an invite code is required to sign up")]
+    MissingInviteCode,
+    #[error(
+        "a signup has already been submitted for the email, retry in {retry_after_secs} seconds"
+    )]
+    DuplicateSignup { retry_after_secs: u64 },
     #[error(transparent)]
     Unknown(#[from] anyhow::Error),
 }
 
 impl SignupRequest {
     /// Build a [SignupRequest] using a [SignupBody] HTTP body
-    pub fn try_from_body(body: SignupBody) -> Result<Self, SignupRequestError> {
-        let password_hash = body.password.hash()?;
+    ///
+    /// # Arguments
+    /// * `body` - HTTP body of the signup,
+    /// * `autoverify_domains` - email domains for which the verification is bypassed,
+    /// * `require_invite_code` - if true, the body must carry an invite code, it is ignored otherwise,
+    /// * `password_hash_algorithm` - algorithm of the password hash, see [crate::Config::password_hash_algorithm]
+    pub fn try_from_body(
+        body: SignupBody,
+        autoverify_domains: &[String],
+        require_invite_code: bool,
+        password_hash_algorithm: PasswordHashAlgorithm,
+    ) -> Result<Self, SignupRequestError> {
+        let invite_code = if require_invite_code {
+            let invite_code = body
+                .invite_code
+                .as_deref()
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .ok_or(SignupRequestError::MissingInviteCode)?;
+            Some(invite_code.to_string())
+        } else {
+            None
+        };
+        let phone_number = match body.channel {
+            VerificationChannel::Email => None,
+            VerificationChannel::Sms => {
+                let phone_number = body
+                    .phone_number
+                    .filter(|v| is_e164_phone_number(v))
+                    .ok_or(SignupRequestError::InvalidPhoneNumber)?;
+                Some(phone_number)
+            }
+        };
+        let auto_verified = autoverify_domains
+            .iter()
+            .any(|domain| domain == body.email.domain());
+        let password_hash = body.password.hash(password_hash_algorithm)?;
         let (verification_plaintext, verification_cyphertext) =
             VerificationSecretStrategy::generate_verification_secret(&body.email)?;
         Ok(Self {
@@ -91,6 +258,10 @@ impl SignupRequest {
             password_hash,
             verification_plaintext,
             verification_cyphertext,
+            auto_verified,
+            channel: body.channel,
+            phone_number,
+            invite_code,
         })
     }
 
@@ -98,19 +269,72 @@ impl SignupRequest {
     pub fn try_from_body_with_existing_account(
         account: Account,
         body: SignupBody,
+        autoverify_domains: &[String],
+        require_invite_code: bool,
+        password_hash_algorithm: PasswordHashAlgorithm,
     ) -> Result<Self, SignupRequestError> {
         if account.verified {
             return Err(SignupRequestError::AccountAlreadyVerified {
                 email: account.email,
             });
         }
-        Self::try_from_body(body)
+        Self::try_from_body(
+            body,
+            autoverify_domains,
+            require_invite_code,
+            password_hash_algorithm,
+        )
+    }
+
+    /// Reject a signup repeated within the debounce window of the previous signup of an unverified account, e.g. a double-submitted form
+    ///
+    /// The previous signup is the last update of the account, i.e. its creation or the last reset of its creation.
+    ///
+    /// # Arguments
+    /// * `account` - previously signed up account,
+    /// * `now` - current date, see [crate::clock::Clock],
+    /// * `debounce` - debounce window, a zero window accepts every signup
+    pub fn ensure_outside_debounce_window(
+        account: &Account,
+        now: DateTime<Utc>,
+        debounce: Duration,
+    ) -> Result<(), SignupRequestError> {
+        if account.verified {
+            return Ok(());
+        }
+        let elapsed = now
+            .signed_duration_since(account.updated_at)
+            .to_std()
+            .unwrap_or_default();
+        match debounce.checked_sub(elapsed) {
+            Some(remaining) if !remaining.is_zero() => Err(SignupRequestError::DuplicateSignup {
+                retry_after_secs: remaining.as_secs_f64().ceil() as u64,
+            }),
+            _ => Ok(()),
+        }
     }
 }
 
+/// E.164 phone number, e.g. `+33612345678`
+fn is_e164_phone_number(v: &str) -> bool {
+    v.strip_prefix('+').is_some_and(|digits| {
+        (8..=15).contains(&digits.len())
+            && digits.chars().all(|c| c.is_ascii_digit())
+            && !digits.starts_with('0')
+    })
+}
+
 /// Errors in the interactions with adapters, e.g. database repository
 #[derive(Error, Debug)]
 pub enum SignupError {
+    #[error("an account already exists for the email")]
+    EmailAlreadyExists,
+    #[error("the invite code is unknown or has already been used")]
+    InvalidInviteCode,
+    #[error(
+        "the account has been signed up again within the debounce window, retry in {retry_after_secs} seconds"
+    )]
+    DuplicateSignup { retry_after_secs: u64 },
     #[error(transparent)]
     Unknown(#[from] anyhow::Error),
 }
@@ -136,6 +360,10 @@ mod signup_tests {
                 password_hash: "$2y$10$EZGQ6TDVUAicnOu4LgVoI.kFmcbFkT9nlOXeLfnKZtJYF8YjMM3mG"
                     .to_string(),
                 verified: true,
+                display_name: None,
+                max_token_lifetime_secs: None,
+                locked_until: None,
+                deactivated_at: None,
                 created_at,
                 updated_at: faker::chrono::en::DateTimeBetween(created_at, Utc::now())
                     .fake_with_rng(rng),
@@ -148,8 +376,17 @@ mod signup_tests {
         let signup_body = SignupBody {
             email: Faker.fake(),
             password: Faker.fake(),
+            channel: VerificationChannel::Email,
+            phone_number: None,
+            invite_code: None,
         };
-        let request = SignupRequest::try_from_body(signup_body.clone()).unwrap();
+        let request = SignupRequest::try_from_body(
+            signup_body.clone(),
+            &[],
+            false,
+            PasswordHashAlgorithm::Argon2id,
+        )
+        .unwrap();
         assert_eq!(request.email, signup_body.email);
         assert!(
             VerificationSecretStrategy::verify_verification_secret(
@@ -162,6 +399,106 @@ mod signup_tests {
         assert!(signup_body.password.verify(&request.password_hash).is_ok());
     }
 
+    #[test]
+    fn test_signup_request_from_body_with_autoverify_domain() {
+        let signup_body = SignupBody {
+            email: Email::new("jane@example.test").unwrap(),
+            password: Faker.fake(),
+            channel: VerificationChannel::Email,
+            phone_number: None,
+            invite_code: None,
+        };
+        let request = SignupRequest::try_from_body(
+            signup_body.clone(),
+            &["example.test".to_string()],
+            false,
+            PasswordHashAlgorithm::Argon2id,
+        )
+        .unwrap();
+        assert!(request.auto_verified);
+
+        let request = SignupRequest::try_from_body(
+            signup_body,
+            &["other.test".to_string()],
+            false,
+            PasswordHashAlgorithm::Argon2id,
+        )
+        .unwrap();
+        assert!(!request.auto_verified);
+    }
+
+    #[test]
+    fn test_signup_request_from_body_with_sms_channel() {
+        let mut signup_body = SignupBody {
+            email: Faker.fake(),
+            password: Faker.fake(),
+            channel: VerificationChannel::Sms,
+            phone_number: Some("+33612345678".to_string()),
+            invite_code: None,
+        };
+        let request = SignupRequest::try_from_body(
+            signup_body.clone(),
+            &[],
+            false,
+            PasswordHashAlgorithm::Argon2id,
+        )
+        .unwrap();
+        assert_eq!(request.phone_number.as_deref(), Some("+33612345678"));
+
+        for phone_number in [None, Some("0612345678"), Some("+33 6 12 34 56 78")] {
+            signup_body.phone_number = phone_number.map(|v| v.to_string());
+            let err = SignupRequest::try_from_body(
+                signup_body.clone(),
+                &[],
+                false,
+                PasswordHashAlgorithm::Argon2id,
+            )
+            .unwrap_err();
+            assert!(matches!(err, SignupRequestError::InvalidPhoneNumber));
+        }
+    }
+
+    #[test]
+    fn test_signup_request_from_body_with_required_invite_code() {
+        let mut signup_body = SignupBody {
+            email: Faker.fake(),
+            password: Faker.fake(),
+            channel: VerificationChannel::Email,
+            phone_number: None,
+            invite_code: Some(" beta-invite ".to_string()),
+        };
+        let request = SignupRequest::try_from_body(
+            signup_body.clone(),
+            &[],
+            true,
+            PasswordHashAlgorithm::Argon2id,
+        )
+        .unwrap();
+        assert_eq!(request.invite_code.as_deref(), Some("beta-invite"));
+
+        // The invite code is ignored if invite codes are not required
+        let request = SignupRequest::try_from_body(
+            signup_body.clone(),
+            &[],
+            false,
+            PasswordHashAlgorithm::Argon2id,
+        )
+        .unwrap();
+        assert_eq!(request.invite_code, None);
+
+        for invite_code in [None, Some("  ")] {
+            signup_body.invite_code = invite_code.map(|v| v.to_string());
+            let err = SignupRequest::try_from_body(
+                signup_body.clone(),
+                &[],
+                true,
+                PasswordHashAlgorithm::Argon2id,
+            )
+            .unwrap_err();
+            assert!(matches!(err, SignupRequestError::MissingInviteCode));
+        }
+    }
+
     #[test]
     fn test_signup_request_from_body_and_account() {
         let mut account: Account = Faker.fake();
@@ -169,10 +506,18 @@ mod signup_tests {
         let signup_body = SignupBody {
             email: Faker.fake(),
             password: Faker.fake(),
+            channel: VerificationChannel::Email,
+            phone_number: None,
+            invite_code: None,
         };
-        let request =
-            SignupRequest::try_from_body_with_existing_account(account, signup_body.clone())
-                .unwrap();
+        let request = SignupRequest::try_from_body_with_existing_account(
+            account,
+            signup_body.clone(),
+            &[],
+            false,
+            PasswordHashAlgorithm::Argon2id,
+        )
+        .unwrap();
         assert_eq!(request.email, signup_body.email);
         assert!(
             VerificationSecretStrategy::verify_verification_secret(
@@ -192,173 +537,659 @@ mod signup_tests {
         let signup_body = SignupBody {
             email: Faker.fake(),
             password: Faker.fake(),
+            channel: VerificationChannel::Email,
+            phone_number: None,
+            invite_code: None,
         };
 
-        let err =
-            SignupRequest::try_from_body_with_existing_account(account, signup_body).unwrap_err();
+        let err = SignupRequest::try_from_body_with_existing_account(
+            account,
+            signup_body,
+            &[],
+            false,
+            PasswordHashAlgorithm::Argon2id,
+        )
+        .unwrap_err();
         if let SignupRequestError::AccountAlreadyVerified { email: _email } = err {
         } else {
             panic!("Invalid error, expected `AccountAlreadyVerified` variant, got {err}");
         }
     }
+
+    #[test]
+    fn test_signup_within_debounce_window() {
+        let now = Utc::now();
+        let mut account: Account = Faker.fake();
+        account.verified = false;
+        account.updated_at = now - TimeDelta::milliseconds(1_500);
+        let debounce = Duration::from_secs(5);
+
+        let err =
+            SignupRequest::ensure_outside_debounce_window(&account, now, debounce).unwrap_err();
+        assert!(matches!(
+            err,
+            SignupRequestError::DuplicateSignup {
+                retry_after_secs: 4
+            }
+        ));
+
+        assert!(
+            SignupRequest::ensure_outside_debounce_window(&account, now, Duration::ZERO).is_ok()
+        );
+        assert!(
+            SignupRequest::ensure_outside_debounce_window(
+                &account,
+                now + TimeDelta::seconds(4),
+                debounce
+            )
+            .is_ok()
+        );
+
+        // Verified accounts are rejected by the signup itself
+        account.verified = true;
+        assert!(SignupRequest::ensure_outside_debounce_window(&account, now, debounce).is_ok());
+    }
 }
 
-// ##########################################################
-// ################## ACCOUNT VERIFICATION ##################
-// ##########################################################
+// #########################################################
+// ################## RESEND VERIFICATION ##################
+// #########################################################
+
+/// Minimum interval between two verification secrets sent to an account by the resend, see [ResendVerificationRequest::ensure_outside_throttle_window]
+pub const RESEND_VERIFICATION_INTERVAL: Duration = Duration::from_secs(60);
 
+/// DTO of the resend of the verification secret of an unverified account
+///
+/// Only the verification ticket is rotated, the password of the account is left untouched.
 #[derive(Debug)]
-pub struct VerifyAccountRequest {
+pub struct ResendVerificationRequest {
     pub account_id: uuid::Uuid,
+    pub email: Email,
+    pub verification_plaintext: String,
+    pub verification_cyphertext: String,
+    /// Channel of the active verification ticket, the secret is resent through the channel chosen at signup
+    pub channel: VerificationChannel,
+    /// Phone number of the active verification ticket, only set for the SMS channel
+    pub phone_number: Option<String>,
 }
 
+/// Errors in the construction of the [ResendVerificationRequest]
 #[derive(Error, Debug)]
-pub enum VerifyAccountRequestError {
-    #[error("invalid verification secret")]
-    InvalidVerificationSecret,
-    #[error("account is already verified for email: {email}")]
+pub enum ResendVerificationRequestError {
+    #[error("account with email {email} is already verified")]
     AccountAlreadyVerified { email: Email },
+    /// The active verification ticket has been created within the throttle window, see [RESEND_VERIFICATION_INTERVAL]
+    #[error("verification secret resent too soon, retry after {retry_after_secs} seconds")]
+    TooSoon { retry_after_secs: u64 },
     #[error(transparent)]
     Unknown(#[from] anyhow::Error),
 }
 
-impl VerifyAccountRequest {
-    pub fn try_from_body(
-        body: VerifyAccountBody,
+impl ResendVerificationRequest {
+    /// Build a [ResendVerificationRequest] with a new verification secret for a previously signed up account
+    ///
+    /// An ineligible account still costs a derivation, see [VerificationSecretStrategy::generate_dummy_verification_secret].
+    ///
+    /// # Arguments
+    /// * `account` - unverified account,
+    /// * `active_ticket` - active verification ticket of the account, if any, the secret is sent by email without one,
+    /// * `now` - current date, see [crate::clock::Clock],
+    /// * `interval` - throttle window, see [ResendVerificationRequest::ensure_outside_throttle_window]
+    pub fn try_from_account(
         account: Account,
-        verification_ticket: Option<AccountVerificationTicket>,
-    ) -> Result<VerifyAccountRequest, VerifyAccountRequestError> {
-        if account.verified {
-            return Err(VerifyAccountRequestError::AccountAlreadyVerified { email: body.email });
-        }
-        let verification_ticket =
-            verification_ticket.ok_or(VerifyAccountRequestError::InvalidVerificationSecret)?;
-
-        if Utc::now()
-            .signed_duration_since(verification_ticket.created_at)
-            .gt(&TimeDelta::minutes(15))
-        {
-            return Err(VerifyAccountRequestError::InvalidVerificationSecret);
+        active_ticket: Option<AccountVerificationTicket>,
+        now: DateTime<Utc>,
+        interval: Duration,
+    ) -> Result<Self, ResendVerificationRequestError> {
+        let eligibility = if account.verified {
+            Err(ResendVerificationRequestError::AccountAlreadyVerified {
+                email: account.email.clone(),
+            })
+        } else if let Some(ticket) = &active_ticket {
+            Self::ensure_outside_throttle_window(ticket.created_at, now, interval).map_err(|e| {
+                match e {
+                    ResendVerificationError::TooSoon { retry_after_secs } => {
+                        ResendVerificationRequestError::TooSoon { retry_after_secs }
+                    }
+                    e => ResendVerificationRequestError::Unknown(e.into()),
+                }
+            })
+        } else {
+            Ok(())
+        };
+        if let Err(e) = eligibility {
+            VerificationSecretStrategy::generate_dummy_verification_secret()?;
+            return Err(e);
         }
 
-        VerificationSecretStrategy::verify_verification_secret(
-            &body.secret,
-            &account.email,
-            &verification_ticket.cyphertext,
-        )
-        .map_err(|e| {
-            warn!("{e}");
-            VerifyAccountRequestError::InvalidVerificationSecret
-        })?;
-
-        Ok(VerifyAccountRequest {
+        let (verification_plaintext, verification_cyphertext) =
+            VerificationSecretStrategy::generate_verification_secret(&account.email)?;
+        let (channel, phone_number) = active_ticket
+            .map(|ticket| (ticket.channel, ticket.phone_number))
+            .unwrap_or_default();
+        Ok(Self {
             account_id: account.id,
+            email: account.email,
+            verification_plaintext,
+            verification_cyphertext,
+            channel,
+            phone_number,
         })
     }
+
+    /// Reject a resend within the throttle window of the active verification ticket, e.g. a resend right after the signup
+    ///
+    /// # Arguments
+    /// * `ticket_created_at` - creation date of the active verification ticket,
+    /// * `now` - current date,
+    /// * `interval` - throttle window, a zero window accepts every resend
+    pub fn ensure_outside_throttle_window(
+        ticket_created_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+        interval: Duration,
+    ) -> Result<(), ResendVerificationError> {
+        let elapsed = now
+            .signed_duration_since(ticket_created_at)
+            .to_std()
+            .unwrap_or_default();
+        match interval.checked_sub(elapsed) {
+            Some(remaining) if !remaining.is_zero() => Err(ResendVerificationError::TooSoon {
+                retry_after_secs: remaining.as_secs_f64().ceil() as u64,
+            }),
+            _ => Ok(()),
+        }
+    }
 }
 
-/// Errors that may occur while using connectors
+/// Errors in the interactions with adapters, e.g. database repository
 #[derive(Error, Debug)]
-pub enum VerifyAccountError {
+pub enum ResendVerificationError {
+    #[error("account with ID {account_id} has been concurrently verified")]
+    AccountAlreadyVerified { account_id: uuid::Uuid },
+    /// The active verification ticket has been created within the throttle window, see [RESEND_VERIFICATION_INTERVAL]
+    #[error("verification secret resent too soon, retry after {retry_after_secs} seconds")]
+    TooSoon { retry_after_secs: u64 },
     #[error(transparent)]
     Unknown(#[from] anyhow::Error),
 }
 
 #[cfg(test)]
-mod verify_account_tests {
-    use chrono::Days;
-    use fake::{Dummy, Fake, Faker, faker};
-
-    use crate::routes::accounts::verification_secret_strategy::VerificationSecretStrategy;
+mod resend_verification_tests {
+    use fake::{Fake, Faker};
 
     use super::*;
 
-    impl<T> Dummy<T> for AccountVerificationTicket {
-        fn dummy_with_rng<R: fake::Rng + ?Sized>(_: &T, rng: &mut R) -> Self {
-            let created_at = faker::chrono::en::DateTimeBefore(
-                Utc::now().checked_sub_days(Days::new(2)).unwrap(),
+    #[test]
+    fn test_resend_verification_request_from_unverified_account() {
+        let mut account: Account = Faker.fake();
+        account.verified = false;
+
+        let request = ResendVerificationRequest::try_from_account(
+            account.clone(),
+            None,
+            Utc::now(),
+            RESEND_VERIFICATION_INTERVAL,
+        )
+        .unwrap();
+        assert_eq!(request.account_id, account.id);
+        assert!(
+            VerificationSecretStrategy::verify_verification_secret(
+                &request.verification_plaintext,
+                &account.email,
+                &request.verification_cyphertext
             )
-            .fake_with_rng(rng);
-            let (_, cyphertext) =
-                VerificationSecretStrategy::generate_verification_secret(&Faker.fake::<Email>())
-                    .unwrap();
-            AccountVerificationTicket {
-                id: uuid::Uuid::new_v4(),
-                account_id: uuid::Uuid::new_v4(),
-                cyphertext,
-                status: AccountVerificationTicketStatus::Active,
-                created_at,
-                updated_at: faker::chrono::en::DateTimeBetween(created_at, Utc::now())
-                    .fake_with_rng(rng),
-            }
-        }
+            .is_ok()
+        );
     }
 
-    fn setup() -> (Account, AccountVerificationTicket, VerifyAccountBody) {
-        let signup_body = SignupBody {
-            email: Faker.fake(),
-            password: Faker.fake(),
-        };
-        let signup_request = SignupRequest::try_from_body(signup_body.clone()).unwrap();
+    #[test]
+    fn test_resend_verification_request_from_verified_account() {
+        let mut account: Account = Faker.fake();
+        account.verified = true;
 
-        let verify_account_body = VerifyAccountBody {
-            email: signup_body.email.clone(),
-            secret: signup_request.verification_plaintext,
-        };
+        let err = ResendVerificationRequest::try_from_account(
+            account,
+            None,
+            Utc::now(),
+            RESEND_VERIFICATION_INTERVAL,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ResendVerificationRequestError::AccountAlreadyVerified { .. }
+        ));
+    }
 
+    #[test]
+    fn test_resend_verification_request_within_throttle_window() {
         let mut account: Account = Faker.fake();
         account.verified = false;
-
+        let now = Utc::now();
         let mut verification_ticket: AccountVerificationTicket = Faker.fake();
-        verification_ticket.created_at = Utc::now();
-        verification_ticket.cyphertext = signup_request.verification_cyphertext.clone();
+        verification_ticket.created_at = now - TimeDelta::seconds(1);
 
-        (account, verification_ticket, verify_account_body)
+        let err = ResendVerificationRequest::try_from_account(
+            account.clone(),
+            Some(verification_ticket.clone()),
+            now,
+            RESEND_VERIFICATION_INTERVAL,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ResendVerificationRequestError::TooSoon { .. }
+        ));
+
+        verification_ticket.created_at =
+            now - TimeDelta::from_std(RESEND_VERIFICATION_INTERVAL).unwrap();
+        assert!(
+            ResendVerificationRequest::try_from_account(
+                account,
+                Some(verification_ticket),
+                now,
+                RESEND_VERIFICATION_INTERVAL,
+            )
+            .is_ok()
+        );
     }
 
     #[test]
-    fn test_verify_account_request_from_body() {
-        let (account, verification_ticket, verify_account_body) = setup();
+    fn test_resend_verification_request_keeps_the_channel_of_the_ticket() {
+        let mut account: Account = Faker.fake();
+        account.verified = false;
+        let mut verification_ticket: AccountVerificationTicket = Faker.fake();
+        verification_ticket.channel = VerificationChannel::Sms;
+        verification_ticket.phone_number = Some("+33612345678".to_string());
 
-        let verify_account_request = VerifyAccountRequest::try_from_body(
-            verify_account_body,
-            account.clone(),
+        let request = ResendVerificationRequest::try_from_account(
+            account,
             Some(verification_ticket),
+            Utc::now(),
+            RESEND_VERIFICATION_INTERVAL,
         )
         .unwrap();
-
-        assert_eq!(verify_account_request.account_id, account.id);
+        assert_eq!(request.channel, VerificationChannel::Sms);
+        assert_eq!(request.phone_number.as_deref(), Some("+33612345678"));
     }
 
     #[test]
-    fn test_verify_account_request_from_body_with_verified_account_must_fail() {
-        let (mut account, verification_ticket, verify_account_body) = setup();
-        account.verified = true;
+    fn test_resend_verification_within_throttle_window() {
+        let now = Utc::now();
 
-        let err = VerifyAccountRequest::try_from_body(
-            verify_account_body,
-            account.clone(),
-            Some(verification_ticket),
+        let err = ResendVerificationRequest::ensure_outside_throttle_window(
+            now - TimeDelta::milliseconds(10_500),
+            now,
+            RESEND_VERIFICATION_INTERVAL,
         )
         .unwrap_err();
+        assert!(matches!(
+            err,
+            ResendVerificationError::TooSoon {
+                retry_after_secs: 50
+            }
+        ));
 
-        if let VerifyAccountRequestError::AccountAlreadyVerified { email: _email } = err {
-        } else {
-            panic!("Invalid error, expected `AccountAlreadyVerified` variant, got {err}");
-        }
+        assert!(
+            ResendVerificationRequest::ensure_outside_throttle_window(
+                now - RESEND_VERIFICATION_INTERVAL,
+                now,
+                RESEND_VERIFICATION_INTERVAL
+            )
+            .is_ok()
+        );
+        assert!(
+            ResendVerificationRequest::ensure_outside_throttle_window(now, now, Duration::ZERO)
+                .is_ok()
+        );
+        // A ticket created in the future, e.g. clock skew, is within the window
+        assert!(
+            ResendVerificationRequest::ensure_outside_throttle_window(
+                now + TimeDelta::seconds(1),
+                now,
+                RESEND_VERIFICATION_INTERVAL
+            )
+            .is_err()
+        );
     }
+}
 
-    #[test]
-    fn test_verify_account_request_from_body_with_no_active_verification_ticket_must_fail() {
-        let (account, _verification_ticket, verify_account_body) = setup();
+// ##################################################
+// ################## INVITE CODES ##################
+// ##################################################
 
-        let err = VerifyAccountRequest::try_from_body(verify_account_body, account.clone(), None)
-            .unwrap_err();
+/// Number of random bytes of an invite code, before encoding
+pub const INVITE_CODE_BYTES: usize = 16;
 
-        if let VerifyAccountRequestError::InvalidVerificationSecret = err {
-        } else {
-            panic!("Invalid error, expected `InvalidVerificationSecret` variant, got {err}");
-        }
+/// Single-use code required to sign up if invite codes are required, it is minted by an admin
+#[derive(FromRow, Clone, Debug)]
+pub struct InviteCode {
+    pub id: uuid::Uuid,
+    pub code: String,
+    /// Account whose signup consumed the invite code
+    pub used_by: Option<uuid::Uuid>,
+    pub used_at: Option<DateTime<Utc>>,
+    // This field is automatically set at creation at the database level
+    pub created_at: DateTime<Utc>,
+    // This field is automatically updated at the database level
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Generate a random invite code, URL safe base64 encoded
+pub fn generate_invite_code() -> String {
+    let mut random_bytes = [0u8; INVITE_CODE_BYTES];
+    new_rng().fill_bytes(&mut random_bytes);
+    BASE64_URL_SAFE_NO_PAD.encode(random_bytes)
+}
+
+// ###################################################
+// ################## ACCOUNT MERGE ##################
+// ###################################################
+
+/// Outcome of the merge of a source account into a target account
+#[derive(Debug)]
+pub struct AccountMerge {
+    /// Target account, as it is after the merge
+    pub target_account: Account,
+    /// Number of access tokens moved from the source account to the target account
+    pub moved_access_tokens: u64,
+}
+
+/// Errors that may occur while merging accounts
+#[derive(Error, Debug)]
+pub enum MergeAccountsError {
+    #[error("an account can not be merged into itself")]
+    SameAccount,
+    #[error("account {account_id} not found")]
+    AccountNotFound { account_id: uuid::Uuid },
+    #[error("merged account would exceed its access token limit: {0}")]
+    ActiveTokenLimitExceeded(u8),
+    #[error(transparent)]
+    Unknown(#[from] anyhow::Error),
+}
+
+// ##########################################################
+// ################## ACCOUNT VERIFICATION ##################
+// ##########################################################
+
+#[derive(Debug)]
+pub struct VerifyAccountRequest {
+    pub account_id: uuid::Uuid,
+    /// ID of the verification ticket whose secret has been verified
+    pub ticket_id: uuid::Uuid,
+}
+
+/// Default number of failed verifications after which the verification ticket is invalidated, see [crate::Config::verification_max_attempts]
+pub const DEFAULT_MAX_VERIFICATION_ATTEMPTS: u32 = 5;
+
+/// Default lifetime of a verification ticket, a confirmed ticket can be replayed for the same duration after its confirmation, see [crate::Config::verification_ticket_lifetime]
+pub const DEFAULT_VERIFICATION_TICKET_LIFETIME: Duration = Duration::from_secs(15 * 60);
+
+/// Expiration date of a verification ticket, a lifetime beyond the representable dates never expires
+fn ticket_expiration(from: DateTime<Utc>, ticket_lifetime: Duration) -> DateTime<Utc> {
+    TimeDelta::from_std(ticket_lifetime)
+        .ok()
+        .and_then(|lifetime| from.checked_add_signed(lifetime))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+#[derive(Error, Debug)]
+pub enum VerifyAccountRequestError {
+    #[error("invalid verification secret")]
+    InvalidVerificationSecret,
+    /// The secret matches the verification ticket but the ticket has expired, a new secret must be requested
+    #[error("expired verification secret")]
+    ExpiredVerificationSecret,
+    /// The secret does not match the active verification ticket, the failed attempt must be recorded
+    #[error("wrong verification secret for verification ticket with ID: {ticket_id}")]
+    WrongVerificationSecret { ticket_id: uuid::Uuid },
+    /// The failed attempt has been recorded, the ticket is invalidated once there are no remaining attempts
+    #[error("failed verification attempt, {remaining_attempts} remaining attempts")]
+    FailedAttempt { remaining_attempts: u32 },
+    #[error("account is already verified for email: {email}")]
+    AccountAlreadyVerified { email: Email },
+    #[error(transparent)]
+    Unknown(#[from] anyhow::Error),
+}
+
+impl VerifyAccountRequest {
+    /// Build a [VerifyAccountRequest] using a [VerifyAccountBody] HTTP body, the verification ticket expires once its lifetime has elapsed since its creation
+    ///
+    /// # Arguments
+    /// * `body` - HTTP body of the verification,
+    /// * `account` - account to verify,
+    /// * `verification_ticket` - active verification ticket of the account,
+    /// * `now` - current date, see [crate::clock::Clock],
+    /// * `clock_skew_tolerance` - grace period after the expiration of the ticket, see [is_expired],
+    /// * `ticket_lifetime` - lifetime of the verification ticket, see [crate::Config::verification_ticket_lifetime]
+    pub fn try_from_body(
+        body: VerifyAccountBody,
+        account: Account,
+        verification_ticket: Option<AccountVerificationTicket>,
+        now: DateTime<Utc>,
+        clock_skew_tolerance: Duration,
+        ticket_lifetime: Duration,
+    ) -> Result<VerifyAccountRequest, VerifyAccountRequestError> {
+        if account.verified {
+            return Err(VerifyAccountRequestError::AccountAlreadyVerified { email: body.email });
+        }
+        let verification_ticket =
+            verification_ticket.ok_or(VerifyAccountRequestError::InvalidVerificationSecret)?;
+
+        let secret_verification = VerificationSecretStrategy::verify_verification_secret(
+            &body.secret,
+            &account.email,
+            &verification_ticket.cyphertext,
+        );
+
+        // The expiration is only disclosed to the holder of the secret, a wrong secret for an expired ticket is merely invalid
+        if is_expired(
+            ticket_expiration(verification_ticket.created_at, ticket_lifetime),
+            now,
+            clock_skew_tolerance,
+        ) {
+            return Err(match secret_verification {
+                Ok(true) => VerifyAccountRequestError::ExpiredVerificationSecret,
+                Ok(false) | Err(_) => VerifyAccountRequestError::InvalidVerificationSecret,
+            });
+        }
+
+        secret_verification.map_err(|e| {
+            warn!("{e}");
+            VerifyAccountRequestError::WrongVerificationSecret {
+                ticket_id: verification_ticket.id,
+            }
+        })?;
+
+        Ok(VerifyAccountRequest {
+            account_id: account.id,
+            ticket_id: verification_ticket.id,
+        })
+    }
+}
+
+impl VerifyAccountRequest {
+    /// Check that a verification of an already verified account replays its successful verification,
+    /// i.e. the secret matches the last confirmed verification ticket and the ticket has been confirmed within its lifetime
+    ///
+    /// # Arguments
+    /// * `body` - HTTP body of the verification,
+    /// * `account` - verified account,
+    /// * `confirmed_ticket` - last confirmed verification ticket of the account,
+    /// * `now` - current date, see [crate::clock::Clock],
+    /// * `clock_skew_tolerance` - grace period after the end of the replay window, see [is_expired],
+    /// * `ticket_lifetime` - lifetime of the verification ticket, i.e. duration of the replay window
+    ///
+    /// # Errors
+    /// * `VerifyAccountRequestError::AccountAlreadyVerified` - the verification is not a replay
+    pub fn verify_replay(
+        body: &VerifyAccountBody,
+        account: &Account,
+        confirmed_ticket: Option<AccountVerificationTicket>,
+        now: DateTime<Utc>,
+        clock_skew_tolerance: Duration,
+        ticket_lifetime: Duration,
+    ) -> Result<(), VerifyAccountRequestError> {
+        let already_verified = || VerifyAccountRequestError::AccountAlreadyVerified {
+            email: body.email.clone(),
+        };
+        let confirmed_ticket = confirmed_ticket.ok_or_else(already_verified)?;
+
+        // The ticket is confirmed at the verification, its last update is the confirmation
+        if is_expired(
+            ticket_expiration(confirmed_ticket.updated_at, ticket_lifetime),
+            now,
+            clock_skew_tolerance,
+        ) {
+            return Err(already_verified());
+        }
+
+        VerificationSecretStrategy::verify_verification_secret(
+            &body.secret,
+            &account.email,
+            &confirmed_ticket.cyphertext,
+        )
+        .map_err(|e| {
+            warn!("{e}");
+            already_verified()
+        })?;
+
+        Ok(())
+    }
+}
+
+/// Errors that may occur while using connectors
+#[derive(Error, Debug)]
+pub enum VerifyAccountError {
+    #[error("account with ID {account_id} has been concurrently verified")]
+    AccountAlreadyVerified { account_id: uuid::Uuid },
+    #[error("account with ID {account_id} has no active verification ticket")]
+    NoActiveVerificationTicket { account_id: uuid::Uuid },
+    #[error(transparent)]
+    Unknown(#[from] anyhow::Error),
+}
+
+#[cfg(test)]
+mod verify_account_tests {
+    use chrono::Days;
+    use fake::{Dummy, Fake, Faker, faker};
+
+    use crate::routes::accounts::verification_secret_strategy::VerificationSecretStrategy;
+
+    use super::*;
+
+    impl<T> Dummy<T> for AccountVerificationTicket {
+        fn dummy_with_rng<R: fake::Rng + ?Sized>(_: &T, rng: &mut R) -> Self {
+            let created_at = faker::chrono::en::DateTimeBefore(
+                Utc::now().checked_sub_days(Days::new(2)).unwrap(),
+            )
+            .fake_with_rng(rng);
+            let (_, cyphertext) =
+                VerificationSecretStrategy::generate_verification_secret(&Faker.fake::<Email>())
+                    .unwrap();
+            AccountVerificationTicket {
+                id: uuid::Uuid::new_v4(),
+                account_id: uuid::Uuid::new_v4(),
+                cyphertext,
+                status: AccountVerificationTicketStatus::Active,
+                channel: VerificationChannel::Email,
+                phone_number: None,
+                created_at,
+                updated_at: faker::chrono::en::DateTimeBetween(created_at, Utc::now())
+                    .fake_with_rng(rng),
+            }
+        }
+    }
+
+    fn setup() -> (Account, AccountVerificationTicket, VerifyAccountBody) {
+        let signup_body = SignupBody {
+            email: Faker.fake(),
+            password: Faker.fake(),
+            channel: VerificationChannel::Email,
+            phone_number: None,
+            invite_code: None,
+        };
+        let signup_request = SignupRequest::try_from_body(
+            signup_body.clone(),
+            &[],
+            false,
+            PasswordHashAlgorithm::Argon2id,
+        )
+        .unwrap();
+
+        let verify_account_body = VerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: signup_request.verification_plaintext,
+            issue_token: false,
+            token_name: None,
+        };
+
+        let mut account: Account = Faker.fake();
+        account.email = signup_body.email.clone();
+        account.verified = false;
+
+        let mut verification_ticket: AccountVerificationTicket = Faker.fake();
+        verification_ticket.created_at = Utc::now();
+        verification_ticket.cyphertext = signup_request.verification_cyphertext.clone();
+
+        (account, verification_ticket, verify_account_body)
+    }
+
+    #[test]
+    fn test_verify_account_request_from_body() {
+        let (account, verification_ticket, verify_account_body) = setup();
+
+        let verify_account_request = VerifyAccountRequest::try_from_body(
+            verify_account_body,
+            account.clone(),
+            Some(verification_ticket),
+            Utc::now(),
+            Duration::ZERO,
+            DEFAULT_VERIFICATION_TICKET_LIFETIME,
+        )
+        .unwrap();
+
+        assert_eq!(verify_account_request.account_id, account.id);
+    }
+
+    #[test]
+    fn test_verify_account_request_from_body_with_verified_account_must_fail() {
+        let (mut account, verification_ticket, verify_account_body) = setup();
+        account.verified = true;
+
+        let err = VerifyAccountRequest::try_from_body(
+            verify_account_body,
+            account.clone(),
+            Some(verification_ticket),
+            Utc::now(),
+            Duration::ZERO,
+            DEFAULT_VERIFICATION_TICKET_LIFETIME,
+        )
+        .unwrap_err();
+
+        if let VerifyAccountRequestError::AccountAlreadyVerified { email: _email } = err {
+        } else {
+            panic!("Invalid error, expected `AccountAlreadyVerified` variant, got {err}");
+        }
+    }
+
+    #[test]
+    fn test_verify_account_request_from_body_with_no_active_verification_ticket_must_fail() {
+        let (account, _verification_ticket, verify_account_body) = setup();
+
+        let err = VerifyAccountRequest::try_from_body(
+            verify_account_body,
+            account.clone(),
+            None,
+            Utc::now(),
+            Duration::ZERO,
+            DEFAULT_VERIFICATION_TICKET_LIFETIME,
+        )
+        .unwrap_err();
+
+        if let VerifyAccountRequestError::InvalidVerificationSecret = err {
+        } else {
+            panic!("Invalid error, expected `InvalidVerificationSecret` variant, got {err}");
+        }
     }
 
     #[test]
@@ -373,6 +1204,37 @@ mod verify_account_tests {
             verify_account_body,
             account.clone(),
             Some(verification_ticket),
+            Utc::now(),
+            Duration::ZERO,
+            DEFAULT_VERIFICATION_TICKET_LIFETIME,
+        )
+        .unwrap_err();
+
+        if let VerifyAccountRequestError::ExpiredVerificationSecret = err {
+        } else {
+            panic!("Invalid error, expected `ExpiredVerificationSecret` variant, got {err}");
+        }
+    }
+
+    #[test]
+    fn test_verify_account_request_from_body_with_expired_verification_ticket_and_invalid_plaintext_must_fail()
+     {
+        let (account, mut verification_ticket, mut verify_account_body) = setup();
+
+        verification_ticket.created_at = Utc::now()
+            .checked_sub_signed(TimeDelta::minutes(16))
+            .unwrap();
+        let (other_plaintext, _) =
+            VerificationSecretStrategy::generate_verification_secret(&account.email).unwrap();
+        verify_account_body.secret = other_plaintext;
+
+        let err = VerifyAccountRequest::try_from_body(
+            verify_account_body,
+            account.clone(),
+            Some(verification_ticket),
+            Utc::now(),
+            Duration::ZERO,
+            DEFAULT_VERIFICATION_TICKET_LIFETIME,
         )
         .unwrap_err();
 
@@ -382,6 +1244,55 @@ mod verify_account_tests {
         }
     }
 
+    #[test]
+    fn test_verify_account_request_from_body_within_clock_skew_tolerance() {
+        let (account, mut verification_ticket, verify_account_body) = setup();
+        let now = Utc::now();
+        verification_ticket.created_at =
+            now - TimeDelta::from_std(DEFAULT_VERIFICATION_TICKET_LIFETIME).unwrap();
+
+        // Expired at the end of the lifetime without tolerance
+        let err = VerifyAccountRequest::try_from_body(
+            verify_account_body.clone(),
+            account.clone(),
+            Some(verification_ticket.clone()),
+            now,
+            Duration::ZERO,
+            DEFAULT_VERIFICATION_TICKET_LIFETIME,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            VerifyAccountRequestError::ExpiredVerificationSecret
+        ));
+
+        // Still valid until the end of the tolerance
+        let verify_account_request = VerifyAccountRequest::try_from_body(
+            verify_account_body.clone(),
+            account.clone(),
+            Some(verification_ticket.clone()),
+            now + TimeDelta::seconds(29),
+            Duration::from_secs(30),
+            DEFAULT_VERIFICATION_TICKET_LIFETIME,
+        )
+        .unwrap();
+        assert_eq!(verify_account_request.account_id, account.id);
+
+        let err = VerifyAccountRequest::try_from_body(
+            verify_account_body,
+            account,
+            Some(verification_ticket),
+            now + TimeDelta::seconds(30),
+            Duration::from_secs(30),
+            DEFAULT_VERIFICATION_TICKET_LIFETIME,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            VerifyAccountRequestError::ExpiredVerificationSecret
+        ));
+    }
+
     #[test]
     fn test_verify_account_request_from_body_with_invalid_plaintext_must_fail() {
         let (account, verification_ticket, mut verify_account_body) = setup();
@@ -389,17 +1300,741 @@ mod verify_account_tests {
         let (other_plaintext, _) =
             VerificationSecretStrategy::generate_verification_secret(&account.email).unwrap();
         verify_account_body.secret = other_plaintext;
+        let verification_ticket_id = verification_ticket.id;
 
         let err = VerifyAccountRequest::try_from_body(
             verify_account_body,
             account.clone(),
             Some(verification_ticket),
+            Utc::now(),
+            Duration::ZERO,
+            DEFAULT_VERIFICATION_TICKET_LIFETIME,
         )
         .unwrap_err();
 
-        if let VerifyAccountRequestError::InvalidVerificationSecret = err {
+        if let VerifyAccountRequestError::WrongVerificationSecret { ticket_id } = err {
+            assert_eq!(ticket_id, verification_ticket_id);
         } else {
-            panic!("Invalid error, expected `InvalidVerificationSecret` variant, got {err}");
+            panic!("Invalid error, expected `WrongVerificationSecret` variant, got {err}");
         }
     }
+
+    #[test]
+    fn test_verify_replay() {
+        let (mut account, mut verification_ticket, verify_account_body) = setup();
+        account.verified = true;
+        verification_ticket.status = AccountVerificationTicketStatus::Confirmed;
+        verification_ticket.updated_at = Utc::now();
+
+        assert!(
+            VerifyAccountRequest::verify_replay(
+                &verify_account_body,
+                &account,
+                Some(verification_ticket),
+                Utc::now(),
+                Duration::ZERO,
+                DEFAULT_VERIFICATION_TICKET_LIFETIME
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_verify_replay_with_invalid_plaintext_must_fail() {
+        let (mut account, mut verification_ticket, mut verify_account_body) = setup();
+        account.verified = true;
+        verification_ticket.status = AccountVerificationTicketStatus::Confirmed;
+        verification_ticket.updated_at = Utc::now();
+        let (other_plaintext, _) =
+            VerificationSecretStrategy::generate_verification_secret(&account.email).unwrap();
+        verify_account_body.secret = other_plaintext;
+
+        let err = VerifyAccountRequest::verify_replay(
+            &verify_account_body,
+            &account,
+            Some(verification_ticket),
+            Utc::now(),
+            Duration::ZERO,
+            DEFAULT_VERIFICATION_TICKET_LIFETIME,
+        )
+        .unwrap_err();
+
+        if let VerifyAccountRequestError::AccountAlreadyVerified { email: _email } = err {
+        } else {
+            panic!("Invalid error, expected `AccountAlreadyVerified` variant, got {err}");
+        }
+    }
+
+    #[test]
+    fn test_verify_replay_after_window_must_fail() {
+        let (mut account, mut verification_ticket, verify_account_body) = setup();
+        account.verified = true;
+        verification_ticket.status = AccountVerificationTicketStatus::Confirmed;
+        verification_ticket.updated_at = Utc::now()
+            .checked_sub_signed(TimeDelta::minutes(16))
+            .unwrap();
+
+        let err = VerifyAccountRequest::verify_replay(
+            &verify_account_body,
+            &account,
+            Some(verification_ticket),
+            Utc::now(),
+            Duration::ZERO,
+            DEFAULT_VERIFICATION_TICKET_LIFETIME,
+        )
+        .unwrap_err();
+
+        if let VerifyAccountRequestError::AccountAlreadyVerified { email: _email } = err {
+        } else {
+            panic!("Invalid error, expected `AccountAlreadyVerified` variant, got {err}");
+        }
+    }
+}
+
+// ####################################################
+// ################## PROFILE UPDATE ##################
+// ####################################################
+
+pub const MAX_DISPLAY_NAME_LENGTH: usize = 64;
+
+/// DTO of the profile update action
+///
+/// A `None` field is left untouched, a `Some(None)` field is cleared.
+#[derive(Debug)]
+pub struct UpdateProfileRequest {
+    pub display_name: Option<Option<String>>,
+    /// If set, the account is only updated if its last update date is still this one, see [Account::etag]
+    pub expected_updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Error, Debug)]
+pub enum UpdateProfileRequestError {
+    #[error("invalid display name")]
+    InvalidDisplayName,
+    #[error("the `If-Match` header does not match any entity tag of an account")]
+    PreconditionFailed,
+}
+
+impl UpdateProfileRequest {
+    /// Build a [UpdateProfileRequest] using a [UpdateProfileBody] HTTP body, the display name is trimmed
+    ///
+    /// # Arguments
+    /// * `body` - HTTP body of the profile update,
+    /// * `if_match` - value of the `If-Match` header, either `*` or an entity tag given by [Account::etag]
+    pub fn try_from_body(
+        body: UpdateProfileBody,
+        if_match: Option<&str>,
+    ) -> Result<Self, UpdateProfileRequestError> {
+        let expected_updated_at = match if_match.map(str::trim) {
+            None | Some("*") => None,
+            Some(etag) => {
+                let updated_at = etag
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .and_then(|v| v.parse::<i64>().ok())
+                    .and_then(DateTime::from_timestamp_micros)
+                    .ok_or(UpdateProfileRequestError::PreconditionFailed)?;
+                Some(updated_at)
+            }
+        };
+        let display_name = body
+            .display_name
+            .map(|v| {
+                v.map(|display_name| {
+                    let display_name = display_name.trim();
+                    let length = display_name.chars().count();
+                    if length == 0 || length > MAX_DISPLAY_NAME_LENGTH {
+                        return Err(UpdateProfileRequestError::InvalidDisplayName);
+                    }
+                    Ok(display_name.to_string())
+                })
+                .transpose()
+            })
+            .transpose()?;
+        Ok(Self {
+            display_name,
+            expected_updated_at,
+        })
+    }
+}
+
+/// Errors that may occur while using connectors
+#[derive(Error, Debug)]
+pub enum UpdateProfileError {
+    #[error("field {field} does not satisfy a database constraint")]
+    ConstraintViolation { field: &'static str },
+    #[error("account has been updated since its entity tag was retrieved")]
+    PreconditionFailed,
+    #[error(transparent)]
+    Unknown(#[from] anyhow::Error),
+}
+
+#[cfg(test)]
+mod update_profile_tests {
+    use fake::{Fake, Faker};
+
+    use super::*;
+
+    #[test]
+    fn test_update_profile_request_from_body() {
+        let request = UpdateProfileRequest::try_from_body(
+            UpdateProfileBody {
+                display_name: Some(Some("  Jane Doe ".to_string())),
+            },
+            None,
+        )
+        .unwrap();
+        assert_eq!(request.display_name, Some(Some("Jane Doe".to_string())));
+
+        let request =
+            UpdateProfileRequest::try_from_body(UpdateProfileBody { display_name: None }, None)
+                .unwrap();
+        assert_eq!(request.display_name, None);
+
+        let request = UpdateProfileRequest::try_from_body(
+            UpdateProfileBody {
+                display_name: Some(None),
+            },
+            None,
+        )
+        .unwrap();
+        assert_eq!(request.display_name, Some(None));
+    }
+
+    #[test]
+    fn test_update_profile_request_from_body_with_invalid_display_name_must_fail() {
+        for display_name in ["   ".to_string(), "a".repeat(MAX_DISPLAY_NAME_LENGTH + 1)] {
+            let err = UpdateProfileRequest::try_from_body(
+                UpdateProfileBody {
+                    display_name: Some(Some(display_name)),
+                },
+                None,
+            )
+            .unwrap_err();
+            assert!(matches!(err, UpdateProfileRequestError::InvalidDisplayName));
+        }
+    }
+
+    #[test]
+    fn test_update_profile_request_from_body_with_if_match() {
+        let account: Account = Faker.fake();
+        let body = || UpdateProfileBody { display_name: None };
+
+        let request = UpdateProfileRequest::try_from_body(body(), Some(&account.etag())).unwrap();
+        assert_eq!(
+            request.expected_updated_at.map(|v| v.timestamp_micros()),
+            Some(account.updated_at.timestamp_micros())
+        );
+
+        let request = UpdateProfileRequest::try_from_body(body(), Some("*")).unwrap();
+        assert_eq!(request.expected_updated_at, None);
+
+        for if_match in [
+            "1760000000123456",
+            "W/\"1760000000123456\"",
+            "\"not-a-date\"",
+        ] {
+            let err = UpdateProfileRequest::try_from_body(body(), Some(if_match)).unwrap_err();
+            assert!(matches!(err, UpdateProfileRequestError::PreconditionFailed));
+        }
+    }
+}
+
+// ####################################################
+// ################## PASSWORD RESET ##################
+// ####################################################
+
+/// Lifetime of a password reset ticket, a reset code must be used within this duration after its request
+pub const PASSWORD_RESET_TICKET_LIFETIME: Duration = Duration::from_secs(15 * 60);
+
+/// Minimum interval between two password reset codes sent to an account, a request within the interval sends nothing
+pub const PASSWORD_RESET_REQUEST_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(FromRow, Clone, Debug)]
+pub struct PasswordResetTicket {
+    pub id: uuid::Uuid,
+    pub account_id: uuid::Uuid,
+    pub cyphertext: String,
+    pub status: PasswordResetTicketStatus,
+    // This field is automatically set at creation at the database level
+    pub created_at: DateTime<Utc>,
+    // This field is automatically updated at the database level
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::Type, Clone, Debug)]
+#[sqlx(type_name = "password_reset_ticket_status", rename_all = "lowercase")]
+pub enum PasswordResetTicketStatus {
+    Active,
+    Cancelled,
+    Confirmed,
+}
+
+/// DTO of the request of a password reset code for a verified account
+///
+/// The code is generated and verified as a verification secret, see [VerificationSecretStrategy].
+#[derive(Debug)]
+pub struct RequestPasswordResetRequest {
+    pub account_id: uuid::Uuid,
+    pub email: Email,
+    pub reset_plaintext: String,
+    pub reset_cyphertext: String,
+}
+
+/// Errors in the construction of the [RequestPasswordResetRequest]
+#[derive(Error, Debug)]
+pub enum RequestPasswordResetRequestError {
+    #[error("account with email {email} is not verified")]
+    AccountNotVerified { email: Email },
+    /// The active password reset ticket has been created within the throttle window, see [PASSWORD_RESET_REQUEST_INTERVAL]
+    #[error("password reset code requested too soon, retry after {retry_after_secs} seconds")]
+    TooSoon { retry_after_secs: u64 },
+    #[error(transparent)]
+    Unknown(#[from] anyhow::Error),
+}
+
+impl RequestPasswordResetRequest {
+    /// Build a [RequestPasswordResetRequest] with a new reset code for a verified account
+    ///
+    /// An ineligible account still costs a derivation, see [VerificationSecretStrategy::generate_dummy_verification_secret].
+    ///
+    /// # Arguments
+    /// * `account` - verified account,
+    /// * `active_ticket_created_at` - creation date of the active password reset ticket of the account, if any,
+    /// * `now` - current date, see [crate::clock::Clock],
+    /// * `interval` - throttle window, see [RequestPasswordResetRequest::ensure_outside_throttle_window]
+    pub fn try_from_account(
+        account: Account,
+        active_ticket_created_at: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+        interval: Duration,
+    ) -> Result<Self, RequestPasswordResetRequestError> {
+        let eligibility = if account.state(now) != AccountState::Active {
+            Err(RequestPasswordResetRequestError::AccountNotVerified {
+                email: account.email.clone(),
+            })
+        } else if let Some(created_at) = active_ticket_created_at {
+            Self::ensure_outside_throttle_window(created_at, now, interval).map_err(|e| match e {
+                RequestPasswordResetError::TooSoon { retry_after_secs } => {
+                    RequestPasswordResetRequestError::TooSoon { retry_after_secs }
+                }
+                RequestPasswordResetError::Unknown(e) => {
+                    RequestPasswordResetRequestError::Unknown(e)
+                }
+            })
+        } else {
+            Ok(())
+        };
+        if let Err(e) = eligibility {
+            VerificationSecretStrategy::generate_dummy_verification_secret()?;
+            return Err(e);
+        }
+
+        let (reset_plaintext, reset_cyphertext) =
+            VerificationSecretStrategy::generate_verification_secret(&account.email)?;
+        Ok(Self {
+            account_id: account.id,
+            email: account.email,
+            reset_plaintext,
+            reset_cyphertext,
+        })
+    }
+
+    /// Reject a request within the throttle window of the active password reset ticket, e.g. a request submitted twice
+    ///
+    /// # Arguments
+    /// * `ticket_created_at` - creation date of the active password reset ticket,
+    /// * `now` - current date,
+    /// * `interval` - throttle window, a zero window accepts every request
+    pub fn ensure_outside_throttle_window(
+        ticket_created_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+        interval: Duration,
+    ) -> Result<(), RequestPasswordResetError> {
+        ResendVerificationRequest::ensure_outside_throttle_window(ticket_created_at, now, interval)
+            .map_err(|e| match e {
+                ResendVerificationError::TooSoon { retry_after_secs } => {
+                    RequestPasswordResetError::TooSoon { retry_after_secs }
+                }
+                e => RequestPasswordResetError::Unknown(e.into()),
+            })
+    }
+}
+
+/// Errors in the interactions with adapters, e.g. database repository
+#[derive(Error, Debug)]
+pub enum RequestPasswordResetError {
+    /// The active password reset ticket has been created within the throttle window, see [PASSWORD_RESET_REQUEST_INTERVAL]
+    #[error("password reset code requested too soon, retry after {retry_after_secs} seconds")]
+    TooSoon { retry_after_secs: u64 },
+    #[error(transparent)]
+    Unknown(#[from] anyhow::Error),
+}
+
+/// DTO of the password reset action
+#[derive(Debug)]
+pub struct ResetPasswordRequest {
+    pub account_id: uuid::Uuid,
+    pub ticket_id: uuid::Uuid,
+    pub password_hash: String,
+}
+
+/// Errors in the construction of the [ResetPasswordRequest]
+#[derive(Error, Debug)]
+pub enum ResetPasswordRequestError {
+    #[error("invalid password reset code")]
+    InvalidCode,
+    /// The code matches the password reset ticket but the ticket has expired, a new code must be requested
+    #[error("expired password reset code")]
+    ExpiredCode,
+    #[error(transparent)]
+    Unknown(#[from] anyhow::Error),
+}
+
+impl ResetPasswordRequest {
+    /// Build a [ResetPasswordRequest] using a [ResetPasswordBody] HTTP body, the new password is hashed once the code is verified
+    ///
+    /// # Arguments
+    /// * `body` - HTTP body of the password reset,
+    /// * `account` - account whose password is reset,
+    /// * `reset_ticket` - active password reset ticket of the account,
+    /// * `now` - current date, see [crate::clock::Clock],
+    /// * `clock_skew_tolerance` - grace period after the expiration of the ticket, see [is_expired],
+    /// * `password_hash_algorithm` - algorithm of the new password hash, see [crate::Config::password_hash_algorithm]
+    pub fn try_from_body(
+        body: ResetPasswordBody,
+        account: Account,
+        reset_ticket: Option<PasswordResetTicket>,
+        now: DateTime<Utc>,
+        clock_skew_tolerance: Duration,
+        password_hash_algorithm: PasswordHashAlgorithm,
+    ) -> Result<Self, ResetPasswordRequestError> {
+        let Some(reset_ticket) = reset_ticket else {
+            VerificationSecretStrategy::verify_dummy_verification_secret(&body.code);
+            return Err(ResetPasswordRequestError::InvalidCode);
+        };
+
+        let code_verification = VerificationSecretStrategy::verify_verification_secret(
+            &body.code,
+            &account.email,
+            &reset_ticket.cyphertext,
+        );
+
+        // The expiration is only disclosed to the holder of the code, a wrong code for an expired ticket is merely invalid
+        if is_expired(
+            ticket_expiration(reset_ticket.created_at, PASSWORD_RESET_TICKET_LIFETIME),
+            now,
+            clock_skew_tolerance,
+        ) {
+            return Err(match code_verification {
+                Ok(true) => ResetPasswordRequestError::ExpiredCode,
+                Ok(false) | Err(_) => ResetPasswordRequestError::InvalidCode,
+            });
+        }
+
+        match code_verification {
+            Ok(true) => {}
+            Ok(false) => return Err(ResetPasswordRequestError::InvalidCode),
+            Err(e) => {
+                warn!("{e}");
+                return Err(ResetPasswordRequestError::InvalidCode);
+            }
+        }
+
+        Ok(Self {
+            account_id: account.id,
+            ticket_id: reset_ticket.id,
+            password_hash: body.new_password.hash(password_hash_algorithm)?,
+        })
+    }
+}
+
+/// Errors that may occur while using connectors
+#[derive(Error, Debug)]
+pub enum ResetPasswordError {
+    #[error("password reset ticket with ID {ticket_id} is no longer active")]
+    NoActivePasswordResetTicket { ticket_id: uuid::Uuid },
+    #[error(transparent)]
+    Unknown(#[from] anyhow::Error),
+}
+
+#[cfg(test)]
+mod password_reset_tests {
+    use fake::{Fake, Faker};
+
+    use crate::routes::newtypes::Password;
+
+    use super::*;
+
+    fn setup() -> (Account, PasswordResetTicket, ResetPasswordBody) {
+        let mut account: Account = Faker.fake();
+        account.verified = true;
+        let now = Utc::now();
+        let request = RequestPasswordResetRequest::try_from_account(
+            account.clone(),
+            None,
+            now,
+            PASSWORD_RESET_REQUEST_INTERVAL,
+        )
+        .unwrap();
+        let reset_ticket = PasswordResetTicket {
+            id: uuid::Uuid::new_v4(),
+            account_id: account.id,
+            cyphertext: request.reset_cyphertext,
+            status: PasswordResetTicketStatus::Active,
+            created_at: now,
+            updated_at: now,
+        };
+        let body = ResetPasswordBody {
+            email: account.email.clone(),
+            code: request.reset_plaintext,
+            new_password: Faker.fake(),
+        };
+        (account, reset_ticket, body)
+    }
+
+    #[test]
+    fn test_request_password_reset_request_from_unverified_account_must_fail() {
+        let mut account: Account = Faker.fake();
+        account.verified = false;
+
+        let err = RequestPasswordResetRequest::try_from_account(
+            account,
+            None,
+            Utc::now(),
+            PASSWORD_RESET_REQUEST_INTERVAL,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            RequestPasswordResetRequestError::AccountNotVerified { .. }
+        ));
+    }
+
+    #[test]
+    fn test_request_password_reset_request_within_throttle_window_must_fail() {
+        let (account, reset_ticket, _) = setup();
+
+        let err = RequestPasswordResetRequest::try_from_account(
+            account.clone(),
+            Some(reset_ticket.created_at),
+            reset_ticket.created_at + TimeDelta::seconds(1),
+            PASSWORD_RESET_REQUEST_INTERVAL,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            RequestPasswordResetRequestError::TooSoon { retry_after_secs } if retry_after_secs == PASSWORD_RESET_REQUEST_INTERVAL.as_secs() - 1
+        ));
+
+        // A request after the window is accepted
+        assert!(
+            RequestPasswordResetRequest::try_from_account(
+                account,
+                Some(reset_ticket.created_at),
+                reset_ticket.created_at
+                    + TimeDelta::from_std(PASSWORD_RESET_REQUEST_INTERVAL).unwrap(),
+                PASSWORD_RESET_REQUEST_INTERVAL,
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_reset_password_request_from_body() {
+        let (account, reset_ticket, body) = setup();
+        let new_password: Password = body.new_password.clone();
+
+        let request = ResetPasswordRequest::try_from_body(
+            body,
+            account.clone(),
+            Some(reset_ticket.clone()),
+            Utc::now(),
+            Duration::ZERO,
+            PasswordHashAlgorithm::Argon2id,
+        )
+        .unwrap();
+        assert_eq!(request.account_id, account.id);
+        assert_eq!(request.ticket_id, reset_ticket.id);
+        assert!(new_password.verify(&request.password_hash).is_ok());
+    }
+
+    #[test]
+    fn test_reset_password_request_from_body_with_wrong_code_must_fail() {
+        let (account, reset_ticket, mut body) = setup();
+        // A code generated for the same email but for another ticket
+        (body.code, _) =
+            VerificationSecretStrategy::generate_verification_secret(&account.email).unwrap();
+
+        let err = ResetPasswordRequest::try_from_body(
+            body,
+            account,
+            Some(reset_ticket),
+            Utc::now(),
+            Duration::ZERO,
+            PasswordHashAlgorithm::Argon2id,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ResetPasswordRequestError::InvalidCode));
+    }
+
+    #[test]
+    fn test_reset_password_request_from_body_without_ticket_must_fail() {
+        let (account, _, body) = setup();
+
+        let err = ResetPasswordRequest::try_from_body(
+            body,
+            account,
+            None,
+            Utc::now(),
+            Duration::ZERO,
+            PasswordHashAlgorithm::Argon2id,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ResetPasswordRequestError::InvalidCode));
+    }
+
+    #[test]
+    fn test_reset_password_request_from_body_with_expired_ticket_must_fail() {
+        let (account, reset_ticket, body) = setup();
+        let expired_at =
+            reset_ticket.created_at + TimeDelta::from_std(PASSWORD_RESET_TICKET_LIFETIME).unwrap();
+
+        let err = ResetPasswordRequest::try_from_body(
+            body,
+            account,
+            Some(reset_ticket),
+            expired_at,
+            Duration::ZERO,
+            PasswordHashAlgorithm::Argon2id,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ResetPasswordRequestError::ExpiredCode));
+    }
+}
+
+// #####################################################
+// ################## PASSWORD CHANGE ##################
+// #####################################################
+
+/// DTO of the password change of an authenticated account
+#[derive(Debug)]
+pub struct ChangePasswordRequest {
+    pub account_id: uuid::Uuid,
+    pub password_hash: String,
+    /// If true, the access tokens and the refresh tokens of the account are revoked along with the change
+    pub revoke_tokens: bool,
+}
+
+/// Errors in the construction of the [ChangePasswordRequest]
+#[derive(Error, Debug)]
+pub enum ChangePasswordRequestError {
+    #[error("invalid current password")]
+    InvalidPassword,
+    #[error("the new password is the current password")]
+    SamePassword,
+    #[error(transparent)]
+    Unknown(#[from] anyhow::Error),
+}
+
+impl ChangePasswordRequest {
+    /// Build a [ChangePasswordRequest] using a [ChangePasswordBody] HTTP body, the new password is hashed once the current password is verified
+    ///
+    /// # Arguments
+    /// * `body` - HTTP body of the password change,
+    /// * `account` - authenticated account,
+    /// * `password_hash_algorithm` - algorithm of the new password hash, see [crate::Config::password_hash_algorithm]
+    pub fn try_from_body(
+        body: ChangePasswordBody,
+        account: &Account,
+        password_hash_algorithm: PasswordHashAlgorithm,
+    ) -> Result<Self, ChangePasswordRequestError> {
+        if body
+            .current_password
+            .verify(&account.password_hash)
+            .is_err()
+        {
+            return Err(ChangePasswordRequestError::InvalidPassword);
+        }
+        if body.new_password == body.current_password {
+            return Err(ChangePasswordRequestError::SamePassword);
+        }
+        Ok(Self {
+            account_id: account.id,
+            password_hash: body.new_password.hash(password_hash_algorithm)?,
+            revoke_tokens: body.revoke_tokens,
+        })
+    }
+}
+
+#[cfg(test)]
+mod change_password_tests {
+    use fake::{Fake, Faker};
+
+    use crate::routes::newtypes::Password;
+
+    use super::*;
+
+    fn setup() -> (Account, Password) {
+        let mut account: Account = Faker.fake();
+        let password: Password = Faker.fake();
+        account.password_hash = password.hash(PasswordHashAlgorithm::Argon2id).unwrap();
+        (account, password)
+    }
+
+    #[test]
+    fn test_change_password_request_from_body() {
+        let (account, password) = setup();
+        let new_password: Password = Faker.fake();
+
+        let request = ChangePasswordRequest::try_from_body(
+            ChangePasswordBody {
+                current_password: password,
+                new_password: new_password.clone(),
+                revoke_tokens: true,
+            },
+            &account,
+            PasswordHashAlgorithm::Bcrypt,
+        )
+        .unwrap();
+        assert_eq!(request.account_id, account.id);
+        assert!(request.revoke_tokens);
+        // The new password is hashed with the given algorithm
+        assert!(request.password_hash.starts_with("$2b$"));
+        assert!(new_password.verify(&request.password_hash).is_ok());
+    }
+
+    #[test]
+    fn test_change_password_request_from_body_with_invalid_password_must_fail() {
+        let (account, _) = setup();
+
+        let err = ChangePasswordRequest::try_from_body(
+            ChangePasswordBody {
+                current_password: Faker.fake(),
+                new_password: Faker.fake(),
+                revoke_tokens: true,
+            },
+            &account,
+            PasswordHashAlgorithm::Argon2id,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ChangePasswordRequestError::InvalidPassword));
+    }
+
+    #[test]
+    fn test_change_password_request_from_body_with_same_password_must_fail() {
+        let (account, password) = setup();
+
+        let err = ChangePasswordRequest::try_from_body(
+            ChangePasswordBody {
+                current_password: password.clone(),
+                new_password: password,
+                revoke_tokens: false,
+            },
+            &account,
+            PasswordHashAlgorithm::Argon2id,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ChangePasswordRequestError::SamePassword));
+    }
 }
@@ -0,0 +1,171 @@
+use crate::{
+    newtypes::{Email, Opaque},
+    rng::new_rng,
+};
+use anyhow::anyhow;
+use base64::prelude::*;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use ring::aead::{Aad, CHACHA20_POLY1305, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use sha3::Sha3_256;
+
+/// Prefix of the stored encrypted emails, it identifies the layout of the rest of the value
+///
+/// Version 1: base64 encoded nonce (12 bytes) followed by the ChaCha20-Poly1305 cyphertext of the email
+const ENCRYPTED_EMAIL_PREFIX_V1: &str = "enc:v1:";
+
+/// Labels of the keys derived from the configured key, each usage has its own key
+const LOOKUP_KEY_LABEL: &[u8] = b"soko-email-lookup";
+const ENCRYPTION_KEY_LABEL: &[u8] = b"soko-email-encryption";
+
+/// Protection of the stored emails, see [crate::Config::email_protection_key]
+///
+/// An email is stored encrypted, alongside a keyed hash of its normalized form used to look the account up.
+/// The lookup hash is deterministic so that the uniqueness of the emails is still enforced by the database.
+#[derive(Clone, Debug)]
+pub struct EmailProtection {
+    lookup_key: Opaque<[u8; 32]>,
+    encryption_key: Opaque<[u8; 32]>,
+}
+
+impl EmailProtection {
+    /// Derive the lookup and the encryption keys from the configured key
+    ///
+    /// # Arguments
+    /// * `key` - configured key, see [crate::Config::email_protection_key]
+    pub fn new(key: &Opaque<[u8; 32]>) -> Self {
+        Self {
+            lookup_key: Opaque::new(derive_key(key.extract_inner(), LOOKUP_KEY_LABEL)),
+            encryption_key: Opaque::new(derive_key(key.extract_inner(), ENCRYPTION_KEY_LABEL)),
+        }
+    }
+
+    /// Compute the lookup value of an email, i.e. the hex encoded HMAC-SHA3-256 of its lowercase form
+    pub fn lookup(&self, email: &Email) -> String {
+        let mut hmac = Hmac::<Sha3_256>::new_from_slice(self.lookup_key.extract_inner())
+            .expect("HMAC accepts keys of any size");
+        hmac.update(email.as_str().to_lowercase().as_bytes());
+        hmac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    /// Encrypt an email with a random nonce, the result is prefixed with its version, see [ENCRYPTED_EMAIL_PREFIX_V1]
+    pub fn encrypt(&self, email: &Email) -> Result<String, anyhow::Error> {
+        let mut nonce = [0u8; NONCE_LEN];
+        new_rng().fill_bytes(&mut nonce);
+
+        let mut in_out = email.as_str().as_bytes().to_vec();
+        self.cipher()
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut in_out,
+            )
+            .map_err(|_| anyhow!("failed to encrypt email"))?;
+
+        let mut cyphertext = nonce.to_vec();
+        cyphertext.extend_from_slice(&in_out);
+        Ok(format!(
+            "{ENCRYPTED_EMAIL_PREFIX_V1}{}",
+            BASE64_STANDARD_NO_PAD.encode(cyphertext)
+        ))
+    }
+
+    /// Decrypt a stored email, a value without the version prefix is returned as is, e.g. an email stored before the protection has been enabled
+    pub fn decrypt(&self, stored: &str) -> Result<Email, anyhow::Error> {
+        let Some(encoded) = stored.strip_prefix(ENCRYPTED_EMAIL_PREFIX_V1) else {
+            return Ok(Email::new_unchecked(stored));
+        };
+        let mut cyphertext = BASE64_STANDARD_NO_PAD
+            .decode(encoded)
+            .map_err(|e| anyhow!(e).context("failed to decode encrypted email from base64"))?;
+        if cyphertext.len() < NONCE_LEN {
+            return Err(anyhow!("invalid size for encrypted email"));
+        }
+        let (nonce, in_out) = cyphertext.split_at_mut(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce)
+            .map_err(|_| anyhow!("invalid nonce for encrypted email"))?;
+        let plaintext = self
+            .cipher()
+            .open_in_place(nonce, Aad::empty(), in_out)
+            .map_err(|_| anyhow!("failed to decrypt email"))?;
+        let email = std::str::from_utf8(plaintext)
+            .map_err(|e| anyhow!(e).context("decrypted email is not valid UTF-8"))?;
+        Ok(Email::new_unchecked(email))
+    }
+
+    fn cipher(&self) -> LessSafeKey {
+        LessSafeKey::new(
+            UnboundKey::new(&CHACHA20_POLY1305, self.encryption_key.extract_inner())
+                .expect("ChaCha20-Poly1305 keys are 32 bytes long"),
+        )
+    }
+}
+
+fn derive_key(key: &[u8; 32], label: &[u8]) -> [u8; 32] {
+    let mut hmac = Hmac::<Sha3_256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    hmac.update(label);
+    hmac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn protection(byte: u8) -> EmailProtection {
+        EmailProtection::new(&Opaque::new([byte; 32]))
+    }
+
+    #[test]
+    fn test_lookup_ignores_case() {
+        let protection = protection(1);
+        let lookup = protection.lookup(&Email::new_unchecked("jane@example.com"));
+
+        assert_eq!(lookup.len(), 64);
+        assert_eq!(
+            lookup,
+            protection.lookup(&Email::new_unchecked("Jane@Example.com"))
+        );
+        assert_ne!(
+            lookup,
+            protection.lookup(&Email::new_unchecked("john@example.com"))
+        );
+        assert_ne!(
+            lookup,
+            self::protection(2).lookup(&Email::new_unchecked("jane@example.com"))
+        );
+    }
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let protection = protection(1);
+        let email = Email::new_unchecked("jane@example.com");
+
+        let encrypted = protection.encrypt(&email).unwrap();
+        assert!(encrypted.starts_with(ENCRYPTED_EMAIL_PREFIX_V1));
+        assert!(!encrypted.contains("jane"));
+        assert_ne!(encrypted, protection.encrypt(&email).unwrap());
+
+        assert_eq!(protection.decrypt(&encrypted).unwrap(), email);
+        assert_eq!(protection.clone().decrypt(&encrypted).unwrap(), email);
+        assert!(self::protection(2).decrypt(&encrypted).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_plaintext_and_tampered_values() {
+        let protection = protection(1);
+        let email = Email::new_unchecked("jane@example.com");
+
+        assert_eq!(protection.decrypt("jane@example.com").unwrap(), email);
+
+        let encrypted = protection.encrypt(&email).unwrap();
+        let mut tampered = encrypted.clone();
+        tampered.pop();
+        tampered.push(if encrypted.ends_with('A') { 'B' } else { 'A' });
+        assert!(protection.decrypt(&tampered).is_err());
+        assert!(protection.decrypt("enc:v1:AAAA").is_err());
+    }
+}
@@ -1,30 +1,81 @@
-use axum::{Json, Router, extract::State, http::StatusCode, routing::post};
+use axum::{
+    Json, Router,
+    extract::State,
+    http::{
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
+        header::{ETAG, IF_MATCH},
+    },
+    routing::{get, post, put},
+};
 use chrono::{DateTime, Utc};
+use metrics::counter;
 use serde::{Deserialize, Serialize};
-use tracing::error;
+use tracing::{error, info, warn};
 use validator::{Validate, ValidationError, ValidationErrors};
 
 mod domain;
-pub use domain::Account;
+pub(crate) use domain::AccountQueryError;
+pub use domain::VerifyAccountError;
+pub use domain::{Account, AccountState};
+pub(crate) use domain::{AccountMerge, MergeAccountsError};
+pub(crate) use domain::{InviteCode, generate_invite_code};
+
 use domain::{
-    AccountQueryError, SignupError, SignupRequest, SignupRequestError, VerifyAccountError,
-    VerifyAccountRequest, VerifyAccountRequestError,
+    ChangePasswordRequest, ChangePasswordRequestError, RequestPasswordResetError,
+    RequestPasswordResetRequest, RequestPasswordResetRequestError, ResendVerificationRequestError,
+    ResetPasswordError, ResetPasswordRequest, ResetPasswordRequestError, SignupError,
+    SignupRequest, SignupRequestError, UpdateProfileError, UpdateProfileRequest,
+    UpdateProfileRequestError, VerifyAccountRequest, VerifyAccountRequestError,
+};
+pub use domain::{
+    DEFAULT_MAX_VERIFICATION_ATTEMPTS, DEFAULT_VERIFICATION_TICKET_LIFETIME,
+    MAX_DISPLAY_NAME_LENGTH, PASSWORD_RESET_REQUEST_INTERVAL, PASSWORD_RESET_TICKET_LIFETIME,
+    RESEND_VERIFICATION_INTERVAL,
 };
+pub use domain::{ResendVerificationError, ResendVerificationRequest};
+
+mod email_protection;
+pub use email_protection::EmailProtection;
 
 mod repository;
 pub use repository::{AccountRepository, PostgresAccountRepository};
 
-use super::{ApiError, ValidatedJson};
-use crate::newtypes::Email;
+use super::{
+    ApiError, CurrentAccount, ErrorResponse, RecentlyAuthenticatedAccount, Timestamped,
+    ValidatedJson, deserialize_present, ensure_active, timestamp,
+    tokens::{
+        AccessTokenCreatedResponse, CreateAccessTokenRequest, CreateAccessTokenRequestError,
+        CreateRefreshTokenRequest, DEFAULT_LIFETIME, DEFAULT_NAME, MAX_ACTIVE_SESSIONS,
+        MAX_ACTIVE_TOKENS, RefreshTokenCreatedResponse, SESSION_ACCESS_TOKEN_LIFETIME,
+        SessionCreatedResponse,
+    },
+};
+use crate::{
+    database::commit_transaction,
+    newtypes::Email,
+    observability::{
+        FAILED_PASSWORD_COUNTER, SIGNUP_COUNTER, TOKEN_CREATION_COUNTER, VERIFY_EMAIL_COUNTER,
+        record_outcome,
+    },
+    third_party::AccountVerifiedEvent,
+};
 
 use super::AppState;
 mod verification_secret_strategy;
-use super::newtypes::Password;
+use super::newtypes::{CharacterClass, MAX_PASSWORD_LENGTH, MIN_PASSWORD_LENGTH, Password};
+use verification_secret_strategy::VerificationSecretStrategy;
 
 pub fn accounts_router() -> Router<AppState> {
     Router::new()
         .route("/signup", post(signup_account))
         .route("/verify-email", post(verify_email))
+        .route("/login", post(login))
+        .route("/resend-verification", post(resend_verification))
+        .route("/request-password-reset", post(request_password_reset))
+        .route("/reset-password", post(reset_password))
+        .route("/password", put(change_password))
+        .route("/me", get(get_current_account).patch(update_profile))
+        .route("/password-policy", get(get_password_policy))
 }
 
 // ############################################
@@ -35,7 +86,28 @@ impl From<AccountQueryError> for ApiError {
     fn from(value: AccountQueryError) -> Self {
         match value {
             AccountQueryError::AccountNotFound => ApiError::NotFound,
-            AccountQueryError::Unknown(e) => ApiError::InternalServerError(e),
+            AccountQueryError::Unknown(e) => e.into(),
+        }
+    }
+}
+
+impl From<MergeAccountsError> for ApiError {
+    fn from(value: MergeAccountsError) -> Self {
+        match value {
+            MergeAccountsError::SameAccount => ApiError::bad_request(
+                "targetAccountId",
+                "same-account",
+                "The target account must differ from the source account",
+            ),
+            MergeAccountsError::AccountNotFound { .. } => ApiError::NotFound,
+            MergeAccountsError::ActiveTokenLimitExceeded(max_active_token) => ApiError::conflict(
+                "sourceAccountId",
+                "too-many-tokens",
+                format!(
+                    "The merged account would have more than {max_active_token} active access tokens"
+                ),
+            ),
+            MergeAccountsError::Unknown(e) => e.into(),
         }
     }
 }
@@ -48,14 +120,31 @@ impl From<AccountQueryError> for ApiError {
 #[serde(rename_all = "camelCase")]
 pub struct AccountResponse {
     pub email: Email,
+    pub display_name: Option<String>,
+    /// False while the verification secret sent at signup has not been confirmed
+    pub verified: bool,
+    #[serde(with = "timestamp")]
     pub created_at: DateTime<Utc>,
+    #[serde(with = "timestamp")]
     pub updated_at: DateTime<Utc>,
 }
 
+impl Timestamped for AccountResponse {
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+}
+
 impl From<domain::Account> for AccountResponse {
     fn from(value: domain::Account) -> Self {
         AccountResponse {
             email: value.email,
+            display_name: value.display_name,
+            verified: value.verified,
             created_at: value.created_at,
             updated_at: value.updated_at,
         }
@@ -71,14 +160,64 @@ impl From<domain::Account> for AccountResponse {
 pub struct SignupBody {
     pub email: Email,
     pub password: Password,
+    #[serde(default)]
+    pub channel: VerificationChannel,
+    /// Phone number in E.164 format, required for the SMS channel
+    pub phone_number: Option<String>,
+    /// Single-use invite code, required if `SIGNUP_REQUIRE_INVITE` is enabled
+    pub invite_code: Option<String>,
+}
+
+/// Channel through which the verification secret is sent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "verification_channel", rename_all = "lowercase")]
+pub enum VerificationChannel {
+    #[default]
+    Email,
+    Sms,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignupResponse {
+    #[serde(flatten)]
+    pub account: AccountResponse,
+    /// True if the email was already associated with an unverified account, the verification secret has then been sent again
+    pub resent: bool,
+    /// Plaintext verification secret, only returned if `DEV_RETURN_VERIFICATION_SECRET` is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification_secret: Option<String>,
 }
 
 async fn signup_account(
     State(app_state): State<AppState>,
     ValidatedJson(body): ValidatedJson<SignupBody>,
-) -> Result<(StatusCode, Json<AccountResponse>), ApiError> {
+) -> Result<(StatusCode, Json<SignupResponse>), ApiError> {
+    // A new account and a reset account creation do not perform the same work,
+    // every signup lasts at least the minimum duration so that its latency does not tell whether the email was registered
+    let deadline = tokio::time::Instant::now() + app_state.config.security.signup_min_duration;
+    let result = signup(app_state, body).await;
+    record_outcome(SIGNUP_COUNTER, &result);
+    tokio::time::sleep_until(deadline).await;
+    result
+}
+
+async fn signup(
+    app_state: AppState,
+    body: SignupBody,
+) -> Result<(StatusCode, Json<SignupResponse>), ApiError> {
+    body.password
+        .ensure_prehash_mode(app_state.config.password_prehash)?;
+    body.password
+        .ensure_policy(&app_state.config.password_policy)?;
+    if app_state.config.password_reject_email {
+        body.password.ensure_unrelated_to_email(&body.email)?;
+    }
+
     let signup_request: SignupRequest;
     let signed_up_account: Account;
+    let resent: bool;
 
     let existing_account_opt = match app_state
         .account_repository
@@ -95,43 +234,94 @@ async fn signup_account(
         }
     };
 
+    // Password hashing and verification secret derivation are bounded, see [crate::hashing::HashingLimiter]
+    let autoverify_domains = app_state.config.verification_autoverify_domains.clone();
+    let require_invite_code = app_state.config.signup_require_invite;
+    let password_hash_algorithm = app_state.config.password_hash_algorithm;
     if let Some(existing_account) = existing_account_opt {
-        signup_request =
-            SignupRequest::try_from_body_with_existing_account(existing_account, body)?;
+        resent = true;
+        SignupRequest::ensure_outside_debounce_window(
+            &existing_account,
+            app_state.clock.now(),
+            app_state.config.security.signup_debounce,
+        )?;
+        signup_request = app_state
+            .hashing_limiter
+            .run(move || {
+                SignupRequest::try_from_body_with_existing_account(
+                    existing_account,
+                    body,
+                    &autoverify_domains,
+                    require_invite_code,
+                    password_hash_algorithm,
+                )
+            })
+            .await??;
 
         signed_up_account = app_state
             .account_repository
-            .reset_account_creation(&signup_request)
+            .reset_account_creation(&signup_request, app_state.config.security.signup_debounce)
             .await?;
     } else {
-        signup_request = SignupRequest::try_from_body(body)?;
+        resent = false;
+        signup_request = app_state
+            .hashing_limiter
+            .run(move || {
+                SignupRequest::try_from_body(
+                    body,
+                    &autoverify_domains,
+                    require_invite_code,
+                    password_hash_algorithm,
+                )
+            })
+            .await??;
         signed_up_account = app_state
             .account_repository
             .create_account(&signup_request)
             .await?
     };
 
-    if let Err(e) = app_state
-        .mailing_service
-        .send_email(
+    if signup_request.auto_verified {
+        warn!(
+            "account with email \"{}\" has been verified without email round-trip",
+            &signup_request.email
+        );
+    } else {
+        send_verification_secret(
+            &app_state,
             &signup_request.email,
+            signup_request.channel,
+            signup_request.phone_number.as_deref(),
             &signup_request.verification_plaintext,
         )
-        .await
-    {
-        error!(
-            "failed to send email to email \"{}\" with error {e}",
-            &signup_request.email
-        );
+        .await;
     }
 
-    Ok((StatusCode::CREATED, Json(signed_up_account.into())))
+    let verification_secret = (app_state.config.dev_return_verification_secret
+        && !signup_request.auto_verified)
+        .then_some(signup_request.verification_plaintext);
+
+    Ok((
+        StatusCode::CREATED,
+        Json(SignupResponse {
+            account: signed_up_account.into(),
+            resent,
+            verification_secret,
+        }),
+    ))
 }
 
 impl From<SignupError> for ApiError {
     fn from(value: SignupError) -> Self {
         match value {
-            SignupError::Unknown(e) => ApiError::InternalServerError(e),
+            SignupError::EmailAlreadyExists => ApiError::conflict(
+                "email",
+                "existing-email",
+                "Email is already associated with an account",
+            ),
+            SignupError::InvalidInviteCode => invalid_invite_code(),
+            SignupError::DuplicateSignup { retry_after_secs } => duplicate_signup(retry_after_secs),
+            SignupError::Unknown(e) => e.into(),
         }
     }
 }
@@ -139,52 +329,245 @@ impl From<SignupError> for ApiError {
 impl From<SignupRequestError> for ApiError {
     fn from(value: SignupRequestError) -> ApiError {
         match value {
-            SignupRequestError::Unknown(e) => ApiError::InternalServerError(e),
-            SignupRequestError::AccountAlreadyVerified { email: _email } => {
-                let mut errors = ValidationErrors::new();
-                errors.add(
-                    "email",
-                    ValidationError::new("existing-email")
-                        .with_message("Email is already associated with a verified account".into()),
-                );
-                ApiError::BadRequest(errors)
+            SignupRequestError::Unknown(e) => e.into(),
+            SignupRequestError::AccountAlreadyVerified { email: _email } => ApiError::bad_request(
+                "email",
+                "existing-email",
+                "Email is already associated with a verified account",
+            ),
+            SignupRequestError::InvalidPhoneNumber => ApiError::bad_request(
+                "phoneNumber",
+                "invalid-phone-number",
+                "A phone number in E.164 format is required for the SMS channel",
+            ),
+            SignupRequestError::MissingInviteCode => invalid_invite_code(),
+            SignupRequestError::DuplicateSignup { retry_after_secs } => {
+                duplicate_signup(retry_after_secs)
+            }
+        }
+    }
+}
+
+fn duplicate_signup(retry_after_secs: u64) -> ApiError {
+    ApiError::TooManyRequests {
+        error: ErrorResponse::new(
+            "duplicate_signup",
+            "A signup has just been submitted for this email, check your inbox or retry later",
+        ),
+        retry_after_secs,
+    }
+}
+
+fn invalid_invite_code() -> ApiError {
+    ApiError::bad_request(
+        "inviteCode",
+        "invalid-invite-code",
+        "A valid and unused invite code is required to sign up",
+    )
+}
+
+/// Send the verification secret through the channel of the verification ticket, a failure is logged and does not fail the request
+///
+/// # Arguments
+/// * `email` - email of the account,
+/// * `channel` - channel of the verification ticket,
+/// * `phone_number` - phone number of the verification ticket, required for the SMS channel,
+/// * `verification_plaintext` - plaintext verification secret
+async fn send_verification_secret(
+    app_state: &AppState,
+    email: &Email,
+    channel: VerificationChannel,
+    phone_number: Option<&str>,
+    verification_plaintext: &str,
+) {
+    match (channel, phone_number) {
+        (VerificationChannel::Sms, Some(phone_number)) => {
+            if let Err(e) = app_state
+                .sms_service
+                .send_sms(phone_number, verification_plaintext)
+                .await
+            {
+                error!("failed to send SMS for email \"{email}\" with error {e}");
+            }
+        }
+        (VerificationChannel::Sms, None) => {
+            error!("no phone number to send the verification SMS for email \"{email}\"");
+        }
+        (VerificationChannel::Email, _) => {
+            if let Err(e) = app_state
+                .mailing_service
+                .send_verification_email(email, verification_plaintext)
+                .await
+            {
+                error!("failed to send email to email \"{email}\" with error {e}");
             }
         }
     }
 }
 
+// #########################################################
+// ################## RESEND VERIFICATION ##################
+// #########################################################
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResendVerificationBody {
+    pub email: Email,
+}
+
+/// The response does not tell whether the email is registered
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResendVerificationResponse {
+    /// Plaintext verification secret, only returned if `DEV_RETURN_VERIFICATION_SECRET` is enabled and a secret has been sent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification_secret: Option<String>,
+}
+
+/// Send a new verification secret to an unverified account, the previous secret is invalidated
+///
+/// Unlike a repeated signup, the password of the account is left untouched. The secret is sent through the channel chosen at signup.
+/// Unknown emails, verified accounts and resends within [RESEND_VERIFICATION_INTERVAL] of the previous secret are accepted without sending anything so that the email registration is not disclosed.
+async fn resend_verification(
+    State(app_state): State<AppState>,
+    ValidatedJson(body): ValidatedJson<ResendVerificationBody>,
+) -> Result<(StatusCode, Json<ResendVerificationResponse>), ApiError> {
+    // Verification secret derivation is bounded, see [crate::hashing::HashingLimiter]
+    // Every request costs a derivation so that the latency does not tell whether the email is registered
+    let (existing_account, verification_ticket) = match app_state
+        .account_repository
+        .get_account_by_email_with_verification_ticket(&body.email)
+        .await
+    {
+        Ok(v) => v,
+        Err(AccountQueryError::AccountNotFound) => {
+            app_state
+                .hashing_limiter
+                .run(VerificationSecretStrategy::generate_dummy_verification_secret)
+                .await??;
+            return Ok((StatusCode::OK, Json(ResendVerificationResponse::default())));
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let now = app_state.clock.now();
+    let resend_verification_request = match app_state
+        .hashing_limiter
+        .run(move || {
+            ResendVerificationRequest::try_from_account(
+                existing_account,
+                verification_ticket,
+                now,
+                RESEND_VERIFICATION_INTERVAL,
+            )
+        })
+        .await?
+    {
+        Ok(v) => v,
+        Err(ResendVerificationRequestError::AccountAlreadyVerified { .. }) => {
+            return Ok((StatusCode::OK, Json(ResendVerificationResponse::default())));
+        }
+        Err(ResendVerificationRequestError::TooSoon { retry_after_secs }) => {
+            warn!(
+                "verification of email \"{}\" resent again within {retry_after_secs} seconds, no secret has been sent",
+                &body.email
+            );
+            return Ok((StatusCode::OK, Json(ResendVerificationResponse::default())));
+        }
+        Err(ResendVerificationRequestError::Unknown(e)) => return Err(e.into()),
+    };
+
+    match app_state
+        .account_repository
+        .regenerate_verification_ticket(&resend_verification_request, RESEND_VERIFICATION_INTERVAL)
+        .await
+    {
+        Ok(_) => {}
+        // A concurrent verification or resend
+        Err(
+            ResendVerificationError::AccountAlreadyVerified { .. }
+            | ResendVerificationError::TooSoon { .. },
+        ) => {
+            return Ok((StatusCode::OK, Json(ResendVerificationResponse::default())));
+        }
+        Err(ResendVerificationError::Unknown(e)) => return Err(e.into()),
+    };
+
+    send_verification_secret(
+        &app_state,
+        &resend_verification_request.email,
+        resend_verification_request.channel,
+        resend_verification_request.phone_number.as_deref(),
+        &resend_verification_request.verification_plaintext,
+    )
+    .await;
+
+    let verification_secret = app_state
+        .config
+        .dev_return_verification_secret
+        .then_some(resend_verification_request.verification_plaintext);
+
+    Ok((
+        StatusCode::OK,
+        Json(ResendVerificationResponse {
+            verification_secret,
+        }),
+    ))
+}
+
 // ####################################################
 // ################## VERIFY ACCOUNT ##################
 // ####################################################
 
-#[derive(Debug, Validate, Serialize, Deserialize)]
+#[derive(Debug, Clone, Validate, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VerifyAccountBody {
     pub email: Email,
     #[validate(length(min = 1))]
     pub secret: String,
+    /// If true, a first access token is issued along with the verification
+    #[serde(default)]
+    pub issue_token: bool,
+    /// Name of the issued access token, defaults to [DEFAULT_NAME]
+    pub token_name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyAccountResponse {
+    #[serde(flatten)]
+    pub account: AccountResponse,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<AccessTokenCreatedResponse>,
 }
 
 impl From<VerifyAccountRequestError> for ApiError {
     fn from(value: VerifyAccountRequestError) -> Self {
         match value {
-            VerifyAccountRequestError::Unknown(e) => ApiError::InternalServerError(e),
+            VerifyAccountRequestError::Unknown(e) => e.into(),
             VerifyAccountRequestError::AccountAlreadyVerified { email: _email } => {
-                let mut errors = ValidationErrors::new();
-                errors.add(
-                    "email",
-                    ValidationError::new("email-verified")
-                        .with_message("Account is already verified".into()),
-                );
-                ApiError::BadRequest(errors)
+                ApiError::bad_request("email", "email-verified", "Account is already verified")
+            }
+            VerifyAccountRequestError::InvalidVerificationSecret
+            | VerifyAccountRequestError::WrongVerificationSecret { .. } => {
+                ApiError::bad_request("secret", "secret-validity", "Secret is invalid")
             }
-            VerifyAccountRequestError::InvalidVerificationSecret => {
+            VerifyAccountRequestError::ExpiredVerificationSecret => ApiError::bad_request(
+                "secret",
+                "secret-expired",
+                "Secret has expired, request a new secret using `POST /accounts/resend-verification`",
+            ),
+            VerifyAccountRequestError::FailedAttempt { remaining_attempts } => {
+                let mut error = if remaining_attempts == 0 {
+                    ValidationError::new("secret-invalidated").with_message(
+                        "Secret is invalid, the verification has been invalidated after too many failed attempts, request a new secret using `POST /accounts/resend-verification`".into(),
+                    )
+                } else {
+                    ValidationError::new("secret-validity").with_message("Secret is invalid".into())
+                };
+                error.add_param("remaining_attempts".into(), &remaining_attempts);
                 let mut errors = ValidationErrors::new();
-                errors.add(
-                    "secret",
-                    ValidationError::new("secret-validity")
-                        .with_message("Secret is invalid".into()),
-                );
+                errors.add("secret", error);
                 ApiError::BadRequest(errors)
             }
         }
@@ -194,7 +577,13 @@ impl From<VerifyAccountRequestError> for ApiError {
 impl From<VerifyAccountError> for ApiError {
     fn from(value: VerifyAccountError) -> Self {
         match value {
-            VerifyAccountError::Unknown(e) => ApiError::InternalServerError(e),
+            VerifyAccountError::Unknown(e) => e.into(),
+            VerifyAccountError::AccountAlreadyVerified {
+                account_id: _account_id,
+            } => ApiError::conflict("email", "email-verified", "Account is already verified"),
+            VerifyAccountError::NoActiveVerificationTicket {
+                account_id: _account_id,
+            } => ApiError::conflict("secret", "secret-validity", "Secret is no longer valid"),
         }
     }
 }
@@ -202,19 +591,742 @@ impl From<VerifyAccountError> for ApiError {
 async fn verify_email(
     State(app_state): State<AppState>,
     ValidatedJson(body): ValidatedJson<VerifyAccountBody>,
-) -> Result<(StatusCode, Json<AccountResponse>), ApiError> {
-    let (existing_account, verification_ticket) = app_state
+) -> Result<(StatusCode, Json<VerifyAccountResponse>), ApiError> {
+    let result = verify(app_state, body).await;
+    record_outcome(VERIFY_EMAIL_COUNTER, &result);
+    result
+}
+
+async fn verify(
+    app_state: AppState,
+    body: VerifyAccountBody,
+) -> Result<(StatusCode, Json<VerifyAccountResponse>), ApiError> {
+    let (existing_account, verification_ticket) = match app_state
         .account_repository
         .get_account_by_email_with_verification_ticket(&body.email)
-        .await?;
+        .await
+    {
+        Ok(v) => v,
+        // An unknown email fails as a wrong secret so that the response does not tell whether the email is registered,
+        // the secret is verified against a dummy secret so that the latency does not tell it either
+        Err(AccountQueryError::AccountNotFound)
+            if app_state.config.security.verify_nonenumeration =>
+        {
+            app_state
+                .hashing_limiter
+                .run(move || {
+                    VerificationSecretStrategy::verify_dummy_verification_secret(&body.secret)
+                })
+                .await?;
+            return Err(VerifyAccountRequestError::InvalidVerificationSecret.into());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    // A retried verification, e.g. after a lost response, succeeds again without issuing an access token
+    if existing_account.verified {
+        let confirmed_ticket = app_state
+            .account_repository
+            .get_last_confirmed_verification_ticket(existing_account.id)
+            .await?;
+        let now = app_state.clock.now();
+        let clock_skew_tolerance = app_state.config.clock_skew_tolerance;
+        let ticket_lifetime = app_state.config.verification_ticket_lifetime;
+        let verify_nonenumeration = app_state.config.security.verify_nonenumeration;
+        let existing_account = app_state
+            .hashing_limiter
+            .run(move || {
+                VerifyAccountRequest::verify_replay(
+                    &body,
+                    &existing_account,
+                    confirmed_ticket,
+                    now,
+                    clock_skew_tolerance,
+                    ticket_lifetime,
+                )
+                .map(|_| existing_account)
+            })
+            .await?
+            .map_err(|e| match e {
+                // A failed replay would tell that the email belongs to a verified account
+                VerifyAccountRequestError::AccountAlreadyVerified { .. }
+                    if verify_nonenumeration =>
+                {
+                    VerifyAccountRequestError::InvalidVerificationSecret
+                }
+                e => e,
+            })?;
+        return Ok((
+            StatusCode::OK,
+            Json(VerifyAccountResponse {
+                account: existing_account.into(),
+                access_token: None,
+            }),
+        ));
+    }
+
+    // The access token request is built before the verification so that an invalid token name does not leave the account verified without token
+    let create_access_token_request = if body.issue_token {
+        Some(CreateAccessTokenRequest::try_new(
+            &existing_account,
+            body.token_name.as_deref().unwrap_or(DEFAULT_NAME),
+            // The first access token must comply with the account policy, if an admin already set one
+            DEFAULT_LIFETIME.min(existing_account.max_token_lifetime()),
+            &app_state.config.access_token_secret,
+            app_state.config.access_token_bytes,
+        )?)
+    } else {
+        None
+    };
+
+    let now = app_state.clock.now();
+    let clock_skew_tolerance = app_state.config.clock_skew_tolerance;
+    let ticket_lifetime = app_state.config.verification_ticket_lifetime;
+    let verify_account_request = match app_state
+        .hashing_limiter
+        .run(move || {
+            VerifyAccountRequest::try_from_body(
+                body,
+                existing_account,
+                verification_ticket,
+                now,
+                clock_skew_tolerance,
+                ticket_lifetime,
+            )
+        })
+        .await?
+    {
+        Ok(v) => v,
+        Err(VerifyAccountRequestError::WrongVerificationSecret { ticket_id }) => {
+            let remaining_attempts = app_state
+                .account_repository
+                .record_failed_verification_attempt(
+                    ticket_id,
+                    app_state.config.verification_max_attempts,
+                )
+                .await?;
+            if remaining_attempts == 0 {
+                warn!("verification ticket {ticket_id} invalidated after too many failed attempts");
+            }
+            // The remaining attempts would tell that the email is registered
+            if app_state.config.security.verify_nonenumeration {
+                return Err(VerifyAccountRequestError::InvalidVerificationSecret.into());
+            }
+            return Err(VerifyAccountRequestError::FailedAttempt { remaining_attempts }.into());
+        }
+        Err(e) => return Err(e.into()),
+    };
 
-    let verify_account_request =
-        VerifyAccountRequest::try_from_body(body, existing_account, verification_ticket)?;
+    // The verification and the access token creation are committed together, a failed access token creation rolls back the verification
+    let mut transaction = app_state.account_repository.begin_transaction().await?;
 
     let updated_account = app_state
         .account_repository
-        .verify_account(verify_account_request.account_id)
+        .verify_account_in_transaction(
+            &mut transaction,
+            verify_account_request.account_id,
+            verify_account_request.ticket_id,
+        )
+        .await?;
+
+    // An access token is only issued on the transition to verified, a failed verification returns early
+    let access_token = match create_access_token_request {
+        Some(req) => {
+            let access_token = app_state
+                .access_token_repository
+                .create_token_in_transaction(&mut transaction, &req, MAX_ACTIVE_TOKENS)
+                .await?;
+            Some(AccessTokenCreatedResponse::new(access_token, req.token))
+        }
+        None => None,
+    };
+
+    commit_transaction(transaction).await?;
+
+    // The webhook is notified in the background, a failed delivery does not fail the verification
+    let event = AccountVerifiedEvent {
+        account_id: updated_account.id,
+        email: updated_account.email.clone(),
+        verified_at: updated_account.updated_at,
+    };
+    let webhook_notifier = app_state.webhook_notifier.clone();
+    tokio::spawn(async move {
+        if let Err(e) = webhook_notifier.notify_account_verified(&event).await {
+            warn!(
+                "verification webhook of account {} failed: {e:?}",
+                event.account_id
+            );
+        }
+    });
+
+    Ok((
+        StatusCode::OK,
+        Json(VerifyAccountResponse {
+            account: updated_account.into(),
+            access_token,
+        }),
+    ))
+}
+
+// ###########################################
+// ################## LOGIN ##################
+// ###########################################
+
+#[derive(Debug, Clone, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginBody {
+    pub email: Email,
+    pub password: Password,
+}
+
+/// Authenticate a verified account with its email and password, an access token with the default lifetime is issued
+///
+/// If refresh tokens are enabled, the access token lives for [SESSION_ACCESS_TOKEN_LIFETIME] and is issued along a refresh token, see `POST /tokens/refresh`.
+/// A login never fails on the number of active access tokens, beyond [MAX_ACTIVE_SESSIONS] the oldest session is ended.
+///
+/// An unknown email, an unverified or deactivated account and a wrong password fail alike with a `401`, the password is verified in every case so that the latency does not tell them apart.
+/// A locked account fails with a `403` once its password has been verified.
+async fn login(
+    State(app_state): State<AppState>,
+    ValidatedJson(body): ValidatedJson<LoginBody>,
+) -> Result<(StatusCode, Json<SessionCreatedResponse>), ApiError> {
+    let result = log_in(app_state, body).await;
+    record_outcome(TOKEN_CREATION_COUNTER, &result);
+    result
+}
+
+async fn log_in(
+    app_state: AppState,
+    body: LoginBody,
+) -> Result<(StatusCode, Json<SessionCreatedResponse>), ApiError> {
+    body.password
+        .ensure_prehash_mode(app_state.config.password_prehash)?;
+
+    let account = match app_state
+        .account_repository
+        .get_verified_account_by_email(&body.email)
+        .await
+    {
+        Ok(account) => Some(account),
+        Err(AccountQueryError::AccountNotFound) => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    let account_found = account.is_some();
+    let account_state = account
+        .as_ref()
+        .map(|account| account.state(app_state.clock.now()));
+    let config = app_state.config.clone();
+    let lifetime = match config.refresh_token_lifetime {
+        Some(_) => SESSION_ACCESS_TOKEN_LIFETIME,
+        None => DEFAULT_LIFETIME,
+    };
+    let req = app_state
+        .hashing_limiter
+        .run(move || {
+            CreateAccessTokenRequest::try_from_login(
+                &body.password,
+                account.as_ref(),
+                lifetime,
+                &config.access_token_secret,
+                config.access_token_bytes,
+                config.password_hash_algorithm,
+            )
+        })
+        .await?
+        .inspect_err(|e| {
+            if account_found && matches!(e, CreateAccessTokenRequestError::InvalidPassword) {
+                counter!(FAILED_PASSWORD_COUNTER).increment(1);
+            }
+        })?;
+    // The lock is only disclosed once the password has been verified
+    if let Some(account_state) = account_state {
+        ensure_active(account_state)?;
+    }
+
+    let Some(refresh_token_lifetime) = app_state.config.refresh_token_lifetime else {
+        let access_token = app_state
+            .access_token_repository
+            .create_token(&req, MAX_ACTIVE_SESSIONS)
+            .await?;
+        return Ok((
+            StatusCode::OK,
+            Json(SessionCreatedResponse {
+                access_token: AccessTokenCreatedResponse::new(access_token, req.token),
+                refresh_token: None,
+            }),
+        ));
+    };
+
+    let refresh_req = CreateRefreshTokenRequest::try_new(
+        req.account_id,
+        refresh_token_lifetime,
+        &app_state.config.access_token_secret,
+        app_state.config.access_token_bytes,
+    )?;
+    let (access_token, refresh_token) = app_state
+        .access_token_repository
+        .create_token_with_refresh_token(&req, &refresh_req, MAX_ACTIVE_SESSIONS)
+        .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(SessionCreatedResponse {
+            access_token: AccessTokenCreatedResponse::new(access_token, req.token),
+            refresh_token: Some(RefreshTokenCreatedResponse::new(
+                refresh_token,
+                refresh_req.token,
+            )),
+        }),
+    ))
+}
+
+// ####################################################
+// ################## PASSWORD RESET ##################
+// ####################################################
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestPasswordResetBody {
+    pub email: Email,
+}
+
+/// Send a password reset code to a verified account, the previous code is invalidated
+///
+/// The response is always a `200` so that the email registration is not disclosed,
+/// unknown emails, unverified accounts and requests within [PASSWORD_RESET_REQUEST_INTERVAL] of the previous one send nothing.
+async fn request_password_reset(
+    State(app_state): State<AppState>,
+    ValidatedJson(body): ValidatedJson<RequestPasswordResetBody>,
+) -> Result<StatusCode, ApiError> {
+    // Reset code derivation is bounded, see [crate::hashing::HashingLimiter]
+    // Every request costs a derivation so that the latency does not tell whether the email is registered
+    let (existing_account, reset_ticket) = match app_state
+        .account_repository
+        .get_account_by_email_with_password_reset_ticket(&body.email)
+        .await
+    {
+        Ok(v) => v,
+        Err(AccountQueryError::AccountNotFound) => {
+            app_state
+                .hashing_limiter
+                .run(VerificationSecretStrategy::generate_dummy_verification_secret)
+                .await??;
+            return Ok(StatusCode::OK);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let now = app_state.clock.now();
+    let request_password_reset_request = match app_state
+        .hashing_limiter
+        .run(move || {
+            RequestPasswordResetRequest::try_from_account(
+                existing_account,
+                reset_ticket.map(|ticket| ticket.created_at),
+                now,
+                PASSWORD_RESET_REQUEST_INTERVAL,
+            )
+        })
+        .await?
+    {
+        Ok(v) => v,
+        Err(RequestPasswordResetRequestError::AccountNotVerified { .. }) => {
+            return Ok(StatusCode::OK);
+        }
+        Err(RequestPasswordResetRequestError::TooSoon { retry_after_secs }) => {
+            warn!(
+                "password reset of email \"{}\" requested again within {retry_after_secs} seconds, no code has been sent",
+                &body.email
+            );
+            return Ok(StatusCode::OK);
+        }
+        Err(RequestPasswordResetRequestError::Unknown(e)) => return Err(e.into()),
+    };
+
+    match app_state
+        .account_repository
+        .create_password_reset_ticket(
+            &request_password_reset_request,
+            PASSWORD_RESET_REQUEST_INTERVAL,
+        )
+        .await
+    {
+        Ok(()) => {}
+        Err(RequestPasswordResetError::TooSoon { retry_after_secs }) => {
+            warn!(
+                "password reset of email \"{}\" requested again within {retry_after_secs} seconds, no code has been sent",
+                &request_password_reset_request.email
+            );
+            return Ok(StatusCode::OK);
+        }
+        Err(RequestPasswordResetError::Unknown(e)) => return Err(e.into()),
+    };
+
+    if let Err(e) = app_state
+        .mailing_service
+        .send_password_reset_email(
+            &request_password_reset_request.email,
+            &request_password_reset_request.reset_plaintext,
+        )
+        .await
+    {
+        error!(
+            "failed to send email to email \"{}\" with error {e}",
+            &request_password_reset_request.email
+        );
+    }
+
+    Ok(StatusCode::OK)
+}
+
+#[derive(Debug, Clone, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResetPasswordBody {
+    pub email: Email,
+    #[validate(length(min = 1))]
+    pub code: String,
+    pub new_password: Password,
+}
+
+/// Reset the password of a verified account with the code sent by `POST /accounts/request-password-reset`
+///
+/// The code can only be used once, an unknown email fails as a wrong code so that the response does not tell whether the email is registered.
+/// The access tokens and the refresh tokens of the account are revoked along with the reset.
+async fn reset_password(
+    State(app_state): State<AppState>,
+    ValidatedJson(body): ValidatedJson<ResetPasswordBody>,
+) -> Result<(StatusCode, Json<AccountResponse>), ApiError> {
+    body.new_password
+        .ensure_prehash_mode(app_state.config.password_prehash)?;
+    body.new_password
+        .ensure_policy(&app_state.config.password_policy)?;
+    if app_state.config.password_reject_email {
+        body.new_password.ensure_unrelated_to_email(&body.email)?;
+    }
+
+    let (existing_account, reset_ticket) = match app_state
+        .account_repository
+        .get_account_by_email_with_password_reset_ticket(&body.email)
+        .await
+    {
+        Ok(v) => v,
+        Err(AccountQueryError::AccountNotFound) => {
+            app_state
+                .hashing_limiter
+                .run(move || {
+                    VerificationSecretStrategy::verify_dummy_verification_secret(&body.code)
+                })
+                .await?;
+            return Err(ResetPasswordRequestError::InvalidCode.into());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    // Reset code verification and password hashing are bounded, see [crate::hashing::HashingLimiter]
+    let now = app_state.clock.now();
+    let clock_skew_tolerance = app_state.config.clock_skew_tolerance;
+    let password_hash_algorithm = app_state.config.password_hash_algorithm;
+    let reset_password_request = app_state
+        .hashing_limiter
+        .run(move || {
+            ResetPasswordRequest::try_from_body(
+                body,
+                existing_account,
+                reset_ticket,
+                now,
+                clock_skew_tolerance,
+                password_hash_algorithm,
+            )
+        })
+        .await??;
+
+    // The password reset and the revocation are committed together, the sessions opened with the former password do not survive the reset
+    let mut transaction = app_state.account_repository.begin_transaction().await?;
+    let account = app_state
+        .account_repository
+        .reset_password(&mut transaction, &reset_password_request)
+        .await?;
+    let revoked_tokens = app_state
+        .access_token_repository
+        .revoke_account_tokens_in_transaction(&mut transaction, reset_password_request.account_id)
+        .await?;
+    commit_transaction(transaction).await?;
+    info!(
+        "{revoked_tokens} access tokens of account {} revoked along with its password reset",
+        reset_password_request.account_id
+    );
+
+    Ok((StatusCode::OK, Json(account.into())))
+}
+
+impl From<ResetPasswordRequestError> for ApiError {
+    fn from(value: ResetPasswordRequestError) -> Self {
+        match value {
+            ResetPasswordRequestError::InvalidCode => {
+                ApiError::bad_request("code", "code-validity", "Code is invalid")
+            }
+            ResetPasswordRequestError::ExpiredCode => ApiError::bad_request(
+                "code",
+                "code-expired",
+                "Code has expired, request a new code using `POST /accounts/request-password-reset`",
+            ),
+            ResetPasswordRequestError::Unknown(e) => e.into(),
+        }
+    }
+}
+
+impl From<ResetPasswordError> for ApiError {
+    fn from(value: ResetPasswordError) -> Self {
+        match value {
+            ResetPasswordError::NoActivePasswordResetTicket {
+                ticket_id: _ticket_id,
+            } => ApiError::conflict("code", "code-validity", "Code is no longer valid"),
+            ResetPasswordError::Unknown(e) => e.into(),
+        }
+    }
+}
+
+// #####################################################
+// ################## PASSWORD CHANGE ##################
+// #####################################################
+
+#[derive(Debug, Clone, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangePasswordBody {
+    pub current_password: Password,
+    pub new_password: Password,
+    /// If true, the access tokens and the refresh tokens of the account are revoked, the one of the request included
+    #[serde(default = "default_revoke_tokens")]
+    pub revoke_tokens: bool,
+}
+
+fn default_revoke_tokens() -> bool {
+    true
+}
+
+/// Change the password of the current account, see [CurrentAccount]
+///
+/// A wrong current password fails with a `401`. Unless `revokeTokens` is false, the existing sessions are revoked along with the change
+/// so that a compromised password does not keep them alive.
+async fn change_password(
+    State(app_state): State<AppState>,
+    CurrentAccount(account): CurrentAccount,
+    ValidatedJson(body): ValidatedJson<ChangePasswordBody>,
+) -> Result<(StatusCode, Json<AccountResponse>), ApiError> {
+    body.new_password
+        .ensure_prehash_mode(app_state.config.password_prehash)?;
+    body.new_password
+        .ensure_policy(&app_state.config.password_policy)?;
+    if app_state.config.password_reject_email {
+        body.new_password
+            .ensure_unrelated_to_email(&account.email)?;
+    }
+
+    // Password verification and hashing are bounded, see [crate::hashing::HashingLimiter]
+    let password_hash_algorithm = app_state.config.password_hash_algorithm;
+    let req = app_state
+        .hashing_limiter
+        .run(move || ChangePasswordRequest::try_from_body(body, &account, password_hash_algorithm))
+        .await?
+        .inspect_err(|e| {
+            if matches!(e, ChangePasswordRequestError::InvalidPassword) {
+                counter!(FAILED_PASSWORD_COUNTER).increment(1);
+            }
+        })?;
+
+    if !req.revoke_tokens {
+        let account = app_state
+            .account_repository
+            .update_password(req.account_id, &req.password_hash)
+            .await?;
+        return Ok((StatusCode::OK, Json(account.into())));
+    }
+
+    // The password change and the revocation are committed together, a failed revocation leaves the password unchanged
+    let mut transaction = app_state.account_repository.begin_transaction().await?;
+    let account = app_state
+        .account_repository
+        .update_password_in_transaction(&mut transaction, req.account_id, &req.password_hash)
+        .await?;
+    let revoked_tokens = app_state
+        .access_token_repository
+        .revoke_account_tokens_in_transaction(&mut transaction, req.account_id)
+        .await?;
+    commit_transaction(transaction).await?;
+    info!(
+        "{revoked_tokens} access tokens of account {} revoked along with its password change",
+        req.account_id
+    );
+
+    Ok((StatusCode::OK, Json(account.into())))
+}
+
+impl From<ChangePasswordRequestError> for ApiError {
+    fn from(value: ChangePasswordRequestError) -> Self {
+        match value {
+            ChangePasswordRequestError::InvalidPassword => ApiError::Unauthorized,
+            ChangePasswordRequestError::SamePassword => ApiError::bad_request(
+                "newPassword",
+                "same-password",
+                "New password must differ from the current password",
+            ),
+            ChangePasswordRequestError::Unknown(e) => e.into(),
+        }
+    }
+}
+
+// #####################################################
+// ################## CURRENT ACCOUNT ##################
+// #####################################################
+
+/// Account owning the access token presented as a bearer token, see [CurrentAccount]
+///
+/// The response carries the `ETag` of the account, it can be given in the `If-Match` header of a profile update.
+async fn get_current_account(
+    CurrentAccount(account): CurrentAccount,
+) -> Result<
+    (
+        StatusCode,
+        [(HeaderName, HeaderValue); 1],
+        Json<AccountResponse>,
+    ),
+    ApiError,
+> {
+    let etag = HeaderValue::from_str(&account.etag())
+        .map_err(|e| ApiError::InternalServerError(e.into()))?;
+    Ok((StatusCode::OK, [(ETAG, etag)], Json(account.into())))
+}
+
+// ####################################################
+// ################## PROFILE UPDATE ##################
+// ####################################################
+
+/// Partial update of the profile, absent fields are left untouched and `null` fields are cleared
+#[derive(Debug, Clone, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateProfileBody {
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub display_name: Option<Option<String>>,
+}
+
+/// Update the profile of the account, the update is conditional if an `If-Match` header is given
+///
+/// Updating the profile is a sensitive action, see [RecentlyAuthenticatedAccount].
+/// The response carries the `ETag` of the updated account, see [Account::etag].
+async fn update_profile(
+    State(app_state): State<AppState>,
+    RecentlyAuthenticatedAccount(account): RecentlyAuthenticatedAccount,
+    headers: HeaderMap,
+    ValidatedJson(body): ValidatedJson<UpdateProfileBody>,
+) -> Result<
+    (
+        StatusCode,
+        [(HeaderName, HeaderValue); 1],
+        Json<AccountResponse>,
+    ),
+    ApiError,
+> {
+    let if_match = headers
+        .get(IF_MATCH)
+        .map(HeaderValue::to_str)
+        .transpose()
+        .map_err(|_| UpdateProfileRequestError::PreconditionFailed)?;
+    let req = UpdateProfileRequest::try_from_body(body, if_match)?;
+
+    let account = app_state
+        .account_repository
+        .update_profile(account.id, &req)
         .await?;
 
-    Ok((StatusCode::OK, Json(updated_account.into())))
+    let etag = HeaderValue::from_str(&account.etag())
+        .map_err(|e| ApiError::InternalServerError(e.into()))?;
+    Ok((StatusCode::OK, [(ETAG, etag)], Json(account.into())))
+}
+
+fn precondition_failed() -> ApiError {
+    ApiError::PreconditionFailed(ErrorResponse::new(
+        "precondition_failed",
+        "Account has been updated in the meantime, retrieve it again before updating it",
+    ))
+}
+
+impl From<UpdateProfileRequestError> for ApiError {
+    fn from(value: UpdateProfileRequestError) -> Self {
+        match value {
+            UpdateProfileRequestError::InvalidDisplayName => ApiError::bad_request(
+                "displayName",
+                "invalid-length",
+                format!(
+                    "display name must not be empty and must be at most {MAX_DISPLAY_NAME_LENGTH} characters long"
+                ),
+            ),
+            UpdateProfileRequestError::PreconditionFailed => precondition_failed(),
+        }
+    }
+}
+
+impl From<UpdateProfileError> for ApiError {
+    fn from(value: UpdateProfileError) -> Self {
+        match value {
+            UpdateProfileError::ConstraintViolation { field } => {
+                warn!("database constraint violated by field {field}");
+                let mut validation_errors = ValidationErrors::new();
+                validation_errors.add(field, ValidationError::new("invalid-value"));
+                ApiError::BadRequest(validation_errors)
+            }
+            UpdateProfileError::PreconditionFailed => precondition_failed(),
+            UpdateProfileError::Unknown(e) => e.into(),
+        }
+    }
+}
+
+// #####################################################
+// ################## PASSWORD POLICY ##################
+// #####################################################
+
+/// Minimum number of characters of a class required in a password
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CharacterClassRequirement {
+    pub class: CharacterClass,
+    pub min_count: usize,
+}
+
+/// Password policy enforced at signup, clients may use it to validate the passwords locally
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordPolicyResponse {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub requirements: Vec<CharacterClassRequirement>,
+    /// Whether the password is expected as the hex encoded SHA-256 digest of the plaintext password, see `PASSWORD_PREHASH`
+    pub prehash: bool,
+    /// Whether a password containing the local part of the email is rejected, see `PASSWORD_REJECT_EMAIL`
+    pub reject_email: bool,
+}
+
+/// Return the password policy enforced at signup
+///
+/// The requirements are the ones of the configured policy enforced by [Password::ensure_policy] so that both never drift apart.
+async fn get_password_policy(
+    State(app_state): State<AppState>,
+) -> (StatusCode, Json<PasswordPolicyResponse>) {
+    let requirements = app_state
+        .config
+        .password_policy
+        .requirements
+        .iter()
+        .map(|&(class, min_count)| CharacterClassRequirement { class, min_count })
+        .collect();
+    (
+        StatusCode::OK,
+        Json(PasswordPolicyResponse {
+            min_length: MIN_PASSWORD_LENGTH,
+            max_length: MAX_PASSWORD_LENGTH,
+            requirements,
+            prehash: app_state.config.password_prehash,
+            reject_email: app_state.config.password_reject_email,
+        }),
+    )
 }
@@ -1,15 +1,29 @@
+use super::EmailProtection;
 use super::domain::{
-    Account, AccountQueryError, AccountVerificationTicket, SignupError, SignupRequest,
-    VerifyAccountError,
+    Account, AccountMerge, AccountQueryError, AccountState, AccountVerificationTicket, InviteCode,
+    MergeAccountsError, PasswordResetTicket, RequestPasswordResetError,
+    RequestPasswordResetRequest, ResendVerificationError, ResendVerificationRequest,
+    ResetPasswordError, ResetPasswordRequest, SignupError, SignupRequest, UpdateProfileError,
+    UpdateProfileRequest, VerifyAccountError,
+};
+use crate::database::{
+    DatabaseTransaction, RepositoryError, begin_transaction, commit_transaction, map_sqlx_error,
 };
 use crate::newtypes::Email;
-use anyhow::anyhow;
 use async_trait::async_trait;
-use sqlx::{Pool, Postgres, types::uuid};
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Pool, Postgres, types::uuid};
+use std::time::Duration;
 
 #[async_trait]
 pub trait AccountRepository: Send + Sync {
-    /// Get an account by email
+    /// Begin a transaction in which several repository operations can be composed, see [DatabaseTransaction]
+    ///
+    /// # Errors
+    /// * `RepositoryError` - the transaction could not be started
+    async fn begin_transaction(&self) -> Result<DatabaseTransaction, RepositoryError>;
+
+    /// Get an account by email, emails are compared regardless of their case
     ///
     /// # Arguments
     /// * `email` - Email of the account
@@ -19,14 +33,40 @@ pub trait AccountRepository: Send + Sync {
     /// * `AccountQueryError::AccountNotFound` - account not found
     async fn get_account_by_email(&self, email: &Email) -> Result<Account, AccountQueryError>;
 
-    /// Get a verified account by email
+    /// Get an account by ID
+    ///
+    /// # Arguments
+    /// * `account_id` - ID of the account
+    ///
+    /// # Errors
+    /// * `AccountQueryError::Unknown` - unknown error
+    /// * `AccountQueryError::AccountNotFound` - account not found
+    async fn get_account_by_id(&self, account_id: uuid::Uuid)
+    -> Result<Account, AccountQueryError>;
+
+    /// Get the account owning an access token, deactivated accounts are not returned
+    ///
+    /// # Arguments
+    /// * `access_token_id` - ID of the access token
+    ///
+    /// # Errors
+    /// * `AccountQueryError::Unknown` - unknown error
+    /// * `AccountQueryError::AccountNotFound` - access token or account not found, or account deactivated
+    async fn get_account_by_access_token(
+        &self,
+        access_token_id: uuid::Uuid,
+    ) -> Result<Account, AccountQueryError>;
+
+    /// Get an account which can authenticate by email, i.e. an active or a locked account, see [AccountState]
+    ///
+    /// A locked account is returned so that the lock is only disclosed once the password has been verified.
     ///
     /// # Arguments
     /// * `email` - Email of the account
     ///
     /// # Errors
     /// * `AccountQueryError::Unknown` - unknown error
-    /// * `AccountQueryError::AccountNotFound` - verified account not found
+    /// * `AccountQueryError::AccountNotFound` - account not found, unverified or deactivated
     async fn get_verified_account_by_email(
         &self,
         email: &Email,
@@ -34,6 +74,8 @@ pub trait AccountRepository: Send + Sync {
 
     /// Get an account by email with active verification ticket
     ///
+    /// The account and the ticket are read from the same snapshot, a concurrent verification can not leave an unverified account without its active ticket.
+    ///
     /// # Arguments
     /// * `email` - Email of the account
     ///
@@ -45,85 +87,515 @@ pub trait AccountRepository: Send + Sync {
         email: &Email,
     ) -> Result<(Account, Option<AccountVerificationTicket>), AccountQueryError>;
 
-    /// Create an account and creates an active verification ticket
+    /// Get the last confirmed verification ticket of an account
+    ///
+    /// # Arguments
+    /// * `account_id` - ID of the account
+    ///
+    /// # Errors
+    /// * `AccountQueryError::Unknown` - unknown error
+    async fn get_last_confirmed_verification_ticket(
+        &self,
+        account_id: uuid::Uuid,
+    ) -> Result<Option<AccountVerificationTicket>, AccountQueryError>;
+
+    /// Create an account and creates an active verification ticket, auto verified accounts are created verified without ticket
+    ///
+    /// The invite code of the request, if any, is consumed along with the account creation.
     ///
     /// # Arguments
     /// * `signup_request` - DTO for signup
     ///
     /// # Errors
+    /// * `SignupError::EmailAlreadyExists` - an account already exists for the email, regardless of its case
+    /// * `SignupError::InvalidInviteCode` - the invite code is unknown or has already been used
     /// * `SignupError::Unknown` - unknown error
     async fn create_account(&self, signup_request: &SignupRequest) -> Result<Account, SignupError>;
 
     /// Reset an account creation:
-    /// - update the password hash,
+    /// - update the password hash if the account has not been updated within the debounce window,
     /// - cancel last active verification ticket,
-    /// - creates a new active verification ticket
+    /// - creates a new active verification ticket, or verify the account if auto verified,
+    /// - consume the invite code of the request, if any
+    ///
+    /// The debounce window is checked by the update itself, concurrent signups can not both reset the account creation.
     ///
     /// # Arguments
-    /// * `password_hash` - Hash of the new password,
-    /// * `verification_cyphertext` - Cyphertext of the verification ticket
+    /// * `signup_request` - DTO for signup,
+    /// * `debounce` - debounce window, see [SignupRequest::ensure_outside_debounce_window]
     ///
     /// # Errors
+    /// * `SignupError::DuplicateSignup` - the account has been updated within the debounce window
+    /// * `SignupError::InvalidInviteCode` - the invite code is unknown or has already been used
     /// * `SignupError::Unknown` - unknown error
     async fn reset_account_creation(
         &self,
         signup_request: &SignupRequest,
+        debounce: Duration,
     ) -> Result<Account, SignupError>;
 
+    /// Regenerate the verification ticket of an unverified account, unlike [AccountRepository::reset_account_creation] the password hash is left untouched:
+    /// - lock the account for the duration of the regeneration,
+    /// - check that the last active verification ticket is older than the minimum interval,
+    /// - cancel last active verification ticket,
+    /// - creates a new active verification ticket
+    ///
+    /// # Arguments
+    /// * `resend_verification_request` - DTO for verification resend,
+    /// * `min_interval` - minimum age of the active verification ticket, see [ResendVerificationRequest::ensure_outside_throttle_window]
+    ///
+    /// # Errors
+    /// * `ResendVerificationError::AccountAlreadyVerified` - account has been verified in the meantime
+    /// * `ResendVerificationError::TooSoon` - the active verification ticket is more recent than the minimum interval
+    /// * `ResendVerificationError::Unknown` - unknown error
+    async fn regenerate_verification_ticket(
+        &self,
+        resend_verification_request: &ResendVerificationRequest,
+        min_interval: Duration,
+    ) -> Result<Account, ResendVerificationError>;
+
     /// Verify an account:
-    /// - update the `verified` to true,
-    /// - confirm the verification ticket
+    /// - lock the account for the duration of the verification,
+    /// - update the `verified` to true if the account is not verified,
+    /// - confirm the verified ticket if it is still active
+    ///
+    /// Verifying an already verified account is a no-op returning `VerifyAccountError::AccountAlreadyVerified`.
     ///
     /// # Arguments
     /// * `account_id` - ID of the account,
+    /// * `ticket_id` - ID of the verification ticket whose secret has been verified
     ///
     /// # Errors
+    /// * `VerifyAccountError::AccountAlreadyVerified` - account has been verified in the meantime
+    /// * `VerifyAccountError::NoActiveVerificationTicket` - active verification ticket has been cancelled in the meantime
     /// * `VerifyAccountError::Unknown` - unknown error
-    async fn verify_account(&self, account_id: uuid::Uuid) -> Result<Account, VerifyAccountError>;
+    async fn verify_account(
+        &self,
+        account_id: uuid::Uuid,
+        ticket_id: uuid::Uuid,
+    ) -> Result<Account, VerifyAccountError>;
+
+    /// Same as [AccountRepository::verify_account] within a transaction, the lock is held until the end of the transaction
+    ///
+    /// The transaction must be rolled back on error as the account may have been updated
+    async fn verify_account_in_transaction(
+        &self,
+        transaction: &mut DatabaseTransaction,
+        account_id: uuid::Uuid,
+        ticket_id: uuid::Uuid,
+    ) -> Result<Account, VerifyAccountError>;
+
+    /// Record a failed verification attempt on an active verification ticket, the ticket is cancelled at the last allowed attempt
+    ///
+    /// Returns the number of remaining attempts, a ticket which is no longer active has no remaining attempts.
+    ///
+    /// # Arguments
+    /// * `ticket_id` - ID of the verification ticket,
+    /// * `max_attempts` - number of failed attempts after which the ticket is cancelled
+    ///
+    /// # Errors
+    /// * `AccountQueryError::Unknown` - unknown error
+    async fn record_failed_verification_attempt(
+        &self,
+        ticket_id: uuid::Uuid,
+        max_attempts: u32,
+    ) -> Result<u32, AccountQueryError>;
+
+    /// Get an account by email with active password reset ticket
+    ///
+    /// # Arguments
+    /// * `email` - Email of the account
+    ///
+    /// # Errors
+    /// * `AccountQueryError::Unknown` - unknown error
+    /// * `AccountQueryError::AccountNotFound` - account not found
+    async fn get_account_by_email_with_password_reset_ticket(
+        &self,
+        email: &Email,
+    ) -> Result<(Account, Option<PasswordResetTicket>), AccountQueryError>;
+
+    /// Create a password reset ticket for an account:
+    /// - lock the account for the duration of the creation,
+    /// - check that the last active password reset ticket is older than the minimum interval,
+    /// - cancel last active password reset ticket,
+    /// - create a new active password reset ticket
+    ///
+    /// # Arguments
+    /// * `request_password_reset_request` - DTO for password reset request,
+    /// * `min_interval` - minimum age of the active password reset ticket, see [RequestPasswordResetRequest::ensure_outside_throttle_window]
+    ///
+    /// # Errors
+    /// * `RequestPasswordResetError::TooSoon` - the active password reset ticket is more recent than the minimum interval
+    /// * `RequestPasswordResetError::Unknown` - unknown error
+    async fn create_password_reset_ticket(
+        &self,
+        request_password_reset_request: &RequestPasswordResetRequest,
+        min_interval: Duration,
+    ) -> Result<(), RequestPasswordResetError>;
+
+    /// Reset the password of an account within a transaction, committed by the caller along the revocation of the account tokens:
+    /// - confirm the active password reset ticket of the request,
+    /// - update the password hash
+    ///
+    /// # Arguments
+    /// * `transaction` - transaction of the password reset,
+    /// * `reset_password_request` - DTO for password reset
+    ///
+    /// # Errors
+    /// * `ResetPasswordError::NoActivePasswordResetTicket` - the ticket has been used or cancelled in the meantime
+    /// * `ResetPasswordError::Unknown` - unknown error
+    async fn reset_password(
+        &self,
+        transaction: &mut DatabaseTransaction,
+        reset_password_request: &ResetPasswordRequest,
+    ) -> Result<Account, ResetPasswordError>;
+
+    /// Update the password hash of an account
+    ///
+    /// # Arguments
+    /// * `account_id` - ID of the account,
+    /// * `password_hash` - hash of the new password
+    ///
+    /// # Errors
+    /// * `AccountQueryError::AccountNotFound` - account not found
+    /// * `AccountQueryError::Unknown` - unknown error
+    async fn update_password(
+        &self,
+        account_id: uuid::Uuid,
+        password_hash: &str,
+    ) -> Result<Account, AccountQueryError>;
+
+    /// Same as [AccountRepository::update_password] within a transaction
+    async fn update_password_in_transaction(
+        &self,
+        transaction: &mut DatabaseTransaction,
+        account_id: uuid::Uuid,
+        password_hash: &str,
+    ) -> Result<Account, AccountQueryError>;
+
+    /// Update the profile fields of an account, only the fields provided in the request are updated
+    ///
+    /// # Arguments
+    /// * `account_id` - ID of the account,
+    /// * `update_profile_request` - DTO for profile update
+    ///
+    /// # Errors
+    /// * `UpdateProfileError::PreconditionFailed` - account has been updated since the expected last update date
+    /// * `UpdateProfileError::Unknown` - unknown error
+    async fn update_profile(
+        &self,
+        account_id: uuid::Uuid,
+        update_profile_request: &UpdateProfileRequest,
+    ) -> Result<Account, UpdateProfileError>;
+
+    /// Create an unused invite code
+    ///
+    /// # Arguments
+    /// * `code` - plaintext invite code
+    ///
+    /// # Errors
+    /// * `AccountQueryError::Unknown` - unknown error
+    async fn create_invite_code(&self, code: &str) -> Result<InviteCode, AccountQueryError>;
+
+    /// Set the maximum lifetime of the access tokens of an account, `None` removes the account policy
+    ///
+    /// # Arguments
+    /// * `account_id` - ID of the account,
+    /// * `max_token_lifetime_secs` - maximum lifetime in seconds
+    ///
+    /// # Errors
+    /// * `AccountQueryError::AccountNotFound` - account not found
+    /// * `AccountQueryError::Unknown` - unknown error
+    async fn set_max_token_lifetime(
+        &self,
+        account_id: uuid::Uuid,
+        max_token_lifetime_secs: Option<u32>,
+    ) -> Result<Account, AccountQueryError>;
+
+    /// Merge a source account into a target account, within one transaction:
+    /// - lock both accounts,
+    /// - move the access tokens of the source account to the target account, the names already used by active access tokens of the target account are suffixed,
+    /// - check the active access tokens of the target account against the limit, sessions excluded,
+    /// - attribute the invite code used by the source account to the target account,
+    /// - delete the verification and password reset tickets of the source account,
+    /// - delete the source account
+    ///
+    /// # Arguments
+    /// * `source_account_id` - ID of the account which is deleted,
+    /// * `target_account_id` - ID of the account which is kept,
+    /// * `max_active_token` - maximum number of active token allowed
+    ///
+    /// # Errors
+    /// * `MergeAccountsError::SameAccount` - source and target accounts are the same
+    /// * `MergeAccountsError::AccountNotFound` - source or target account not found
+    /// * `MergeAccountsError::ActiveTokenLimitExceeded` - the merged account would exceed the active access token limit
+    /// * `MergeAccountsError::Unknown` - unknown error
+    async fn merge_accounts(
+        &self,
+        source_account_id: uuid::Uuid,
+        target_account_id: uuid::Uuid,
+        max_active_token: u8,
+    ) -> Result<AccountMerge, MergeAccountsError>;
+
+    /// Delete a batch of stale verification tickets, i.e. unconfirmed and created before the given date, see [crate::cleanup]
+    ///
+    /// # Arguments
+    /// * `stale_before` - unconfirmed tickets created before this date are stale,
+    /// * `batch_size` - maximum number of tickets deleted
+    ///
+    /// # Errors
+    /// * `anyhow::Error` - unknown error
+    async fn delete_stale_verification_tickets(
+        &self,
+        stale_before: DateTime<Utc>,
+        batch_size: u32,
+    ) -> Result<u64, anyhow::Error>;
 }
 
 pub struct PostgresAccountRepository {
     pool: Pool<Postgres>,
+    email_protection: Option<EmailProtection>,
 }
 
 impl From<Pool<Postgres>> for PostgresAccountRepository {
     fn from(value: Pool<Postgres>) -> Self {
-        PostgresAccountRepository { pool: value }
+        PostgresAccountRepository {
+            pool: value,
+            email_protection: None,
+        }
+    }
+}
+
+impl PostgresAccountRepository {
+    /// Protect the stored emails, they are stored in plaintext if `None`, see [EmailProtection]
+    pub fn with_email_protection(mut self, email_protection: Option<EmailProtection>) -> Self {
+        self.email_protection = email_protection;
+        self
+    }
+
+    /// Condition matching the account of an email against the given query parameter, with the value to bind to the parameter
+    ///
+    /// Protected emails are matched by their lookup value, plaintext emails are compared regardless of their case.
+    fn email_condition(&self, email: &Email, parameter: &str) -> (String, String) {
+        match &self.email_protection {
+            Some(protection) => (
+                format!(r#""email_lookup" = {parameter}"#),
+                protection.lookup(email),
+            ),
+            None => (
+                format!(r#"lower("email") = lower({parameter})"#),
+                email.as_str().to_owned(),
+            ),
+        }
+    }
+
+    /// Values of the `email` and `email_lookup` columns for an email
+    fn stored_email(&self, email: &Email) -> Result<(String, Option<String>), anyhow::Error> {
+        match &self.email_protection {
+            Some(protection) => Ok((protection.encrypt(email)?, Some(protection.lookup(email)))),
+            None => Ok((email.as_str().to_owned(), None)),
+        }
+    }
+
+    /// Count the accounts whose email is stored in plaintext, i.e. created before the emails have been protected
+    ///
+    /// # Errors
+    /// * `anyhow::Error` - unknown error
+    pub async fn count_plaintext_emails(&self) -> Result<u64, anyhow::Error> {
+        let count = sqlx::query_scalar::<_, i64>(
+            r#"SELECT COUNT(*) FROM "account" WHERE "email_lookup" IS NULL"#,
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| map_sqlx_error("failed to count plaintext emails", e))?;
+        Ok(count.unsigned_abs())
+    }
+
+    /// Encrypt the plaintext emails and set their lookup value, batch after batch until none is left
+    ///
+    /// Returns the number of protected emails. A plaintext email whose lookup value is already taken, e.g. signed up again once the emails have been protected, fails the backfill.
+    ///
+    /// # Arguments
+    /// * `batch_size` - maximum number of emails protected by a single transaction
+    ///
+    /// # Errors
+    /// * `anyhow::Error` - the emails are not protected or unknown error
+    pub async fn protect_plaintext_emails(&self, batch_size: u32) -> Result<u64, anyhow::Error> {
+        let protection = self
+            .email_protection
+            .as_ref()
+            .ok_or(anyhow::anyhow!("the emails are not protected"))?;
+        let mut protected = 0;
+        loop {
+            let mut transaction = begin_transaction(&self.pool).await?;
+            let plaintext_emails = sqlx::query_as::<_, (uuid::Uuid, String)>(
+                r#"
+                SELECT "id", "email"
+                FROM "account"
+                WHERE "email_lookup" IS NULL
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            "#,
+            )
+            .bind(i64::from(batch_size))
+            .fetch_all(&mut *transaction)
+            .await
+            .map_err(|e| map_sqlx_error("failed query for plaintext emails", e))?;
+
+            for (account_id, email) in &plaintext_emails {
+                let email = Email::new_unchecked(email);
+                sqlx::query(
+                    r#"
+                    UPDATE "account"
+                    SET "email" = $2, "email_lookup" = $3
+                    WHERE "id" = $1
+                "#,
+                )
+                .bind(account_id)
+                .bind(protection.encrypt(&email)?)
+                .bind(protection.lookup(&email))
+                .execute(&mut *transaction)
+                .await
+                .map_err(|e| {
+                    map_sqlx_error(
+                        &format!("failed to protect the email of account with ID: {account_id}"),
+                        e,
+                    )
+                })?;
+            }
+
+            commit_transaction(transaction).await?;
+            protected += plaintext_emails.len() as u64;
+            if plaintext_emails.len() < batch_size as usize {
+                return Ok(protected);
+            }
+        }
+    }
+
+    /// Get an account by email with the given executor, see [AccountRepository::get_account_by_email]
+    async fn fetch_account_by_email<'e, E>(
+        &self,
+        executor: E,
+        email: &Email,
+    ) -> Result<Account, AccountQueryError>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let (condition, value) = self.email_condition(email, "$1");
+        let account = sqlx::query_as::<_, Account>(&format!(
+            r#"
+                SELECT
+                    id,
+                    email,
+                    password_hash,
+                    verified,
+                    display_name,
+                    max_token_lifetime_secs,
+                    locked_until,
+                    deactivated_at,
+                    created_at,
+                    updated_at
+                FROM "account"
+                WHERE {condition}
+                "#,
+        ))
+        .bind(value)
+        .fetch_one(executor)
+        .await
+        .map_err(|e| map_sqlx_error(&format!("failed query for account with email: {email}"), e))?;
+        let account = self.reveal(account)?;
+
+        Ok(account)
+    }
+
+    /// Decrypt the email of a fetched account if the emails are protected
+    fn reveal(&self, mut account: Account) -> Result<Account, anyhow::Error> {
+        if let Some(protection) = &self.email_protection {
+            account.email = protection.decrypt(account.email.as_str())?;
+        }
+        Ok(account)
     }
 }
 
 #[async_trait]
 impl AccountRepository for PostgresAccountRepository {
+    async fn begin_transaction(&self) -> Result<DatabaseTransaction, RepositoryError> {
+        begin_transaction(&self.pool).await
+    }
+
     async fn get_account_by_email(&self, email: &Email) -> Result<Account, AccountQueryError> {
-        let query_result = sqlx::query_as::<_, Account>(
+        self.fetch_account_by_email(&self.pool, email).await
+    }
+
+    async fn get_account_by_id(
+        &self,
+        account_id: uuid::Uuid,
+    ) -> Result<Account, AccountQueryError> {
+        let account = sqlx::query_as::<_, Account>(
             r#"
                 SELECT
                     id,
                     email,
                     password_hash,
                     verified,
+                    display_name,
+                    max_token_lifetime_secs,
+                    locked_until,
+                    deactivated_at,
                     created_at,
                     updated_at
                 FROM "account"
-                WHERE "email" = $1
+                WHERE "id" = $1
                 "#,
         )
-        .bind(email)
+        .bind(account_id)
         .fetch_one(&self.pool)
-        .await;
-
-        match query_result {
-            Ok(v) => Ok(v),
-            Err(e) => {
-                if let sqlx::Error::RowNotFound = e {
-                    Err(AccountQueryError::AccountNotFound)
-                } else {
-                    Err(anyhow!(e)
-                        .context(format!("failed query for account with email: {email}"))
-                        .into())
-                }
-            }
-        }
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!("failed query for account with ID: {account_id}"),
+                e,
+            )
+        })?;
+        let account = self.reveal(account)?;
+
+        Ok(account)
+    }
+
+    async fn get_account_by_access_token(
+        &self,
+        access_token_id: uuid::Uuid,
+    ) -> Result<Account, AccountQueryError> {
+        let account = sqlx::query_as::<_, Account>(
+            r#"
+                SELECT
+                    "account"."id",
+                    "account"."email",
+                    "account"."password_hash",
+                    "account"."verified",
+                    "account"."display_name",
+                    "account"."max_token_lifetime_secs",
+                    "account"."locked_until",
+                    "account"."deactivated_at",
+                    "account"."created_at",
+                    "account"."updated_at"
+                FROM "account"
+                JOIN "access_token" ON "access_token"."account_id" = "account"."id"
+                WHERE "access_token"."id" = $1 AND "account"."deactivated_at" IS NULL
+                "#,
+        )
+        .bind(access_token_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!("failed query for account owning access token with ID: {access_token_id}"),
+                e,
+            )
+        })?;
+        let account = self.reveal(account)?;
+
+        Ok(account)
     }
 
     async fn get_verified_account_by_email(
@@ -131,24 +603,38 @@ impl AccountRepository for PostgresAccountRepository {
         email: &Email,
     ) -> Result<Account, AccountQueryError> {
         let account = self.get_account_by_email(email).await?;
-        if !account.verified {
-            return Err(AccountQueryError::AccountNotFound);
+        match account.state(Utc::now()) {
+            AccountState::Active | AccountState::Locked => Ok(account),
+            AccountState::Unverified | AccountState::Deactivated => {
+                Err(AccountQueryError::AccountNotFound)
+            }
         }
-        Ok(account)
     }
 
     async fn get_account_by_email_with_verification_ticket(
         &self,
         email: &Email,
     ) -> Result<(Account, Option<AccountVerificationTicket>), AccountQueryError> {
-        let account = self.get_account_by_email(email).await?;
-        let verification_ticket = match sqlx::query_as::<_, AccountVerificationTicket>(
+        // Both reads share a snapshot, a concurrent verification is either entirely seen or not at all,
+        // i.e. a verified account comes with its confirmed ticket and an unverified one with its active ticket
+        let mut transaction = begin_transaction(&self.pool).await?;
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| map_sqlx_error("failed to set transaction isolation level", e))?;
+
+        let account = self
+            .fetch_account_by_email(&mut *transaction, email)
+            .await?;
+        let verification_ticket = sqlx::query_as::<_, AccountVerificationTicket>(
             r#"
                 SELECT
                     id,
                     account_id,
                     cyphertext,
                     status,
+                    channel,
+                    phone_number,
                     created_at,
                     updated_at
                 FROM "account_verification_ticket"
@@ -156,124 +642,360 @@ impl AccountRepository for PostgresAccountRepository {
             "#,
         )
         .bind(account.id)
-        .fetch_one(&self.pool)
+        .fetch_optional(&mut *transaction)
         .await
-        {
-            Ok(v) => Some(v),
-            Err(e) => {
-                if let sqlx::Error::RowNotFound = e {
-                    None
-                } else {
-                    return Err(anyhow!(e)
-                        .context(format!(
-                            "failed query for active verification ticket with account ID: {}",
-                            account.id
-                        ))
-                        .into());
-                }
-            }
-        };
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed query for active verification ticket with account ID: {}",
+                    account.id
+                ),
+                e,
+            )
+        })?;
+
+        commit_transaction(transaction).await?;
 
         Ok((account, verification_ticket))
     }
 
+    async fn get_last_confirmed_verification_ticket(
+        &self,
+        account_id: uuid::Uuid,
+    ) -> Result<Option<AccountVerificationTicket>, AccountQueryError> {
+        let verification_ticket = sqlx::query_as::<_, AccountVerificationTicket>(
+            r#"
+                SELECT
+                    id,
+                    account_id,
+                    cyphertext,
+                    status,
+                    channel,
+                    phone_number,
+                    created_at,
+                    updated_at
+                FROM "account_verification_ticket"
+                WHERE "account_id" = $1 AND "status" = 'confirmed'
+                ORDER BY "updated_at" DESC
+                LIMIT 1
+            "#,
+        )
+        .bind(account_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed query for confirmed verification ticket with account ID: {account_id}"
+                ),
+                e,
+            )
+        })?;
+
+        Ok(verification_ticket)
+    }
+
     async fn create_account(&self, req: &SignupRequest) -> Result<Account, SignupError> {
         let mut transaction = self
             .pool
             .begin()
             .await
-            .map_err(|e| anyhow!(e).context("failed to start transaction"))?;
+            .map_err(|e| map_sqlx_error("failed to start transaction", e))?;
 
+        let (stored_email, email_lookup) = self.stored_email(&req.email)?;
         let account = sqlx::query_as::<_, Account>(
             r#"
                 INSERT INTO "account" (
                     "email",
-                    "password_hash"
+                    "password_hash",
+                    "verified",
+                    "email_lookup"
                 ) VALUES (
                     $1,
-                    $2
-                ) RETURNING 
+                    $2,
+                    $3,
+                    $4
+                ) RETURNING
                     id,
                     email,
                     password_hash,
                     verified,
+                    display_name,
+                    max_token_lifetime_secs,
+                    locked_until,
+                    deactivated_at,
                     created_at,
                     updated_at
             "#,
         )
-        .bind(&req.email)
+        .bind(stored_email)
         .bind(&req.password_hash)
+        .bind(req.auto_verified)
+        .bind(email_lookup)
         .fetch_one(&mut *transaction)
         .await
         .map_err(|e| {
-            anyhow!(e).context(format!(
-                "failed to insert account with email: {}",
-                req.email
-            ))
-        })?;
-
-        sqlx::query(
-            r#"
-        INSERT INTO "account_verification_ticket" (
-            "account_id",
-            "cyphertext"
-        ) VALUES (
-            $1,
-            $2
-        );
-    "#,
-        )
-        .bind(account.id)
-        .bind(&req.verification_cyphertext)
-        .execute(&mut *transaction)
-        .await
-        .map_err(|e| {
-            anyhow!(e).context(format!(
-                "failed to insert active verification ticket for created account with email: {}",
-                req.email
-            ))
+            map_sqlx_error(
+                &format!("failed to insert account with email: {}", req.email),
+                e,
+            )
         })?;
+        let account = self.reveal(account)?;
 
-        transaction
-            .commit()
-            .await
-            .map_err(|e| anyhow!(e).context("failed to commit transaction"))?;
-
-        Ok(account)
+        // Auto verified accounts do not need a verification ticket
+        if !req.auto_verified {
+            sqlx::query(
+                r#"
+            INSERT INTO "account_verification_ticket" (
+                "account_id",
+                "cyphertext",
+                "channel",
+                "phone_number"
+            ) VALUES (
+                $1,
+                $2,
+                $3,
+                $4
+            );
+        "#,
+            )
+            .bind(account.id)
+            .bind(&req.verification_cyphertext)
+            .bind(req.channel)
+            .bind(&req.phone_number)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| {
+                map_sqlx_error(
+                    &format!(
+                        "failed to insert active verification ticket for created account with email: {}",
+                        req.email
+                    ),
+                    e,
+                )
+            })?;
+        }
+
+        if let Some(invite_code) = &req.invite_code {
+            use_invite_code(&mut transaction, invite_code, account.id).await?;
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| map_sqlx_error("failed to commit transaction", e))?;
+
+        Ok(account)
     }
 
-    async fn reset_account_creation(&self, req: &SignupRequest) -> Result<Account, SignupError> {
+    async fn reset_account_creation(
+        &self,
+        req: &SignupRequest,
+        debounce: Duration,
+    ) -> Result<Account, SignupError> {
         let mut transaction = self
             .pool
             .begin()
             .await
-            .map_err(|e| anyhow!(e).context("failed to start transaction"))?;
+            .map_err(|e| map_sqlx_error("failed to start transaction", e))?;
 
-        let account = sqlx::query_as::<_, Account>(
+        let (stored_email, email_lookup) = self.stored_email(&req.email)?;
+        let (condition, value) = self.email_condition(&req.email, "$5");
+        let account = sqlx::query_as::<_, Account>(&format!(
             r#"
             UPDATE "account"
-            SET "password_hash" = $2
-            WHERE "email" = $1
+            SET "email" = $1, "password_hash" = $2, "verified" = $3, "email_lookup" = $4
+            WHERE {condition} AND "updated_at" <= CURRENT_TIMESTAMP - make_interval(secs => $6)
             RETURNING
                 id,
                 email,
                 password_hash,
                 verified,
+                display_name,
+                max_token_lifetime_secs,
+                locked_until,
+                deactivated_at,
                 created_at,
                 updated_at
         "#,
-        )
-        .bind(&req.email)
+        ))
+        .bind(stored_email)
         .bind(&req.password_hash)
-        .fetch_one(&mut *transaction)
+        .bind(req.auto_verified)
+        .bind(email_lookup)
+        .bind(value)
+        .bind(debounce.as_secs_f64())
+        .fetch_optional(&mut *transaction)
         .await
         .map_err(|e| {
-            anyhow!(e).context(format!(
-                "failed to update account with email: {}",
-                req.email
+            map_sqlx_error(
+                &format!("failed to update account with email: {}", req.email),
+                e,
+            )
+        })?;
+        let Some(account) = account else {
+            // The account has been updated within the debounce window, e.g. by a concurrent signup
+            let (condition, value) = self.email_condition(&req.email, "$1");
+            let retry_after_secs = sqlx::query_scalar::<_, i64>(&format!(
+                r#"
+                SELECT CEIL(EXTRACT(EPOCH FROM "updated_at" + make_interval(secs => $2) - CURRENT_TIMESTAMP))::BIGINT
+                FROM "account"
+                WHERE {condition}
+            "#,
             ))
+            .bind(value)
+            .bind(debounce.as_secs_f64())
+            .fetch_one(&mut *transaction)
+            .await
+            .map_err(|e| {
+                map_sqlx_error(
+                    &format!("failed query for account with email: {}", req.email),
+                    e,
+                )
+            })?;
+            return Err(SignupError::DuplicateSignup {
+                retry_after_secs: retry_after_secs.max(1).unsigned_abs(),
+            });
+        };
+        let account = self.reveal(account)?;
+
+        sqlx::query(
+            r#"
+            UPDATE "account_verification_ticket"
+            SET "status" = 'cancelled'
+            WHERE "account_id" = $1 AND "status" = 'active';
+            "#,
+        )
+        .bind(account.id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed to cancel previous active verification ticket for account ID: {}",
+                    account.id
+                ),
+                e,
+            )
         })?;
 
+        // Auto verified accounts do not need a verification ticket
+        if !req.auto_verified {
+            sqlx::query(
+                r#"
+                INSERT INTO "account_verification_ticket" (
+                    "account_id",
+                    "cyphertext",
+                    "channel",
+                    "phone_number"
+                ) VALUES (
+                    $1,
+                    $2,
+                    $3,
+                    $4
+                );
+            "#,
+            )
+            .bind(account.id)
+            .bind(&req.verification_cyphertext)
+            .bind(req.channel)
+            .bind(&req.phone_number)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| {
+                map_sqlx_error(
+                    &format!(
+                        "failed to create new active verification ticket for ID: {}",
+                        account.id
+                    ),
+                    e,
+                )
+            })?;
+        }
+
+        if let Some(invite_code) = &req.invite_code {
+            use_invite_code(&mut transaction, invite_code, account.id).await?;
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| map_sqlx_error("failed to commit transaction", e))?;
+
+        Ok(account)
+    }
+
+    async fn regenerate_verification_ticket(
+        &self,
+        req: &ResendVerificationRequest,
+        min_interval: Duration,
+    ) -> Result<Account, ResendVerificationError> {
+        let mut transaction = begin_transaction(&self.pool).await?;
+
+        let account = sqlx::query_as::<_, Account>(
+            r#"
+            SELECT
+                id,
+                email,
+                password_hash,
+                verified,
+                display_name,
+                max_token_lifetime_secs,
+                locked_until,
+                deactivated_at,
+                created_at,
+                updated_at
+            FROM "account"
+            WHERE "id" = $1
+            FOR UPDATE
+        "#,
+        )
+        .bind(req.account_id)
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!("failed to lock account with ID: {}", req.account_id),
+                e,
+            )
+        })?;
+        let account = self.reveal(account)?;
+        if account.verified {
+            return Err(ResendVerificationError::AccountAlreadyVerified {
+                account_id: account.id,
+            });
+        }
+
+        // Both dates are given by the database which sets the creation date of the tickets
+        let active_ticket_dates = sqlx::query_as::<_, (DateTime<Utc>, DateTime<Utc>)>(
+            r#"
+            SELECT "created_at", CURRENT_TIMESTAMP
+            FROM "account_verification_ticket"
+            WHERE "account_id" = $1 AND "status" = 'active'
+            ORDER BY "created_at" DESC
+            LIMIT 1
+        "#,
+        )
+        .bind(account.id)
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed to get active verification ticket for account ID: {}",
+                    account.id
+                ),
+                e,
+            )
+        })?;
+        if let Some((created_at, now)) = active_ticket_dates {
+            ResendVerificationRequest::ensure_outside_throttle_window(
+                created_at,
+                now,
+                min_interval,
+            )?;
+        }
+
         sqlx::query(
             r#"
             UPDATE "account_verification_ticket"
@@ -285,91 +1007,891 @@ impl AccountRepository for PostgresAccountRepository {
         .execute(&mut *transaction)
         .await
         .map_err(|e| {
-            anyhow!(e).context(format!(
-                "failed to cancel previous active verification ticket for account ID: {}",
-                account.id
-            ))
+            map_sqlx_error(
+                &format!(
+                    "failed to cancel previous active verification ticket for account ID: {}",
+                    account.id
+                ),
+                e,
+            )
         })?;
 
         sqlx::query(
             r#"
             INSERT INTO "account_verification_ticket" (
                 "account_id",
-                "cyphertext"
+                "cyphertext",
+                "channel",
+                "phone_number"
             ) VALUES (
                 $1,
-                $2
+                $2,
+                $3,
+                $4
             );
         "#,
         )
         .bind(account.id)
         .bind(&req.verification_cyphertext)
+        .bind(req.channel)
+        .bind(&req.phone_number)
         .execute(&mut *transaction)
         .await
         .map_err(|e| {
-            anyhow!(e).context(format!(
-                "failed to create new active verification ticket for ID: {}",
-                account.id
-            ))
+            map_sqlx_error(
+                &format!(
+                    "failed to create new active verification ticket for ID: {}",
+                    account.id
+                ),
+                e,
+            )
         })?;
 
-        transaction
-            .commit()
-            .await
-            .map_err(|e| anyhow!(e).context("failed to commit transaction"))?;
+        commit_transaction(transaction).await?;
 
         Ok(account)
     }
 
-    async fn verify_account(&self, account_id: uuid::Uuid) -> Result<Account, VerifyAccountError> {
-        let mut transaction = self
-            .pool
-            .begin()
+    async fn verify_account(
+        &self,
+        account_id: uuid::Uuid,
+        ticket_id: uuid::Uuid,
+    ) -> Result<Account, VerifyAccountError> {
+        let mut transaction = begin_transaction(&self.pool).await?;
+        let account = self
+            .verify_account_in_transaction(&mut transaction, account_id, ticket_id)
+            .await?;
+        commit_transaction(transaction).await?;
+
+        Ok(account)
+    }
+
+    async fn verify_account_in_transaction(
+        &self,
+        transaction: &mut DatabaseTransaction,
+        account_id: uuid::Uuid,
+        ticket_id: uuid::Uuid,
+    ) -> Result<Account, VerifyAccountError> {
+        // Concurrent verifications of the same account are serialized until the end of the transaction,
+        // the account state is then guarded by the updates as it may have changed while waiting for the lock
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1::text, 0))")
+            .bind(account_id)
+            .execute(&mut **transaction)
             .await
-            .map_err(|e| anyhow!(e).context("failed to start transaction"))?;
+            .map_err(|e| {
+                map_sqlx_error(
+                    &format!(
+                        "failed to acquire verification lock for account with ID: {account_id}"
+                    ),
+                    e,
+                )
+            })?;
 
         let account = sqlx::query_as::<_, Account>(
             r#"
             UPDATE "account"
             SET "verified" = TRUE
-            WHERE "id" = $1
+            WHERE "id" = $1 AND "verified" = FALSE
             RETURNING
                 id,
                 email,
                 password_hash,
                 verified,
+                display_name,
+                max_token_lifetime_secs,
+                locked_until,
+                deactivated_at,
                 created_at,
                 updated_at
         "#,
         )
         .bind(account_id)
+        .fetch_optional(&mut **transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!("failed to update account with ID: {account_id}"),
+                e,
+            )
+        })?
+        .ok_or(VerifyAccountError::AccountAlreadyVerified { account_id })?;
+        let account = self.reveal(account)?;
+
+        // The verified ticket is confirmed, the account update is rolled back with the transaction if it is no longer active
+        let confirmed_tickets = sqlx::query(
+            r#"
+            UPDATE "account_verification_ticket"
+            SET "status" = 'confirmed'
+            WHERE "account_id" = $1 AND "id" = $2 AND "status" = 'active'
+        "#,
+        )
+        .bind(account_id)
+        .bind(ticket_id)
+        .execute(&mut **transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!("failed to confirm verification ticket with ID: {ticket_id}"),
+                e,
+            )
+        })?
+        .rows_affected();
+        if confirmed_tickets == 0 {
+            return Err(VerifyAccountError::NoActiveVerificationTicket { account_id });
+        }
+
+        Ok(account)
+    }
+
+    async fn record_failed_verification_attempt(
+        &self,
+        ticket_id: uuid::Uuid,
+        max_attempts: u32,
+    ) -> Result<u32, AccountQueryError> {
+        let max_attempts = i32::try_from(max_attempts).map_err(|e| {
+            anyhow::anyhow!(e).context("maximum verification attempts out of range")
+        })?;
+        // The expressions of the `SET` clause are evaluated against the row before the update
+        let failed_attempts = sqlx::query_scalar::<_, i32>(
+            r#"
+            UPDATE "account_verification_ticket"
+            SET
+                "failed_attempts" = "failed_attempts" + 1,
+                "status" = CASE
+                    WHEN "failed_attempts" + 1 >= $2 THEN 'cancelled'::account_verification_ticket_status
+                    ELSE "status"
+                END
+            WHERE "id" = $1 AND "status" = 'active'
+            RETURNING "failed_attempts"
+        "#,
+        )
+        .bind(ticket_id)
+        .bind(max_attempts)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed to record failed attempt for verification ticket with ID: {ticket_id}"
+                ),
+                e,
+            )
+        })?;
+
+        let remaining_attempts =
+            failed_attempts.map_or(0, |failed_attempts| (max_attempts - failed_attempts).max(0));
+        Ok(remaining_attempts.unsigned_abs())
+    }
+
+    async fn get_account_by_email_with_password_reset_ticket(
+        &self,
+        email: &Email,
+    ) -> Result<(Account, Option<PasswordResetTicket>), AccountQueryError> {
+        let account = self.get_account_by_email(email).await?;
+        let reset_ticket = sqlx::query_as::<_, PasswordResetTicket>(
+            r#"
+                SELECT
+                    id,
+                    account_id,
+                    cyphertext,
+                    status,
+                    created_at,
+                    updated_at
+                FROM "password_reset_ticket"
+                WHERE "account_id" = $1 AND "status" = 'active'
+            "#,
+        )
+        .bind(account.id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed query for active password reset ticket with account ID: {}",
+                    account.id
+                ),
+                e,
+            )
+        })?;
+
+        Ok((account, reset_ticket))
+    }
+
+    async fn create_password_reset_ticket(
+        &self,
+        req: &RequestPasswordResetRequest,
+        min_interval: Duration,
+    ) -> Result<(), RequestPasswordResetError> {
+        let mut transaction = begin_transaction(&self.pool).await?;
+
+        sqlx::query(
+            r#"
+            SELECT "id" FROM "account"
+            WHERE "id" = $1
+            FOR UPDATE
+        "#,
+        )
+        .bind(req.account_id)
         .fetch_one(&mut *transaction)
         .await
         .map_err(|e| {
-            anyhow!(e).context(format!("failed to update account with ID: {account_id}"))
+            map_sqlx_error(
+                &format!("failed to lock account with ID: {}", req.account_id),
+                e,
+            )
+        })?;
+
+        // Both dates are given by the database which sets the creation date of the tickets
+        let active_ticket_dates = sqlx::query_as::<_, (DateTime<Utc>, DateTime<Utc>)>(
+            r#"
+            SELECT "created_at", CURRENT_TIMESTAMP
+            FROM "password_reset_ticket"
+            WHERE "account_id" = $1 AND "status" = 'active'
+            ORDER BY "created_at" DESC
+            LIMIT 1
+        "#,
+        )
+        .bind(req.account_id)
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed to get active password reset ticket for account ID: {}",
+                    req.account_id
+                ),
+                e,
+            )
+        })?;
+        if let Some((created_at, now)) = active_ticket_dates {
+            RequestPasswordResetRequest::ensure_outside_throttle_window(
+                created_at,
+                now,
+                min_interval,
+            )?;
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE "password_reset_ticket"
+            SET "status" = 'cancelled'
+            WHERE "account_id" = $1 AND "status" = 'active';
+            "#,
+        )
+        .bind(req.account_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed to cancel previous active password reset ticket for account ID: {}",
+                    req.account_id
+                ),
+                e,
+            )
         })?;
 
         sqlx::query(
             r#"
-            UPDATE "account_verification_ticket"
+            INSERT INTO "password_reset_ticket" (
+                "account_id",
+                "cyphertext"
+            ) VALUES (
+                $1,
+                $2
+            );
+        "#,
+        )
+        .bind(req.account_id)
+        .bind(&req.reset_cyphertext)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed to create new active password reset ticket for account ID: {}",
+                    req.account_id
+                ),
+                e,
+            )
+        })?;
+
+        commit_transaction(transaction).await?;
+
+        Ok(())
+    }
+
+    async fn reset_password(
+        &self,
+        transaction: &mut DatabaseTransaction,
+        req: &ResetPasswordRequest,
+    ) -> Result<Account, ResetPasswordError> {
+        // The ticket is confirmed first, a concurrent reset with the same code waits for the row and then finds it confirmed
+        let confirmed_tickets = sqlx::query(
+            r#"
+            UPDATE "password_reset_ticket"
             SET "status" = 'confirmed'
-            WHERE "account_id" = $1 AND "status" = 'active'
+            WHERE "id" = $1 AND "account_id" = $2 AND "status" = 'active'
+        "#,
+        )
+        .bind(req.ticket_id)
+        .bind(req.account_id)
+        .execute(&mut **transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed to confirm password reset ticket with ID: {}",
+                    req.ticket_id
+                ),
+                e,
+            )
+        })?
+        .rows_affected();
+        if confirmed_tickets == 0 {
+            return Err(ResetPasswordError::NoActivePasswordResetTicket {
+                ticket_id: req.ticket_id,
+            });
+        }
+
+        let account = sqlx::query_as::<_, Account>(
+            r#"
+            UPDATE "account"
+            SET "password_hash" = $2
+            WHERE "id" = $1
+            RETURNING
+                id,
+                email,
+                password_hash,
+                verified,
+                display_name,
+                max_token_lifetime_secs,
+                locked_until,
+                deactivated_at,
+                created_at,
+                updated_at
+        "#,
+        )
+        .bind(req.account_id)
+        .bind(&req.password_hash)
+        .fetch_one(&mut **transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed to update password of account with ID: {}",
+                    req.account_id
+                ),
+                e,
+            )
+        })?;
+        let account = self.reveal(account)?;
+
+        Ok(account)
+    }
+
+    async fn update_password(
+        &self,
+        account_id: uuid::Uuid,
+        password_hash: &str,
+    ) -> Result<Account, AccountQueryError> {
+        let mut transaction = begin_transaction(&self.pool).await?;
+        let account = self
+            .update_password_in_transaction(&mut transaction, account_id, password_hash)
+            .await?;
+        commit_transaction(transaction).await?;
+
+        Ok(account)
+    }
+
+    async fn update_password_in_transaction(
+        &self,
+        transaction: &mut DatabaseTransaction,
+        account_id: uuid::Uuid,
+        password_hash: &str,
+    ) -> Result<Account, AccountQueryError> {
+        let account = sqlx::query_as::<_, Account>(
+            r#"
+            UPDATE "account"
+            SET "password_hash" = $2
+            WHERE "id" = $1
+            RETURNING
+                id,
+                email,
+                password_hash,
+                verified,
+                display_name,
+                max_token_lifetime_secs,
+                locked_until,
+                deactivated_at,
+                created_at,
+                updated_at
         "#,
         )
         .bind(account_id)
-        .execute(&mut *transaction)
+        .bind(password_hash)
+        .fetch_one(&mut **transaction)
         .await
         .map_err(|e| {
-            anyhow!(e).context(format!(
-                "failed to confirm verification ticket for account with ID: {account_id}"
-            ))
+            map_sqlx_error(
+                &format!("failed to update password of account with ID: {account_id}"),
+                e,
+            )
         })?;
+        let account = self.reveal(account)?;
 
-        transaction
-            .commit()
-            .await
-            .map_err(|e| anyhow!(e).context("failed to commit transaction"))?;
+        Ok(account)
+    }
+
+    async fn update_profile(
+        &self,
+        account_id: uuid::Uuid,
+        req: &UpdateProfileRequest,
+    ) -> Result<Account, UpdateProfileError> {
+        let account = sqlx::query_as::<_, Account>(
+            r#"
+            UPDATE "account"
+            SET "display_name" = CASE WHEN $2 THEN $3 ELSE "display_name" END
+            WHERE "id" = $1 AND ($4::timestamptz IS NULL OR "updated_at" = $4)
+            RETURNING
+                id,
+                email,
+                password_hash,
+                verified,
+                display_name,
+                max_token_lifetime_secs,
+                locked_until,
+                deactivated_at,
+                created_at,
+                updated_at
+        "#,
+        )
+        .bind(account_id)
+        .bind(req.display_name.is_some())
+        .bind(req.display_name.as_ref().and_then(|v| v.as_deref()))
+        .bind(req.expected_updated_at)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!("failed to update profile of account with ID: {account_id}"),
+                e,
+            )
+        })?
+        .ok_or_else(|| match req.expected_updated_at {
+            Some(_) => UpdateProfileError::PreconditionFailed,
+            None => anyhow::anyhow!("account with ID {account_id} not found").into(),
+        })?;
+        let account = self.reveal(account)?;
 
         Ok(account)
     }
+
+    async fn create_invite_code(&self, code: &str) -> Result<InviteCode, AccountQueryError> {
+        let invite_code = sqlx::query_as::<_, InviteCode>(
+            r#"
+            INSERT INTO "invite_code" ("code")
+            VALUES ($1)
+            RETURNING
+                id,
+                code,
+                used_by,
+                used_at,
+                created_at,
+                updated_at
+        "#,
+        )
+        .bind(code)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| map_sqlx_error("failed to insert invite code", e))?;
+
+        Ok(invite_code)
+    }
+
+    async fn set_max_token_lifetime(
+        &self,
+        account_id: uuid::Uuid,
+        max_token_lifetime_secs: Option<u32>,
+    ) -> Result<Account, AccountQueryError> {
+        let max_token_lifetime_secs = max_token_lifetime_secs
+            .map(i32::try_from)
+            .transpose()
+            .map_err(|e| {
+                anyhow::anyhow!(e).context("maximum access token lifetime out of range")
+            })?;
+        let account = sqlx::query_as::<_, Account>(
+            r#"
+            UPDATE "account"
+            SET "max_token_lifetime_secs" = $2
+            WHERE "id" = $1
+            RETURNING
+                id,
+                email,
+                password_hash,
+                verified,
+                display_name,
+                max_token_lifetime_secs,
+                locked_until,
+                deactivated_at,
+                created_at,
+                updated_at
+        "#,
+        )
+        .bind(account_id)
+        .bind(max_token_lifetime_secs)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed to set maximum access token lifetime of account with ID: {account_id}"
+                ),
+                e,
+            )
+        })?;
+        let account = self.reveal(account)?;
+
+        Ok(account)
+    }
+
+    async fn merge_accounts(
+        &self,
+        source_account_id: uuid::Uuid,
+        target_account_id: uuid::Uuid,
+        max_active_token: u8,
+    ) -> Result<AccountMerge, MergeAccountsError> {
+        if source_account_id == target_account_id {
+            return Err(MergeAccountsError::SameAccount);
+        }
+
+        let mut transaction = begin_transaction(&self.pool).await?;
+
+        let locked_account_ids: Vec<uuid::Uuid> = sqlx::query_scalar(
+            r#"
+            SELECT "id" FROM "account"
+            WHERE "id" = ANY($1)
+            ORDER BY "id"
+            FOR UPDATE
+        "#,
+        )
+        .bind([source_account_id, target_account_id])
+        .fetch_all(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed to lock accounts with IDs: {source_account_id}, {target_account_id}"
+                ),
+                e,
+            )
+        })?;
+        for account_id in [source_account_id, target_account_id] {
+            if !locked_account_ids.contains(&account_id) {
+                return Err(MergeAccountsError::AccountNotFound { account_id });
+            }
+        }
+
+        // Active access tokens of an account have distinct names, the conflicting names of the source account are suffixed
+        sqlx::query(
+            r#"
+            UPDATE "access_token" AS "source"
+            SET "name" = left("source"."name", 31) || '-' || left("source"."id"::text, 8)
+            WHERE "source"."account_id" = $1
+                AND "source"."revoked_at" IS NULL
+                AND EXISTS (
+                    SELECT 1 FROM "access_token" AS "target"
+                    WHERE "target"."account_id" = $2
+                        AND "target"."revoked_at" IS NULL
+                        AND "target"."name" = "source"."name"
+                )
+        "#,
+        )
+        .bind(source_account_id)
+        .bind(target_account_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed to rename conflicting access tokens of account with ID: {source_account_id}"
+                ),
+                e,
+            )
+        })?;
+
+        let moved_access_tokens = sqlx::query(
+            r#"
+            UPDATE "access_token"
+            SET "account_id" = $2
+            WHERE "account_id" = $1
+        "#,
+        )
+        .bind(source_account_id)
+        .bind(target_account_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!("failed to move access tokens of account with ID: {source_account_id}"),
+                e,
+            )
+        })?
+        .rows_affected();
+
+        // Both accounts are locked, the count can not change before the commit
+        let active_access_tokens: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM "access_token"
+            WHERE "account_id" = $1
+                AND NOT "session"
+                AND "revoked_at" IS NULL
+                AND "expires_at" > CURRENT_TIMESTAMP
+        "#,
+        )
+        .bind(target_account_id)
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed to retrieve active access token count of account with ID: {target_account_id}"
+                ),
+                e,
+            )
+        })?;
+        if active_access_tokens > max_active_token.into() {
+            return Err(MergeAccountsError::ActiveTokenLimitExceeded(
+                max_active_token,
+            ));
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE "invite_code"
+            SET "used_by" = $2
+            WHERE "used_by" = $1
+        "#,
+        )
+        .bind(source_account_id)
+        .bind(target_account_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!("failed to move invite code of account with ID: {source_account_id}"),
+                e,
+            )
+        })?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM "account_verification_ticket"
+            WHERE "account_id" = $1
+        "#,
+        )
+        .bind(source_account_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed to delete verification tickets of account with ID: {source_account_id}"
+                ),
+                e,
+            )
+        })?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM "password_reset_ticket"
+            WHERE "account_id" = $1
+        "#,
+        )
+        .bind(source_account_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed to delete password reset tickets of account with ID: {source_account_id}"
+                ),
+                e,
+            )
+        })?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM "account"
+            WHERE "id" = $1
+        "#,
+        )
+        .bind(source_account_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!("failed to delete account with ID: {source_account_id}"),
+                e,
+            )
+        })?;
+
+        let target_account = sqlx::query_as::<_, Account>(
+            r#"
+            SELECT
+                id,
+                email,
+                password_hash,
+                verified,
+                display_name,
+                max_token_lifetime_secs,
+                locked_until,
+                deactivated_at,
+                created_at,
+                updated_at
+            FROM "account"
+            WHERE "id" = $1
+        "#,
+        )
+        .bind(target_account_id)
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!("failed to get account with ID: {target_account_id}"),
+                e,
+            )
+        })?;
+        let target_account = self.reveal(target_account)?;
+
+        commit_transaction(transaction).await?;
+
+        Ok(AccountMerge {
+            target_account,
+            moved_access_tokens,
+        })
+    }
+
+    async fn delete_stale_verification_tickets(
+        &self,
+        stale_before: DateTime<Utc>,
+        batch_size: u32,
+    ) -> Result<u64, anyhow::Error> {
+        // The batch is bounded so that the statement does not hold its locks for long on a large table
+        let result = sqlx::query(
+            r#"
+            DELETE FROM "account_verification_ticket"
+            WHERE "id" IN (
+                SELECT "id"
+                FROM "account_verification_ticket"
+                WHERE "status" <> 'confirmed' AND "created_at" < $1
+                LIMIT $2
+            )
+        "#,
+        )
+        .bind(stale_before)
+        .bind(i64::from(batch_size))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| map_sqlx_error("failed to delete stale verification tickets", e))?;
+        Ok(result.rows_affected())
+    }
+}
+
+/// Mark an unused invite code as used by an account, within the transaction of the signup
+async fn use_invite_code(
+    transaction: &mut DatabaseTransaction,
+    code: &str,
+    account_id: uuid::Uuid,
+) -> Result<(), SignupError> {
+    let used_invite_codes = sqlx::query(
+        r#"
+        UPDATE "invite_code"
+        SET "used_by" = $2, "used_at" = CURRENT_TIMESTAMP
+        WHERE "code" = $1 AND "used_at" IS NULL
+    "#,
+    )
+    .bind(code)
+    .bind(account_id)
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        map_sqlx_error(
+            &format!("failed to use invite code for account with ID: {account_id}"),
+            e,
+        )
+    })?
+    .rows_affected();
+    if used_invite_codes == 0 {
+        return Err(SignupError::InvalidInviteCode);
+    }
+    Ok(())
+}
+
+impl From<RepositoryError> for AccountQueryError {
+    fn from(value: RepositoryError) -> Self {
+        match value {
+            RepositoryError::NotFound { .. } => AccountQueryError::AccountNotFound,
+            e => AccountQueryError::Unknown(e.into()),
+        }
+    }
+}
+
+impl From<RepositoryError> for SignupError {
+    fn from(value: RepositoryError) -> Self {
+        match value {
+            RepositoryError::UniqueViolation {
+                constraint: Some(constraint),
+                ..
+            } if constraint == "account_email_key"
+                || constraint == "account_email_lower_key"
+                || constraint == "account_email_lookup_key" =>
+            {
+                SignupError::EmailAlreadyExists
+            }
+            e => SignupError::Unknown(e.into()),
+        }
+    }
+}
+
+impl From<RepositoryError> for VerifyAccountError {
+    fn from(value: RepositoryError) -> Self {
+        VerifyAccountError::Unknown(value.into())
+    }
+}
+
+impl From<RepositoryError> for ResendVerificationError {
+    fn from(value: RepositoryError) -> Self {
+        ResendVerificationError::Unknown(value.into())
+    }
+}
+
+impl From<RepositoryError> for RequestPasswordResetError {
+    fn from(value: RepositoryError) -> Self {
+        RequestPasswordResetError::Unknown(value.into())
+    }
+}
+
+impl From<RepositoryError> for ResetPasswordError {
+    fn from(value: RepositoryError) -> Self {
+        ResetPasswordError::Unknown(value.into())
+    }
+}
+
+impl From<RepositoryError> for MergeAccountsError {
+    fn from(value: RepositoryError) -> Self {
+        MergeAccountsError::Unknown(value.into())
+    }
+}
+
+impl From<RepositoryError> for UpdateProfileError {
+    fn from(value: RepositoryError) -> Self {
+        match value {
+            RepositoryError::CheckViolation { field, .. } => {
+                UpdateProfileError::ConstraintViolation { field }
+            }
+            e => UpdateProfileError::Unknown(e.into()),
+        }
+    }
 }
@@ -1,10 +1,10 @@
-use crate::newtypes;
+use crate::{newtypes, rng::new_rng};
 use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier, password_hash::Salt};
 use base64::prelude::*;
 use hmac::{Hmac, Mac};
-use rand::prelude::*;
-use rand_chacha::ChaCha20Rng;
+use rand::CryptoRng;
 use sha3::Sha3_256;
+use std::sync::LazyLock;
 
 #[derive(Debug)]
 pub struct VerificationSecretStrategy;
@@ -12,6 +12,23 @@ pub struct VerificationSecretStrategy;
 const MAC_LENGTH: usize = 32;
 const SERIALIZED_KEY_LENGTH: usize = 97;
 
+/// Version of the cyphertext layout, prefixed to the generated cyphertexts
+///
+/// Version 1: serialized Argon2id key (97 bytes) followed by the mac (32 bytes)
+const CYPHERTEXT_VERSION_1: u8 = 1;
+/// First byte of the serialized Argon2id key, i.e. of the cyphertexts generated before the version prefix
+const LEGACY_CYPHERTEXT_FIRST_BYTE: u8 = b'$';
+/// Email linked to the dummy verification secrets, the `.invalid` top level domain is never registered
+const DUMMY_EMAIL: &str = "dummy@soko.invalid";
+/// Cyphertext of a random secret, see [VerificationSecretStrategy::verify_dummy_verification_secret]
+static DUMMY_CYPHERTEXT: LazyLock<String> = LazyLock::new(|| {
+    VerificationSecretStrategy::generate_verification_secret(&newtypes::Email::new_unchecked(
+        DUMMY_EMAIL,
+    ))
+    .expect("failed to generate the dummy verification secret")
+    .1
+});
+
 impl VerificationSecretStrategy {
     /// Generate a verification secret linked to an email with its encryption
     ///
@@ -26,8 +43,14 @@ impl VerificationSecretStrategy {
     pub fn generate_verification_secret(
         email: &newtypes::Email,
     ) -> Result<(String, String), anyhow::Error> {
-        let mut rng = ChaCha20Rng::from_os_rng();
+        Self::generate_verification_secret_with_rng(email, &mut new_rng())
+    }
 
+    /// Same as [VerificationSecretStrategy::generate_verification_secret] but the salt and the secret are generated using the given random number generator
+    pub fn generate_verification_secret_with_rng(
+        email: &newtypes::Email,
+        rng: &mut impl CryptoRng,
+    ) -> Result<(String, String), anyhow::Error> {
         let mut salt = [0u8; 16];
         rng.fill_bytes(&mut salt);
         let base64_salt = BASE64_STANDARD_NO_PAD.encode(salt);
@@ -47,11 +70,13 @@ impl VerificationSecretStrategy {
         hmac.update(email.as_str().as_bytes());
         let mac = hmac.finalize().into_bytes();
 
-        // Mac is 32 bytes
+        // Version is 1 byte
         // Key is a string of 97 bytes
-        let mut cyphertext = [0u8; MAC_LENGTH + SERIALIZED_KEY_LENGTH];
-        cyphertext[..SERIALIZED_KEY_LENGTH].copy_from_slice(key.serialize().as_bytes());
-        cyphertext[SERIALIZED_KEY_LENGTH..].copy_from_slice(&mac);
+        // Mac is 32 bytes
+        let mut cyphertext = [0u8; 1 + SERIALIZED_KEY_LENGTH + MAC_LENGTH];
+        cyphertext[0] = CYPHERTEXT_VERSION_1;
+        cyphertext[1..1 + SERIALIZED_KEY_LENGTH].copy_from_slice(key.serialize().as_bytes());
+        cyphertext[1 + SERIALIZED_KEY_LENGTH..].copy_from_slice(&mac);
 
         Ok((
             BASE64_URL_SAFE.encode(secret),
@@ -59,10 +84,34 @@ impl VerificationSecretStrategy {
         ))
     }
 
+    /// Generate a verification secret and discard it
+    ///
+    /// It takes as long as [VerificationSecretStrategy::generate_verification_secret], e.g. it is used for unknown accounts so that the latency does not tell whether an account exists.
+    /// The throttle window of a resend or of a password reset request is checked before the derivation,
+    /// an ineligible account is then derived a dummy secret so that the latency does not tell whether a secret or a code has been sent.
+    pub fn generate_dummy_verification_secret() -> Result<(), anyhow::Error> {
+        Self::generate_verification_secret(&newtypes::Email::new_unchecked(DUMMY_EMAIL)).map(|_| ())
+    }
+
+    /// Verify a secret against the cyphertext of a random secret, the verification always fails
+    ///
+    /// It takes as long as [VerificationSecretStrategy::verify_verification_secret], e.g. it is used for unknown accounts so that the latency does not tell whether an account exists.
+    ///
+    /// # Arguments
+    /// * `secret` - base64 URL safe encoded secret
+    pub fn verify_dummy_verification_secret(secret: &str) -> bool {
+        let _ = Self::verify_verification_secret(
+            secret,
+            &newtypes::Email::new_unchecked(DUMMY_EMAIL),
+            &DUMMY_CYPHERTEXT,
+        );
+        false
+    }
+
     /// Verify a verification secret, returns true if secret is correct, false otherwise
     ///
-    /// The secret is verified against the Argon2id generated key.
-    /// The mail is verified against the HMAC of the generated key hash, the email and using SHA3-256
+    /// The cyphertext starts with a version byte, the rest of the cyphertext is parsed according to its version.
+    /// Cyphertexts generated before the version prefix are parsed as version 1.
     ///
     /// # Arguments
     /// * `secret` - base64 URL safe encoded secret,
@@ -75,20 +124,41 @@ impl VerificationSecretStrategy {
     ) -> Result<bool, anyhow::Error> {
         let secret_bytes = BASE64_URL_SAFE.decode(secret)?;
         let cyphertext_bytes = BASE64_STANDARD_NO_PAD.decode(cyphertext)?;
-        if cyphertext_bytes.len() != MAC_LENGTH + SERIALIZED_KEY_LENGTH {
+        match cyphertext_bytes.split_first() {
+            Some((&LEGACY_CYPHERTEXT_FIRST_BYTE, _)) => {
+                Self::verify_version_1(&secret_bytes, email, &cyphertext_bytes)
+            }
+            Some((&CYPHERTEXT_VERSION_1, payload)) => {
+                Self::verify_version_1(&secret_bytes, email, payload)
+            }
+            Some((version, _)) => Err(anyhow::anyhow!("Unsupported cyphertext version {version}")),
+            None => Err(anyhow::anyhow!("Empty cyphertext")),
+        }
+    }
+
+    /// Verify a secret against a version 1 cyphertext payload, i.e. without its version byte
+    ///
+    /// The secret is verified against the Argon2id generated key.
+    /// The mail is verified against the HMAC of the generated key hash, the email and using SHA3-256
+    fn verify_version_1(
+        secret_bytes: &[u8],
+        email: &newtypes::Email,
+        payload: &[u8],
+    ) -> Result<bool, anyhow::Error> {
+        if payload.len() != SERIALIZED_KEY_LENGTH + MAC_LENGTH {
             return Err(anyhow::anyhow!(
                 "Expected {} bytes length string, got {}",
-                MAC_LENGTH + SERIALIZED_KEY_LENGTH,
-                cyphertext_bytes.len()
+                SERIALIZED_KEY_LENGTH + MAC_LENGTH,
+                payload.len()
             ));
         }
-        let (key, mac) = cyphertext_bytes.split_at(97);
+        let (key, mac) = payload.split_at(SERIALIZED_KEY_LENGTH);
 
         let password_hash =
             PasswordHash::new(std::str::from_utf8(key)?).map_err(|e| anyhow::anyhow!("{e}"))?;
 
         Argon2::default()
-            .verify_password(&secret_bytes, &password_hash)
+            .verify_password(secret_bytes, &password_hash)
             .map_err(|e| anyhow::anyhow!("{e}"))?;
         let mut hmac: Hmac<Sha3_256> = Hmac::new_from_slice(
             password_hash
@@ -115,7 +185,57 @@ mod tests {
             VerificationSecretStrategy::generate_verification_secret(&email).unwrap();
         assert!(
             VerificationSecretStrategy::verify_verification_secret(&secret, &email, &cyphertext)
-                .is_ok()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_verification_secret_cyphertext_versions() {
+        let email: newtypes::Email = Faker.fake();
+        let (secret, cyphertext) =
+            VerificationSecretStrategy::generate_verification_secret(&email).unwrap();
+        let cyphertext_bytes = BASE64_STANDARD_NO_PAD.decode(&cyphertext).unwrap();
+        assert_eq!(cyphertext_bytes[0], CYPHERTEXT_VERSION_1);
+        assert_eq!(
+            cyphertext_bytes.len(),
+            1 + SERIALIZED_KEY_LENGTH + MAC_LENGTH
+        );
+
+        // Cyphertexts generated before the version prefix are verified as version 1
+        let legacy_cyphertext = BASE64_STANDARD_NO_PAD.encode(&cyphertext_bytes[1..]);
+        assert!(
+            VerificationSecretStrategy::verify_verification_secret(
+                &secret,
+                &email,
+                &legacy_cyphertext
+            )
+            .unwrap()
+        );
+
+        // Unknown versions are rejected whatever the length of the cyphertext
+        for payload_length in [0, SERIALIZED_KEY_LENGTH + MAC_LENGTH, 512] {
+            let mut unknown_version_bytes = vec![2u8];
+            unknown_version_bytes.extend(std::iter::repeat_n(0u8, payload_length));
+            let error = VerificationSecretStrategy::verify_verification_secret(
+                &secret,
+                &email,
+                &BASE64_STANDARD_NO_PAD.encode(unknown_version_bytes),
+            )
+            .unwrap_err();
+            assert_eq!(error.to_string(), "Unsupported cyphertext version 2");
+        }
+
+        // Truncated version 1 cyphertexts are rejected
+        assert!(
+            VerificationSecretStrategy::verify_verification_secret(
+                &secret,
+                &email,
+                &BASE64_STANDARD_NO_PAD.encode(&cyphertext_bytes[..64])
+            )
+            .is_err()
+        );
+        assert!(
+            VerificationSecretStrategy::verify_verification_secret(&secret, &email, "").is_err()
         );
     }
 }
@@ -0,0 +1,198 @@
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    routing::{post, put},
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use validator::Validate;
+
+use super::{
+    Admin, ApiError, AppState, UuidPath, ValidatedJson,
+    accounts::{AccountMerge, InviteCode, generate_invite_code},
+    timestamp,
+    tokens::{MAX_ACTIVE_TOKENS, MAX_LIFETIME},
+};
+
+/// Routes reserved to the operators of the service, see [Admin]
+///
+/// # Arguments
+/// * `test_clock` - if true, the route advancing the clock is served, see [crate::Config::test_clock]
+pub fn admin_router(test_clock: bool) -> Router<AppState> {
+    let router = Router::new()
+        .route(
+            "/accounts/{id}/token-lifetime-policy",
+            put(set_token_lifetime_policy),
+        )
+        .route("/accounts/merge", post(merge_accounts))
+        .route("/invite-codes", post(create_invite_code));
+    if test_clock {
+        router.route("/advance-clock", post(advance_clock))
+    } else {
+        router
+    }
+}
+
+// ###########################################################
+// ################## TOKEN LIFETIME POLICY ##################
+// ###########################################################
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetTokenLifetimePolicyBody {
+    /// Maximum lifetime in seconds of the access tokens of the account, `null` removes the account policy
+    #[validate(range(min = 1, max = MAX_LIFETIME))]
+    pub max_token_lifetime_secs: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenLifetimePolicyResponse {
+    pub account_id: uuid::Uuid,
+    pub max_token_lifetime_secs: Option<i32>,
+}
+
+async fn set_token_lifetime_policy(
+    _: Admin,
+    State(app_state): State<AppState>,
+    UuidPath(account_id): UuidPath,
+    ValidatedJson(body): ValidatedJson<SetTokenLifetimePolicyBody>,
+) -> Result<(StatusCode, Json<TokenLifetimePolicyResponse>), ApiError> {
+    let account = app_state
+        .account_repository
+        .set_max_token_lifetime(account_id, body.max_token_lifetime_secs)
+        .await?;
+
+    info!(
+        "maximum access token lifetime of account {} set to {:?}",
+        account.id, account.max_token_lifetime_secs
+    );
+
+    Ok((
+        StatusCode::OK,
+        Json(TokenLifetimePolicyResponse {
+            account_id: account.id,
+            max_token_lifetime_secs: account.max_token_lifetime_secs,
+        }),
+    ))
+}
+
+// ###################################################
+// ################## ACCOUNT MERGE ##################
+// ###################################################
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeAccountsBody {
+    /// Account which is deleted once its access tokens are moved
+    pub source_account_id: uuid::Uuid,
+    /// Account which receives the access tokens of the source account
+    pub target_account_id: uuid::Uuid,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountMergeResponse {
+    pub account_id: uuid::Uuid,
+    pub moved_access_tokens: u64,
+}
+
+impl From<AccountMerge> for AccountMergeResponse {
+    fn from(value: AccountMerge) -> Self {
+        AccountMergeResponse {
+            account_id: value.target_account.id,
+            moved_access_tokens: value.moved_access_tokens,
+        }
+    }
+}
+
+async fn merge_accounts(
+    _: Admin,
+    State(app_state): State<AppState>,
+    ValidatedJson(body): ValidatedJson<MergeAccountsBody>,
+) -> Result<(StatusCode, Json<AccountMergeResponse>), ApiError> {
+    let account_merge = app_state
+        .account_repository
+        .merge_accounts(
+            body.source_account_id,
+            body.target_account_id,
+            MAX_ACTIVE_TOKENS,
+        )
+        .await?;
+
+    info!(
+        "account {} merged into account {}, {} access tokens moved",
+        body.source_account_id, body.target_account_id, account_merge.moved_access_tokens
+    );
+
+    Ok((StatusCode::OK, Json(account_merge.into())))
+}
+
+// ##################################################
+// ################## INVITE CODES ##################
+// ##################################################
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InviteCodeResponse {
+    pub code: String,
+    #[serde(with = "timestamp")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<InviteCode> for InviteCodeResponse {
+    fn from(value: InviteCode) -> Self {
+        InviteCodeResponse {
+            code: value.code,
+            created_at: value.created_at,
+        }
+    }
+}
+
+async fn create_invite_code(
+    _: Admin,
+    State(app_state): State<AppState>,
+) -> Result<(StatusCode, Json<InviteCodeResponse>), ApiError> {
+    let invite_code = app_state
+        .account_repository
+        .create_invite_code(&generate_invite_code())
+        .await?;
+
+    info!("invite code {} created", invite_code.id);
+
+    Ok((StatusCode::CREATED, Json(invite_code.into())))
+}
+
+// ################################################
+// ################## TEST CLOCK ##################
+// ################################################
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdvanceClockBody {
+    /// Duration in seconds by which the clock is advanced
+    #[validate(range(min = 1))]
+    pub seconds: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdvanceClockResponse {
+    #[serde(with = "timestamp")]
+    pub now: DateTime<Utc>,
+}
+
+async fn advance_clock(
+    _: Admin,
+    State(app_state): State<AppState>,
+    ValidatedJson(body): ValidatedJson<AdvanceClockBody>,
+) -> Result<(StatusCode, Json<AdvanceClockResponse>), ApiError> {
+    let test_clock = app_state.test_clock.ok_or(ApiError::NotFound)?;
+
+    let now = test_clock.advance(TimeDelta::seconds(body.seconds.into()));
+    warn!("clock advanced by {} seconds to {now}", body.seconds);
+
+    Ok((StatusCode::OK, Json(AdvanceClockResponse { now })))
+}
@@ -1,50 +1,187 @@
-use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use std::{borrow::Cow, sync::Arc, time::Duration};
 use tracing::{error, warn};
 
 use axum::{
-    Json, Router,
-    extract::FromRequest,
-    http::StatusCode,
+    Extension, Json, Router,
+    extract::{
+        DefaultBodyLimit, FromRequest, FromRequestParts, Path, Query, Request, State,
+        rejection::{JsonDataError, JsonRejection},
+    },
+    http::{
+        HeaderMap, StatusCode,
+        header::{ACCEPT, AUTHORIZATION, RETRY_AFTER, WWW_AUTHENTICATE},
+        request::Parts,
+    },
+    middleware::{self, Next},
     response::{IntoResponse, Response},
     routing::get,
 };
-use serde::{Deserialize, Serialize, de::DeserializeOwned};
-use validator::{Validate, ValidationErrors};
+use serde::{
+    Deserialize, Serialize,
+    de::{DeserializeOwned, Unexpected, Visitor},
+};
+use sha3::{Digest, Sha3_256};
+use validator::{Validate, ValidationError, ValidationErrors};
 pub mod accounts;
+mod admin;
 mod newtypes;
+pub mod system;
 pub mod tokens;
+pub use newtypes::{
+    CharacterClass, MAX_PASSWORD_LENGTH, MIN_PASSWORD_LENGTH, PasswordHashAlgorithm, PasswordPolicy,
+};
+
+use metrics_exporter_prometheus::PrometheusHandle;
+
+use super::{
+    Config,
+    clock::{Clock, SystemClock, TestClock},
+    database::RepositoryError,
+    hashing::{HASHING_QUEUE_TIMEOUT, HashingError, HashingLimiter},
+    health::{HealthRepository, Readiness},
+    observability::{buffer_error_body, track_http_metrics},
+    third_party::{
+        EmailRetryPolicy, InstrumentedMailingService, MailingService, RetryingMailingService,
+        SmsService, WebhookNotifier,
+    },
+};
+use accounts::{Account, AccountQueryError, AccountRepository, AccountState};
+use system::SystemState;
+use tokens::{
+    AccessToken, AccessTokenRepository, LAST_USE_GRANULARITY, TOKEN_PREFIX, TokenQueryError,
+    compute_token_mac,
+};
 
-use super::{Config, third_party::MailingService};
-use accounts::AccountRepository;
-use tokens::AccessTokenRepository;
+/// Timeout of a request
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default maximum cumulated size in bytes of the header names and values of a request, see [Config::max_request_header_bytes]
+pub const DEFAULT_MAX_REQUEST_HEADER_BYTES: usize = 8192;
+/// Default maximum number of headers of a request, it leaves room for the headers added by the browsers and the proxies, see [Config::max_request_headers]
+pub const DEFAULT_MAX_REQUEST_HEADERS: usize = 100;
+/// Default maximum size in bytes of the body of a request, the public endpoints only accept small JSON bodies, see [Config::max_body_bytes]
+pub const DEFAULT_MAX_BODY_BYTES: usize = 64 * 1024;
+/// Default maximum size in bytes of the body of a request to the admin routes, see [Config::admin_max_body_bytes]
+pub const DEFAULT_ADMIN_MAX_BODY_BYTES: usize = 8 * 1024 * 1024;
 
+#[allow(clippy::too_many_arguments)]
 pub fn app_router(
     config: &Config,
     account_repository: impl AccountRepository + 'static,
     access_token_repository: impl AccessTokenRepository + 'static,
     mailing_service: impl MailingService + 'static,
+    sms_service: impl SmsService + 'static,
+    webhook_notifier: impl WebhookNotifier + 'static,
+    health_repository: impl HealthRepository + 'static,
+    readiness: Readiness,
+    metrics_handle: Option<PrometheusHandle>,
 ) -> Router {
+    // The test clock is never used in release builds, see [Config::test_clock]
+    let test_clock =
+        (config.test_clock && cfg!(debug_assertions)).then(|| Arc::new(TestClock::default()));
+    let clock: Arc<dyn Clock> = match &test_clock {
+        Some(test_clock) => test_clock.clone(),
+        None => Arc::new(SystemClock),
+    };
+    // Every send is measured, whatever the mailing provider, a send is measured once with its retries
+    let mailing_service: Arc<dyn MailingService> = Arc::new(InstrumentedMailingService::from(
+        RetryingMailingService::new(
+            mailing_service,
+            EmailRetryPolicy {
+                max_retries: config.email_max_retries,
+                base_delay: config.email_retry_base_delay,
+                // The emails are sent within the requests, the other half is left to the rest of the request
+                budget: config.request_timeout / 2,
+            },
+        ),
+    ));
+    let sms_service: Arc<dyn SmsService> = Arc::new(sms_service);
+    let hashing_limiter = HashingLimiter::new(config.max_concurrent_hashes, HASHING_QUEUE_TIMEOUT);
+    let health_repository: Arc<dyn HealthRepository> = Arc::new(health_repository);
+    let system_state = SystemState {
+        health_repository: health_repository.clone(),
+        readiness,
+        mailing_service: mailing_service.clone(),
+        sms_service: sms_service.clone(),
+        hashing_limiter: hashing_limiter.clone(),
+    };
     let app_state = AppState {
+        config: Arc::new(config.clone()),
+        health_repository,
         account_repository: Arc::new(account_repository),
         access_token_repository: Arc::new(access_token_repository),
-        mailing_service: Arc::new(mailing_service),
+        mailing_service,
+        sms_service,
+        webhook_notifier: Arc::new(webhook_notifier),
+        clock,
+        test_clock,
+        hashing_limiter,
     };
-    Router::new()
+    let router = Router::new()
         .nest("/accounts", accounts::accounts_router())
-        .nest(
-            "/tokens",
-            tokens::tokens_router(config.access_token_secret.clone()),
+        .nest("/tokens", tokens::tokens_router())
+        .nest("/health", system::system_router(system_state))
+        .route("/ready", get(system::get_database_readiness))
+        .fallback(not_found_handler);
+    // The admin routes are only served if an admin API key is configured, their body limit overrides the global one
+    let router = if config.admin_api_key.is_some() {
+        router.nest(
+            "/admin",
+            admin::admin_router(app_state.test_clock.is_some())
+                .layer(DefaultBodyLimit::max(config.admin_max_body_bytes)),
         )
-        .route("/health", get(get_healthcheck))
-        .fallback(not_found_handler)
+    } else {
+        router
+    };
+    // The metrics route is added after the metrics layer, the scrapes are not recorded
+    let router = router.layer(middleware::from_fn(track_http_metrics));
+    let router = match metrics_handle {
+        Some(metrics_handle) => router.merge(system::metrics_router(metrics_handle)),
+        None => router,
+    };
+    let router = match &config.base_path {
+        Some(base_path) => Router::new()
+            .nest(base_path, router)
+            .fallback(not_found_handler),
+        None => router,
+    };
+    let router = router
+        .layer(DefaultBodyLimit::max(config.max_body_bytes))
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            set_validation_error_status,
+        ))
+        .layer(middleware::from_fn(timestamp::scope_request_timezone));
+    // Bodies may contain personal data, they are only logged in debug builds
+    let router = if config.log_error_bodies && cfg!(debug_assertions) {
+        router.layer(middleware::from_fn(buffer_error_body))
+    } else {
+        router
+    };
+    // Oversized headers are rejected before anything else
+    router
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            limit_request_headers,
+        ))
         .with_state(app_state)
 }
 
 #[derive(Clone)]
 pub struct AppState {
+    config: Arc<Config>,
+    /// Probe of the database for the readiness route, see [system::get_database_readiness]
+    health_repository: Arc<dyn HealthRepository>,
     account_repository: Arc<dyn AccountRepository>,
     access_token_repository: Arc<dyn AccessTokenRepository>,
     mailing_service: Arc<dyn MailingService>,
+    sms_service: Arc<dyn SmsService>,
+    webhook_notifier: Arc<dyn WebhookNotifier>,
+    clock: Arc<dyn Clock>,
+    /// Same clock as `clock` if it can be advanced, see [Config::test_clock]
+    test_clock: Option<Arc<TestClock>>,
+    /// Bound on the concurrent Argon2 operations of the requests, see [Config::max_concurrent_hashes]
+    hashing_limiter: HashingLimiter,
 }
 
 // ############################################
@@ -57,6 +194,30 @@ enum ApiError {
     BadRequest(ValidationErrors),
     NotFound,
     Unauthorized,
+    /// No bearer token has been presented, see [AuthenticatedAccessToken]
+    MissingBearerToken,
+    /// The presented bearer token is malformed, unknown or revoked, see [AuthenticatedAccessToken]
+    InvalidBearerToken,
+    /// The presented bearer token is known but has expired, see [AuthenticatedAccessToken]
+    ExpiredBearerToken,
+    /// The presented bearer token is older than the sensitive action window, see [RecentlyAuthenticatedAccount]
+    ReauthenticationRequired {
+        max_age_secs: u64,
+    },
+    Forbidden(ErrorResponse),
+    Conflict(ValidationErrors),
+    PreconditionFailed(ErrorResponse),
+    InvalidId,
+    /// Too many concurrent password hashing operations, see [HashingLimiter]
+    HashingSaturated,
+    /// No database connection could be acquired in time, see [RepositoryError::PoolTimeout]
+    DatabaseUnavailable,
+    TooManyRequests {
+        error: ErrorResponse,
+        retry_after_secs: u64,
+    },
+    /// The headers of the request exceed the configured limits, see [limit_request_headers]
+    RequestHeaderFieldsTooLarge,
 }
 
 impl IntoResponse for ApiError {
@@ -66,13 +227,296 @@ impl IntoResponse for ApiError {
                 error!("{e:?}");
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response()
             }
-            Self::BadRequest(errors) => (StatusCode::BAD_REQUEST, Json(errors)).into_response(),
-            Self::NotFound => (StatusCode::NOT_FOUND, "Not found").into_response(),
+            Self::BadRequest(errors) => (
+                StatusCode::BAD_REQUEST,
+                Json(ValidationErrorResponse::from(errors)),
+            )
+                .into_response(),
+            Self::NotFound => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorResponse::new("not_found", "Not found")),
+            )
+                .into_response(),
             Self::Unauthorized => StatusCode::UNAUTHORIZED.into_response(),
+            // Challenges of RFC 6750, the error lets clients tell an error to surface from a login to prompt
+            Self::MissingBearerToken => {
+                (StatusCode::UNAUTHORIZED, [(WWW_AUTHENTICATE, "Bearer")]).into_response()
+            }
+            Self::InvalidBearerToken => (
+                StatusCode::UNAUTHORIZED,
+                [(
+                    WWW_AUTHENTICATE,
+                    r#"Bearer error="invalid_token", error_description="The access token is malformed, unknown or revoked""#,
+                )],
+            )
+                .into_response(),
+            Self::ExpiredBearerToken => (
+                StatusCode::UNAUTHORIZED,
+                [(
+                    WWW_AUTHENTICATE,
+                    r#"Bearer error="invalid_token", error_description="The access token has expired""#,
+                )],
+            )
+                .into_response(),
+            // Step-up challenge of RFC 9470, the client is expected to log in again
+            Self::ReauthenticationRequired { max_age_secs } => (
+                StatusCode::UNAUTHORIZED,
+                [(
+                    WWW_AUTHENTICATE,
+                    format!(
+                        r#"Bearer error="insufficient_user_authentication", error_description="A more recent authentication is required", max_age={max_age_secs}"#
+                    ),
+                )],
+                Json(ErrorResponse::new(
+                    "reauthentication_required",
+                    "Log in again to perform this action",
+                )),
+            )
+                .into_response(),
+            Self::Forbidden(error) => (StatusCode::FORBIDDEN, Json(error)).into_response(),
+            Self::Conflict(errors) => (
+                StatusCode::CONFLICT,
+                Json(ValidationErrorResponse::from(errors)),
+            )
+                .into_response(),
+            Self::PreconditionFailed(error) => {
+                (StatusCode::PRECONDITION_FAILED, Json(error)).into_response()
+            }
+            Self::InvalidId => (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::new(
+                    "invalid_id",
+                    "Invalid id in path, expected a UUID",
+                )),
+            )
+                .into_response(),
+            Self::TooManyRequests {
+                error,
+                retry_after_secs,
+            } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after_secs.to_string())],
+                Json(error),
+            )
+                .into_response(),
+            Self::HashingSaturated => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(RETRY_AFTER, "1")],
+                Json(ErrorResponse::new(
+                    "service_unavailable",
+                    "Too many concurrent requests, retry later",
+                )),
+            )
+                .into_response(),
+            Self::DatabaseUnavailable => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(RETRY_AFTER, "1")],
+                Json(ErrorResponse::new(
+                    "service_unavailable",
+                    "The database is busy, retry later",
+                )),
+            )
+                .into_response(),
+            Self::RequestHeaderFieldsTooLarge => (
+                StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+                Json(ErrorResponse::new(
+                    "request_header_fields_too_large",
+                    "Request headers are too large or too numerous",
+                )),
+            )
+                .into_response(),
+        }
+    }
+}
+
+impl ApiError {
+    /// `400` with a single validation error on a field, see [field_error]
+    fn bad_request(
+        field: &'static str,
+        code: &'static str,
+        message: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        Self::BadRequest(field_error(field, code, message))
+    }
+
+    /// `409` with a single validation error on a field, see [field_error]
+    fn conflict(
+        field: &'static str,
+        code: &'static str,
+        message: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        Self::Conflict(field_error(field, code, message))
+    }
+}
+
+/// Validation errors made of a single error on a field
+///
+/// # Arguments
+/// * `field` - name of the field in the body, e.g. `email`,
+/// * `code` - code of the error, e.g. `existing-email`,
+/// * `message` - human readable message of the error
+fn field_error(
+    field: &'static str,
+    code: &'static str,
+    message: impl Into<Cow<'static, str>>,
+) -> ValidationErrors {
+    let mut errors = ValidationErrors::new();
+    errors.add(
+        field,
+        ValidationError::new(code).with_message(message.into()),
+    );
+    errors
+}
+
+/// Unknown errors of the domains are internal server errors, unless they carry a classified [RepositoryError]
+impl From<anyhow::Error> for ApiError {
+    fn from(value: anyhow::Error) -> Self {
+        match value.downcast::<RepositoryError>() {
+            Ok(e) => e.into(),
+            Err(e) => ApiError::InternalServerError(e),
         }
     }
 }
 
+/// Repository errors which are not converted into an error of their domain, see [crate::database::map_sqlx_error]
+///
+/// Missing rows are `404`, unique violations `409`, check violations `400` on their field and pool timeouts `503`.
+impl From<RepositoryError> for ApiError {
+    fn from(value: RepositoryError) -> Self {
+        match value {
+            RepositoryError::NotFound { ref op } => {
+                warn!("{op}: row not found");
+                ApiError::NotFound
+            }
+            RepositoryError::UniqueViolation {
+                ref op,
+                ref constraint,
+            } => {
+                warn!("{op}: unique constraint violation on {constraint:?}");
+                ApiError::conflict(
+                    "id",
+                    "conflict",
+                    "Request conflicts with an existing resource",
+                )
+            }
+            RepositoryError::CheckViolation { ref op, field, .. } => {
+                warn!("{op}: check constraint violation on {field}");
+                ApiError::bad_request(field, "constraint", format!("{field} is invalid"))
+            }
+            RepositoryError::PoolTimeout { ref op } => {
+                warn!("{op}: timed out while acquiring a connection");
+                ApiError::DatabaseUnavailable
+            }
+            RepositoryError::Unknown(e) => ApiError::InternalServerError(e),
+        }
+    }
+}
+
+impl From<HashingError> for ApiError {
+    fn from(value: HashingError) -> Self {
+        match value {
+            HashingError::Saturated => {
+                warn!("{value}");
+                ApiError::HashingSaturated
+            }
+            HashingError::Unknown(e) => e.into(),
+        }
+    }
+}
+
+/// Body of the error responses, validation errors are returned as is
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub code: String,
+    pub message: String,
+}
+
+impl ErrorResponse {
+    fn new(code: &str, message: &str) -> Self {
+        Self {
+            code: code.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Body of the validation error responses, the message summarizes the errors of the fields, e.g. `name: name must not be empty`
+#[derive(Debug, Serialize)]
+pub struct ValidationErrorResponse {
+    pub message: String,
+    pub fields: ValidationErrors,
+}
+
+impl From<ValidationErrors> for ValidationErrorResponse {
+    fn from(fields: ValidationErrors) -> Self {
+        let mut field_errors: Vec<_> = fields.field_errors().into_iter().collect();
+        // Fields are sorted so that the message does not depend on the order of the map
+        field_errors.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let message = field_errors
+            .iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| {
+                    let reason = error.message.as_deref().unwrap_or(&error.code);
+                    format!("{field}: {reason}")
+                })
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        Self { message, fields }
+    }
+}
+
+/// Response extension marking a well-formed request which failed validation
+///
+/// Only the rejections of the extractors are marked, the business errors of the handlers keep their status.
+/// The status of marked responses is set by [set_validation_error_status] according to the configuration.
+#[derive(Clone, Copy, Debug)]
+struct ValidationFailure;
+
+/// Rejection of an extractor whose deserialized value fails its validation, see [ValidationFailure]
+fn validation_failure(errors: ValidationErrors) -> Response {
+    (
+        Extension(ValidationFailure),
+        ApiError::BadRequest(errors).into_response(),
+    )
+        .into_response()
+}
+
+async fn set_validation_error_status(
+    State(app_state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(req).await;
+    if response.extensions().get::<ValidationFailure>().is_some() {
+        *response.status_mut() = app_state.config.validation_error_status;
+    }
+    response
+}
+
+/// Reject requests whose headers exceed [Config::max_request_headers] or [Config::max_request_header_bytes] with `431`
+async fn limit_request_headers(
+    State(app_state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let headers = req.headers();
+    let header_bytes: usize = headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len())
+        .sum();
+    if headers.len() > app_state.config.max_request_headers
+        || header_bytes > app_state.config.max_request_header_bytes
+    {
+        warn!(
+            "request rejected with {} headers of {header_bytes} bytes",
+            headers.len()
+        );
+        return ApiError::RequestHeaderFieldsTooLarge.into_response();
+    }
+    next.run(req).await
+}
+
 // ###########################################
 // ################## UTILS ##################
 // ###########################################
@@ -89,31 +533,821 @@ where
     async fn from_request(req: axum::extract::Request, state: &S) -> Result<Self, Self::Rejection> {
         let payload: Json<T> = match Json::from_request(req, state).await {
             Ok(p) => p,
+            // Well-formed JSON body which does not match the expected type
+            Err(JsonRejection::JsonDataError(e)) => {
+                warn!("{}", redacted_data_error(&e));
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Extension(ValidationFailure),
+                    e.body_text(),
+                )
+                    .into_response());
+            }
+            // Body exceeding the body limit, see [Config::max_body_bytes]
+            Err(JsonRejection::BytesRejection(e))
+                if e.status() == StatusCode::PAYLOAD_TOO_LARGE =>
+            {
+                warn!("{e}");
+                return Err((StatusCode::PAYLOAD_TOO_LARGE, e.body_text()).into_response());
+            }
             Err(e) => {
                 warn!("{e}");
                 return Err((StatusCode::BAD_REQUEST, e.body_text()).into_response());
             }
         };
         if let Err(e) = payload.validate() {
-            return Err((StatusCode::BAD_REQUEST, Json(e)).into_response());
+            return Err(validation_failure(e));
         }
 
         Ok(Self(payload.0))
     }
 }
 
-// #################################################
-// ################## HEALTHCHECK ##################
-// #################################################
+/// Log message of a JSON body which does not match the expected type, it only names the rejected field
+///
+/// The deserialization error may echo the rejected value, e.g. an integer given as password, which must not be logged as it may be a password or a secret.
+fn redacted_data_error(e: &JsonDataError) -> String {
+    // The message of the rejection itself embeds the deserialization error
+    let mut source = std::error::Error::source(e);
+    while let Some(error) = source {
+        if let Some(error) = error.downcast_ref::<serde_path_to_error::Error<serde_json::Error>>() {
+            return format!(
+                "failed to deserialize the JSON body, invalid value at `{}`",
+                error.path()
+            );
+        }
+        source = error.source();
+    }
+    "failed to deserialize the JSON body".to_string()
+}
+
+/// Number of items of a page of a list endpoint when no limit is given
+pub const DEFAULT_PAGE_SIZE: u32 = 20;
+/// Maximum number of items of a page of a list endpoint, larger limits are rejected with `400`
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+/// Pagination parameters of the list endpoints, e.g. `?limit=20&offset=40`, see [ValidatedQuery]
+#[derive(Debug, Clone, Validate, Deserialize)]
+pub struct PaginationQuery {
+    /// Maximum number of items of the page, defaults to [DEFAULT_PAGE_SIZE]
+    #[validate(range(min = 1, max = MAX_PAGE_SIZE))]
+    pub limit: Option<u32>,
+    /// Number of items to skip, defaults to zero
+    pub offset: Option<u32>,
+}
+
+impl PaginationQuery {
+    pub fn limit(&self) -> u32 {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE)
+    }
+
+    pub fn offset(&self) -> u32 {
+        self.offset.unwrap_or(0)
+    }
+}
+
+/// Query string deserialized and validated like [ValidatedJson], e.g. the pagination parameters of a list endpoint
+///
+/// A query string which does not match the expected type or fails the validation is rejected with `400`.
+pub struct ValidatedQuery<T>(pub T);
+
+impl<S, T> FromRequestParts<S> for ValidatedQuery<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let query: Query<T> = match Query::from_request_parts(parts, state).await {
+            Ok(q) => q,
+            Err(e) => {
+                warn!("{e}");
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Extension(ValidationFailure),
+                    e.body_text(),
+                )
+                    .into_response());
+            }
+        };
+        if let Err(e) = query.validate() {
+            return Err(validation_failure(e));
+        }
+
+        Ok(Self(query.0))
+    }
+}
+
+/// UUID path parameter, a malformed UUID is rejected with `400` and the standard error body
+struct UuidPath(uuid::Uuid);
+
+impl<S> FromRequestParts<S> for UuidPath
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match Path::<uuid::Uuid>::from_request_parts(parts, state).await {
+            Ok(Path(id)) => Ok(Self(id)),
+            Err(e) => {
+                warn!("{e}");
+                Err(ApiError::InvalidId)
+            }
+        }
+    }
+}
+
+/// Deserialize a field which is present in the body, to be used with `#[serde(default)]` on an `Option<Option<T>>` field
+///
+/// An absent field is then deserialized as `None`, a `null` field as `Some(None)`.
+fn deserialize_present<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Deserialize a `u32` from either a JSON number or a numeric string, e.g. `3600` or `"3600"`
+///
+/// To be used with `#[serde(deserialize_with = "deserialize_u32_from_number_or_string")]`, some clients stringify numbers.
+fn deserialize_u32_from_number_or_string<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct NumberOrStringVisitor;
+
+    impl Visitor<'_> for NumberOrStringVisitor {
+        type Value = u32;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter
+                .write_str("a non negative integer or a string containing a non negative integer")
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            u32::try_from(v).map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            u32::try_from(v).map_err(|_| E::invalid_value(Unexpected::Signed(v), &self))
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            v.trim()
+                .parse::<u32>()
+                .map_err(|_| E::invalid_value(Unexpected::Str(v), &self))
+        }
+    }
+
+    deserializer.deserialize_any(NumberOrStringVisitor)
+}
+
+// ####################################################
+// ################## AUTHENTICATION ##################
+// ####################################################
+
+/// Active access token presented as a bearer token in the `Authorization` header
+///
+/// Missing, malformed, unknown, revoked or expired access tokens are rejected with `401`,
+/// the `WWW-Authenticate` header carries an `invalid_token` error unless no bearer token has been presented,
+/// its description tells expired access tokens apart so that clients can prompt a new login.
+/// The use of an accepted access token is recorded once per [LAST_USE_GRANULARITY], see [AccessTokenRepository::record_token_use].
+struct AuthenticatedAccessToken(AccessToken);
+
+impl FromRequestParts<AppState> for AuthenticatedAccessToken {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        // A missing or empty header, another scheme or an empty bearer token are missing credentials
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .map(|v| v.to_str().map(str::trim))
+            .transpose()
+            .map_err(|_| ApiError::InvalidBearerToken)?
+            .and_then(|v| {
+                let (scheme, token) = v.split_once(' ').unwrap_or((v, ""));
+                scheme
+                    .eq_ignore_ascii_case("bearer")
+                    .then_some(token.trim())
+            })
+            .filter(|token| !token.is_empty())
+            .ok_or(ApiError::MissingBearerToken)?;
+        if !token.starts_with(TOKEN_PREFIX) {
+            warn!("malformed access token");
+            return Err(ApiError::InvalidBearerToken);
+        }
+
+        let mac = compute_token_mac(token, &state.config.access_token_secret)
+            .map_err(ApiError::InternalServerError)?;
+        let mut access_token = match state.access_token_repository.find_by_mac(&mac).await {
+            Ok(v) => v,
+            Err(TokenQueryError::TokenNotFound) => {
+                warn!("access token not found");
+                return Err(ApiError::InvalidBearerToken);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if access_token.revoked_at.is_some() {
+            warn!("revoked access token {}", access_token.id);
+            return Err(ApiError::InvalidBearerToken);
+        }
+        if access_token.is_expired(state.clock.now(), state.config.clock_skew_tolerance) {
+            warn!("expired access token {}", access_token.id);
+            return Err(ApiError::ExpiredBearerToken);
+        }
+
+        if let Some(last_used_at) = state
+            .access_token_repository
+            .record_token_use(access_token.id, LAST_USE_GRANULARITY)
+            .await?
+        {
+            access_token.last_used_at = last_used_at;
+        }
+
+        Ok(Self(access_token))
+    }
+}
+
+/// Current account, i.e. the verified account owning the access token presented as a bearer token, see [AuthenticatedAccessToken]
+///
+/// Accounts which are not active are rejected, see [ensure_active].
+struct CurrentAccount(Account);
 
-#[derive(Serialize, Deserialize)]
-pub struct GetHealthcheckResponse {
-    pub ok: bool,
+impl FromRequestParts<AppState> for CurrentAccount {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let AuthenticatedAccessToken(access_token) =
+            AuthenticatedAccessToken::from_request_parts(parts, state).await?;
+        verified_account_of(&access_token, state).await.map(Self)
+    }
 }
-async fn get_healthcheck() -> (StatusCode, Json<GetHealthcheckResponse>) {
-    (StatusCode::OK, Json(GetHealthcheckResponse { ok: true }))
+
+/// Verified account owning the access token presented as a bearer token, see [AuthenticatedAccessToken]
+///
+/// The access token must have been created within the sensitive action window, see [crate::SecurityConfig::sensitive_action_window],
+/// older access tokens are rejected with `401` prompting a new login. Accounts which are not active are rejected, see [ensure_active].
+struct RecentlyAuthenticatedAccount(Account);
+
+impl FromRequestParts<AppState> for RecentlyAuthenticatedAccount {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let AuthenticatedAccessToken(access_token) =
+            AuthenticatedAccessToken::from_request_parts(parts, state).await?;
+
+        if let Some(window) = state.config.security.sensitive_action_window
+            && !access_token.is_recent(state.clock.now(), window)
+        {
+            warn!(
+                "stale access token {} for a sensitive action",
+                access_token.id
+            );
+            return Err(ApiError::ReauthenticationRequired {
+                max_age_secs: window.as_secs(),
+            });
+        }
+
+        verified_account_of(&access_token, state).await.map(Self)
+    }
 }
 
-async fn not_found_handler() -> impl IntoResponse {
-    (StatusCode::NOT_FOUND, "Not found")
+/// Verified account owning the access token, see [AccountRepository::get_account_by_access_token]
+///
+/// Unknown or deactivated accounts are rejected with `401` and accounts which can not authenticate as per [ensure_active].
+async fn verified_account_of(
+    access_token: &AccessToken,
+    state: &AppState,
+) -> Result<Account, ApiError> {
+    let account = match state
+        .account_repository
+        .get_account_by_access_token(access_token.id)
+        .await
+    {
+        Ok(v) => v,
+        Err(AccountQueryError::AccountNotFound) => {
+            warn!("account not found for access token {}", access_token.id);
+            return Err(ApiError::InvalidBearerToken);
+        }
+        Err(AccountQueryError::Unknown(e)) => return Err(e.into()),
+    };
+
+    ensure_active(account.state(state.clock.now()))?;
+
+    Ok(account)
+}
+
+/// Reject the accounts which are not active, see [AccountState]
+///
+/// Unverified and locked accounts are rejected with `403`, deactivated accounts with `401` as unknown accounts.
+fn ensure_active(account_state: AccountState) -> Result<(), ApiError> {
+    match account_state {
+        AccountState::Active => Ok(()),
+        AccountState::Unverified => Err(ApiError::Forbidden(ErrorResponse::new(
+            "account_not_verified",
+            "Account must be verified",
+        ))),
+        AccountState::Locked => Err(ApiError::Forbidden(ErrorResponse::new(
+            "account_locked",
+            "Account is locked",
+        ))),
+        AccountState::Deactivated => Err(ApiError::InvalidBearerToken),
+    }
+}
+
+/// Header carrying the admin API key
+const ADMIN_API_KEY_HEADER: &str = "x-api-key";
+
+/// Admin presenting the configured admin API key in the `x-api-key` header
+///
+/// Missing or wrong keys are rejected with `401`, as well as any key if no admin API key is configured.
+struct Admin;
+
+impl FromRequestParts<AppState> for Admin {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let expected_key = state
+            .config
+            .admin_api_key
+            .as_ref()
+            .ok_or(ApiError::Unauthorized)?;
+        let key = parts
+            .headers
+            .get(ADMIN_API_KEY_HEADER)
+            .ok_or(ApiError::Unauthorized)?;
+
+        // Digests are compared so that the comparison time does not depend on the common prefix of the keys
+        if Sha3_256::digest(key.as_bytes())
+            != Sha3_256::digest(expected_key.extract_inner().as_bytes())
+        {
+            warn!("invalid admin API key");
+            return Err(ApiError::Unauthorized);
+        }
+
+        Ok(Self)
+    }
+}
+
+// ################################################
+// ################## TIMESTAMPS ##################
+// ################################################
+
+/// Response exposing the creation and last update dates of a resource
+///
+/// Timestamps of responses must be serialized using the [timestamp] module so that they share the same format.
+pub trait Timestamped {
+    fn created_at(&self) -> DateTime<Utc>;
+    fn updated_at(&self) -> DateTime<Utc>;
+}
+
+/// Serialization of response timestamps as RFC 3339 strings with microsecond precision, e.g. `2025-09-16T13:41:58.123456Z`
+///
+/// Timestamps are serialized in UTC, or in the timezone requested with the `x-timezone` header with their offset, e.g. `2025-09-16T09:41:58.123456-04:00`, see [timestamp::scope_request_timezone].
+/// To be used with `#[serde(with = "timestamp")]`, see [timestamp::option] for optional timestamps.
+pub mod timestamp {
+    use std::str::FromStr;
+
+    use axum::{extract::Request, middleware::Next, response::Response};
+    use chrono::{DateTime, SecondsFormat, Utc};
+    use chrono_tz::Tz;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use tracing::warn;
+
+    /// Header carrying the IANA name of the timezone of the response timestamps, e.g. `America/New_York`
+    pub const TIMEZONE_HEADER: &str = "x-timezone";
+
+    tokio::task_local! {
+        /// Timezone requested for the timestamps of the response being built
+        static RESPONSE_TIMEZONE: Tz;
+    }
+
+    /// Serialize the timestamps of the response in the timezone of the `x-timezone` header, if it is a valid IANA name
+    ///
+    /// Unknown timezones are ignored, the timestamps are then serialized in UTC.
+    pub async fn scope_request_timezone(req: Request, next: Next) -> Response {
+        let timezone = req
+            .headers()
+            .get(TIMEZONE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| {
+                Tz::from_str(v.trim())
+                    .inspect_err(|_| warn!("unknown timezone {v}, timestamps are returned in UTC"))
+                    .ok()
+            });
+        match timezone {
+            Some(timezone) => RESPONSE_TIMEZONE.scope(timezone, next.run(req)).await,
+            None => next.run(req).await,
+        }
+    }
+
+    pub fn serialize<S>(value: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let serialized = match RESPONSE_TIMEZONE.try_with(|timezone| *timezone) {
+            Ok(timezone) => value
+                .with_timezone(&timezone)
+                .to_rfc3339_opts(SecondsFormat::Micros, false),
+            Err(_) => value.to_rfc3339_opts(SecondsFormat::Micros, true),
+        };
+        serializer.serialize_str(&serialized)
+    }
+
+    /// Timestamps with an offset are converted to UTC
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        DateTime::<Utc>::deserialize(deserializer)
+    }
+
+    pub mod option {
+        use chrono::{DateTime, Utc};
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S>(value: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match value {
+                Some(v) => super::serialize(v, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            Option::<DateTime<Utc>>::deserialize(deserializer)
+        }
+    }
+}
+
+/// Plain text is only returned to clients explicitly accepting it and not accepting JSON
+async fn not_found_handler(headers: HeaderMap) -> Response {
+    let accept = headers
+        .get(ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if accept.contains("text/plain") && !accept.contains("application/json") {
+        return (StatusCode::NOT_FOUND, "Not found").into_response();
+    }
+    ApiError::NotFound.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use chrono::{SubsecRound, TimeDelta};
+    use fake::{Fake, Faker};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{newtypes::Opaque, test_support::CapturedLogs};
+    use accounts::AccountResponse;
+    use tokens::AccessTokenCreatedResponse;
+    use validator::ValidationError;
+
+    #[test]
+    fn test_responses_serialize_timestamps_identically() {
+        // Timestamps are stored with microsecond precision in database
+        let created_at = Utc::now().trunc_subsecs(6);
+        let updated_at = created_at + TimeDelta::microseconds(1_234_567);
+
+        let account_response = AccountResponse {
+            email: Faker.fake(),
+            display_name: None,
+            verified: false,
+            created_at,
+            updated_at,
+        };
+        let access_token_response = AccessTokenCreatedResponse {
+            id: uuid::Uuid::new_v4(),
+            name: "test-token".to_string(),
+            access_token: Opaque::new("soko__token".to_string()),
+            created_at,
+            updated_at,
+            expires_at: updated_at,
+            lifetime_secs: 1,
+            revoked_at: None,
+        };
+        assert_eq!(
+            account_response.created_at(),
+            access_token_response.created_at()
+        );
+        assert_eq!(
+            account_response.updated_at(),
+            access_token_response.updated_at()
+        );
+
+        let account_json = serde_json::to_value(&account_response).unwrap();
+        let access_token_json = serde_json::to_value(&access_token_response).unwrap();
+
+        assert_eq!(account_json["createdAt"], access_token_json["createdAt"]);
+        assert_eq!(account_json["updatedAt"], access_token_json["updatedAt"]);
+        assert_eq!(
+            account_json["updatedAt"],
+            serde_json::Value::String(
+                updated_at.to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+            )
+        );
+        assert_eq!(access_token_json["revokedAt"], serde_json::Value::Null);
+
+        let deserialized: AccountResponse = serde_json::from_value(account_json).unwrap();
+        assert_eq!(deserialized.created_at, created_at);
+        assert_eq!(deserialized.updated_at, updated_at);
+    }
+
+    #[tokio::test]
+    async fn test_timestamps_are_serialized_in_the_requested_timezone() {
+        let created_at = DateTime::parse_from_rfc3339("2025-09-16T13:41:58.123456Z")
+            .unwrap()
+            .to_utc();
+        let router: Router = Router::new()
+            .route(
+                "/",
+                get(move || async move {
+                    Json(AccountResponse {
+                        email: Faker.fake(),
+                        display_name: None,
+                        verified: false,
+                        created_at,
+                        updated_at: created_at,
+                    })
+                }),
+            )
+            .layer(middleware::from_fn(timestamp::scope_request_timezone));
+
+        for (timezone, expected_created_at) in [
+            (Some("America/New_York"), "2025-09-16T09:41:58.123456-04:00"),
+            (Some("Asia/Kolkata"), "2025-09-16T19:11:58.123456+05:30"),
+            (Some("Mars/Olympus_Mons"), "2025-09-16T13:41:58.123456Z"),
+            (None, "2025-09-16T13:41:58.123456Z"),
+        ] {
+            let mut request = Request::get("/");
+            if let Some(timezone) = timezone {
+                request = request.header(timestamp::TIMEZONE_HEADER, timezone);
+            }
+            let response = router
+                .clone()
+                .oneshot(request.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let account_json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(
+                account_json["createdAt"], expected_created_at,
+                "{timezone:?}"
+            );
+
+            let deserialized: AccountResponse = serde_json::from_value(account_json).unwrap();
+            assert_eq!(deserialized.created_at, created_at);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_uuid_path() {
+        let router: Router = Router::new().route(
+            "/{id}",
+            get(|UuidPath(id): UuidPath| async move { id.to_string() }),
+        );
+
+        let id = uuid::Uuid::new_v4();
+        let response = router
+            .clone()
+            .oneshot(Request::get(format!("/{id}")).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = router
+            .oneshot(Request::get("/not-a-uuid").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let error_response: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error_response.code, "invalid_id");
+    }
+
+    #[tokio::test]
+    async fn test_rejected_body_logs_do_not_contain_passwords() {
+        let router: Router = Router::new().route(
+            "/",
+            axum::routing::post(|ValidatedJson(_): ValidatedJson<accounts::SignupBody>| async {}),
+        );
+
+        let logs = CapturedLogs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(logs.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        for (body, password) in [
+            (
+                r#"{"email":"jane@example.test","password":98765432101234}"#,
+                "98765432101234",
+            ),
+            (
+                r#"{"email":"jane@example.test","password":"short4pw"}"#,
+                "short4pw",
+            ),
+            (
+                r#"{"email":"jane@example.test","password":"AB12{&abcdef","channel":"Xq7!pa55"}"#,
+                "Xq7!pa55",
+            ),
+        ] {
+            let response = router
+                .clone()
+                .oneshot(
+                    Request::post("/")
+                        .header("content-type", "application/json")
+                        .body(Body::from(body))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            let logs = logs.contents();
+            assert!(logs.contains("invalid value at"), "{logs}");
+            assert!(!logs.contains(password), "{logs}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validated_query() {
+        #[derive(Deserialize, Validate)]
+        struct ListQuery {
+            #[validate(range(min = 1, max = 100))]
+            limit: Option<u32>,
+            cursor: Option<String>,
+        }
+        let router: Router = Router::new().route(
+            "/",
+            get(
+                |ValidatedQuery(query): ValidatedQuery<ListQuery>| async move {
+                    format!("{:?} {:?}", query.limit, query.cursor)
+                },
+            ),
+        );
+
+        for (uri, expected_status) in [
+            ("/", StatusCode::OK),
+            ("/?limit=100&cursor=abc", StatusCode::OK),
+            ("/?limit=101", StatusCode::BAD_REQUEST),
+            ("/?limit=0", StatusCode::BAD_REQUEST),
+            ("/?limit=ten", StatusCode::BAD_REQUEST),
+        ] {
+            let response = router
+                .clone()
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), expected_status, "{uri}");
+        }
+
+        // Validation failures share the error envelope of the JSON bodies
+        let response = router
+            .oneshot(Request::get("/?limit=101").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let error_response: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error_response["fields"]["limit"][0]["code"], "range");
+    }
+
+    #[test]
+    fn test_field_error() {
+        let validation_errors = field_error("email", "existing-email", "Email is already used");
+        let mut expected_validation_errors = ValidationErrors::new();
+        expected_validation_errors.add(
+            "email",
+            ValidationError::new("existing-email").with_message("Email is already used".into()),
+        );
+        assert_eq!(validation_errors, expected_validation_errors);
+
+        let ApiError::BadRequest(validation_errors) = ApiError::bad_request(
+            "lifetime",
+            "invalid-range",
+            format!("at most {} seconds", 60),
+        ) else {
+            panic!("expected a bad request");
+        };
+        let errors = validation_errors.field_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors["lifetime"][0].code, "invalid-range");
+        assert_eq!(
+            errors["lifetime"][0].message.as_deref(),
+            Some("at most 60 seconds")
+        );
+
+        assert!(matches!(
+            ApiError::conflict("name", "existing-name", "Name is already used"),
+            ApiError::Conflict(_)
+        ));
+    }
+
+    #[test]
+    fn test_repository_errors_keep_their_classification() {
+        let op = "op".to_string();
+        for (error, expected_status) in [
+            (
+                RepositoryError::NotFound { op: op.clone() },
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                RepositoryError::UniqueViolation {
+                    op: op.clone(),
+                    constraint: Some("account_email_key".to_string()),
+                },
+                StatusCode::CONFLICT,
+            ),
+            (
+                RepositoryError::CheckViolation {
+                    op: op.clone(),
+                    constraint: "account_display_name_length".to_string(),
+                    field: "displayName",
+                },
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                RepositoryError::PoolTimeout { op: op.clone() },
+                StatusCode::SERVICE_UNAVAILABLE,
+            ),
+            (
+                RepositoryError::Unknown(anyhow::anyhow!("unknown")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+        ] {
+            // Domain errors carry repository errors as unknown errors
+            let api_error = ApiError::from(anyhow::Error::from(error));
+            assert_eq!(api_error.into_response().status(), expected_status);
+        }
+
+        let api_error = ApiError::from(anyhow::anyhow!("unknown"));
+        assert_eq!(
+            api_error.into_response().status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[test]
+    fn test_validation_error_response() {
+        let mut validation_errors = ValidationErrors::new();
+        validation_errors.add(
+            "password",
+            ValidationError::new("invalid-password").with_message("too short".into()),
+        );
+        validation_errors.add("email", ValidationError::new("invalid-email"));
+
+        let response = ValidationErrorResponse::from(validation_errors);
+
+        assert_eq!(
+            response.message,
+            "email: invalid-email; password: too short"
+        );
+        let serialized = serde_json::to_value(&response).unwrap();
+        assert_eq!(
+            serialized["fields"]["password"][0]["code"],
+            "invalid-password"
+        );
+        assert_eq!(serialized["fields"]["email"][0]["code"], "invalid-email");
+    }
 }
@@ -1,20 +1,54 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, str::FromStr, sync::LazyLock};
 
 use anyhow::anyhow;
 use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier, password_hash::Salt};
 use base64::{Engine, prelude::BASE64_STANDARD_NO_PAD};
 use fake::{Dummy, Fake, faker};
-use rand::{RngCore, SeedableRng};
-use rand_chacha::ChaCha20Rng;
-use serde::{Deserialize, de::Visitor};
+use rand::CryptoRng;
+use serde::{Deserialize, Serialize, de::Visitor};
+
+use crate::{newtypes::Email, rng::new_rng};
+
+use super::ApiError;
 
 // ##################################################
 // #################### PASSWORD ####################
 // ##################################################
 
 /// This type is meant to be used internally and in incoming IO requests (body payloads)
+///
+/// A password is either the plaintext password or, if pre-hashing is enabled, the hex encoded SHA-256 digest of the plaintext password computed by the client.
+/// In both cases, the value is hashed using Argon2id before being stored.
 #[derive(Clone, PartialEq, Eq)]
-pub struct Password(String);
+pub struct Password {
+    value: String,
+    prehashed: bool,
+}
+
+/// Length of a hex encoded SHA-256 digest
+const PREHASHED_PASSWORD_LENGTH: usize = 64;
+/// Minimum length of the local parts checked by [Password::ensure_unrelated_to_email], shorter ones would reject unrelated passwords
+const MIN_CHECKED_LOCAL_PART_LENGTH: usize = 3;
+/// Hashes of a random password using the Argon2id and the bcrypt algorithms, see [Password::verify_dummy]
+static DUMMY_PASSWORD_HASHES: LazyLock<[String; 2]> = LazyLock::new(|| {
+    let password = Password {
+        value: BASE64_STANDARD_NO_PAD.encode(rand::random::<[u8; 32]>()),
+        prehashed: false,
+    };
+    [
+        PasswordHashAlgorithm::Argon2id,
+        PasswordHashAlgorithm::Bcrypt,
+    ]
+    .map(|algorithm| {
+        password
+            .hash(algorithm)
+            .expect("failed to hash the dummy password")
+    })
+});
+/// Minimum length of a plaintext password
+pub const MIN_PASSWORD_LENGTH: usize = 10;
+/// Maximum length of a plaintext password
+pub const MAX_PASSWORD_LENGTH: usize = 40;
 
 #[derive(Debug)]
 pub enum PasswordError {
@@ -22,8 +56,123 @@ pub enum PasswordError {
     InvalidPassword(String),
 }
 
+/// Class of characters a password may be required to contain
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CharacterClass {
+    Uppercase,
+    Lowercase,
+    Digit,
+    /// Any character which is neither an ASCII letter nor an ASCII digit
+    Special,
+}
+
+impl CharacterClass {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Self::Uppercase => c.is_ascii_uppercase(),
+            Self::Lowercase => c.is_ascii_lowercase(),
+            Self::Digit => c.is_ascii_digit(),
+            Self::Special => !c.is_ascii_alphanumeric(),
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Self::Uppercase => "uppercase letters",
+            Self::Lowercase => "lowercase letters",
+            Self::Digit => "numbers",
+            Self::Special => "special characters",
+        }
+    }
+}
+
+/// Character classes a plaintext password must contain, each with its minimum count, see [crate::Config::password_policy]
+///
+/// The default policy requires at least two uppercase letters, two numbers and two special characters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PasswordPolicy {
+    pub requirements: Vec<(CharacterClass, usize)>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            requirements: vec![
+                (CharacterClass::Uppercase, 2),
+                (CharacterClass::Digit, 2),
+                (CharacterClass::Special, 2),
+            ],
+        }
+    }
+}
+
+/// Parse a policy from a comma separated list of `class:min_count`, e.g. `uppercase:2,digit:2,special:2`, or `none` for a policy without requirement
+impl FromStr for PasswordPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        if s == "none" {
+            return Ok(Self {
+                requirements: vec![],
+            });
+        }
+        let mut requirements: Vec<(CharacterClass, usize)> = vec![];
+        for requirement in s.split(',') {
+            let (class, min_count) = requirement.split_once(':').ok_or(format!(
+                "`{requirement}` must be formatted as class:min_count"
+            ))?;
+            let class = match class.trim() {
+                "uppercase" => CharacterClass::Uppercase,
+                "lowercase" => CharacterClass::Lowercase,
+                "digit" => CharacterClass::Digit,
+                "special" => CharacterClass::Special,
+                _ => {
+                    return Err(format!(
+                        "`{}` must be either uppercase, lowercase, digit or special",
+                        class.trim()
+                    ));
+                }
+            };
+            let min_count = match min_count.trim().parse::<usize>() {
+                Ok(v) if v > 0 => v,
+                _ => {
+                    return Err(format!(
+                        "`{}` must be a number greater than 0",
+                        min_count.trim()
+                    ));
+                }
+            };
+            if requirements.iter().any(|(c, _)| *c == class) {
+                return Err(format!("{} must be listed once", class.description()));
+            }
+            requirements.push((class, min_count));
+        }
+        Ok(Self { requirements })
+    }
+}
+
+impl PasswordPolicy {
+    /// Check that a password contains the required character classes, requirements are checked in order
+    ///
+    /// # Errors
+    /// * `PasswordError::InvalidPassword` - a requirement is not met, the message describes the first unmet requirement
+    fn check(&self, v: &str) -> Result<(), PasswordError> {
+        for (class, min_count) in &self.requirements {
+            if v.chars().filter(|c| class.matches(*c)).count() < *min_count {
+                return Err(PasswordError::InvalidPassword(format!(
+                    "password must contain at least {min_count} {}",
+                    class.description()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Password {
-    /// Creates a new `Password` instance after validating the provided string.
+    /// Creates a new `Password` instance after validating the provided string against the default [PasswordPolicy].
     ///
     /// # Arguments
     ///
@@ -43,83 +192,198 @@ impl Password {
     /// - `PasswordError::Empty` if the password is empty.
     /// - `PasswordError::InvalidPassword` with a descriptive message if any other rule is violated.
     pub fn new(v: &str) -> Result<Self, PasswordError> {
+        Self::new_with_policy(v, &PasswordPolicy::default())
+    }
+
+    /// Creates a new `Password` instance after validating the provided string, the character classes requirements are the ones of the given policy.
+    ///
+    /// See [Password::new] for the other validation rules.
+    pub fn new_with_policy(v: &str, policy: &PasswordPolicy) -> Result<Self, PasswordError> {
+        if v.is_empty() {
+            return Err(PasswordError::Empty);
+        }
+        if v.len() < MIN_PASSWORD_LENGTH || v.len() > MAX_PASSWORD_LENGTH {
+            return Err(PasswordError::InvalidPassword(format!(
+                "password length must be at least {MIN_PASSWORD_LENGTH} characters and at most {MAX_PASSWORD_LENGTH} characters"
+            )));
+        }
+        policy.check(v)?;
+
+        Ok(Password {
+            value: v.to_string(),
+            prehashed: false,
+        })
+    }
+
+    /// Creates a new `Password` instance from a client-side pre-hashed password, i.e. the hex encoded SHA-256 digest of the plaintext password.
+    ///
+    /// The password policy can not be enforced on a digest, the client is responsible for enforcing it on the plaintext password.
+    ///
+    /// # Errors
+    ///
+    /// - `PasswordError::Empty` if the password is empty.
+    /// - `PasswordError::InvalidPassword` if the password is not a hex encoded SHA-256 digest.
+    pub fn new_prehashed(v: &str) -> Result<Self, PasswordError> {
         if v.is_empty() {
             return Err(PasswordError::Empty);
         }
-        // Password must be at least 10 characters long, at most 40 characters long
-        if v.len() < 10 || v.len() > 40 {
+        if !is_prehashed_password(v) {
             return Err(PasswordError::InvalidPassword(
-                "password length must be at least 10 characters and at most 40 characters"
-                    .to_string(),
+                "pre-hashed password must be a hex encoded SHA-256 digest".to_string(),
             ));
         }
-        // Password must contain:
-        //  - at least two capital letters,
-        //  - at least two numbers,
-        //  - at least two special characters (not number nor letter)
-        let mut uppercase_count = 0;
-        let mut number_count = 0;
-        let mut special_count = 0;
-
-        for c in v.chars() {
-            if c.is_ascii_uppercase() {
-                uppercase_count += 1;
-            } else if c.is_ascii_digit() {
-                number_count += 1;
-            } else if !c.is_ascii_alphanumeric() {
-                special_count += 1;
-            }
+        Ok(Password {
+            value: v.to_ascii_lowercase(),
+            prehashed: true,
+        })
+    }
+
+    /// Ensure that the password contains the character classes required by the policy, see [crate::Config::password_policy]
+    ///
+    /// Pre-hashed passwords can not be checked and are accepted, see [Password::new_prehashed].
+    ///
+    /// # Arguments
+    /// * `policy` - configured password policy
+    pub fn ensure_policy(&self, policy: &PasswordPolicy) -> Result<(), PasswordError> {
+        if self.prehashed {
+            return Ok(());
         }
+        policy.check(&self.value)
+    }
 
-        if uppercase_count < 2 {
-            return Err(PasswordError::InvalidPassword(
-                "password must contain at least two uppercase letters".to_string(),
-            ));
+    /// Ensure that the password has been received in the expected form, pre-hashed or plaintext
+    ///
+    /// # Arguments
+    /// * `prehash_enabled` - whether passwords are expected to be pre-hashed by the client
+    pub fn ensure_prehash_mode(&self, prehash_enabled: bool) -> Result<(), PasswordError> {
+        match (prehash_enabled, self.prehashed) {
+            (true, false) => Err(PasswordError::InvalidPassword(
+                "password must be pre-hashed as a hex encoded SHA-256 digest".to_string(),
+            )),
+            (false, true) => Err(PasswordError::InvalidPassword(
+                "pre-hashed passwords are not accepted".to_string(),
+            )),
+            _ => Ok(()),
         }
-        if number_count < 2 {
-            return Err(PasswordError::InvalidPassword(
-                "password must contain at least two numbers".to_string(),
-            ));
+    }
+
+    /// Ensure that the password does not contain the local part of the email, regardless of the case
+    ///
+    /// Pre-hashed passwords can not be checked and are accepted, as are the local parts shorter than [MIN_CHECKED_LOCAL_PART_LENGTH].
+    ///
+    /// # Arguments
+    /// * `email` - email of the account of the password
+    pub fn ensure_unrelated_to_email(&self, email: &Email) -> Result<(), PasswordError> {
+        let local_part = email.local_part().to_lowercase();
+        if self.prehashed || local_part.chars().count() < MIN_CHECKED_LOCAL_PART_LENGTH {
+            return Ok(());
         }
-        if special_count < 2 {
+        if self.value.to_lowercase().contains(&local_part) {
             return Err(PasswordError::InvalidPassword(
-                "password must contain at least two special characters".to_string(),
+                "password must not contain the email or its local part".to_string(),
             ));
         }
-
-        Ok(Password(v.to_string()))
+        Ok(())
     }
 
-    /// Hash a password using the Argon2id algorithm. The returned string is a argon2-formatted hash.
+    /// Hash a password. The returned string is a PHC-formatted hash.
     ///
     /// # Arguments
-    /// * `password` - Password to hash
-    pub fn hash(&self) -> Result<String, anyhow::Error> {
+    /// * `algorithm` - algorithm of the hash, see [crate::Config::password_hash_algorithm]
+    pub fn hash(&self, algorithm: PasswordHashAlgorithm) -> Result<String, anyhow::Error> {
+        self.hash_with_rng(algorithm, &mut new_rng())
+    }
+
+    /// Same as [Password::hash] but the salt is generated using the given random number generator
+    pub fn hash_with_rng(
+        &self,
+        algorithm: PasswordHashAlgorithm,
+        rng: &mut impl CryptoRng,
+    ) -> Result<String, anyhow::Error> {
         let mut salt = [0u8; 16];
-        let mut rng = ChaCha20Rng::from_os_rng();
         rng.fill_bytes(&mut salt);
-        let base64_salt = BASE64_STANDARD_NO_PAD.encode(salt);
-        let argon_salt = Salt::from_b64(&base64_salt).map_err(|e| {
-            anyhow!(e).context("failed to build Salt struct from base64 salt string")
-        })?;
-        Argon2::default()
-            .hash_password(self.0.as_bytes(), argon_salt)
-            .map_err(|e| anyhow!(e).context("failed to hash password"))
-            .map(|v| v.to_string())
+        match algorithm {
+            PasswordHashAlgorithm::Argon2id => {
+                let base64_salt = BASE64_STANDARD_NO_PAD.encode(salt);
+                let argon_salt = Salt::from_b64(&base64_salt).map_err(|e| {
+                    anyhow!(e).context("failed to build Salt struct from base64 salt string")
+                })?;
+                Argon2::default()
+                    .hash_password(self.value.as_bytes(), argon_salt)
+                    .map_err(|e| anyhow!(e).context("failed to hash password"))
+                    .map(|v| v.to_string())
+            }
+            PasswordHashAlgorithm::Bcrypt => {
+                bcrypt::hash_with_salt(self.value.as_bytes(), bcrypt::DEFAULT_COST, salt)
+                    .map_err(|e| anyhow!(e).context("failed to hash password"))
+                    .map(|v| v.format_for_version(bcrypt::Version::TwoB))
+            }
+        }
     }
 
-    /// Verify a password validity against an Argon2id formatted key
+    /// Verify a password validity against a PHC-formatted hash, the algorithm is identified by the hash, see [PasswordHashAlgorithm::identify]
     ///
     /// # Arguments
     /// * `password` - Password to hash
-    /// * `password_hash` - Argon2id formatted key
+    /// * `password_hash` - PHC-formatted hash, e.g. `$argon2id$...` or `$2y$...`
     pub fn verify(&self, password_hash: &str) -> Result<(), anyhow::Error> {
-        let password_hash = PasswordHash::new(password_hash).map_err(|e| {
-            anyhow!(e).context("failed to build PasswordHash struct from raw string")
-        })?;
-        Argon2::default()
-            .verify_password(self.0.as_bytes(), &password_hash)
-            .map_err(|e| anyhow!(e).context("failed to verify password"))
+        match PasswordHashAlgorithm::identify(password_hash) {
+            Some(PasswordHashAlgorithm::Argon2id) => {
+                let password_hash = PasswordHash::new(password_hash).map_err(|e| {
+                    anyhow!(e).context("failed to build PasswordHash struct from raw string")
+                })?;
+                Argon2::default()
+                    .verify_password(self.value.as_bytes(), &password_hash)
+                    .map_err(|e| anyhow!(e).context("failed to verify password"))
+            }
+            Some(PasswordHashAlgorithm::Bcrypt) => {
+                match bcrypt::verify(self.value.as_bytes(), password_hash) {
+                    Ok(true) => Ok(()),
+                    Ok(false) => Err(anyhow!("failed to verify password")),
+                    Err(e) => Err(anyhow!(e).context("failed to verify password")),
+                }
+            }
+            None => Err(anyhow!("unsupported password hash algorithm")),
+        }
+    }
+
+    /// Verify the password against the hash of a random password, the verification always fails
+    ///
+    /// It takes as long as [Password::verify] against a hash of the given algorithm, e.g. it is used for unknown accounts so that the latency does not tell whether an account exists.
+    ///
+    /// # Arguments
+    /// * `algorithm` - algorithm of the hashes of the new passwords, see [crate::Config::password_hash_algorithm]
+    pub fn verify_dummy(&self, algorithm: PasswordHashAlgorithm) -> Result<(), anyhow::Error> {
+        let dummy_password_hash = match algorithm {
+            PasswordHashAlgorithm::Argon2id => &DUMMY_PASSWORD_HASHES[0],
+            PasswordHashAlgorithm::Bcrypt => &DUMMY_PASSWORD_HASHES[1],
+        };
+        let _ = self.verify(dummy_password_hash);
+        Err(anyhow!("failed to verify password"))
+    }
+}
+
+/// Algorithm of a password hash, identified by the PHC identifier prefixing the hash
+///
+/// New hashes use the configured algorithm, see [crate::Config::password_hash_algorithm], the other algorithms are kept so that existing hashes can still be verified.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PasswordHashAlgorithm {
+    /// `$argon2id$...`
+    #[default]
+    Argon2id,
+    /// `$2a$...`, `$2b$...`, `$2x$...` or `$2y$...`, legacy hashes
+    Bcrypt,
+}
+
+impl PasswordHashAlgorithm {
+    /// Identify the algorithm of a PHC-formatted hash, `None` if the algorithm is not supported
+    pub fn identify(password_hash: &str) -> Option<Self> {
+        let identifier = password_hash.strip_prefix('$')?.split('$').next()?;
+        match identifier {
+            "argon2id" => Some(Self::Argon2id),
+            "2a" | "2b" | "2x" | "2y" => Some(Self::Bcrypt),
+            _ => None,
+        }
     }
 }
 
@@ -140,24 +404,43 @@ impl<T> Dummy<T> for Password {
         let mut password: String = faker::internet::en::Password(10..36).fake_with_rng(rng);
         password += "{&";
         password += "24";
-        Password(password)
+        Password {
+            value: password,
+            prehashed: false,
+        }
     }
 }
 
+fn is_prehashed_password(v: &str) -> bool {
+    v.len() == PREHASHED_PASSWORD_LENGTH && v.chars().all(|c| c.is_ascii_hexdigit())
+}
+
 struct PasswordVisitor;
 
 impl<'de> Visitor<'de> for PasswordVisitor {
     type Value = Password;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("a valid password of 10 to 40 characters. Must contain at least 2 special characters, 2 digits and 2 capital letters")
+        formatter.write_str("a valid password of 10 to 40 characters, or a hex encoded SHA-256 digest if pre-hashing is enabled")
     }
 
     fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
     where
         E: serde::de::Error,
     {
-        Password::new(v).map_err(|e| match e {
+        // A plaintext password is at most 40 characters long, it can not be mistaken for a digest
+        // The character classes depend on the configuration, they are checked by the handlers, see [Password::ensure_policy]
+        let password = if is_prehashed_password(v) {
+            Password::new_prehashed(v)
+        } else {
+            Password::new_with_policy(
+                v,
+                &PasswordPolicy {
+                    requirements: vec![],
+                },
+            )
+        };
+        password.map_err(|e| match e {
             PasswordError::Empty => serde::de::Error::custom("password must not be empty"),
             PasswordError::InvalidPassword(reason) => serde::de::Error::custom(reason),
         })
@@ -179,3 +462,184 @@ impl<'de> Deserialize<'de> for Password {
         deserializer.deserialize_string(PasswordVisitor)
     }
 }
+
+impl From<PasswordError> for ApiError {
+    fn from(value: PasswordError) -> Self {
+        let message = match value {
+            PasswordError::Empty => "password must not be empty".to_string(),
+            PasswordError::InvalidPassword(reason) => reason,
+        };
+        ApiError::bad_request("password", "invalid-password", message)
+    }
+}
+
+#[cfg(test)]
+mod password_tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy() {
+        assert!(Password::new("AB12{&abcdef").is_ok());
+        assert!(matches!(
+            Password::new("Ab12{&abcdef"),
+            Err(PasswordError::InvalidPassword(_))
+        ));
+        assert!(matches!(
+            Password::new("AB1{&abcdefg"),
+            Err(PasswordError::InvalidPassword(_))
+        ));
+        assert!(matches!(
+            Password::new("AB12{abcdefg"),
+            Err(PasswordError::InvalidPassword(_))
+        ));
+    }
+
+    #[test]
+    fn test_password_unrelated_to_email() {
+        let email = Email::new_unchecked("john@x.com");
+        for password in ["AB12{&john", "AB12{&JoHn", "john@x.comAB12{&"] {
+            assert!(
+                matches!(
+                    Password::new(password)
+                        .unwrap()
+                        .ensure_unrelated_to_email(&email),
+                    Err(PasswordError::InvalidPassword(_))
+                ),
+                "{password}"
+            );
+        }
+        assert!(
+            Password::new("AB12{&abcdef")
+                .unwrap()
+                .ensure_unrelated_to_email(&email)
+                .is_ok()
+        );
+
+        // Short local parts and pre-hashed passwords are not checked
+        assert!(
+            Password::new("AB12{&abcdef")
+                .unwrap()
+                .ensure_unrelated_to_email(&Email::new_unchecked("ab@x.com"))
+                .is_ok()
+        );
+        assert!(
+            Password::new_prehashed(&"a".repeat(PREHASHED_PASSWORD_LENGTH))
+                .unwrap()
+                .ensure_unrelated_to_email(&Email::new_unchecked("aaaa@x.com"))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_custom_policy() {
+        let policy = PasswordPolicy {
+            requirements: vec![(CharacterClass::Lowercase, 1), (CharacterClass::Digit, 1)],
+        };
+
+        assert!(Password::new_with_policy("abcdefghi1", &policy).is_ok());
+        // No special character nor uppercase letter is required
+        assert!(Password::new("abcdefghi1").is_err());
+
+        match Password::new_with_policy("ABCDEFGHI1", &policy) {
+            Err(PasswordError::InvalidPassword(reason)) => {
+                assert_eq!(reason, "password must contain at least 1 lowercase letters")
+            }
+            other => panic!("expected an invalid password, got {other:?}"),
+        }
+        assert!(matches!(
+            Password::new_with_policy("abcdefghij", &policy),
+            Err(PasswordError::InvalidPassword(_))
+        ));
+        assert!(matches!(
+            Password::new_with_policy("", &policy),
+            Err(PasswordError::Empty)
+        ));
+    }
+
+    #[test]
+    fn test_policy_parsing() {
+        assert_eq!(
+            "uppercase:2,digit:2,special:2".parse::<PasswordPolicy>(),
+            Ok(PasswordPolicy::default())
+        );
+        assert_eq!(
+            " Lowercase:1 , digit:3 ".parse::<PasswordPolicy>(),
+            Ok(PasswordPolicy {
+                requirements: vec![(CharacterClass::Lowercase, 1), (CharacterClass::Digit, 3)],
+            })
+        );
+        assert_eq!(
+            "none".parse::<PasswordPolicy>(),
+            Ok(PasswordPolicy {
+                requirements: vec![],
+            })
+        );
+        for invalid in [
+            "uppercase",
+            "uppercase:0",
+            "uppercase:two",
+            "emoji:1",
+            "digit:1,digit:2",
+        ] {
+            assert!(invalid.parse::<PasswordPolicy>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_password_hash_algorithm_identification() {
+        for (password_hash, algorithm) in [
+            (
+                "$argon2id$v=19$m=19456,t=2,p=1$c2FsdHNhbHQ$aGFzaA",
+                Some(PasswordHashAlgorithm::Argon2id),
+            ),
+            (
+                "$2y$10$EZGQ6TDVUAicnOu4LgVoI.kFmcbFkT9nlOXeLfnKZtJYF8YjMM3mG",
+                Some(PasswordHashAlgorithm::Bcrypt),
+            ),
+            (
+                "$2b$10$EZGQ6TDVUAicnOu4LgVoI.kFmcbFkT9nlOXeLfnKZtJYF8YjMM3mG",
+                Some(PasswordHashAlgorithm::Bcrypt),
+            ),
+            ("$argon2i$v=19$m=19456,t=2,p=1$c2FsdHNhbHQ$aGFzaA", None),
+            ("$scrypt$ln=16,r=8,p=1$c2FsdA$aGFzaA", None),
+            ("argon2id", None),
+            ("", None),
+        ] {
+            assert_eq!(
+                PasswordHashAlgorithm::identify(password_hash),
+                algorithm,
+                "{password_hash}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify_dispatches_on_the_hash_algorithm() {
+        let password = Password::new("AB12{&abcdef").unwrap();
+        let other_password = Password::new("CD34{&ghijkl").unwrap();
+
+        let argon2id_hash = password.hash(PasswordHashAlgorithm::Argon2id).unwrap();
+        assert!(argon2id_hash.starts_with("$argon2id$"));
+        assert!(password.verify(&argon2id_hash).is_ok());
+        assert!(other_password.verify(&argon2id_hash).is_err());
+
+        // Legacy bcrypt hashes, e.g. `$2y$` hashes of PHP applications
+        let bcrypt_hash = bcrypt::hash_with_salt(password.value.as_bytes(), 4, [7u8; 16])
+            .unwrap()
+            .format_for_version(bcrypt::Version::TwoY);
+        assert!(bcrypt_hash.starts_with("$2y$04$"));
+        assert!(password.verify(&bcrypt_hash).is_ok());
+        assert!(other_password.verify(&bcrypt_hash).is_err());
+
+        let bcrypt_hash = password.hash(PasswordHashAlgorithm::Bcrypt).unwrap();
+        assert!(bcrypt_hash.starts_with("$2b$"));
+        assert!(password.verify(&bcrypt_hash).is_ok());
+
+        assert!(
+            password
+                .verify("$scrypt$ln=16,r=8,p=1$c2FsdA$aGFzaA")
+                .is_err()
+        );
+        assert!(password.verify("not-a-hash").is_err());
+    }
+}
@@ -2,12 +2,21 @@ use anyhow::anyhow;
 use base64::{Engine, prelude::BASE64_STANDARD_NO_PAD};
 use chrono::{DateTime, TimeDelta, Utc};
 use hmac::{Hmac, Mac};
-use rand::{Rng, SeedableRng};
+use rand::CryptoRng;
 use sha3::Sha3_256;
 use sqlx::prelude::FromRow;
+use std::time::Duration;
 use thiserror::Error;
 
-use crate::{Opaque, routes::accounts::Account};
+use crate::{
+    Opaque,
+    clock::is_expired,
+    rng::new_rng,
+    routes::{
+        accounts::Account,
+        newtypes::{Password, PasswordHashAlgorithm},
+    },
+};
 
 use super::CreateAccessTokenBody;
 
@@ -18,6 +27,8 @@ use super::CreateAccessTokenBody;
 /// Errors for everything related to querying
 #[derive(Error, Debug)]
 pub enum TokenQueryError {
+    #[error("Access token not found")]
+    TokenNotFound,
     #[error(transparent)]
     Unknown(#[from] anyhow::Error),
 }
@@ -39,15 +50,124 @@ pub struct AccessToken {
     pub last_used_at: DateTime<Utc>,
     pub expires_at: DateTime<Utc>,
     pub revoked_at: Option<DateTime<Utc>>,
+    /// Date of the password authentication the access token derives from, it is the creation date unless the access token has been issued by a refresh
+    pub authenticated_at: DateTime<Utc>,
+}
+
+impl AccessToken {
+    /// An access token is active if it has not been revoked and is not expired
+    ///
+    /// # Arguments
+    /// * `now` - current date,
+    /// * `clock_skew_tolerance` - grace period after the expiration date, see [is_expired]
+    pub fn is_active(&self, now: DateTime<Utc>, clock_skew_tolerance: Duration) -> bool {
+        self.revoked_at.is_none() && !self.is_expired(now, clock_skew_tolerance)
+    }
+
+    /// An access token is expired once its expiration date and the clock skew tolerance have passed, whether it has been revoked or not
+    ///
+    /// # Arguments
+    /// * `now` - current date,
+    /// * `clock_skew_tolerance` - grace period after the expiration date, see [is_expired]
+    pub fn is_expired(&self, now: DateTime<Utc>, clock_skew_tolerance: Duration) -> bool {
+        is_expired(self.expires_at, now, clock_skew_tolerance)
+    }
+
+    /// An access token is recent if the password has been given within the window, see [AccessToken::authenticated_at]
+    ///
+    /// The last use of the access token is not taken into account, a regularly used access token would never get stale otherwise.
+    /// Likewise, refreshing an access token does not make it recent.
+    ///
+    /// # Arguments
+    /// * `now` - current date,
+    /// * `window` - maximum age of the access token
+    pub fn is_recent(&self, now: DateTime<Utc>, window: Duration) -> bool {
+        TimeDelta::from_std(window)
+            .ok()
+            .and_then(|window| self.authenticated_at.checked_add_signed(window))
+            .is_some_and(|deadline| now < deadline)
+    }
+
+    /// Remaining lifetime of the access token, it is zero once the access token is expired
+    pub fn expires_in(&self, now: DateTime<Utc>) -> TimeDelta {
+        self.expires_at
+            .signed_duration_since(now)
+            .max(TimeDelta::zero())
+    }
+
+    /// Effective lifetime granted to the access token, in seconds
+    ///
+    /// The expiration date is derived before the creation date is set by the database, the difference is rounded to the nearest second.
+    pub fn lifetime_secs(&self) -> i64 {
+        let milliseconds = self
+            .expires_at
+            .signed_duration_since(self.created_at)
+            .num_milliseconds();
+        ((milliseconds + 500) / 1000).max(0)
+    }
+}
+
+/// Compute the MAC of an access token, only the MAC of an access token is stored
+///
+/// # Arguments
+/// * `token` - plaintext access token, including its prefix,
+/// * `hmac_secret` - secret used to compute the MAC
+pub fn compute_token_mac(
+    token: &str,
+    hmac_secret: &Opaque<[u8; 32]>,
+) -> Result<[u8; 32], anyhow::Error> {
+    let mut hmac = Hmac::<Sha3_256>::new_from_slice(hmac_secret.extract_inner())
+        .map_err(|e| anyhow!(e).context("failed to initialize hmac"))?;
+    hmac.update(token.as_bytes());
+    Ok(hmac.finalize().into_bytes().into())
+}
+
+/// Generate a random token and its MAC, e.g. an access token or a refresh token
+///
+/// # Arguments
+/// * `prefix` - prefix of the token, e.g. [TOKEN_PREFIX],
+/// * `token_bytes` - number of random bytes of the token, between [MIN_TOKEN_BYTES] and [MAX_TOKEN_BYTES],
+/// * `hmac_secret` - secret used to compute the MAC,
+/// * `rng` - random number generator
+fn generate_token(
+    prefix: &str,
+    token_bytes: usize,
+    hmac_secret: &Opaque<[u8; 32]>,
+    rng: &mut impl CryptoRng,
+) -> Result<(Opaque<String>, [u8; 32]), anyhow::Error> {
+    if !(MIN_TOKEN_BYTES..=MAX_TOKEN_BYTES).contains(&token_bytes) {
+        return Err(anyhow!("invalid number of token bytes: {token_bytes}"));
+    }
+    let mut random_bytes = vec![0u8; token_bytes];
+    rng.fill_bytes(&mut random_bytes);
+    // The MAC is computed over the whole token, prefix included
+    let token = format!("{prefix}{}", BASE64_STANDARD_NO_PAD.encode(random_bytes));
+
+    let mac = compute_token_mac(&token, hmac_secret)?;
+
+    Ok((Opaque::new(token), mac))
 }
 
 // ###########################################################
 // ################## ACCESS TOKEN CREATION ##################
 // ###########################################################
 
+pub const TOKEN_PREFIX: &str = "soko__";
+/// Number of random bytes of an access token, before encoding
+pub const DEFAULT_TOKEN_BYTES: usize = 64;
+pub const MIN_TOKEN_BYTES: usize = 32;
+pub const MAX_TOKEN_BYTES: usize = 256;
 pub const MAX_LIFETIME: u32 = 90 * 24 * 60 * 60; // 90 days
+pub const DEFAULT_LIFETIME: u32 = 7 * 24 * 60 * 60; // 7 days
+pub const DEFAULT_NAME: &str = "default";
+/// Prefix of the names generated for the access tokens created without name, it is followed by a random suffix
+pub const GENERATED_NAME_PREFIX: &str = "token-";
 pub const MAX_ACTIVE_TOKENS: u8 = 3;
+/// Maximum number of active sessions of an account, a new login beyond it ends the oldest session
+pub const MAX_ACTIVE_SESSIONS: u8 = 10;
 pub const MAX_NAME_LENGTH: usize = 40;
+/// Granularity of the last use date of the access tokens, a use within it of the recorded one is not recorded again
+pub const LAST_USE_GRANULARITY: Duration = Duration::from_secs(60);
 
 #[derive(Clone, Debug)]
 pub struct CreateAccessTokenRequest {
@@ -56,6 +176,12 @@ pub struct CreateAccessTokenRequest {
     pub token: Opaque<String>,
     pub mac: [u8; 32],
     pub expires_at: DateTime<Utc>,
+    /// Date of the password authentication the access token derives from, the creation date if absent, see [AccessToken::authenticated_at]
+    pub authenticated_at: Option<DateTime<Utc>>,
+    /// If true, the access token is issued by a login or a refresh, it counts towards [MAX_ACTIVE_SESSIONS] instead of [MAX_ACTIVE_TOKENS]
+    pub session: bool,
+    /// If true, the name has been generated, see [generate_token_name], a new name is generated if it collides with an active access token of the account
+    pub name_generated: bool,
 }
 
 #[derive(Debug, Error)]
@@ -66,6 +192,8 @@ pub enum CreateAccessTokenRequestError {
     InvalidName,
     #[error("invalid lifetime")]
     InvalidLifetime,
+    #[error("lifetime exceeds the maximum lifetime of the account: {max_lifetime}")]
+    LifetimeExceedsAccountPolicy { max_lifetime: u32 },
     #[error(transparent)]
     Unknown(#[from] anyhow::Error),
 }
@@ -74,21 +202,156 @@ pub enum CreateAccessTokenRequestError {
 pub enum CreateAccessTokenError {
     #[error("account has reached its access token limit: {0}")]
     ActiveTokenLimitReached(u8),
+    /// The name of a revoked access token can be reused
+    #[error("an active access token of the account already has the name")]
+    NameAlreadyExists,
     #[error(transparent)]
     Unknown(#[from] anyhow::Error),
 }
 
+/// Generate a readable name for an access token created without name, e.g. `token-3f9a1c2e`
+pub fn generate_token_name(rng: &mut impl CryptoRng) -> String {
+    format!("{GENERATED_NAME_PREFIX}{:08x}", rng.next_u32())
+}
+
 impl CreateAccessTokenRequest {
+    /// Build a [CreateAccessTokenRequest] using a [CreateAccessTokenBody] HTTP body, the password is verified against the account
+    ///
+    /// A name is generated if the body has none, see [generate_token_name].
     pub fn try_from_body(
         body: CreateAccessTokenBody,
         account: &Account,
-        hmac_secret: Opaque<[u8; 32]>,
+        hmac_secret: &Opaque<[u8; 32]>,
+        token_bytes: usize,
     ) -> Result<Self, CreateAccessTokenRequestError> {
         if body.password.verify(&account.password_hash).is_err() {
             return Err(CreateAccessTokenRequestError::InvalidPassword);
         }
 
-        let trimmed_name = body.name.trim();
+        let mut rng = new_rng();
+        let name_generated = body.name.is_none();
+        let name = body.name.unwrap_or_else(|| generate_token_name(&mut rng));
+        let mut request = Self::try_new_with_rng(
+            account,
+            &name,
+            body.lifetime,
+            hmac_secret,
+            token_bytes,
+            &mut rng,
+        )?;
+        request.name_generated = name_generated;
+        Ok(request)
+    }
+
+    /// Build a [CreateAccessTokenRequest] authenticating the account with its password, the access token is a session with a generated name
+    ///
+    /// An absent account, e.g. an unknown email, fails as a wrong password after a verification taking as long, see [Password::verify_dummy].
+    ///
+    /// # Arguments
+    /// * `password` - password of the account,
+    /// * `account` - account owning the access token, if found,
+    /// * `lifetime` - lifetime of the access token in seconds, it is capped by the account policy,
+    /// * `hmac_secret` - secret used to compute the MAC of the access token,
+    /// * `token_bytes` - number of random bytes of the access token, between [MIN_TOKEN_BYTES] and [MAX_TOKEN_BYTES],
+    /// * `password_hash_algorithm` - algorithm of the dummy hash verified without account, see [crate::Config::password_hash_algorithm]
+    pub fn try_from_login(
+        password: &Password,
+        account: Option<&Account>,
+        lifetime: u32,
+        hmac_secret: &Opaque<[u8; 32]>,
+        token_bytes: usize,
+        password_hash_algorithm: PasswordHashAlgorithm,
+    ) -> Result<Self, CreateAccessTokenRequestError> {
+        let Some(account) = account else {
+            let _ = password.verify_dummy(password_hash_algorithm);
+            return Err(CreateAccessTokenRequestError::InvalidPassword);
+        };
+        if password.verify(&account.password_hash).is_err() {
+            return Err(CreateAccessTokenRequestError::InvalidPassword);
+        }
+
+        let mut rng = new_rng();
+        let name = generate_token_name(&mut rng);
+        let mut request = Self::try_new_with_rng(
+            account,
+            &name,
+            // The access token must comply with the account policy, if an admin set one
+            lifetime.min(account.max_token_lifetime()),
+            hmac_secret,
+            token_bytes,
+            &mut rng,
+        )?;
+        request.session = true;
+        request.name_generated = true;
+        Ok(request)
+    }
+
+    /// Build a [CreateAccessTokenRequest] exchanging a refresh token, the access token has a generated name and the session lifetime
+    ///
+    /// The access token derives from the same password authentication as the refresh token, see [AccessToken::authenticated_at].
+    ///
+    /// # Arguments
+    /// * `account` - account owning the refresh token,
+    /// * `refresh_token` - refresh token being exchanged, it must be usable, see [RefreshToken::ensure_usable],
+    /// * `hmac_secret` - secret used to compute the MAC of the access token,
+    /// * `token_bytes` - number of random bytes of the access token, between [MIN_TOKEN_BYTES] and [MAX_TOKEN_BYTES]
+    pub fn try_from_refresh(
+        account: &Account,
+        refresh_token: &RefreshToken,
+        hmac_secret: &Opaque<[u8; 32]>,
+        token_bytes: usize,
+    ) -> Result<Self, CreateAccessTokenRequestError> {
+        let mut rng = new_rng();
+        let name = generate_token_name(&mut rng);
+        let mut request = Self::try_new_with_rng(
+            account,
+            &name,
+            SESSION_ACCESS_TOKEN_LIFETIME.min(account.max_token_lifetime()),
+            hmac_secret,
+            token_bytes,
+            &mut rng,
+        )?;
+        request.authenticated_at = Some(refresh_token.authenticated_at);
+        request.session = true;
+        request.name_generated = true;
+        Ok(request)
+    }
+
+    /// Build a [CreateAccessTokenRequest] for an account that has already been authenticated
+    ///
+    /// # Arguments
+    /// * `account` - account owning the access token,
+    /// * `name` - name of the access token, it is trimmed,
+    /// * `lifetime` - lifetime of the access token in seconds,
+    /// * `hmac_secret` - secret used to compute the MAC of the access token,
+    /// * `token_bytes` - number of random bytes of the access token, between [MIN_TOKEN_BYTES] and [MAX_TOKEN_BYTES]
+    pub fn try_new(
+        account: &Account,
+        name: &str,
+        lifetime: u32,
+        hmac_secret: &Opaque<[u8; 32]>,
+        token_bytes: usize,
+    ) -> Result<Self, CreateAccessTokenRequestError> {
+        Self::try_new_with_rng(
+            account,
+            name,
+            lifetime,
+            hmac_secret,
+            token_bytes,
+            &mut new_rng(),
+        )
+    }
+
+    /// Same as [CreateAccessTokenRequest::try_new] but the token is generated using the given random number generator
+    pub fn try_new_with_rng(
+        account: &Account,
+        name: &str,
+        lifetime: u32,
+        hmac_secret: &Opaque<[u8; 32]>,
+        token_bytes: usize,
+        rng: &mut impl CryptoRng,
+    ) -> Result<Self, CreateAccessTokenRequestError> {
+        let trimmed_name = name.trim();
         if trimmed_name.is_empty() {
             return Err(CreateAccessTokenRequestError::InvalidName);
         }
@@ -96,30 +359,192 @@ impl CreateAccessTokenRequest {
             return Err(CreateAccessTokenRequestError::InvalidName);
         }
 
-        if body.lifetime == 0 {
+        if lifetime == 0 {
             return Err(CreateAccessTokenRequestError::InvalidLifetime);
         }
-        if body.lifetime > MAX_LIFETIME {
+        if lifetime > MAX_LIFETIME {
             return Err(CreateAccessTokenRequestError::InvalidLifetime);
         }
+        let max_lifetime = account.max_token_lifetime();
+        if lifetime > max_lifetime {
+            return Err(
+                CreateAccessTokenRequestError::LifetimeExceedsAccountPolicy { max_lifetime },
+            );
+        }
 
-        let mut rng = rand_chacha::ChaCha20Rng::from_os_rng();
-        let token_bytes: [u8; 64] = rng.random();
-        let token = format!("soko__{}", BASE64_STANDARD_NO_PAD.encode(token_bytes));
-
-        let mut hmac = Hmac::<Sha3_256>::new_from_slice(hmac_secret.extract_inner())
-            .map_err(|e| anyhow!(e).context("failed to initialize hmac"))?;
-        hmac.update(token.as_bytes());
-        let mac = hmac.finalize().into_bytes().into();
+        let (token, mac) = generate_token(TOKEN_PREFIX, token_bytes, hmac_secret, rng)?;
 
         let expires_at = Utc::now()
-            .checked_add_signed(TimeDelta::seconds(body.lifetime.into()))
+            .checked_add_signed(TimeDelta::seconds(lifetime.into()))
             .ok_or(anyhow!("failed to derive expiration date"))?;
 
         Ok(CreateAccessTokenRequest {
             account_id: account.id,
             name: trimmed_name.to_string(),
-            token: Opaque::new(token),
+            token,
+            mac,
+            expires_at,
+            authenticated_at: None,
+            session: false,
+            name_generated: false,
+        })
+    }
+}
+
+// ############################################################
+// ################## REFRESH TOKEN CREATION ##################
+// ############################################################
+
+pub const REFRESH_TOKEN_PREFIX: &str = "soko_refresh__";
+/// Lifetime of the access tokens issued along a refresh token, they are meant to be renewed with the refresh token
+pub const SESSION_ACCESS_TOKEN_LIFETIME: u32 = 15 * 60; // 15 minutes
+
+#[derive(FromRow, Debug)]
+pub struct RefreshToken {
+    pub id: uuid::Uuid,
+    pub account_id: uuid::Uuid,
+    /// Refresh tokens of a family descend from the same login, the whole family is revoked if a rotated refresh token is reused
+    pub family_id: uuid::Uuid,
+    /// Access token issued along the refresh token, it is revoked once the refresh token is rotated
+    pub access_token_id: uuid::Uuid,
+    pub mac: Vec<u8>,
+    /// Date of the login the family descends from, see [AccessToken::authenticated_at]
+    pub authenticated_at: DateTime<Utc>,
+    // This field is automatically set at creation at the database level
+    pub created_at: DateTime<Utc>,
+    // This field is automatically updated at the database level
+    pub updated_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Date at which the refresh token has been exchanged for a new one, it can not be used afterwards
+    pub rotated_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl RefreshToken {
+    /// A refresh token is expired once its expiration date and the clock skew tolerance have passed, see [is_expired]
+    pub fn is_expired(&self, now: DateTime<Utc>, clock_skew_tolerance: Duration) -> bool {
+        is_expired(self.expires_at, now, clock_skew_tolerance)
+    }
+
+    /// Ensure that the refresh token can be exchanged for a new access token
+    ///
+    /// # Arguments
+    /// * `now` - current date,
+    /// * `clock_skew_tolerance` - grace period after the expiration date, see [is_expired]
+    ///
+    /// # Errors
+    /// * `RefreshAccessTokenError::RefreshTokenReused` - the refresh token has already been rotated, the family must be revoked
+    /// * `RefreshAccessTokenError::InvalidRefreshToken` - the refresh token is revoked or expired
+    pub fn ensure_usable(
+        &self,
+        now: DateTime<Utc>,
+        clock_skew_tolerance: Duration,
+    ) -> Result<(), RefreshAccessTokenError> {
+        // A reuse is reported even if the family has been revoked or has expired since
+        if self.rotated_at.is_some() {
+            return Err(RefreshAccessTokenError::RefreshTokenReused {
+                family_id: self.family_id,
+            });
+        }
+        if self.revoked_at.is_some() || self.is_expired(now, clock_skew_tolerance) {
+            return Err(RefreshAccessTokenError::InvalidRefreshToken);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CreateRefreshTokenRequest {
+    pub account_id: uuid::Uuid,
+    pub family_id: uuid::Uuid,
+    pub token: Opaque<String>,
+    pub mac: [u8; 32],
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Errors of the exchange of a refresh token for a new access token
+#[derive(Error, Debug)]
+pub enum RefreshAccessTokenError {
+    /// The refresh token is unknown, revoked or expired
+    #[error("invalid refresh token")]
+    InvalidRefreshToken,
+    /// The refresh token has already been rotated, it may have been stolen
+    #[error("refresh token of family {family_id} has already been rotated")]
+    RefreshTokenReused { family_id: uuid::Uuid },
+    #[error(transparent)]
+    CreateAccessToken(#[from] CreateAccessTokenError),
+    #[error(transparent)]
+    Unknown(#[from] anyhow::Error),
+}
+
+impl CreateRefreshTokenRequest {
+    /// Build a [CreateRefreshTokenRequest] starting a new family, i.e. at login
+    ///
+    /// # Arguments
+    /// * `account_id` - ID of the account owning the refresh token,
+    /// * `lifetime` - lifetime of the refresh token,
+    /// * `hmac_secret` - secret used to compute the MAC of the refresh token,
+    /// * `token_bytes` - number of random bytes of the refresh token, between [MIN_TOKEN_BYTES] and [MAX_TOKEN_BYTES]
+    pub fn try_new(
+        account_id: uuid::Uuid,
+        lifetime: Duration,
+        hmac_secret: &Opaque<[u8; 32]>,
+        token_bytes: usize,
+    ) -> Result<Self, anyhow::Error> {
+        Self::try_new_in_family(
+            account_id,
+            uuid::Uuid::new_v4(),
+            lifetime,
+            hmac_secret,
+            token_bytes,
+        )
+    }
+
+    /// Build a [CreateRefreshTokenRequest] rotating a refresh token, the new refresh token belongs to the same family
+    ///
+    /// # Arguments
+    /// * `previous` - refresh token being rotated, it must be usable, see [RefreshToken::ensure_usable],
+    /// * `lifetime` - lifetime of the refresh token,
+    /// * `hmac_secret` - secret used to compute the MAC of the refresh token,
+    /// * `token_bytes` - number of random bytes of the refresh token, between [MIN_TOKEN_BYTES] and [MAX_TOKEN_BYTES]
+    pub fn try_rotate(
+        previous: &RefreshToken,
+        lifetime: Duration,
+        hmac_secret: &Opaque<[u8; 32]>,
+        token_bytes: usize,
+    ) -> Result<Self, anyhow::Error> {
+        Self::try_new_in_family(
+            previous.account_id,
+            previous.family_id,
+            lifetime,
+            hmac_secret,
+            token_bytes,
+        )
+    }
+
+    fn try_new_in_family(
+        account_id: uuid::Uuid,
+        family_id: uuid::Uuid,
+        lifetime: Duration,
+        hmac_secret: &Opaque<[u8; 32]>,
+        token_bytes: usize,
+    ) -> Result<Self, anyhow::Error> {
+        let (token, mac) = generate_token(
+            REFRESH_TOKEN_PREFIX,
+            token_bytes,
+            hmac_secret,
+            &mut new_rng(),
+        )?;
+
+        let expires_at = TimeDelta::from_std(lifetime)
+            .ok()
+            .and_then(|lifetime| Utc::now().checked_add_signed(lifetime))
+            .ok_or(anyhow!("failed to derive expiration date"))?;
+
+        Ok(CreateRefreshTokenRequest {
+            account_id,
+            family_id,
+            token,
             mac,
             expires_at,
         })
@@ -128,9 +553,15 @@ impl CreateAccessTokenRequest {
 
 #[cfg(test)]
 mod create_access_token_tests {
+    use base64::prelude::BASE64_STANDARD;
     use fake::{Fake, Faker};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
 
-    use crate::routes::{accounts::Account, newtypes::Password};
+    use crate::{
+        decode_access_token_secret,
+        routes::{accounts::Account, newtypes::Password},
+    };
 
     use super::*;
 
@@ -142,12 +573,16 @@ mod create_access_token_tests {
         let body = CreateAccessTokenBody {
             email: account.email.clone(),
             password: wrong_password,
-            name: "test-token".to_string(),
+            name: Some("test-token".to_string()),
             lifetime: 3600, // 1 hour
         };
 
-        let result =
-            CreateAccessTokenRequest::try_from_body(body, &account, Opaque::new(rand::random()));
+        let result = CreateAccessTokenRequest::try_from_body(
+            body,
+            &account,
+            &Opaque::new(rand::random()),
+            DEFAULT_TOKEN_BYTES,
+        );
 
         assert!(matches!(
             result,
@@ -155,21 +590,51 @@ mod create_access_token_tests {
         ));
     }
 
+    #[test]
+    fn test_try_from_body_without_name() {
+        let mut account: Account = Faker.fake();
+        let password: Password = Faker.fake();
+        account.password_hash = password.hash(PasswordHashAlgorithm::Argon2id).unwrap();
+
+        let body = CreateAccessTokenBody {
+            email: account.email.clone(),
+            password,
+            name: None,
+            lifetime: 3600, // 1 hour
+        };
+
+        let request = CreateAccessTokenRequest::try_from_body(
+            body,
+            &account,
+            &Opaque::new(rand::random()),
+            DEFAULT_TOKEN_BYTES,
+        )
+        .unwrap();
+
+        let suffix = request.name.strip_prefix(GENERATED_NAME_PREFIX).unwrap();
+        assert_eq!(suffix.len(), 8);
+        assert!(suffix.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
     #[test]
     fn test_try_from_body_with_empty_name() {
         let mut account: Account = Faker.fake();
         let password: Password = Faker.fake();
-        account.password_hash = password.hash().unwrap();
+        account.password_hash = password.hash(PasswordHashAlgorithm::Argon2id).unwrap();
 
         let body = CreateAccessTokenBody {
             email: account.email.clone(),
             password,
-            name: "".to_string(),
+            name: Some("".to_string()),
             lifetime: 3600, // 1 hour
         };
 
-        let result =
-            CreateAccessTokenRequest::try_from_body(body, &account, Opaque::new(rand::random()));
+        let result = CreateAccessTokenRequest::try_from_body(
+            body,
+            &account,
+            &Opaque::new(rand::random()),
+            DEFAULT_TOKEN_BYTES,
+        );
 
         assert!(matches!(
             result,
@@ -181,17 +646,21 @@ mod create_access_token_tests {
     fn test_try_from_body_with_whitespace_only_name() {
         let mut account: Account = Faker.fake();
         let password: Password = Faker.fake();
-        account.password_hash = password.hash().unwrap();
+        account.password_hash = password.hash(PasswordHashAlgorithm::Argon2id).unwrap();
 
         let body = CreateAccessTokenBody {
             email: account.email.clone(),
             password,
-            name: "   \t\n  ".to_string(),
+            name: Some("   \t\n  ".to_string()),
             lifetime: 3600, // 1 hour
         };
 
-        let result =
-            CreateAccessTokenRequest::try_from_body(body, &account, Opaque::new(rand::random()));
+        let result = CreateAccessTokenRequest::try_from_body(
+            body,
+            &account,
+            &Opaque::new(rand::random()),
+            DEFAULT_TOKEN_BYTES,
+        );
 
         assert!(matches!(
             result,
@@ -203,7 +672,7 @@ mod create_access_token_tests {
     fn test_try_from_body_with_name_too_long() {
         let mut account: Account = Faker.fake();
         let password: Password = Faker.fake();
-        account.password_hash = password.hash().unwrap();
+        account.password_hash = password.hash(PasswordHashAlgorithm::Argon2id).unwrap();
 
         // Create a name longer than 40 characters
         let long_name = "a".repeat(MAX_NAME_LENGTH + 1);
@@ -211,12 +680,16 @@ mod create_access_token_tests {
         let body = CreateAccessTokenBody {
             email: account.email.clone(),
             password,
-            name: long_name,
+            name: Some(long_name),
             lifetime: 3600, // 1 hour
         };
 
-        let result =
-            CreateAccessTokenRequest::try_from_body(body, &account, Opaque::new(rand::random()));
+        let result = CreateAccessTokenRequest::try_from_body(
+            body,
+            &account,
+            &Opaque::new(rand::random()),
+            DEFAULT_TOKEN_BYTES,
+        );
 
         assert!(matches!(
             result,
@@ -228,17 +701,21 @@ mod create_access_token_tests {
     fn test_try_from_body_with_zero_lifetime() {
         let mut account: Account = Faker.fake();
         let password: Password = Faker.fake();
-        account.password_hash = password.hash().unwrap();
+        account.password_hash = password.hash(PasswordHashAlgorithm::Argon2id).unwrap();
 
         let body = CreateAccessTokenBody {
             email: account.email.clone(),
             password,
-            name: "test-token".to_string(),
+            name: Some("test-token".to_string()),
             lifetime: 0,
         };
 
-        let result =
-            CreateAccessTokenRequest::try_from_body(body, &account, Opaque::new(rand::random()));
+        let result = CreateAccessTokenRequest::try_from_body(
+            body,
+            &account,
+            &Opaque::new(rand::random()),
+            DEFAULT_TOKEN_BYTES,
+        );
 
         assert!(matches!(
             result,
@@ -250,21 +727,389 @@ mod create_access_token_tests {
     fn test_try_from_body_with_lifetime_too_big() {
         let mut account: Account = Faker.fake();
         let password: Password = Faker.fake();
-        account.password_hash = password.hash().unwrap();
+        account.password_hash = password.hash(PasswordHashAlgorithm::Argon2id).unwrap();
 
         let body = CreateAccessTokenBody {
             email: account.email.clone(),
             password,
-            name: "test-token".to_string(),
+            name: Some("test-token".to_string()),
             lifetime: MAX_LIFETIME + 1,
         };
 
-        let result =
-            CreateAccessTokenRequest::try_from_body(body, &account, Opaque::new(rand::random()));
+        let result = CreateAccessTokenRequest::try_from_body(
+            body,
+            &account,
+            &Opaque::new(rand::random()),
+            DEFAULT_TOKEN_BYTES,
+        );
 
         assert!(matches!(
             result,
             Err(CreateAccessTokenRequestError::InvalidLifetime)
         ));
     }
+
+    #[test]
+    fn test_try_from_body_with_lifetime_above_account_policy() {
+        let mut account: Account = Faker.fake();
+        let password: Password = Faker.fake();
+        account.password_hash = password.hash(PasswordHashAlgorithm::Argon2id).unwrap();
+        // 7 days, well below the global maximum
+        account.max_token_lifetime_secs = Some(7 * 24 * 60 * 60);
+
+        let body = CreateAccessTokenBody {
+            email: account.email.clone(),
+            password,
+            name: Some("test-token".to_string()),
+            lifetime: 30 * 24 * 60 * 60,
+        };
+
+        let result = CreateAccessTokenRequest::try_from_body(
+            body,
+            &account,
+            &Opaque::new(rand::random()),
+            DEFAULT_TOKEN_BYTES,
+        );
+
+        assert!(matches!(
+            result,
+            Err(
+                CreateAccessTokenRequestError::LifetimeExceedsAccountPolicy {
+                    max_lifetime: 604_800
+                }
+            )
+        ));
+    }
+
+    #[test]
+    fn test_try_from_login() {
+        let mut account: Account = Faker.fake();
+        let password: Password = Faker.fake();
+        account.password_hash = password.hash(PasswordHashAlgorithm::Argon2id).unwrap();
+        account.max_token_lifetime_secs = Some(3600);
+
+        let request = CreateAccessTokenRequest::try_from_login(
+            &password,
+            Some(&account),
+            DEFAULT_LIFETIME,
+            &Opaque::new(rand::random()),
+            DEFAULT_TOKEN_BYTES,
+            PasswordHashAlgorithm::Argon2id,
+        )
+        .unwrap();
+
+        assert_eq!(request.account_id, account.id);
+        assert!(request.name.starts_with(GENERATED_NAME_PREFIX));
+        assert!(request.session);
+        // The default lifetime is capped by the account policy
+        assert!(request.expires_at <= Utc::now() + TimeDelta::seconds(3600));
+    }
+
+    #[test]
+    fn test_try_from_login_with_invalid_password_or_without_account() {
+        let account: Account = Faker.fake();
+        let wrong_password: Password = Faker.fake();
+
+        for account in [Some(&account), None] {
+            let result = CreateAccessTokenRequest::try_from_login(
+                &wrong_password,
+                account,
+                DEFAULT_LIFETIME,
+                &Opaque::new(rand::random()),
+                DEFAULT_TOKEN_BYTES,
+                PasswordHashAlgorithm::Argon2id,
+            );
+            assert!(matches!(
+                result,
+                Err(CreateAccessTokenRequestError::InvalidPassword)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_decoded_secret_produces_the_same_mac_as_per_request_decoding() {
+        let encoded_secret = BASE64_STANDARD.encode(rand::random::<[u8; 32]>());
+        let token = format!("{TOKEN_PREFIX}token");
+
+        let decoded_secret = decode_access_token_secret(&encoded_secret).unwrap();
+        let mac = compute_token_mac(&token, &Opaque::new(decoded_secret)).unwrap();
+
+        let mut hmac =
+            Hmac::<Sha3_256>::new_from_slice(&BASE64_STANDARD.decode(&encoded_secret).unwrap())
+                .unwrap();
+        hmac.update(token.as_bytes());
+        assert_eq!(mac, <[u8; 32]>::from(hmac.finalize().into_bytes()));
+    }
+
+    #[test]
+    fn test_try_new_without_password() {
+        let account: Account = Faker.fake();
+        let hmac_secret: [u8; 32] = rand::random();
+
+        let request = CreateAccessTokenRequest::try_new(
+            &account,
+            "  test-token ",
+            DEFAULT_LIFETIME,
+            &Opaque::new(hmac_secret),
+            DEFAULT_TOKEN_BYTES,
+        )
+        .unwrap();
+
+        assert_eq!(request.account_id, account.id);
+        assert_eq!(request.name, "test-token");
+        let mut hmac = Hmac::<Sha3_256>::new_from_slice(&hmac_secret).unwrap();
+        hmac.update(request.token.extract_inner().as_bytes());
+        assert_eq!(request.mac, <[u8; 32]>::from(hmac.finalize().into_bytes()));
+    }
+
+    #[test]
+    fn test_try_new_with_seeded_rng_is_deterministic() {
+        let account: Account = Faker.fake();
+        let hmac_secret: [u8; 32] = rand::random();
+
+        let build = || {
+            CreateAccessTokenRequest::try_new_with_rng(
+                &account,
+                "test-token",
+                DEFAULT_LIFETIME,
+                &Opaque::new(hmac_secret),
+                DEFAULT_TOKEN_BYTES,
+                &mut ChaCha20Rng::seed_from_u64(42),
+            )
+            .unwrap()
+        };
+        let first = build();
+        let second = build();
+
+        assert_eq!(first.token.extract_inner(), second.token.extract_inner());
+        assert_eq!(first.mac, second.mac);
+        assert_ne!(
+            first.token.extract_inner(),
+            CreateAccessTokenRequest::try_new(
+                &account,
+                "test-token",
+                DEFAULT_LIFETIME,
+                &Opaque::new(hmac_secret),
+                DEFAULT_TOKEN_BYTES,
+            )
+            .unwrap()
+            .token
+            .extract_inner()
+        );
+    }
+
+    #[test]
+    fn test_try_new_with_custom_token_bytes() {
+        let account: Account = Faker.fake();
+        let hmac_secret: [u8; 32] = rand::random();
+
+        for token_bytes in [MIN_TOKEN_BYTES, 100, MAX_TOKEN_BYTES] {
+            let request = CreateAccessTokenRequest::try_new(
+                &account,
+                "test-token",
+                DEFAULT_LIFETIME,
+                &Opaque::new(hmac_secret),
+                token_bytes,
+            )
+            .unwrap();
+
+            let token = request.token.extract_inner();
+            let encoded = token.strip_prefix(TOKEN_PREFIX).unwrap();
+            assert_eq!(
+                BASE64_STANDARD_NO_PAD.decode(encoded).unwrap().len(),
+                token_bytes
+            );
+            assert_eq!(
+                request.mac,
+                compute_token_mac(token, &Opaque::new(hmac_secret)).unwrap()
+            );
+        }
+
+        for token_bytes in [MIN_TOKEN_BYTES - 1, MAX_TOKEN_BYTES + 1] {
+            let result = CreateAccessTokenRequest::try_new(
+                &account,
+                "test-token",
+                DEFAULT_LIFETIME,
+                &Opaque::new(hmac_secret),
+                token_bytes,
+            );
+            assert!(matches!(
+                result,
+                Err(CreateAccessTokenRequestError::Unknown(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_access_token_activity() {
+        let now = Utc::now();
+        let mut access_token = AccessToken {
+            id: uuid::Uuid::new_v4(),
+            account_id: uuid::Uuid::new_v4(),
+            name: "test-token".to_string(),
+            mac: vec![0; 32],
+            created_at: now,
+            updated_at: now,
+            last_used_at: now,
+            expires_at: now + TimeDelta::seconds(60),
+            revoked_at: None,
+            authenticated_at: now,
+        };
+        assert!(access_token.is_active(now, Duration::ZERO));
+        assert_eq!(access_token.expires_in(now), TimeDelta::seconds(60));
+
+        assert!(!access_token.is_expired(now, Duration::ZERO));
+
+        access_token.expires_at = now - TimeDelta::seconds(1);
+        assert!(!access_token.is_active(now, Duration::ZERO));
+        assert!(access_token.is_expired(now, Duration::ZERO));
+        assert_eq!(access_token.expires_in(now), TimeDelta::zero());
+
+        // A token expired within the clock skew tolerance is still accepted
+        assert!(access_token.is_active(now, Duration::from_secs(30)));
+        access_token.expires_at = now - TimeDelta::seconds(30);
+        assert!(!access_token.is_active(now, Duration::from_secs(30)));
+
+        access_token.expires_at = now + TimeDelta::seconds(60);
+        access_token.revoked_at = Some(now);
+        assert!(!access_token.is_active(now, Duration::ZERO));
+        assert!(!access_token.is_expired(now, Duration::ZERO));
+    }
+
+    #[test]
+    fn test_access_token_recency() {
+        let now = Utc::now();
+        let mut access_token = AccessToken {
+            id: uuid::Uuid::new_v4(),
+            account_id: uuid::Uuid::new_v4(),
+            name: "test-token".to_string(),
+            mac: vec![0; 32],
+            created_at: now - TimeDelta::seconds(60),
+            updated_at: now,
+            last_used_at: now,
+            expires_at: now + TimeDelta::seconds(60),
+            revoked_at: None,
+            authenticated_at: now - TimeDelta::seconds(60),
+        };
+        assert!(access_token.is_recent(now, Duration::from_secs(61)));
+        // A recent use does not make the access token recent
+        assert!(!access_token.is_recent(now, Duration::from_secs(60)));
+        assert!(!access_token.is_recent(now, Duration::ZERO));
+
+        // Nor does a recent refresh
+        access_token.created_at = now;
+        assert!(!access_token.is_recent(now, Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_access_token_lifetime() {
+        let now = Utc::now();
+        let access_token = AccessToken {
+            id: uuid::Uuid::new_v4(),
+            account_id: uuid::Uuid::new_v4(),
+            name: "test-token".to_string(),
+            mac: vec![0; 32],
+            created_at: now + TimeDelta::milliseconds(3),
+            updated_at: now,
+            last_used_at: now,
+            expires_at: now + TimeDelta::seconds(60),
+            revoked_at: None,
+            authenticated_at: now,
+        };
+        assert_eq!(access_token.lifetime_secs(), 60);
+    }
+
+    #[test]
+    fn test_rotated_refresh_token_stays_in_its_family() {
+        let hmac_secret: [u8; 32] = rand::random();
+        let account_id = uuid::Uuid::new_v4();
+
+        let request = CreateRefreshTokenRequest::try_new(
+            account_id,
+            Duration::from_secs(3600),
+            &Opaque::new(hmac_secret),
+            DEFAULT_TOKEN_BYTES,
+        )
+        .unwrap();
+        assert!(
+            request
+                .token
+                .extract_inner()
+                .starts_with(REFRESH_TOKEN_PREFIX)
+        );
+        assert_eq!(
+            request.mac,
+            compute_token_mac(request.token.extract_inner(), &Opaque::new(hmac_secret)).unwrap()
+        );
+
+        let now = Utc::now();
+        let refresh_token = RefreshToken {
+            id: uuid::Uuid::new_v4(),
+            account_id,
+            family_id: request.family_id,
+            access_token_id: uuid::Uuid::new_v4(),
+            mac: request.mac.to_vec(),
+            authenticated_at: now,
+            created_at: now,
+            updated_at: now,
+            expires_at: request.expires_at,
+            rotated_at: None,
+            revoked_at: None,
+        };
+        let rotated = CreateRefreshTokenRequest::try_rotate(
+            &refresh_token,
+            Duration::from_secs(3600),
+            &Opaque::new(hmac_secret),
+            DEFAULT_TOKEN_BYTES,
+        )
+        .unwrap();
+        assert_eq!(rotated.account_id, account_id);
+        assert_eq!(rotated.family_id, request.family_id);
+        assert_ne!(rotated.mac, request.mac);
+    }
+
+    #[test]
+    fn test_refresh_token_usability() {
+        let now = Utc::now();
+        let mut refresh_token = RefreshToken {
+            id: uuid::Uuid::new_v4(),
+            account_id: uuid::Uuid::new_v4(),
+            family_id: uuid::Uuid::new_v4(),
+            access_token_id: uuid::Uuid::new_v4(),
+            mac: vec![0; 32],
+            authenticated_at: now,
+            created_at: now,
+            updated_at: now,
+            expires_at: now + TimeDelta::seconds(60),
+            rotated_at: None,
+            revoked_at: None,
+        };
+        assert!(refresh_token.ensure_usable(now, Duration::ZERO).is_ok());
+
+        refresh_token.expires_at = now - TimeDelta::seconds(1);
+        assert!(matches!(
+            refresh_token.ensure_usable(now, Duration::ZERO),
+            Err(RefreshAccessTokenError::InvalidRefreshToken)
+        ));
+        // A refresh token expired within the clock skew tolerance is still accepted
+        assert!(
+            refresh_token
+                .ensure_usable(now, Duration::from_secs(30))
+                .is_ok()
+        );
+
+        refresh_token.expires_at = now + TimeDelta::seconds(60);
+        refresh_token.revoked_at = Some(now);
+        assert!(matches!(
+            refresh_token.ensure_usable(now, Duration::ZERO),
+            Err(RefreshAccessTokenError::InvalidRefreshToken)
+        ));
+
+        // The reuse of a rotated refresh token is reported even once its family has been revoked
+        refresh_token.rotated_at = Some(now);
+        assert!(matches!(
+            refresh_token.ensure_usable(now, Duration::ZERO),
+            Err(RefreshAccessTokenError::RefreshTokenReused { family_id }) if family_id == refresh_token.family_id
+        ));
+    }
 }
@@ -1,29 +1,52 @@
 use axum::{
-    Extension, Json, Router, extract::State, handler::Handler, http::StatusCode, routing::post,
+    Json, Router,
+    extract::State,
+    http::{HeaderName, HeaderValue, StatusCode, header::LOCATION},
+    routing::{get, post},
 };
 use chrono::{DateTime, Utc};
+use metrics::counter;
 use serde::{Deserialize, Serialize};
-use validator::{Validate, ValidationError, ValidationErrors};
+use tracing::warn;
+use validator::Validate;
 
-use crate::newtypes::{Email, Opaque};
+use crate::{
+    newtypes::{Email, Opaque},
+    observability::{FAILED_PASSWORD_COUNTER, TOKEN_CREATION_COUNTER, record_outcome},
+};
 mod domain;
-use super::{ApiError, ValidatedJson};
-use domain::{
-    CreateAccessTokenError, CreateAccessTokenRequest, CreateAccessTokenRequestError,
-    MAX_ACTIVE_TOKENS, TokenQueryError,
+use super::{
+    ApiError, AuthenticatedAccessToken, PaginationQuery, Timestamped, UuidPath, ValidatedJson,
+    ValidatedQuery, deserialize_u32_from_number_or_string, ensure_active, timestamp,
+};
+pub use domain::{AccessToken, CreateAccessTokenError, CreateAccessTokenRequest};
+pub(crate) use domain::{
+    CreateAccessTokenRequestError, CreateRefreshTokenRequest, RefreshAccessTokenError,
+    RefreshToken, TokenQueryError, compute_token_mac,
+};
+pub use domain::{
+    DEFAULT_LIFETIME, DEFAULT_NAME, DEFAULT_TOKEN_BYTES, GENERATED_NAME_PREFIX,
+    LAST_USE_GRANULARITY, MAX_ACTIVE_SESSIONS, MAX_ACTIVE_TOKENS, MAX_LIFETIME, MAX_NAME_LENGTH,
+    MAX_TOKEN_BYTES, MIN_TOKEN_BYTES, REFRESH_TOKEN_PREFIX, SESSION_ACCESS_TOKEN_LIFETIME,
+    TOKEN_PREFIX,
 };
-pub use domain::{MAX_LIFETIME, MAX_NAME_LENGTH};
 
 mod repository;
 pub use repository::{AccessTokenRepository, PostgresAccessTokenRepository};
 
-use super::{AppState, newtypes::Password};
+use super::{
+    AppState,
+    accounts::{AccountQueryError, AccountState},
+    newtypes::Password,
+};
 
-pub fn tokens_router(access_token_secret: Opaque<[u8; 32]>) -> Router<AppState> {
-    Router::new().route(
-        "/",
-        post(create_access_token.layer(Extension(access_token_secret))),
-    )
+pub fn tokens_router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_access_tokens).post(create_access_token))
+        .route("/refresh", post(refresh_access_token))
+        .route("/verify", get(verify_access_token))
+        .route("/whoami", post(whoami))
+        .route("/{id}", get(get_access_token))
 }
 
 // ############################################
@@ -33,7 +56,8 @@ pub fn tokens_router(access_token_secret: Opaque<[u8; 32]>) -> Router<AppState>
 impl From<TokenQueryError> for ApiError {
     fn from(value: TokenQueryError) -> Self {
         match value {
-            TokenQueryError::Unknown(e) => ApiError::InternalServerError(e),
+            TokenQueryError::TokenNotFound => ApiError::Unauthorized,
+            TokenQueryError::Unknown(e) => e.into(),
         }
     }
 }
@@ -47,7 +71,10 @@ impl From<TokenQueryError> for ApiError {
 pub struct CreateAccessTokenBody {
     email: Email,
     password: Password,
-    name: String,
+    /// Name of the access token, a name is generated if absent
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(deserialize_with = "deserialize_u32_from_number_or_string")]
     lifetime: u32,
 }
 
@@ -57,56 +84,166 @@ pub struct AccessTokenCreatedResponse {
     pub id: uuid::Uuid,
     pub name: String,
     pub access_token: Opaque<String>,
+    #[serde(with = "timestamp")]
     pub created_at: DateTime<Utc>,
+    #[serde(with = "timestamp")]
     pub updated_at: DateTime<Utc>,
+    #[serde(with = "timestamp")]
     pub expires_at: DateTime<Utc>,
+    pub lifetime_secs: i64,
+    #[serde(with = "timestamp::option")]
     pub revoked_at: Option<DateTime<Utc>>,
 }
 
+impl Timestamped for AccessTokenCreatedResponse {
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+}
+
+impl AccessTokenCreatedResponse {
+    /// Build the response of a freshly created access token, the plaintext token is only known at creation
+    pub(crate) fn new(access_token: AccessToken, token: Opaque<String>) -> Self {
+        AccessTokenCreatedResponse {
+            lifetime_secs: access_token.lifetime_secs(),
+            id: access_token.id,
+            name: access_token.name,
+            access_token: token,
+            created_at: access_token.created_at,
+            updated_at: access_token.updated_at,
+            expires_at: access_token.expires_at,
+            revoked_at: access_token.revoked_at,
+        }
+    }
+}
+
+/// Refresh token issued along an access token, see [crate::Config::refresh_token_lifetime]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshTokenCreatedResponse {
+    pub refresh_token: Opaque<String>,
+    #[serde(with = "timestamp")]
+    pub refresh_token_expires_at: DateTime<Utc>,
+}
+
+impl RefreshTokenCreatedResponse {
+    /// Build the response of a freshly created refresh token, the plaintext token is only known at creation
+    pub(crate) fn new(refresh_token: RefreshToken, token: Opaque<String>) -> Self {
+        RefreshTokenCreatedResponse {
+            refresh_token: token,
+            refresh_token_expires_at: refresh_token.expires_at,
+        }
+    }
+}
+
+/// Access token issued by a login or a refresh, along with the refresh token renewing it if refresh tokens are enabled
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionCreatedResponse {
+    #[serde(flatten)]
+    pub access_token: AccessTokenCreatedResponse,
+    #[serde(flatten)]
+    pub refresh_token: Option<RefreshTokenCreatedResponse>,
+}
+
+type AccessTokenCreated = (
+    StatusCode,
+    [(HeaderName, HeaderValue); 1],
+    Json<AccessTokenCreatedResponse>,
+);
+
 async fn create_access_token(
     State(app_state): State<AppState>,
-    Extension(access_token_secret): Extension<Opaque<[u8; 32]>>,
     ValidatedJson(body): ValidatedJson<CreateAccessTokenBody>,
-) -> Result<(StatusCode, Json<AccessTokenCreatedResponse>), ApiError> {
-    let account = app_state
+) -> Result<AccessTokenCreated, ApiError> {
+    let result = create(app_state, body).await;
+    record_outcome(TOKEN_CREATION_COUNTER, &result);
+    result
+}
+
+async fn create(
+    app_state: AppState,
+    body: CreateAccessTokenBody,
+) -> Result<AccessTokenCreated, ApiError> {
+    body.password
+        .ensure_prehash_mode(app_state.config.password_prehash)?;
+
+    let account = match app_state
         .account_repository
         .get_verified_account_by_email(&body.email)
-        .await?;
+        .await
+    {
+        Ok(account) => account,
+        // An unknown email fails as a wrong password, the password is verified against a dummy hash
+        // so that neither the response nor its latency tell whether the email is registered
+        Err(AccountQueryError::AccountNotFound) => {
+            let password_hash_algorithm = app_state.config.password_hash_algorithm;
+            let _ = app_state
+                .hashing_limiter
+                .run(move || body.password.verify_dummy(password_hash_algorithm))
+                .await?;
+            return Err(CreateAccessTokenRequestError::InvalidPassword.into());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let account_state = account.state(app_state.clock.now());
 
-    let req = CreateAccessTokenRequest::try_from_body(body, &account, access_token_secret)?;
+    let config = app_state.config.clone();
+    let req = app_state
+        .hashing_limiter
+        .run(move || {
+            CreateAccessTokenRequest::try_from_body(
+                body,
+                &account,
+                &config.access_token_secret,
+                config.access_token_bytes,
+            )
+        })
+        .await?
+        .inspect_err(|e| {
+            if let CreateAccessTokenRequestError::InvalidPassword = e {
+                counter!(FAILED_PASSWORD_COUNTER).increment(1);
+            }
+        })?;
+    // The lock is only disclosed once the password has been verified
+    ensure_active(account_state)?;
 
     let access_token = app_state
         .access_token_repository
         .create_token(&req, MAX_ACTIVE_TOKENS)
         .await?;
 
+    let location = HeaderValue::from_str(&format!(
+        "{}/tokens/{}",
+        app_state.config.base_path.as_deref().unwrap_or_default(),
+        access_token.id
+    ))
+    .map_err(|e| ApiError::InternalServerError(e.into()))?;
+
     Ok((
         StatusCode::CREATED,
-        Json(AccessTokenCreatedResponse {
-            id: access_token.id,
-            name: access_token.name,
-            access_token: req.token,
-            created_at: access_token.created_at,
-            updated_at: access_token.updated_at,
-            expires_at: access_token.expires_at,
-            revoked_at: access_token.revoked_at,
-        }),
+        [(LOCATION, location)],
+        Json(AccessTokenCreatedResponse::new(access_token, req.token)),
     ))
 }
 
 impl From<CreateAccessTokenError> for ApiError {
     fn from(value: CreateAccessTokenError) -> Self {
         match value {
-            CreateAccessTokenError::ActiveTokenLimitReached(_) => {
-                let mut validation_errors = ValidationErrors::new();
-                validation_errors.add(
-                    "global",
-                    ValidationError::new("too-many-tokens")
-                        .with_message("limit of active access token reached".into()),
-                );
-                ApiError::BadRequest(validation_errors)
-            }
-            CreateAccessTokenError::Unknown(e) => ApiError::InternalServerError(e),
+            CreateAccessTokenError::ActiveTokenLimitReached(_) => ApiError::bad_request(
+                "global",
+                "too-many-tokens",
+                "limit of active access token reached",
+            ),
+            CreateAccessTokenError::NameAlreadyExists => ApiError::conflict(
+                "name",
+                "existing-name",
+                "An active access token already has this name",
+            ),
+            CreateAccessTokenError::Unknown(e) => e.into(),
         }
     }
 }
@@ -115,22 +252,333 @@ impl From<CreateAccessTokenRequestError> for ApiError {
     fn from(value: CreateAccessTokenRequestError) -> Self {
         match value {
             CreateAccessTokenRequestError::InvalidPassword => ApiError::Unauthorized,
-            CreateAccessTokenRequestError::InvalidName => {
-                let mut validation_errors = ValidationErrors::new();
-                let error = ValidationError::new("invalid-length").with_message(
-                    "name must not be empty and must be less than 40 characters long".into(),
-                );
-                validation_errors.add("name", error);
-                ApiError::BadRequest(validation_errors)
+            CreateAccessTokenRequestError::InvalidName => ApiError::bad_request(
+                "name",
+                "invalid-length",
+                "name must not be empty and must be less than 40 characters long",
+            ),
+            CreateAccessTokenRequestError::InvalidLifetime => ApiError::bad_request(
+                "lifetime",
+                "invalid-range",
+                "lifetime must be more than 0 and less than 90 days",
+            ),
+            CreateAccessTokenRequestError::LifetimeExceedsAccountPolicy { max_lifetime } => {
+                ApiError::bad_request(
+                    "lifetime",
+                    "invalid-range",
+                    format!("lifetime must be at most {max_lifetime} seconds for this account"),
+                )
             }
-            CreateAccessTokenRequestError::InvalidLifetime => {
-                let mut validation_errors = ValidationErrors::new();
-                let error = ValidationError::new("invalid-range")
-                    .with_message("lifetime must be more than 0 and less than 90 days".into());
-                validation_errors.add("lifetime", error);
-                ApiError::BadRequest(validation_errors)
+            CreateAccessTokenRequestError::Unknown(e) => e.into(),
+        }
+    }
+}
+
+// ##########################################################
+// ################## ACCESS TOKEN REFRESH ##################
+// ##########################################################
+
+#[derive(Debug, Clone, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshAccessTokenBody {
+    refresh_token: Opaque<String>,
+}
+
+/// Exchange a refresh token for a new access token, the refresh token is rotated
+///
+/// An unknown, revoked or expired refresh token fails with a `401`. A refresh token can only be exchanged once,
+/// its reuse is a sign of theft and revokes every refresh token of its family with the access tokens issued along them.
+async fn refresh_access_token(
+    State(app_state): State<AppState>,
+    ValidatedJson(body): ValidatedJson<RefreshAccessTokenBody>,
+) -> Result<(StatusCode, Json<SessionCreatedResponse>), ApiError> {
+    let result = refresh(app_state, body).await;
+    record_outcome(TOKEN_CREATION_COUNTER, &result);
+    result
+}
+
+async fn refresh(
+    app_state: AppState,
+    body: RefreshAccessTokenBody,
+) -> Result<(StatusCode, Json<SessionCreatedResponse>), ApiError> {
+    // The refresh tokens issued before refresh tokens were disabled can not be exchanged
+    let Some(refresh_token_lifetime) = app_state.config.refresh_token_lifetime else {
+        return Err(ApiError::NotFound);
+    };
+
+    let token = body.refresh_token.extract_inner();
+    if !token.starts_with(REFRESH_TOKEN_PREFIX) {
+        warn!("malformed refresh token");
+        return Err(ApiError::Unauthorized);
+    }
+    let mac = compute_token_mac(token, &app_state.config.access_token_secret)?;
+    let previous = match app_state
+        .access_token_repository
+        .find_refresh_token_by_mac(&mac)
+        .await
+    {
+        Ok(v) => v,
+        Err(TokenQueryError::TokenNotFound) => {
+            warn!("refresh token not found");
+            return Err(ApiError::Unauthorized);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if let Err(e) =
+        previous.ensure_usable(app_state.clock.now(), app_state.config.clock_skew_tolerance)
+    {
+        return Err(reject_refresh(&app_state, e).await);
+    }
+
+    let account = match app_state
+        .account_repository
+        .get_account_by_id(previous.account_id)
+        .await
+    {
+        Ok(account) if account.state(app_state.clock.now()) == AccountState::Active => account,
+        Ok(_) | Err(AccountQueryError::AccountNotFound) => {
+            warn!("no verified account for refresh token {}", previous.id);
+            return Err(ApiError::Unauthorized);
+        }
+        Err(AccountQueryError::Unknown(e)) => return Err(e.into()),
+    };
+
+    let req = CreateAccessTokenRequest::try_from_refresh(
+        &account,
+        &previous,
+        &app_state.config.access_token_secret,
+        app_state.config.access_token_bytes,
+    )?;
+    let refresh_req = CreateRefreshTokenRequest::try_rotate(
+        &previous,
+        refresh_token_lifetime,
+        &app_state.config.access_token_secret,
+        app_state.config.access_token_bytes,
+    )?;
+
+    let (access_token, refresh_token) = match app_state
+        .access_token_repository
+        .rotate_refresh_token(&previous, &req, &refresh_req, MAX_ACTIVE_SESSIONS)
+        .await
+    {
+        Ok(v) => v,
+        Err(e) => return Err(reject_refresh(&app_state, e).await),
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(SessionCreatedResponse {
+            access_token: AccessTokenCreatedResponse::new(access_token, req.token),
+            refresh_token: Some(RefreshTokenCreatedResponse::new(
+                refresh_token,
+                refresh_req.token,
+            )),
+        }),
+    ))
+}
+
+/// Error response of a failed refresh, the family of a reused refresh token is revoked beforehand
+async fn reject_refresh(app_state: &AppState, error: RefreshAccessTokenError) -> ApiError {
+    let RefreshAccessTokenError::RefreshTokenReused { family_id } = error else {
+        return error.into();
+    };
+    warn!("rotated refresh token of family {family_id} has been reused, the family is revoked");
+    match app_state
+        .access_token_repository
+        .revoke_refresh_token_family(family_id)
+        .await
+    {
+        Ok(()) => ApiError::Unauthorized,
+        Err(e) => e.into(),
+    }
+}
+
+impl From<RefreshAccessTokenError> for ApiError {
+    fn from(value: RefreshAccessTokenError) -> Self {
+        match value {
+            RefreshAccessTokenError::InvalidRefreshToken => {
+                warn!("{value}");
+                ApiError::Unauthorized
             }
-            CreateAccessTokenRequestError::Unknown(e) => ApiError::InternalServerError(e),
+            RefreshAccessTokenError::RefreshTokenReused { .. } => ApiError::Unauthorized,
+            RefreshAccessTokenError::CreateAccessToken(e) => e.into(),
+            RefreshAccessTokenError::Unknown(e) => e.into(),
+        }
+    }
+}
+
+// ###############################################################
+// ################## ACCESS TOKEN VERIFICATION ##################
+// ###############################################################
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyAccessTokenResponse {
+    pub id: uuid::Uuid,
+    pub name: String,
+    #[serde(with = "timestamp")]
+    pub expires_at: DateTime<Utc>,
+    pub expires_in_secs: i64,
+}
+
+async fn verify_access_token(
+    State(app_state): State<AppState>,
+    AuthenticatedAccessToken(access_token): AuthenticatedAccessToken,
+) -> Result<(StatusCode, Json<VerifyAccessTokenResponse>), ApiError> {
+    let expires_in_secs = access_token.expires_in(app_state.clock.now()).num_seconds();
+
+    Ok((
+        StatusCode::OK,
+        Json(VerifyAccessTokenResponse {
+            id: access_token.id,
+            name: access_token.name,
+            expires_at: access_token.expires_at,
+            expires_in_secs,
+        }),
+    ))
+}
+
+// ############################################
+// ################## WHOAMI ##################
+// ############################################
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WhoamiResponse {
+    pub account_id: uuid::Uuid,
+    pub token_id: uuid::Uuid,
+    pub name: String,
+}
+
+async fn whoami(
+    AuthenticatedAccessToken(access_token): AuthenticatedAccessToken,
+) -> (StatusCode, Json<WhoamiResponse>) {
+    (
+        StatusCode::OK,
+        Json(WhoamiResponse {
+            account_id: access_token.account_id,
+            token_id: access_token.id,
+            name: access_token.name,
+        }),
+    )
+}
+
+// ##########################################################
+// ################## ACCESS TOKEN DETAILS ##################
+// ##########################################################
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessTokenResponse {
+    pub id: uuid::Uuid,
+    pub name: String,
+    #[serde(with = "timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "timestamp")]
+    pub updated_at: DateTime<Utc>,
+    #[serde(with = "timestamp")]
+    pub last_used_at: DateTime<Utc>,
+    #[serde(with = "timestamp")]
+    pub expires_at: DateTime<Utc>,
+    pub lifetime_secs: i64,
+    #[serde(with = "timestamp::option")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl Timestamped for AccessTokenResponse {
+    fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+}
+
+impl From<AccessToken> for AccessTokenResponse {
+    fn from(value: AccessToken) -> Self {
+        AccessTokenResponse {
+            lifetime_secs: value.lifetime_secs(),
+            id: value.id,
+            name: value.name,
+            created_at: value.created_at,
+            updated_at: value.updated_at,
+            last_used_at: value.last_used_at,
+            expires_at: value.expires_at,
+            revoked_at: value.revoked_at,
         }
     }
 }
+
+/// Access tokens of other accounts are reported as not found so that their existence is not disclosed
+async fn get_access_token(
+    AuthenticatedAccessToken(authenticated_token): AuthenticatedAccessToken,
+    State(app_state): State<AppState>,
+    UuidPath(token_id): UuidPath,
+) -> Result<(StatusCode, Json<AccessTokenResponse>), ApiError> {
+    let access_token = app_state
+        .access_token_repository
+        .get_token_by_id(authenticated_token.account_id, token_id)
+        .await
+        .map_err(|e| match e {
+            TokenQueryError::TokenNotFound => ApiError::NotFound,
+            e => e.into(),
+        })?;
+
+    Ok((StatusCode::OK, Json(access_token.into())))
+}
+
+// ##########################################################
+// ################## ACCESS TOKEN LISTING ##################
+// ##########################################################
+
+/// Metadata of a listed access token, the access token itself is never returned
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessTokenSummaryResponse {
+    pub id: uuid::Uuid,
+    pub name: String,
+    #[serde(with = "timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "timestamp")]
+    pub last_used_at: DateTime<Utc>,
+    #[serde(with = "timestamp")]
+    pub expires_at: DateTime<Utc>,
+    pub expired: bool,
+    #[serde(with = "timestamp::option")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// List the access tokens of the authenticated account, the most recently created first
+///
+/// Expired and revoked access tokens are listed as well, expired ones are flagged.
+async fn list_access_tokens(
+    AuthenticatedAccessToken(authenticated_token): AuthenticatedAccessToken,
+    State(app_state): State<AppState>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationQuery>,
+) -> Result<(StatusCode, Json<Vec<AccessTokenSummaryResponse>>), ApiError> {
+    let access_tokens = app_state
+        .access_token_repository
+        .list_tokens(
+            authenticated_token.account_id,
+            pagination.limit(),
+            pagination.offset(),
+        )
+        .await?;
+
+    let now = app_state.clock.now();
+    let access_tokens = access_tokens
+        .into_iter()
+        .map(|access_token| AccessTokenSummaryResponse {
+            expired: access_token.is_expired(now, app_state.config.clock_skew_tolerance),
+            id: access_token.id,
+            name: access_token.name,
+            created_at: access_token.created_at,
+            last_used_at: access_token.last_used_at,
+            expires_at: access_token.expires_at,
+            revoked_at: access_token.revoked_at,
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(access_tokens)))
+}
@@ -1,24 +1,189 @@
-use anyhow::anyhow;
+use std::time::Duration;
+
 use async_trait::async_trait;
-use sqlx::{Pool, Postgres};
+use chrono::{DateTime, Utc};
+use sqlx::{Pool, Postgres, types::uuid};
+use tracing::warn;
+
+use crate::{
+    database::{
+        DatabaseTransaction, RepositoryError, begin_transaction, commit_transaction, map_sqlx_error,
+    },
+    rng::new_rng,
+};
 
-use super::domain::{AccessToken, CreateAccessTokenError, CreateAccessTokenRequest};
+use super::domain::{
+    AccessToken, CreateAccessTokenError, CreateAccessTokenRequest, CreateRefreshTokenRequest,
+    RefreshAccessTokenError, RefreshToken, TokenQueryError, generate_token_name,
+};
+
+/// Maximum number of attempts to insert an access token whose generated name collides with an active access token of the account
+const GENERATED_NAME_ATTEMPTS: usize = 3;
 
 #[async_trait]
 pub trait AccessTokenRepository: Send + Sync {
-    /// Create an access token
+    /// Create an access token, the active access tokens of an account have distinct names
+    ///
+    /// Sessions and the other access tokens are limited separately, see [CreateAccessTokenRequest::session].
+    /// The limit of the sessions is never reached, the oldest active sessions are revoked along their refresh token families to make room for the new one.
     ///
     /// # Arguments
     /// * `req` - DTO for create an access token
-    /// * `max_active_token` - maximum number of active token allowed
+    /// * `max_active_token` - maximum number of active token allowed, of sessions if the access token is a session
     ///
     /// # Errors
+    /// * `CreateAccessTokenError::ActiveTokenLimitReached` - the account has reached its limit of active access tokens, sessions excluded
+    /// * `CreateAccessTokenError::NameAlreadyExists` - an active access token of the account has the same name, revoked access tokens do not count, a generated name is generated again instead, see [CreateAccessTokenRequest::name_generated]
     /// * `CreateAccessTokenError::Unknown` - unknown error
     async fn create_token(
         &self,
         req: &CreateAccessTokenRequest,
         max_active_token: u8,
     ) -> Result<AccessToken, CreateAccessTokenError>;
+
+    /// Same as [AccessTokenRepository::create_token] within a transaction
+    async fn create_token_in_transaction(
+        &self,
+        transaction: &mut DatabaseTransaction,
+        req: &CreateAccessTokenRequest,
+        max_active_token: u8,
+    ) -> Result<AccessToken, CreateAccessTokenError>;
+
+    /// Find an access token by its MAC, revoked and expired access tokens are included
+    ///
+    /// # Arguments
+    /// * `mac` - MAC of the access token
+    ///
+    /// # Errors
+    /// * `TokenQueryError::TokenNotFound` - access token not found
+    /// * `TokenQueryError::Unknown` - unknown error
+    async fn find_by_mac(&self, mac: &[u8; 32]) -> Result<AccessToken, TokenQueryError>;
+
+    /// Get an access token of an account by ID, revoked and expired access tokens are included
+    ///
+    /// # Arguments
+    /// * `account_id` - ID of the account owning the access token,
+    /// * `token_id` - ID of the access token
+    ///
+    /// # Errors
+    /// * `TokenQueryError::TokenNotFound` - access token not found or owned by another account
+    /// * `TokenQueryError::Unknown` - unknown error
+    async fn get_token_by_id(
+        &self,
+        account_id: uuid::Uuid,
+        token_id: uuid::Uuid,
+    ) -> Result<AccessToken, TokenQueryError>;
+
+    /// Record the use of an access token, its last use date is set to the current date unless it is more recent than the granularity
+    ///
+    /// Returns the new last use date, or `None` if the use has not been recorded. The update date of the access token is left untouched.
+    ///
+    /// # Arguments
+    /// * `token_id` - ID of the access token,
+    /// * `granularity` - minimum age of the recorded last use date, see [super::LAST_USE_GRANULARITY]
+    ///
+    /// # Errors
+    /// * `TokenQueryError::Unknown` - unknown error
+    async fn record_token_use(
+        &self,
+        token_id: uuid::Uuid,
+        granularity: Duration,
+    ) -> Result<Option<DateTime<Utc>>, TokenQueryError>;
+
+    /// List the access tokens of an account, the most recently created first, revoked and expired access tokens are included
+    ///
+    /// # Arguments
+    /// * `account_id` - ID of the account owning the access tokens,
+    /// * `limit` - maximum number of access tokens to list,
+    /// * `offset` - number of access tokens to skip
+    ///
+    /// # Errors
+    /// * `TokenQueryError::Unknown` - unknown error
+    async fn list_tokens(
+        &self,
+        account_id: uuid::Uuid,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<AccessToken>, TokenQueryError>;
+
+    /// Create an access token along with the refresh token renewing it, the refresh token starts a new family
+    ///
+    /// # Arguments
+    /// * `req` - DTO for create an access token,
+    /// * `refresh_req` - DTO for create the refresh token,
+    /// * `max_active_token` - maximum number of active token allowed
+    ///
+    /// # Errors
+    /// See [AccessTokenRepository::create_token]
+    async fn create_token_with_refresh_token(
+        &self,
+        req: &CreateAccessTokenRequest,
+        refresh_req: &CreateRefreshTokenRequest,
+        max_active_token: u8,
+    ) -> Result<(AccessToken, RefreshToken), CreateAccessTokenError>;
+
+    /// Find a refresh token by its MAC, rotated, revoked and expired refresh tokens are included
+    ///
+    /// # Arguments
+    /// * `mac` - MAC of the refresh token
+    ///
+    /// # Errors
+    /// * `TokenQueryError::TokenNotFound` - refresh token not found
+    /// * `TokenQueryError::Unknown` - unknown error
+    async fn find_refresh_token_by_mac(
+        &self,
+        mac: &[u8; 32],
+    ) -> Result<RefreshToken, TokenQueryError>;
+
+    /// Rotate a refresh token, a new access token and a new refresh token of the same family are created
+    ///
+    /// The rotated refresh token can not be used anymore and the access token issued along it is revoked.
+    ///
+    /// # Arguments
+    /// * `previous` - refresh token being rotated,
+    /// * `req` - DTO for create the new access token,
+    /// * `refresh_req` - DTO for create the new refresh token,
+    /// * `max_active_token` - maximum number of active token allowed
+    ///
+    /// # Errors
+    /// * `RefreshAccessTokenError::RefreshTokenReused` - the refresh token has been rotated or revoked concurrently
+    /// * `RefreshAccessTokenError::CreateAccessToken` - the new access token can not be created, see [AccessTokenRepository::create_token]
+    /// * `RefreshAccessTokenError::Unknown` - unknown error
+    async fn rotate_refresh_token(
+        &self,
+        previous: &RefreshToken,
+        req: &CreateAccessTokenRequest,
+        refresh_req: &CreateRefreshTokenRequest,
+        max_active_token: u8,
+    ) -> Result<(AccessToken, RefreshToken), RefreshAccessTokenError>;
+
+    /// Revoke the refresh tokens of a family and the access tokens issued along them, e.g. once a rotated refresh token is reused
+    ///
+    /// # Arguments
+    /// * `family_id` - ID of the family
+    ///
+    /// # Errors
+    /// * `TokenQueryError::Unknown` - unknown error
+    async fn revoke_refresh_token_family(
+        &self,
+        family_id: uuid::Uuid,
+    ) -> Result<(), TokenQueryError>;
+
+    /// Revoke the active access tokens and refresh tokens of an account within a transaction, e.g. along a password change
+    ///
+    /// Returns the number of revoked access tokens.
+    ///
+    /// # Arguments
+    /// * `transaction` - transaction of the account update,
+    /// * `account_id` - ID of the account
+    ///
+    /// # Errors
+    /// * `TokenQueryError::Unknown` - unknown error
+    async fn revoke_account_tokens_in_transaction(
+        &self,
+        transaction: &mut DatabaseTransaction,
+        account_id: uuid::Uuid,
+    ) -> Result<u64, TokenQueryError>;
 }
 
 pub struct PostgresAccessTokenRepository {
@@ -38,43 +203,139 @@ impl AccessTokenRepository for PostgresAccessTokenRepository {
         req: &CreateAccessTokenRequest,
         max_active_token: u8,
     ) -> Result<AccessToken, CreateAccessTokenError> {
-        let mut transaction = self
-            .pool
-            .begin()
+        let mut transaction = begin_transaction(&self.pool).await?;
+        let access_token = self
+            .create_token_in_transaction(&mut transaction, req, max_active_token)
+            .await?;
+        commit_transaction(transaction).await?;
+
+        Ok(access_token)
+    }
+
+    async fn create_token_in_transaction(
+        &self,
+        transaction: &mut DatabaseTransaction,
+        req: &CreateAccessTokenRequest,
+        max_active_token: u8,
+    ) -> Result<AccessToken, CreateAccessTokenError> {
+        if req.session {
+            evict_oldest_sessions(transaction, req.account_id, max_active_token).await?;
+        } else {
+            let count: i64 = sqlx::query_scalar(
+                r#"
+                SELECT COUNT(*)
+                FROM "access_token"
+                WHERE "account_id" = $1
+                    AND NOT "session"
+                    AND "revoked_at" IS NULL
+                    AND "expires_at" > CURRENT_TIMESTAMP
+            "#,
+            )
+            .bind(req.account_id)
+            .fetch_one(&mut **transaction)
+            .await
+            .map_err(|e| map_sqlx_error("failed to retrieve active access token count", e))?;
+
+            if count >= max_active_token.into() {
+                return Err(CreateAccessTokenError::ActiveTokenLimitReached(
+                    max_active_token,
+                ));
+            }
+        }
+
+        // A generated name colliding with an active access token of the account is generated again
+        let mut name = req.name.clone();
+        for attempt in 1..=GENERATED_NAME_ATTEMPTS {
+            let access_token = sqlx::query_as::<_, AccessToken>(
+                r#"
+                INSERT INTO "access_token" (
+                    "account_id",
+                    "name",
+                    "mac",
+                    "expires_at",
+                    "authenticated_at",
+                    "session"
+                ) VALUES (
+                    $1,
+                    $2,
+                    $3,
+                    $4,
+                    COALESCE($5, CURRENT_TIMESTAMP),
+                    $6
+                )
+                ON CONFLICT ("account_id", "name") WHERE "revoked_at" IS NULL DO NOTHING
+                RETURNING
+                    id,
+                    account_id,
+                    name,
+                    mac,
+                    created_at,
+                    updated_at,
+                    last_used_at,
+                    expires_at,
+                    revoked_at,
+                    authenticated_at
+            "#,
+            )
+            .bind(req.account_id)
+            .bind(&name)
+            .bind(req.mac)
+            .bind(req.expires_at)
+            .bind(req.authenticated_at)
+            .bind(req.session)
+            .fetch_optional(&mut **transaction)
             .await
-            .map_err(|e| anyhow!(e).context("failed to start transaction"))?;
+            .map_err(|e| map_sqlx_error("failed to insert access token", e))?;
+
+            match access_token {
+                Some(access_token) => return Ok(access_token),
+                None if req.name_generated && attempt < GENERATED_NAME_ATTEMPTS => {
+                    warn!(
+                        "generated access token name {name} already exists, a new name is generated"
+                    );
+                    name = generate_token_name(&mut new_rng());
+                }
+                None => break,
+            }
+        }
+
+        Err(CreateAccessTokenError::NameAlreadyExists)
+    }
 
-        let count: i64 = sqlx::query_scalar(
+    async fn find_by_mac(&self, mac: &[u8; 32]) -> Result<AccessToken, TokenQueryError> {
+        let access_token = sqlx::query_as::<_, AccessToken>(
             r#"
-            SELECT COUNT(*)
+            SELECT
+                id,
+                account_id,
+                name,
+                mac,
+                created_at,
+                updated_at,
+                last_used_at,
+                expires_at,
+                revoked_at,
+                authenticated_at
             FROM "access_token"
-            WHERE "account_id" = $1 AND "revoked_at" IS NULL AND "expires_at" > CURRENT_TIMESTAMP
+            WHERE "mac" = $1
         "#,
         )
-        .bind(req.account_id)
-        .fetch_one(&mut *transaction)
+        .bind(mac)
+        .fetch_one(&self.pool)
         .await
-        .map_err(|e| anyhow!(e).context("failed to retrieve active access token count"))?;
+        .map_err(|e| map_sqlx_error("failed query for access token by MAC", e))?;
 
-        if count >= max_active_token.into() {
-            return Err(CreateAccessTokenError::ActiveTokenLimitReached(
-                max_active_token,
-            ));
-        }
+        Ok(access_token)
+    }
 
+    async fn get_token_by_id(
+        &self,
+        account_id: uuid::Uuid,
+        token_id: uuid::Uuid,
+    ) -> Result<AccessToken, TokenQueryError> {
         let access_token = sqlx::query_as::<_, AccessToken>(
             r#"
-            INSERT INTO "access_token" (
-                "account_id",
-                "name",
-                "mac",
-                "expires_at"
-            ) VALUES (
-                $1,
-                $2,
-                $3,
-                $4
-            ) RETURNING
+            SELECT
                 id,
                 account_id,
                 name,
@@ -83,22 +344,436 @@ impl AccessTokenRepository for PostgresAccessTokenRepository {
                 updated_at,
                 last_used_at,
                 expires_at,
+                revoked_at,
+                authenticated_at
+            FROM "access_token"
+            WHERE "id" = $1 AND "account_id" = $2
+        "#,
+        )
+        .bind(token_id)
+        .bind(account_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!("failed query for access token with ID: {token_id}"),
+                e,
+            )
+        })?;
+
+        Ok(access_token)
+    }
+
+    async fn record_token_use(
+        &self,
+        token_id: uuid::Uuid,
+        granularity: Duration,
+    ) -> Result<Option<DateTime<Utc>>, TokenQueryError> {
+        // Frequent uses of an access token are written once per granularity
+        let last_used_at = sqlx::query_scalar::<_, DateTime<Utc>>(
+            r#"
+            UPDATE "access_token"
+            SET "last_used_at" = CURRENT_TIMESTAMP
+            WHERE "id" = $1 AND "last_used_at" <= CURRENT_TIMESTAMP - make_interval(secs => $2)
+            RETURNING "last_used_at"
+        "#,
+        )
+        .bind(token_id)
+        .bind(granularity.as_secs_f64())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!("failed to record the use of access token with ID: {token_id}"),
+                e,
+            )
+        })?;
+
+        Ok(last_used_at)
+    }
+
+    async fn list_tokens(
+        &self,
+        account_id: uuid::Uuid,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<AccessToken>, TokenQueryError> {
+        let access_tokens = sqlx::query_as::<_, AccessToken>(
+            r#"
+            SELECT
+                id,
+                account_id,
+                name,
+                mac,
+                created_at,
+                updated_at,
+                last_used_at,
+                expires_at,
+                revoked_at,
+                authenticated_at
+            FROM "access_token"
+            WHERE "account_id" = $1
+            ORDER BY "created_at" DESC, "id" DESC
+            LIMIT $2
+            OFFSET $3
+        "#,
+        )
+        .bind(account_id)
+        .bind(i64::from(limit))
+        .bind(i64::from(offset))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!("failed to list access tokens of account with ID: {account_id}"),
+                e,
+            )
+        })?;
+
+        Ok(access_tokens)
+    }
+
+    async fn create_token_with_refresh_token(
+        &self,
+        req: &CreateAccessTokenRequest,
+        refresh_req: &CreateRefreshTokenRequest,
+        max_active_token: u8,
+    ) -> Result<(AccessToken, RefreshToken), CreateAccessTokenError> {
+        let mut transaction = begin_transaction(&self.pool).await?;
+        let access_token = self
+            .create_token_in_transaction(&mut transaction, req, max_active_token)
+            .await?;
+        let refresh_token = insert_refresh_token(
+            &mut transaction,
+            refresh_req,
+            access_token.id,
+            access_token.authenticated_at,
+        )
+        .await?;
+        commit_transaction(transaction).await?;
+
+        Ok((access_token, refresh_token))
+    }
+
+    async fn find_refresh_token_by_mac(
+        &self,
+        mac: &[u8; 32],
+    ) -> Result<RefreshToken, TokenQueryError> {
+        let refresh_token = sqlx::query_as::<_, RefreshToken>(
+            r#"
+            SELECT
+                id,
+                account_id,
+                family_id,
+                access_token_id,
+                mac,
+                authenticated_at,
+                created_at,
+                updated_at,
+                expires_at,
+                rotated_at,
                 revoked_at
+            FROM "refresh_token"
+            WHERE "mac" = $1
         "#,
         )
-        .bind(req.account_id)
-        .bind(&req.name)
-        .bind(req.mac)
-        .bind(req.expires_at)
-        .fetch_one(&mut *transaction)
+        .bind(mac)
+        .fetch_one(&self.pool)
         .await
-        .map_err(|e| anyhow!(e).context("failed to insert access token"))?;
+        .map_err(|e| map_sqlx_error("failed query for refresh token by MAC", e))?;
 
-        transaction
-            .commit()
-            .await
-            .map_err(|e| anyhow!(e).context("failed to commit transaction"))?;
+        Ok(refresh_token)
+    }
 
-        Ok(access_token)
+    async fn rotate_refresh_token(
+        &self,
+        previous: &RefreshToken,
+        req: &CreateAccessTokenRequest,
+        refresh_req: &CreateRefreshTokenRequest,
+        max_active_token: u8,
+    ) -> Result<(AccessToken, RefreshToken), RefreshAccessTokenError> {
+        let mut transaction = begin_transaction(&self.pool).await?;
+
+        // Only one of concurrent rotations of the same refresh token wins, the others are reuses
+        let rotated = sqlx::query_scalar::<_, uuid::Uuid>(
+            r#"
+            UPDATE "refresh_token"
+            SET "rotated_at" = CURRENT_TIMESTAMP
+            WHERE "id" = $1 AND "rotated_at" IS NULL AND "revoked_at" IS NULL
+            RETURNING "id"
+        "#,
+        )
+        .bind(previous.id)
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!("failed to rotate refresh token with ID: {}", previous.id),
+                e,
+            )
+        })?;
+        if rotated.is_none() {
+            return Err(RefreshAccessTokenError::RefreshTokenReused {
+                family_id: previous.family_id,
+            });
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE "access_token"
+            SET "revoked_at" = CURRENT_TIMESTAMP
+            WHERE "id" = $1 AND "revoked_at" IS NULL
+        "#,
+        )
+        .bind(previous.access_token_id)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed to revoke access token with ID: {}",
+                    previous.access_token_id
+                ),
+                e,
+            )
+        })?;
+
+        let access_token = self
+            .create_token_in_transaction(&mut transaction, req, max_active_token)
+            .await?;
+        let refresh_token = insert_refresh_token(
+            &mut transaction,
+            refresh_req,
+            access_token.id,
+            previous.authenticated_at,
+        )
+        .await?;
+        commit_transaction(transaction).await?;
+
+        Ok((access_token, refresh_token))
+    }
+
+    async fn revoke_refresh_token_family(
+        &self,
+        family_id: uuid::Uuid,
+    ) -> Result<(), TokenQueryError> {
+        let mut transaction = begin_transaction(&self.pool).await?;
+
+        let access_token_ids = sqlx::query_scalar::<_, uuid::Uuid>(
+            r#"
+            UPDATE "refresh_token"
+            SET "revoked_at" = COALESCE("revoked_at", CURRENT_TIMESTAMP)
+            WHERE "family_id" = $1
+            RETURNING "access_token_id"
+        "#,
+        )
+        .bind(family_id)
+        .fetch_all(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!("failed to revoke refresh token family with ID: {family_id}"),
+                e,
+            )
+        })?;
+
+        sqlx::query(
+            r#"
+            UPDATE "access_token"
+            SET "revoked_at" = CURRENT_TIMESTAMP
+            WHERE "id" = ANY($1) AND "revoked_at" IS NULL
+        "#,
+        )
+        .bind(access_token_ids)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!(
+                    "failed to revoke access tokens of refresh token family with ID: {family_id}"
+                ),
+                e,
+            )
+        })?;
+        commit_transaction(transaction).await?;
+
+        Ok(())
+    }
+
+    async fn revoke_account_tokens_in_transaction(
+        &self,
+        transaction: &mut DatabaseTransaction,
+        account_id: uuid::Uuid,
+    ) -> Result<u64, TokenQueryError> {
+        sqlx::query(
+            r#"
+            UPDATE "refresh_token"
+            SET "revoked_at" = CURRENT_TIMESTAMP
+            WHERE "account_id" = $1 AND "revoked_at" IS NULL
+        "#,
+        )
+        .bind(account_id)
+        .execute(&mut **transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!("failed to revoke refresh tokens of account with ID: {account_id}"),
+                e,
+            )
+        })?;
+
+        let revoked_tokens = sqlx::query(
+            r#"
+            UPDATE "access_token"
+            SET "revoked_at" = CURRENT_TIMESTAMP
+            WHERE "account_id" = $1 AND "revoked_at" IS NULL
+        "#,
+        )
+        .bind(account_id)
+        .execute(&mut **transaction)
+        .await
+        .map_err(|e| {
+            map_sqlx_error(
+                &format!("failed to revoke access tokens of account with ID: {account_id}"),
+                e,
+            )
+        })?
+        .rows_affected();
+
+        Ok(revoked_tokens)
+    }
+}
+
+/// Revoke the oldest active sessions of an account so that a new one fits in the limit, their refresh token families are revoked as well
+///
+/// # Arguments
+/// * `transaction` - transaction in which the new session is created,
+/// * `account_id` - ID of the account,
+/// * `max_active_sessions` - maximum number of active sessions, the new one included
+async fn evict_oldest_sessions(
+    transaction: &mut DatabaseTransaction,
+    account_id: uuid::Uuid,
+    max_active_sessions: u8,
+) -> Result<(), RepositoryError> {
+    sqlx::query(
+        r#"
+        WITH "evicted" AS (
+            UPDATE "access_token"
+            SET "revoked_at" = CURRENT_TIMESTAMP
+            WHERE "id" IN (
+                SELECT "id"
+                FROM "access_token"
+                WHERE "account_id" = $1
+                    AND "session"
+                    AND "revoked_at" IS NULL
+                    AND "expires_at" > CURRENT_TIMESTAMP
+                ORDER BY "created_at" DESC, "id" DESC
+                OFFSET $2
+            )
+            RETURNING "id"
+        )
+        UPDATE "refresh_token"
+        SET "revoked_at" = COALESCE("revoked_at", CURRENT_TIMESTAMP)
+        WHERE "family_id" IN (
+            SELECT "family_id"
+            FROM "refresh_token"
+            WHERE "access_token_id" IN (SELECT "id" FROM "evicted")
+        )
+    "#,
+    )
+    .bind(account_id)
+    .bind(i64::from(max_active_sessions.saturating_sub(1)))
+    .execute(&mut **transaction)
+    .await
+    .map_err(|e| {
+        map_sqlx_error(
+            &format!("failed to evict the oldest sessions of account with ID: {account_id}"),
+            e,
+        )
+    })?;
+
+    Ok(())
+}
+
+/// Insert a refresh token issued along an access token
+///
+/// # Arguments
+/// * `transaction` - transaction in which the access token has been created,
+/// * `refresh_req` - DTO for create the refresh token,
+/// * `access_token_id` - ID of the access token issued along the refresh token,
+/// * `authenticated_at` - date of the login the family descends from
+async fn insert_refresh_token(
+    transaction: &mut DatabaseTransaction,
+    refresh_req: &CreateRefreshTokenRequest,
+    access_token_id: uuid::Uuid,
+    authenticated_at: DateTime<Utc>,
+) -> Result<RefreshToken, RepositoryError> {
+    sqlx::query_as::<_, RefreshToken>(
+        r#"
+        INSERT INTO "refresh_token" (
+            "account_id",
+            "family_id",
+            "access_token_id",
+            "mac",
+            "authenticated_at",
+            "expires_at"
+        ) VALUES (
+            $1,
+            $2,
+            $3,
+            $4,
+            $5,
+            $6
+        ) RETURNING
+            id,
+            account_id,
+            family_id,
+            access_token_id,
+            mac,
+            authenticated_at,
+            created_at,
+            updated_at,
+            expires_at,
+            rotated_at,
+            revoked_at
+    "#,
+    )
+    .bind(refresh_req.account_id)
+    .bind(refresh_req.family_id)
+    .bind(access_token_id)
+    .bind(refresh_req.mac)
+    .bind(authenticated_at)
+    .bind(refresh_req.expires_at)
+    .fetch_one(&mut **transaction)
+    .await
+    .map_err(|e| map_sqlx_error("failed to insert refresh token", e))
+}
+
+impl From<RepositoryError> for CreateAccessTokenError {
+    fn from(value: RepositoryError) -> Self {
+        match value {
+            RepositoryError::UniqueViolation {
+                constraint: Some(constraint),
+                ..
+            } if constraint == "access_token_account_id_name_active_key" => {
+                CreateAccessTokenError::NameAlreadyExists
+            }
+            e => CreateAccessTokenError::Unknown(e.into()),
+        }
+    }
+}
+
+impl From<RepositoryError> for RefreshAccessTokenError {
+    fn from(value: RepositoryError) -> Self {
+        RefreshAccessTokenError::Unknown(value.into())
+    }
+}
+
+impl From<RepositoryError> for TokenQueryError {
+    fn from(value: RepositoryError) -> Self {
+        match value {
+            RepositoryError::NotFound { .. } => TokenQueryError::TokenNotFound,
+            e => TokenQueryError::Unknown(e.into()),
+        }
     }
 }
@@ -0,0 +1,92 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::anyhow;
+use axum::Router;
+use axum_server::{Handle, tls_rustls::RustlsConfig};
+use rustls::{
+    ServerConfig,
+    crypto::ring,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+};
+use tokio::net::TcpListener;
+
+/// Certificate chain and private key of the in-process TLS termination, see `TLS_CERT_PATH` and `TLS_KEY_PATH`
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    /// PEM file of the certificate chain, the leaf certificate first
+    pub cert_path: PathBuf,
+    /// PEM file of the private key
+    pub key_path: PathBuf,
+}
+
+/// Load the certificate chain and the private key of the TLS termination
+///
+/// The files are read once, an unreadable file, an invalid file or a private key not matching the certificate fails with an error naming the culprit.
+pub fn load_rustls_config(tls: &TlsConfig) -> Result<RustlsConfig, anyhow::Error> {
+    let certs = CertificateDer::pem_file_iter(&tls.cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| {
+            anyhow!(e).context(format!(
+                "failed to load the TLS certificate chain from {}",
+                tls.cert_path.display()
+            ))
+        })?;
+    if certs.is_empty() {
+        return Err(anyhow!(
+            "no TLS certificate found in {}",
+            tls.cert_path.display()
+        ));
+    }
+    let key = PrivateKeyDer::from_pem_file(&tls.key_path).map_err(|e| {
+        anyhow!(e).context(format!(
+            "failed to load the TLS private key from {}",
+            tls.key_path.display()
+        ))
+    })?;
+
+    let mut server_config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| anyhow!(e).context("failed to select the TLS protocol versions"))?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| {
+            anyhow!(e).context("the TLS private key does not match the certificate chain")
+        })?;
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(RustlsConfig::from_config(Arc::new(server_config)))
+}
+
+/// Serve the routes until the shutdown future completes, over HTTPS if a TLS configuration is given and over plain HTTP otherwise
+///
+/// # Arguments
+/// * `listener` - bound TCP listener,
+/// * `app` - routes to serve,
+/// * `tls` - TLS configuration, see [load_rustls_config],
+/// * `shutdown` - future completing once the server must gracefully shut down
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    tls: Option<RustlsConfig>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), anyhow::Error> {
+    let Some(tls) = tls else {
+        return axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown)
+            .await
+            .map_err(|e| anyhow!(e));
+    };
+
+    let handle = Handle::new();
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            shutdown.await;
+            handle.graceful_shutdown(None);
+        }
+    });
+    axum_server::from_tcp_rustls(listener.into_std()?, tls)
+        .handle(handle)
+        .serve(app.into_make_service())
+        .await
+        .map_err(|e| anyhow!(e))
+}
@@ -1,11 +1,62 @@
-use super::newtypes;
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+mod templates;
+pub use templates::{CODE_PLACEHOLDER, EMAIL_PLACEHOLDER, EmailTemplates, RenderedEmail};
+
+use super::newtypes::{self, Opaque};
+use crate::observability::{
+    EMAIL_SEND_COUNTER, EMAIL_SEND_DURATION_HISTOGRAM, record_duration, record_outcome,
+};
+use anyhow::anyhow;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{Mailbox, MultiPart},
+    transport::smtp::authentication::Credentials,
+};
+use serde::{Deserialize, Serialize};
+use sha3::Sha3_256;
+use thiserror::Error;
 use tracing::warn;
 
 #[async_trait]
 pub trait MailingService: Send + Sync {
-    async fn send_email(&self, email: &newtypes::Email, content: &str)
-    -> Result<(), anyhow::Error>;
+    /// Send the email carrying the verification code of an email, the services rendering emails use [EmailTemplates]
+    async fn send_verification_email(
+        &self,
+        email: &newtypes::Email,
+        code: &str,
+    ) -> Result<(), anyhow::Error>;
+
+    /// Send the email carrying the password reset code of an account, see `POST /accounts/request-password-reset`
+    async fn send_password_reset_email(
+        &self,
+        email: &newtypes::Email,
+        code: &str,
+    ) -> Result<(), anyhow::Error>;
+
+    /// Send an email with the given content
+    ///
+    /// The content is the verification code, the email is sent as a verification email.
+    async fn send_email(
+        &self,
+        email: &newtypes::Email,
+        content: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.send_verification_email(email, content).await
+    }
+
+    /// Check that the mailing provider can be reached, used by `GET /health/deep`
+    ///
+    /// Services without probe are considered healthy.
+    async fn health_check(&self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -13,12 +64,614 @@ pub struct ToBeImplementedMailingService;
 
 #[async_trait]
 impl MailingService for ToBeImplementedMailingService {
+    async fn send_verification_email(
+        &self,
+        email: &newtypes::Email,
+        _code: &str,
+    ) -> Result<(), anyhow::Error> {
+        warn!("no mailing service is configured, email to \"{email}\" has not been sent");
+        Ok(())
+    }
+
+    async fn send_password_reset_email(
+        &self,
+        email: &newtypes::Email,
+        _code: &str,
+    ) -> Result<(), anyhow::Error> {
+        warn!("no mailing service is configured, email to \"{email}\" has not been sent");
+        Ok(())
+    }
+}
+
+/// Mailing service recording the outcome and the duration of the emails sent by the wrapped service, see [EMAIL_SEND_COUNTER] and [EMAIL_SEND_DURATION_HISTOGRAM]
+#[derive(Debug, Clone)]
+pub struct InstrumentedMailingService<M> {
+    inner: M,
+}
+
+impl<M: MailingService> From<M> for InstrumentedMailingService<M> {
+    fn from(inner: M) -> Self {
+        InstrumentedMailingService { inner }
+    }
+}
+
+impl<M: MailingService> InstrumentedMailingService<M> {
+    /// Record the outcome and the duration of a send of the wrapped service
+    async fn instrument(
+        &self,
+        send: impl Future<Output = Result<(), anyhow::Error>> + Send,
+    ) -> Result<(), anyhow::Error> {
+        let started_at = Instant::now();
+        let result = send.await;
+        record_duration(EMAIL_SEND_DURATION_HISTOGRAM, &result, started_at.elapsed());
+        record_outcome(EMAIL_SEND_COUNTER, &result);
+        result
+    }
+}
+
+#[async_trait]
+impl<M: MailingService> MailingService for InstrumentedMailingService<M> {
+    async fn send_verification_email(
+        &self,
+        email: &newtypes::Email,
+        code: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.instrument(self.inner.send_verification_email(email, code))
+            .await
+    }
+
+    async fn send_password_reset_email(
+        &self,
+        email: &newtypes::Email,
+        code: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.instrument(self.inner.send_password_reset_email(email, code))
+            .await
+    }
+
+    async fn health_check(&self) -> Result<(), anyhow::Error> {
+        self.inner.health_check().await
+    }
+}
+
+/// Error of an email which would fail again if retried, e.g. an invalid recipient, see [RetryingMailingService]
+///
+/// Mailing services wrap their permanent errors in it, the other errors are considered transient.
+#[derive(Debug, Error)]
+#[error("permanent email failure")]
+pub struct PermanentEmailError(#[source] pub anyhow::Error);
+
+impl PermanentEmailError {
+    /// Whether an error, or one of its causes, is permanent
+    pub fn is_permanent(error: &anyhow::Error) -> bool {
+        error.chain().any(|cause| cause.is::<PermanentEmailError>())
+    }
+}
+
+/// Default number of retries of a failed email, see `EMAIL_MAX_RETRIES`
+pub const DEFAULT_EMAIL_MAX_RETRIES: u32 = 2;
+/// Default delay before the first retry of a failed email, it doubles at each retry, see `EMAIL_RETRY_BASE_MS`
+pub const DEFAULT_EMAIL_RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
+
+/// Retries of the emails failing with a transient error
+#[derive(Clone, Debug)]
+pub struct EmailRetryPolicy {
+    /// Maximum number of retries after the first attempt, zero disables the retries
+    pub max_retries: u32,
+    /// Delay before the first retry, it doubles at each retry and is jittered down to its half
+    pub base_delay: Duration,
+    /// Maximum duration of a send, retries included, it is kept below the timeout of the requests
+    pub budget: Duration,
+}
+
+/// Mailing service retrying the emails of the wrapped service failing with a transient error, with an exponential backoff
+///
+/// Permanent errors are not retried, see [PermanentEmailError]. An attempt is cut short once the budget of the send is spent.
+#[derive(Debug, Clone)]
+pub struct RetryingMailingService<M> {
+    inner: M,
+    policy: EmailRetryPolicy,
+}
+
+impl<M: MailingService> RetryingMailingService<M> {
+    pub fn new(inner: M, policy: EmailRetryPolicy) -> Self {
+        RetryingMailingService { inner, policy }
+    }
+
+    /// Delay before the given retry, the first retry being `0`
+    fn retry_delay(&self, retry: u32) -> Duration {
+        let delay = self
+            .policy
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry));
+        // The jitter spreads the retries of concurrent sends failing together
+        delay.mul_f64(rand::random_range(0.5..=1.0))
+    }
+
+    /// Attempt a send of the wrapped service until it succeeds, fails permanently or runs out of retries or time
+    async fn retry<F, Fut>(&self, email: &newtypes::Email, send: F) -> Result<(), anyhow::Error>
+    where
+        F: Fn() -> Fut + Send + Sync,
+        Fut: Future<Output = Result<(), anyhow::Error>> + Send,
+    {
+        let started_at = Instant::now();
+        let mut retry = 0;
+        loop {
+            let remaining = self.policy.budget.saturating_sub(started_at.elapsed());
+            let error = match tokio::time::timeout(remaining, send()).await {
+                Ok(Ok(())) => return Ok(()),
+                Ok(Err(e)) => e,
+                Err(_) => anyhow!(
+                    "email to \"{email}\" timed out after {:?}",
+                    started_at.elapsed()
+                ),
+            };
+            if PermanentEmailError::is_permanent(&error) || retry >= self.policy.max_retries {
+                return Err(error);
+            }
+            let delay = self.retry_delay(retry);
+            if started_at.elapsed() + delay >= self.policy.budget {
+                return Err(error.context("no time left to retry the email"));
+            }
+            retry += 1;
+            warn!(
+                "retrying email to \"{email}\" in {delay:?} ({retry}/{}): {error:#}",
+                self.policy.max_retries
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[async_trait]
+impl<M: MailingService> MailingService for RetryingMailingService<M> {
+    async fn send_verification_email(
+        &self,
+        email: &newtypes::Email,
+        code: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.retry(email, || self.inner.send_verification_email(email, code))
+            .await
+    }
+
+    async fn send_password_reset_email(
+        &self,
+        email: &newtypes::Email,
+        code: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.retry(email, || self.inner.send_password_reset_email(email, code))
+            .await
+    }
+
+    async fn health_check(&self) -> Result<(), anyhow::Error> {
+        self.inner.health_check().await
+    }
+}
+
+/// Port of the SMTP relay if `SMTP_PORT` is not set, the submission port using STARTTLS
+pub const DEFAULT_SMTP_PORT: u16 = 587;
+/// Port of the SMTP relays using implicit TLS, the other ports use STARTTLS
+pub const SMTP_IMPLICIT_TLS_PORT: u16 = 465;
+/// Timeout of an SMTP exchange, from the connection to the end of the sending, it is below the timeout of the requests
+///
+/// The timeout of the transport only bounds the connection and the commands, a relay which never sends its greeting is bounded by this one.
+const SMTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// SMTP relay through which the emails are sent, see `SMTP_HOST`
+#[derive(Clone, Debug)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    /// Credentials of the relay, the relay is used without authentication if absent
+    pub credentials: Option<(String, Opaque<String>)>,
+    /// Sender of the emails
+    pub from: Mailbox,
+    /// Directory of the templates of the emails, the built-in templates are used if absent, see [EmailTemplates]
+    pub templates_dir: Option<PathBuf>,
+}
+
+/// Mailing service sending the emails through an SMTP relay, the connection is secured with TLS, see [SMTP_IMPLICIT_TLS_PORT]
+#[derive(Clone)]
+pub struct SmtpMailingService {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    templates: EmailTemplates,
+}
+
+impl TryFrom<&SmtpConfig> for SmtpMailingService {
+    type Error = anyhow::Error;
+
+    fn try_from(config: &SmtpConfig) -> Result<Self, Self::Error> {
+        let builder = if config.port == SMTP_IMPLICIT_TLS_PORT {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+        }
+        .map_err(|e| anyhow!(e).context(format!("invalid SMTP host: {}", config.host)))?
+        .port(config.port)
+        .timeout(Some(SMTP_TIMEOUT));
+        let builder = match &config.credentials {
+            Some((username, password)) => builder.credentials(Credentials::new(
+                username.clone(),
+                password.extract_inner().clone(),
+            )),
+            None => builder,
+        };
+        let templates = match &config.templates_dir {
+            Some(directory) => EmailTemplates::load(directory)?,
+            None => EmailTemplates::default(),
+        };
+        Ok(SmtpMailingService {
+            transport: builder.build(),
+            from: config.from.clone(),
+            templates,
+        })
+    }
+}
+
+impl SmtpMailingService {
+    /// Send a rendered email through the relay as a multipart email with a plaintext and an HTML alternative
+    async fn send_rendered(
+        &self,
+        email: &newtypes::Email,
+        rendered: RenderedEmail,
+    ) -> Result<(), anyhow::Error> {
+        let to = email.as_str().parse::<Mailbox>().map_err(|e| {
+            anyhow!(PermanentEmailError(
+                anyhow!(e).context(format!("invalid recipient: {email}"))
+            ))
+        })?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(rendered.subject)
+            .multipart(MultiPart::alternative_plain_html(
+                rendered.text,
+                rendered.html,
+            ))
+            .map_err(|e| {
+                anyhow!(PermanentEmailError(
+                    anyhow!(e).context("failed to build email")
+                ))
+            })?;
+        tokio::time::timeout(SMTP_TIMEOUT, self.transport.send(message))
+            .await
+            .map_err(|_| anyhow!("timed out after {SMTP_TIMEOUT:?}"))
+            .and_then(|result| {
+                // Rejections of the relay with a 5xx code would be rejected again, e.g. an unknown mailbox
+                result.map_err(|e| {
+                    if e.is_permanent() {
+                        anyhow!(PermanentEmailError(anyhow!(e)))
+                    } else {
+                        anyhow!(e)
+                    }
+                })
+            })
+            .map_err(|e| {
+                e.context(format!(
+                    "failed to send email to \"{email}\" through the SMTP relay"
+                ))
+            })?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MailingService for SmtpMailingService {
+    async fn send_verification_email(
+        &self,
+        email: &newtypes::Email,
+        code: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.send_rendered(email, self.templates.render_verification(email, code))
+            .await
+    }
+
+    async fn send_password_reset_email(
+        &self,
+        email: &newtypes::Email,
+        code: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.send_rendered(email, self.templates.render_password_reset(email, code))
+            .await
+    }
+
+    async fn health_check(&self) -> Result<(), anyhow::Error> {
+        let reachable = tokio::time::timeout(SMTP_TIMEOUT, self.transport.test_connection())
+            .await
+            .map_err(|_| anyhow!("timed out after {SMTP_TIMEOUT:?}"))
+            .and_then(|result| result.map_err(|e| anyhow!(e)))
+            .map_err(|e| e.context("failed to connect to the SMTP relay"))?;
+        if !reachable {
+            return Err(anyhow!("SMTP relay is not ready"));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<M: MailingService + ?Sized> MailingService for Box<M> {
+    async fn send_verification_email(
+        &self,
+        email: &newtypes::Email,
+        code: &str,
+    ) -> Result<(), anyhow::Error> {
+        (**self).send_verification_email(email, code).await
+    }
+
+    async fn send_password_reset_email(
+        &self,
+        email: &newtypes::Email,
+        code: &str,
+    ) -> Result<(), anyhow::Error> {
+        (**self).send_password_reset_email(email, code).await
+    }
+
     async fn send_email(
         &self,
-        _email: &newtypes::Email,
+        email: &newtypes::Email,
         content: &str,
     ) -> Result<(), anyhow::Error> {
-        warn!("THIS LOG IS MEANT TO BE DELETED IN THE FUTURE -- Email content is {content}");
+        (**self).send_email(email, content).await
+    }
+
+    async fn health_check(&self) -> Result<(), anyhow::Error> {
+        (**self).health_check().await
+    }
+}
+
+#[async_trait]
+pub trait SmsService: Send + Sync {
+    async fn send_sms(&self, phone_number: &str, content: &str) -> Result<(), anyhow::Error>;
+
+    /// Check that the SMS provider can be reached, used by `GET /health/deep`
+    ///
+    /// Services without probe are considered healthy.
+    async fn health_check(&self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ToBeImplementedSmsService;
+
+#[async_trait]
+impl SmsService for ToBeImplementedSmsService {
+    async fn send_sms(&self, _phone_number: &str, _content: &str) -> Result<(), anyhow::Error> {
+        warn!("no SMS service is configured, SMS has not been sent");
+        Ok(())
+    }
+}
+
+/// Header carrying the hex encoded HMAC-SHA3-256 of `{timestamp}.{body}` of a webhook, computed with the webhook secret, see [sign_webhook_body]
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "x-soko-signature";
+/// Header carrying the signed Unix timestamp in seconds of a webhook delivery, receivers reject the old deliveries so that a captured delivery can not be replayed
+pub const WEBHOOK_TIMESTAMP_HEADER: &str = "x-soko-timestamp";
+/// Timeout of a webhook delivery, the delivery is best-effort and is not retried
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Destination of the webhook notifying the verified accounts, see `VERIFICATION_WEBHOOK_URL`
+#[derive(Clone, Debug)]
+pub struct WebhookConfig {
+    pub url: reqwest::Url,
+    /// Secret signing the deliveries, see [WEBHOOK_SIGNATURE_HEADER]
+    pub secret: Opaque<String>,
+}
+
+/// Event sent once an account has been verified, it is only sent on the transition to verified
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountVerifiedEvent {
+    pub account_id: uuid::Uuid,
+    pub email: newtypes::Email,
+    pub verified_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait WebhookNotifier: Send + Sync {
+    async fn notify_account_verified(
+        &self,
+        event: &AccountVerifiedEvent,
+    ) -> Result<(), anyhow::Error>;
+}
+
+/// Webhook notifier posting the events as JSON to the configured URL, no event is sent if no webhook is configured
+#[derive(Debug, Clone)]
+pub struct HttpWebhookNotifier {
+    client: reqwest::Client,
+    webhook: Option<WebhookConfig>,
+}
+
+impl From<Option<WebhookConfig>> for HttpWebhookNotifier {
+    fn from(webhook: Option<WebhookConfig>) -> Self {
+        HttpWebhookNotifier {
+            client: reqwest::Client::new(),
+            webhook,
+        }
+    }
+}
+
+#[async_trait]
+impl WebhookNotifier for HttpWebhookNotifier {
+    async fn notify_account_verified(
+        &self,
+        event: &AccountVerifiedEvent,
+    ) -> Result<(), anyhow::Error> {
+        let Some(webhook) = &self.webhook else {
+            return Ok(());
+        };
+        let body = serde_json::to_vec(event)?;
+        let timestamp = Utc::now().timestamp();
+        let signature = sign_webhook_body(timestamp, &body, &webhook.secret)?;
+        self.client
+            .post(webhook.url.clone())
+            .timeout(WEBHOOK_TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(WEBHOOK_TIMESTAMP_HEADER, timestamp)
+            .header(WEBHOOK_SIGNATURE_HEADER, signature)
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
         Ok(())
     }
 }
+
+/// Compute the hex encoded HMAC-SHA3-256 of `{timestamp}.{body}` of a webhook, the timestamp is signed along the body so that it can not be replaced
+///
+/// # Arguments
+/// * `timestamp` - Unix timestamp in seconds of the delivery, see [WEBHOOK_TIMESTAMP_HEADER],
+/// * `body` - serialized body of the webhook,
+/// * `secret` - secret of the webhook
+pub fn sign_webhook_body(
+    timestamp: i64,
+    body: &[u8],
+    secret: &Opaque<String>,
+) -> Result<String, anyhow::Error> {
+    let mut hmac = Hmac::<Sha3_256>::new_from_slice(secret.extract_inner().as_bytes())
+        .map_err(|e| anyhow!(e).context("failed to initialize hmac"))?;
+    hmac.update(format!("{timestamp}.").as_bytes());
+    hmac.update(body);
+    Ok(hmac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    /// Mailing service failing a fixed number of times before succeeding
+    struct FlakyMailingService {
+        failures: u32,
+        permanent: bool,
+        attempts: AtomicU32,
+    }
+
+    impl FlakyMailingService {
+        fn new(failures: u32, permanent: bool) -> Self {
+            FlakyMailingService {
+                failures,
+                permanent,
+                attempts: AtomicU32::new(0),
+            }
+        }
+
+        fn attempts(&self) -> u32 {
+            self.attempts.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl MailingService for &FlakyMailingService {
+        async fn send_verification_email(
+            &self,
+            _email: &newtypes::Email,
+            _code: &str,
+        ) -> Result<(), anyhow::Error> {
+            let attempt = self.attempts.fetch_add(1, Ordering::SeqCst);
+            if attempt >= self.failures {
+                return Ok(());
+            }
+            let error = anyhow!("failure {attempt}");
+            if self.permanent {
+                return Err(anyhow!(PermanentEmailError(error)));
+            }
+            Err(error)
+        }
+
+        async fn send_password_reset_email(
+            &self,
+            email: &newtypes::Email,
+            code: &str,
+        ) -> Result<(), anyhow::Error> {
+            self.send_verification_email(email, code).await
+        }
+    }
+
+    fn policy(max_retries: u32) -> EmailRetryPolicy {
+        EmailRetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(1),
+            budget: Duration::from_secs(5),
+        }
+    }
+
+    fn email() -> newtypes::Email {
+        newtypes::Email::new("jane@example.com").unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_the_email_is_sent() {
+        let inner = FlakyMailingService::new(2, false);
+        let service = RetryingMailingService::new(&inner, policy(2));
+
+        service
+            .send_verification_email(&email(), "code")
+            .await
+            .unwrap();
+        assert_eq!(inner.attempts(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_give_up_after_the_maximum_number_of_retries() {
+        let inner = FlakyMailingService::new(3, false);
+        let service = RetryingMailingService::new(&inner, policy(2));
+
+        let err = service
+            .send_verification_email(&email(), "code")
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "failure 2");
+        assert_eq!(inner.attempts(), 3);
+
+        let inner = FlakyMailingService::new(1, false);
+        let service = RetryingMailingService::new(&inner, policy(0));
+        assert!(
+            service
+                .send_verification_email(&email(), "code")
+                .await
+                .is_err()
+        );
+        assert_eq!(inner.attempts(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_permanent_errors_are_not_retried() {
+        let inner = FlakyMailingService::new(1, true);
+        let service = RetryingMailingService::new(&inner, policy(2));
+
+        let err = service
+            .send_verification_email(&email(), "code")
+            .await
+            .unwrap_err();
+        assert!(PermanentEmailError::is_permanent(&err), "{err:#}");
+        assert_eq!(inner.attempts(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retries_stop_once_the_budget_is_spent() {
+        let inner = FlakyMailingService::new(5, false);
+        let service = RetryingMailingService::new(
+            &inner,
+            EmailRetryPolicy {
+                max_retries: 5,
+                base_delay: Duration::from_millis(40),
+                budget: Duration::from_millis(100),
+            },
+        );
+
+        let err = service
+            .send_verification_email(&email(), "code")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no time left"), "{err:#}");
+        // The third retry would wait at least 80ms after at least 60ms of backoff
+        assert!(inner.attempts() <= 3, "{}", inner.attempts());
+    }
+}
@@ -0,0 +1,288 @@
+use std::path::Path;
+
+use anyhow::anyhow;
+
+use crate::newtypes;
+
+/// Placeholder replaced by the verification or password reset code in the templates
+pub const CODE_PLACEHOLDER: &str = "{{ code }}";
+/// Placeholder replaced by the email of the recipient in the templates
+pub const EMAIL_PLACEHOLDER: &str = "{{ email }}";
+
+/// File of the subject of the verification email in the templates directory, only its first line is used
+const VERIFICATION_SUBJECT_FILE: &str = "verification_subject.txt";
+/// File of the plaintext body of the verification email in the templates directory
+const VERIFICATION_TEXT_FILE: &str = "verification.txt";
+/// File of the HTML body of the verification email in the templates directory
+const VERIFICATION_HTML_FILE: &str = "verification.html";
+/// File of the subject of the password reset email in the templates directory, only its first line is used
+const PASSWORD_RESET_SUBJECT_FILE: &str = "password_reset_subject.txt";
+/// File of the plaintext body of the password reset email in the templates directory
+const PASSWORD_RESET_TEXT_FILE: &str = "password_reset.txt";
+/// File of the HTML body of the password reset email in the templates directory
+const PASSWORD_RESET_HTML_FILE: &str = "password_reset.html";
+
+/// Email rendered from a template, sent as a multipart email with a plaintext and an HTML alternative
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+/// Subject and bodies of the template of an email
+#[derive(Clone, Debug)]
+struct EmailTemplate {
+    subject: String,
+    text: String,
+    html: String,
+}
+
+impl EmailTemplate {
+    /// Replace the placeholders of the template by their values
+    fn render(&self, email: &newtypes::Email, code: &str) -> RenderedEmail {
+        let render = |template: &str, email: &str, code: &str| {
+            template
+                .replace(EMAIL_PLACEHOLDER, email)
+                .replace(CODE_PLACEHOLDER, code)
+        };
+        RenderedEmail {
+            subject: render(
+                self.subject.lines().next().unwrap_or_default(),
+                email.as_str(),
+                code,
+            )
+            .trim()
+            .to_string(),
+            text: render(&self.text, email.as_str(), code),
+            html: render(&self.html, &escape_html(email.as_str()), &escape_html(code)),
+        }
+    }
+}
+
+/// Templates of the emails, see `EMAIL_TEMPLATES_DIR`
+///
+/// The `{{ code }}` and `{{ email }}` placeholders are replaced by their values, the values are escaped in the HTML body.
+#[derive(Clone, Debug)]
+pub struct EmailTemplates {
+    verification: EmailTemplate,
+    password_reset: EmailTemplate,
+}
+
+impl Default for EmailTemplates {
+    /// Built-in templates, see the `templates` directory next to this module
+    fn default() -> Self {
+        EmailTemplates {
+            verification: EmailTemplate {
+                subject: include_str!("templates/verification_subject.txt").to_string(),
+                text: include_str!("templates/verification.txt").to_string(),
+                html: include_str!("templates/verification.html").to_string(),
+            },
+            password_reset: EmailTemplate {
+                subject: include_str!("templates/password_reset_subject.txt").to_string(),
+                text: include_str!("templates/password_reset.txt").to_string(),
+                html: include_str!("templates/password_reset.html").to_string(),
+            },
+        }
+    }
+}
+
+impl EmailTemplates {
+    /// Load the templates from a directory, the directory must contain the files of every template
+    ///
+    /// The bodies must contain the `{{ code }}` placeholder, the recipient would not receive the code otherwise.
+    pub fn load(directory: &Path) -> Result<Self, anyhow::Error> {
+        let read = |file: &str| {
+            let path = directory.join(file);
+            std::fs::read_to_string(&path).map_err(|e| {
+                anyhow!(e).context(format!(
+                    "failed to load the email template {}",
+                    path.display()
+                ))
+            })
+        };
+        let templates = EmailTemplates {
+            verification: EmailTemplate {
+                subject: read(VERIFICATION_SUBJECT_FILE)?,
+                text: read(VERIFICATION_TEXT_FILE)?,
+                html: read(VERIFICATION_HTML_FILE)?,
+            },
+            password_reset: EmailTemplate {
+                subject: read(PASSWORD_RESET_SUBJECT_FILE)?,
+                text: read(PASSWORD_RESET_TEXT_FILE)?,
+                html: read(PASSWORD_RESET_HTML_FILE)?,
+            },
+        };
+        for (file, body) in [
+            (VERIFICATION_TEXT_FILE, &templates.verification.text),
+            (VERIFICATION_HTML_FILE, &templates.verification.html),
+            (PASSWORD_RESET_TEXT_FILE, &templates.password_reset.text),
+            (PASSWORD_RESET_HTML_FILE, &templates.password_reset.html),
+        ] {
+            if !body.contains(CODE_PLACEHOLDER) {
+                return Err(anyhow!(
+                    "the email template {} does not contain the {CODE_PLACEHOLDER} placeholder",
+                    directory.join(file).display()
+                ));
+            }
+        }
+        Ok(templates)
+    }
+
+    /// Render the email carrying the verification code of an email
+    ///
+    /// # Arguments
+    /// * `email` - recipient of the email,
+    /// * `code` - verification code
+    pub fn render_verification(&self, email: &newtypes::Email, code: &str) -> RenderedEmail {
+        self.verification.render(email, code)
+    }
+
+    /// Render the email carrying the password reset code of an account
+    ///
+    /// # Arguments
+    /// * `email` - recipient of the email,
+    /// * `code` - password reset code
+    pub fn render_password_reset(&self, email: &newtypes::Email, code: &str) -> RenderedEmail {
+        self.password_reset.render(email, code)
+    }
+}
+
+/// Escape the characters with a meaning in HTML
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_verification_with_the_default_templates() {
+        let email = newtypes::Email::new("jane@example.com").unwrap();
+
+        let rendered = EmailTemplates::default().render_verification(&email, "secret-code");
+
+        assert_eq!(rendered.subject, "Verify your email");
+        assert!(rendered.text.contains("secret-code"), "{}", rendered.text);
+        assert!(
+            rendered.text.contains("jane@example.com"),
+            "{}",
+            rendered.text
+        );
+        assert!(
+            rendered.html.contains("<strong>secret-code</strong>"),
+            "{}",
+            rendered.html
+        );
+        assert!(
+            !rendered.html.contains(CODE_PLACEHOLDER),
+            "{}",
+            rendered.html
+        );
+    }
+
+    #[test]
+    fn test_render_password_reset_with_the_default_templates() {
+        let email = newtypes::Email::new("jane@example.com").unwrap();
+
+        let rendered = EmailTemplates::default().render_password_reset(&email, "secret-code");
+
+        assert_eq!(rendered.subject, "Reset your password");
+        assert!(rendered.text.contains("secret-code"), "{}", rendered.text);
+        assert!(
+            rendered.html.contains("<strong>secret-code</strong>"),
+            "{}",
+            rendered.html
+        );
+    }
+
+    #[test]
+    fn test_render_verification_escapes_the_html_body() {
+        let email = newtypes::Email::new("jane@example.com").unwrap();
+
+        let rendered = EmailTemplates::default().render_verification(&email, "<b>&code");
+
+        assert!(rendered.text.contains("<b>&code"), "{}", rendered.text);
+        assert!(
+            rendered.html.contains("&lt;b&gt;&amp;code"),
+            "{}",
+            rendered.html
+        );
+    }
+
+    #[test]
+    fn test_load_templates_from_a_directory() {
+        let directory =
+            std::env::temp_dir().join(format!("soko-templates-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(
+            directory.join(VERIFICATION_SUBJECT_FILE),
+            "Welcome {{ email }}\nignored line",
+        )
+        .unwrap();
+        std::fs::write(directory.join(VERIFICATION_TEXT_FILE), "Code: {{ code }}").unwrap();
+        std::fs::write(directory.join(VERIFICATION_HTML_FILE), "<p>No code</p>").unwrap();
+        std::fs::write(directory.join(PASSWORD_RESET_SUBJECT_FILE), "Reset").unwrap();
+        std::fs::write(
+            directory.join(PASSWORD_RESET_TEXT_FILE),
+            "Reset: {{ code }}",
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join(PASSWORD_RESET_HTML_FILE),
+            "<p>Reset: {{ code }}</p>",
+        )
+        .unwrap();
+
+        // The HTML body misses the code
+        let err = EmailTemplates::load(&directory).unwrap_err();
+        assert!(err.to_string().contains(VERIFICATION_HTML_FILE), "{err}");
+
+        std::fs::write(directory.join(VERIFICATION_HTML_FILE), "<p>{{ code }}</p>").unwrap();
+        let templates = EmailTemplates::load(&directory).unwrap();
+        let rendered = templates.render_verification(
+            &newtypes::Email::new("jane@example.com").unwrap(),
+            "secret-code",
+        );
+        assert_eq!(
+            rendered,
+            RenderedEmail {
+                subject: "Welcome jane@example.com".to_string(),
+                text: "Code: secret-code".to_string(),
+                html: "<p>secret-code</p>".to_string(),
+            }
+        );
+        assert_eq!(
+            templates
+                .render_password_reset(
+                    &newtypes::Email::new("jane@example.com").unwrap(),
+                    "secret-code",
+                )
+                .text,
+            "Reset: secret-code"
+        );
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_load_templates_from_an_incomplete_directory() {
+        let directory =
+            std::env::temp_dir().join(format!("soko-templates-{}", uuid::Uuid::new_v4()));
+
+        let err = EmailTemplates::load(&directory).unwrap_err();
+        assert!(err.to_string().contains(VERIFICATION_SUBJECT_FILE), "{err}");
+    }
+}
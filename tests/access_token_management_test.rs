@@ -3,7 +3,14 @@ use chrono::{DateTime, Utc};
 use fake::{Fake, Faker};
 use reqwest::StatusCode;
 use serde::Deserialize;
-use soko::routes::tokens::{MAX_LIFETIME, MAX_NAME_LENGTH};
+use serde_json::json;
+use soko::{
+    database::{connect_options, pool_options},
+    routes::{
+        DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+        tokens::{GENERATED_NAME_PREFIX, LAST_USE_GRANULARITY, MAX_LIFETIME, MAX_NAME_LENGTH},
+    },
+};
 
 mod common;
 
@@ -17,9 +24,32 @@ struct TestAccessTokenCreatedResponse {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub expires_at: DateTime<Utc>,
+    pub lifetime_secs: i64,
     pub revoked_at: Option<DateTime<Utc>>,
 }
 
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+#[serde(rename_all = "camelCase")]
+struct TestAccessTokenResponse {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub lifetime_secs: i64,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+#[serde(rename_all = "camelCase")]
+struct TestVerifyAccountResponse {
+    pub email: String,
+    pub access_token: Option<TestAccessTokenCreatedResponse>,
+}
+
 #[tokio::test]
 async fn test_access_token_creation() {
     let test_state = common::setup().await.unwrap();
@@ -72,6 +102,10 @@ async fn test_access_token_creation() {
     assert_eq!(json_response.name, create_access_token_body.name);
     assert!(!json_response.access_token.is_empty());
     assert!(json_response.revoked_at.is_none());
+    assert_eq!(
+        json_response.lifetime_secs,
+        i64::from(create_access_token_body.lifetime)
+    );
 }
 
 #[tokio::test]
@@ -137,3 +171,939 @@ async fn test_create_too_many_access_tokens() {
         .unwrap();
     assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 }
+
+#[tokio::test]
+async fn test_access_token_issued_at_email_verification() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let response = client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&json!({
+            "email": signup_body.email,
+            "secret": test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+            "issueToken": true,
+            "tokenName": "onboarding"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let access_token = response
+        .json::<TestVerifyAccountResponse>()
+        .await
+        .unwrap()
+        .access_token
+        .unwrap();
+    assert_eq!(access_token.name, "onboarding");
+    assert!(access_token.access_token.starts_with("soko__"));
+    assert!(access_token.revoked_at.is_none());
+
+    // The issued token is active, only two more tokens can be created
+    for _ in 0..2 {
+        let create_access_token_body = TestCreateAccessTokenBody {
+            email: signup_body.email.clone(),
+            password: signup_body.password.clone(),
+            name: (1..MAX_NAME_LENGTH).fake(),
+            lifetime: (1..MAX_LIFETIME).fake(),
+        };
+        let response = client
+            .post(format!("{}/tokens", &test_state.server_url))
+            .json(&create_access_token_body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+    let create_access_token_body = TestCreateAccessTokenBody {
+        email: signup_body.email.clone(),
+        password: signup_body.password.clone(),
+        name: (1..MAX_NAME_LENGTH).fake(),
+        lifetime: (1..MAX_LIFETIME).fake(),
+    };
+    let response = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&create_access_token_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+#[serde(rename_all = "camelCase")]
+struct TestVerifyAccessTokenResponse {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub expires_at: DateTime<Utc>,
+    pub expires_in_secs: i64,
+}
+
+#[tokio::test]
+async fn test_access_token_remaining_lifetime() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let lifetime = 3_600;
+    let access_token = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&TestCreateAccessTokenBody {
+            email: signup_body.email.clone(),
+            password: signup_body.password.clone(),
+            name: (1..MAX_NAME_LENGTH).fake(),
+            lifetime,
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<TestAccessTokenCreatedResponse>()
+        .await
+        .unwrap();
+
+    let response = client
+        .get(format!("{}/tokens/verify", &test_state.server_url))
+        .bearer_auth(&access_token.access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let first_verification = response
+        .json::<TestVerifyAccessTokenResponse>()
+        .await
+        .unwrap();
+    assert_eq!(first_verification.id, access_token.id);
+    assert!(first_verification.expires_in_secs > 0);
+    assert!(first_verification.expires_in_secs <= lifetime as i64);
+
+    tokio::time::sleep(std::time::Duration::from_millis(1_100)).await;
+
+    let second_verification = client
+        .get(format!("{}/tokens/verify", &test_state.server_url))
+        .bearer_auth(&access_token.access_token)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<TestVerifyAccessTokenResponse>()
+        .await
+        .unwrap();
+    assert!(second_verification.expires_in_secs > 0);
+    assert!(second_verification.expires_in_secs < first_verification.expires_in_secs);
+}
+
+#[tokio::test]
+async fn test_verify_unknown_access_token() {
+    let test_state = common::setup().await.unwrap();
+
+    let client = reqwest::Client::new();
+    let response = client
+        .get(format!("{}/tokens/verify", &test_state.server_url))
+        .bearer_auth("soko__unknown")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = client
+        .get(format!("{}/tokens/verify", &test_state.server_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_www_authenticate_challenges() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let (_, access_token) = common::signup_with_access_token(&test_state).await;
+    let client = reqwest::Client::new();
+    sqlx::query(r#"UPDATE "access_token" SET "revoked_at" = NOW() WHERE "id" = $1"#)
+        .bind(access_token.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let invalid_token_challenge = r#"Bearer error="invalid_token", error_description="The access token is malformed, unknown or revoked""#;
+    for (authorization, expected_challenge) in [
+        (None, "Bearer"),
+        (Some(String::new()), "Bearer"),
+        (Some("Basic dXNlcjpwYXNzd29yZA==".to_string()), "Bearer"),
+        (
+            Some("Bearer not-a-token".to_string()),
+            invalid_token_challenge,
+        ),
+        (
+            Some(format!("Bearer {}", access_token.access_token)),
+            invalid_token_challenge,
+        ),
+    ] {
+        let mut request = client.get(format!("{}/tokens/verify", &test_state.server_url));
+        if let Some(authorization) = &authorization {
+            request = request.header("authorization", authorization);
+        }
+        let response = request.send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers()["www-authenticate"],
+            expected_challenge,
+            "{authorization:?}"
+        );
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+#[serde(rename_all = "camelCase")]
+struct TestWhoamiResponse {
+    pub account_id: uuid::Uuid,
+    pub token_id: uuid::Uuid,
+    pub name: String,
+}
+
+#[tokio::test]
+async fn test_whoami() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let create_access_token_body = TestCreateAccessTokenBody {
+        email: signup_body.email.clone(),
+        password: signup_body.password.clone(),
+        name: (1..MAX_NAME_LENGTH).fake(),
+        lifetime: (1..MAX_LIFETIME).fake(),
+    };
+    let first_access_token = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&create_access_token_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<TestAccessTokenCreatedResponse>()
+        .await
+        .unwrap();
+    // Active access tokens of an account have distinct names
+    let second_access_token = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&TestCreateAccessTokenBody {
+            name: "second-access-token".to_string(),
+            ..create_access_token_body
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<TestAccessTokenCreatedResponse>()
+        .await
+        .unwrap();
+
+    let first_whoami = client
+        .post(format!("{}/tokens/whoami", &test_state.server_url))
+        .bearer_auth(&first_access_token.access_token)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<TestWhoamiResponse>()
+        .await
+        .unwrap();
+    assert_eq!(first_whoami.token_id, first_access_token.id);
+    assert_eq!(first_whoami.name, create_access_token_body.name);
+
+    let second_whoami = client
+        .post(format!("{}/tokens/whoami", &test_state.server_url))
+        .bearer_auth(&second_access_token.access_token)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<TestWhoamiResponse>()
+        .await
+        .unwrap();
+    assert_eq!(second_whoami.token_id, second_access_token.id);
+    // Both access tokens belong to the same account
+    assert_eq!(second_whoami.account_id, first_whoami.account_id);
+
+    let response = client
+        .post(format!("{}/tokens/whoami", &test_state.server_url))
+        .bearer_auth(format!("{}x", first_access_token.access_token))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_access_token_creation_with_stringified_lifetime() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    for (lifetime, expected_status) in [
+        (json!(3600), StatusCode::CREATED),
+        (json!("3600"), StatusCode::CREATED),
+        (json!("one hour"), StatusCode::BAD_REQUEST),
+        (json!(-1), StatusCode::BAD_REQUEST),
+        // Range validation still applies to numeric strings
+        (json!("0"), StatusCode::BAD_REQUEST),
+    ] {
+        let response = client
+            .post(format!("{}/tokens", &test_state.server_url))
+            .json(&json!({
+                "email": signup_body.email,
+                "password": signup_body.password,
+                // Active access tokens of an account have distinct names
+                "name": format!("my-token-{lifetime}"),
+                "lifetime": lifetime,
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), expected_status, "lifetime {lifetime}");
+    }
+}
+
+#[tokio::test]
+async fn test_access_token_creation_at_maximum_lifetime() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let json_response = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&TestCreateAccessTokenBody {
+            email: signup_body.email.clone(),
+            password: signup_body.password.clone(),
+            name: "my-token".to_string(),
+            lifetime: MAX_LIFETIME,
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<TestAccessTokenCreatedResponse>()
+        .await
+        .unwrap();
+
+    assert_eq!(json_response.lifetime_secs, i64::from(MAX_LIFETIME));
+}
+
+#[tokio::test]
+async fn test_access_token_details() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let mut created_access_tokens = vec![];
+    for _ in 0..2 {
+        let signup_body = Faker.fake::<TestSignupBody>();
+        client
+            .post(format!("{}/accounts/signup", &test_state.server_url))
+            .json(&signup_body)
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+        client
+            .post(format!("{}/accounts/verify-email", &test_state.server_url))
+            .json(&TestVerifyAccountBody {
+                email: signup_body.email.clone(),
+                secret: test_state
+                    .mailing_service
+                    .get_verification_secret(&signup_body.email)
+                    .unwrap()
+                    .unwrap(),
+            })
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+        let response = client
+            .post(format!("{}/tokens", &test_state.server_url))
+            .json(&TestCreateAccessTokenBody {
+                email: signup_body.email.clone(),
+                password: signup_body.password.clone(),
+                name: (1..MAX_NAME_LENGTH).fake(),
+                lifetime: (1..MAX_LIFETIME).fake(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let location = response
+            .headers()
+            .get("location")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let created_access_token = response
+            .json::<TestAccessTokenCreatedResponse>()
+            .await
+            .unwrap();
+        assert_eq!(location, format!("/tokens/{}", created_access_token.id));
+        created_access_tokens.push(created_access_token);
+    }
+    let (owned_access_token, other_access_token) =
+        (&created_access_tokens[0], &created_access_tokens[1]);
+
+    // The owner gets the details of the access token, without the secret
+    let response = client
+        .get(format!(
+            "{}/tokens/{}",
+            &test_state.server_url, owned_access_token.id
+        ))
+        .bearer_auth(&owned_access_token.access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert!(body.get("accessToken").is_none());
+    assert!(body.get("mac").is_none());
+    let access_token: TestAccessTokenResponse = serde_json::from_value(body).unwrap();
+    assert_eq!(access_token.id, owned_access_token.id);
+    assert_eq!(access_token.name, owned_access_token.name);
+    assert_eq!(access_token.expires_at, owned_access_token.expires_at);
+    assert_eq!(access_token.lifetime_secs, owned_access_token.lifetime_secs);
+    assert!(access_token.revoked_at.is_none());
+    // A use within the granularity of the last use is not recorded
+    assert_eq!(access_token.last_used_at, owned_access_token.created_at);
+
+    // Once the last use is older than the granularity, the use is recorded and it is not an update
+    sqlx::query(
+        r#"UPDATE "access_token" SET "last_used_at" = "last_used_at" - $2::interval WHERE "id" = $1"#,
+    )
+    .bind(owned_access_token.id)
+    .bind(format!("{} seconds", LAST_USE_GRANULARITY.as_secs()))
+    .execute(&pool)
+    .await
+    .unwrap();
+    let access_token = client
+        .get(format!(
+            "{}/tokens/{}",
+            &test_state.server_url, owned_access_token.id
+        ))
+        .bearer_auth(&owned_access_token.access_token)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<TestAccessTokenResponse>()
+        .await
+        .unwrap();
+    assert!(access_token.last_used_at > owned_access_token.created_at);
+    assert_eq!(access_token.updated_at, owned_access_token.updated_at);
+
+    // Access tokens of other accounts and unknown access tokens are not found
+    for token_id in [other_access_token.id, uuid::Uuid::new_v4()] {
+        let response = client
+            .get(format!("{}/tokens/{token_id}", &test_state.server_url))
+            .bearer_auth(&owned_access_token.access_token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    let response = client
+        .get(format!(
+            "{}/tokens/{}",
+            &test_state.server_url, owned_access_token.id
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_access_token_creation_without_name() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = common::signup_verified_account(&test_state).await;
+
+    let client = reqwest::Client::new();
+
+    let mut names = vec![];
+    for _ in 0..2 {
+        let response = client
+            .post(format!("{}/tokens", &test_state.server_url))
+            .json(&json!({
+                "email": signup_body.email,
+                "password": signup_body.password,
+                "lifetime": 3600,
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let access_token = response
+            .json::<TestAccessTokenCreatedResponse>()
+            .await
+            .unwrap();
+        assert!(access_token.name.starts_with(GENERATED_NAME_PREFIX));
+        assert!(access_token.name.len() > GENERATED_NAME_PREFIX.len());
+        names.push(access_token.name);
+    }
+    assert_ne!(names[0], names[1]);
+}
+
+#[tokio::test]
+async fn test_access_token_name_reuse_after_revocation() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let create_access_token_body = TestCreateAccessTokenBody {
+        email: signup_body.email.clone(),
+        password: signup_body.password.clone(),
+        name: "ci-runner".to_string(),
+        lifetime: 3600,
+    };
+    let response = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&create_access_token_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let first_access_token = response
+        .json::<TestAccessTokenCreatedResponse>()
+        .await
+        .unwrap();
+
+    // The name of an active access token can not be reused
+    let response = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&create_access_token_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    sqlx::query(r#"UPDATE "access_token" SET "revoked_at" = CURRENT_TIMESTAMP WHERE "id" = $1"#)
+        .bind(first_access_token.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // The name of a revoked access token can be reused
+    let response = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&create_access_token_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let second_access_token = response
+        .json::<TestAccessTokenCreatedResponse>()
+        .await
+        .unwrap();
+    assert_eq!(second_access_token.name, "ci-runner");
+    assert_ne!(second_access_token.id, first_access_token.id);
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+#[serde(rename_all = "camelCase")]
+struct TestAccessTokenSummaryResponse {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub expired: bool,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[tokio::test]
+async fn test_list_access_tokens() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let mut created_access_tokens = vec![];
+    for name in ["first", "second", "third"] {
+        let access_token = client
+            .post(format!("{}/tokens", &test_state.server_url))
+            .json(&TestCreateAccessTokenBody {
+                email: signup_body.email.clone(),
+                password: signup_body.password.clone(),
+                name: name.to_string(),
+                lifetime: 3600,
+            })
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .json::<TestAccessTokenCreatedResponse>()
+            .await
+            .unwrap();
+        created_access_tokens.push(access_token);
+    }
+    sqlx::query(
+        r#"UPDATE "access_token" SET "expires_at" = NOW() - INTERVAL '1 day' WHERE "id" = $1"#,
+    )
+    .bind(created_access_tokens[0].id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let authentication_token = &created_access_tokens[2].access_token;
+
+    // The most recently created access tokens come first, the expired one is flagged
+    let response = client
+        .get(format!("{}/tokens", &test_state.server_url))
+        .bearer_auth(authentication_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    for access_token in body.as_array().unwrap() {
+        assert!(access_token.get("accessToken").is_none(), "{body}");
+        assert!(access_token.get("mac").is_none(), "{body}");
+    }
+    let access_tokens: Vec<TestAccessTokenSummaryResponse> = serde_json::from_value(body).unwrap();
+    assert_eq!(
+        access_tokens
+            .iter()
+            .map(|access_token| access_token.id)
+            .collect::<Vec<_>>(),
+        created_access_tokens
+            .iter()
+            .rev()
+            .map(|access_token| access_token.id)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        access_tokens
+            .iter()
+            .map(|access_token| access_token.expired)
+            .collect::<Vec<_>>(),
+        vec![false, false, true]
+    );
+
+    // Pagination
+    for (query, expected_names) in [
+        ("limit=2", vec!["third", "second"]),
+        ("limit=2&offset=2", vec!["first"]),
+        ("offset=3", vec![]),
+    ] {
+        let access_tokens = client
+            .get(format!("{}/tokens?{query}", &test_state.server_url))
+            .bearer_auth(authentication_token)
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .json::<Vec<TestAccessTokenSummaryResponse>>()
+            .await
+            .unwrap();
+        assert_eq!(
+            access_tokens
+                .iter()
+                .map(|access_token| access_token.name.as_str())
+                .collect::<Vec<_>>(),
+            expected_names,
+            "{query}"
+        );
+    }
+
+    for query in [
+        "limit=0".to_string(),
+        format!("limit={}", MAX_PAGE_SIZE + 1),
+        "offset=-1".to_string(),
+    ] {
+        let response = client
+            .get(format!("{}/tokens?{query}", &test_state.server_url))
+            .bearer_auth(authentication_token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{query}");
+    }
+
+    let response = client
+        .get(format!("{}/tokens", &test_state.server_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // The access tokens of other accounts are not listed
+    let (_, other_access_token) = common::signup_with_access_token(&test_state).await;
+    let access_tokens = client
+        .get(format!("{}/tokens", &test_state.server_url))
+        .bearer_auth(&other_access_token.access_token)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<Vec<TestAccessTokenSummaryResponse>>()
+        .await
+        .unwrap();
+    assert_eq!(access_tokens.len(), 1);
+    assert_eq!(access_tokens[0].id, other_access_token.id);
+}
+
+#[tokio::test]
+async fn test_list_access_tokens_page_size() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let (_, access_token) = common::signup_with_access_token(&test_state).await;
+    let client = reqwest::Client::new();
+
+    // More revoked access tokens than a default page
+    let revoked_tokens = DEFAULT_PAGE_SIZE + 5;
+    sqlx::query(
+        r#"
+        INSERT INTO "access_token" ("account_id", "name", "mac", "expires_at", "revoked_at")
+        SELECT "account_id", 'revoked-' || i, sha256(i::text::bytea), "expires_at", CURRENT_TIMESTAMP
+        FROM "access_token", generate_series(1, $2) AS i
+        WHERE "id" = $1
+    "#,
+    )
+    .bind(access_token.id)
+    .bind(revoked_tokens as i32)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    for (query, expected_count) in [
+        (String::new(), DEFAULT_PAGE_SIZE),
+        (format!("limit={MAX_PAGE_SIZE}"), revoked_tokens + 1),
+    ] {
+        let access_tokens = client
+            .get(format!("{}/tokens?{query}", &test_state.server_url))
+            .bearer_auth(&access_token.access_token)
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .json::<Vec<TestAccessTokenSummaryResponse>>()
+            .await
+            .unwrap();
+        assert_eq!(access_tokens.len(), expected_count as usize, "{query}");
+    }
+
+    let response = client
+        .get(format!(
+            "{}/tokens?limit={}",
+            &test_state.server_url,
+            MAX_PAGE_SIZE + 1
+        ))
+        .bearer_auth(&access_token.access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["fields"]["limit"][0]["code"], json!("range"), "{body}");
+}
@@ -0,0 +1,64 @@
+use soko::{
+    database::{connect_options, pool_options},
+    newtypes::{Email, Opaque},
+    routes::{
+        accounts::{AccountRepository, PostgresAccountRepository},
+        tokens::{
+            AccessTokenRepository, CreateAccessTokenError, CreateAccessTokenRequest,
+            DEFAULT_LIFETIME, DEFAULT_TOKEN_BYTES, MAX_ACTIVE_TOKENS,
+            PostgresAccessTokenRepository,
+        },
+    },
+};
+
+mod common;
+
+#[tokio::test]
+async fn test_colliding_generated_name_is_generated_again() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+    let account_repository = PostgresAccountRepository::from(pool.clone());
+    let access_token_repository = PostgresAccessTokenRepository::from(pool);
+
+    let signup_body = common::signup_verified_account(&test_state).await;
+    let account = account_repository
+        .get_account_by_email(&Email::new(&signup_body.email).unwrap())
+        .await
+        .unwrap();
+
+    let hmac_secret = Opaque::new(rand::random());
+    let name = "token-00000000";
+    let new_request = || {
+        CreateAccessTokenRequest::try_new(
+            &account,
+            name,
+            DEFAULT_LIFETIME,
+            &hmac_secret,
+            DEFAULT_TOKEN_BYTES,
+        )
+        .unwrap()
+    };
+    access_token_repository
+        .create_token(&new_request(), MAX_ACTIVE_TOKENS)
+        .await
+        .unwrap();
+
+    // A name given by the client is not replaced
+    let err = access_token_repository
+        .create_token(&new_request(), MAX_ACTIVE_TOKENS)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, CreateAccessTokenError::NameAlreadyExists));
+
+    let mut request = new_request();
+    request.name_generated = true;
+    let access_token = access_token_repository
+        .create_token(&request, MAX_ACTIVE_TOKENS)
+        .await
+        .unwrap();
+    assert_ne!(access_token.name, name);
+}
@@ -0,0 +1,411 @@
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use fake::{Fake, Faker};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use soko::{
+    database::{connect_options, pool_options},
+    routes::{
+        ErrorResponse,
+        tokens::{DEFAULT_LIFETIME, GENERATED_NAME_PREFIX, MAX_ACTIVE_SESSIONS, MAX_ACTIVE_TOKENS},
+    },
+};
+
+use crate::common::{TestCreateAccessTokenBody, TestSignupBody, TestVerifyAccountBody};
+
+mod common;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TestAccessTokenCreatedResponse {
+    pub name: String,
+    pub access_token: String,
+    pub lifetime_secs: i64,
+}
+
+#[tokio::test]
+async fn test_login() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // An unverified account can not log in
+    let response = client
+        .post(format!("{}/accounts/login", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Successive logins issue distinct access tokens
+    for _ in 0..2 {
+        let response = client
+            .post(format!("{}/accounts/login", &test_state.server_url))
+            .json(&signup_body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let access_token = response
+            .json::<TestAccessTokenCreatedResponse>()
+            .await
+            .unwrap();
+        assert!(access_token.name.starts_with(GENERATED_NAME_PREFIX));
+        assert_eq!(access_token.lifetime_secs, i64::from(DEFAULT_LIFETIME));
+
+        let response = client
+            .get(format!("{}/tokens/verify", &test_state.server_url))
+            .bearer_auth(&access_token.access_token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let response = client
+        .post(format!("{}/accounts/login", &test_state.server_url))
+        .json(&TestSignupBody {
+            email: signup_body.email.clone(),
+            password: Faker.fake::<TestSignupBody>().password,
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+async fn set_account_state_columns(
+    pool: &sqlx::PgPool,
+    email: &str,
+    locked_until: Option<DateTime<Utc>>,
+    deactivated_at: Option<DateTime<Utc>>,
+) {
+    sqlx::query(
+        r#"
+        UPDATE "account"
+        SET "locked_until" = $2, "deactivated_at" = $3
+        WHERE lower("email") = lower($1)
+    "#,
+    )
+    .bind(email)
+    .bind(locked_until)
+    .bind(deactivated_at)
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_locked_and_deactivated_accounts_can_not_authenticate() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let access_token = client
+        .post(format!("{}/accounts/login", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<TestAccessTokenCreatedResponse>()
+        .await
+        .unwrap()
+        .access_token;
+
+    // A locked account is disclosed only with the right password
+    set_account_state_columns(
+        &pool,
+        &signup_body.email,
+        Some(Utc::now() + TimeDelta::hours(1)),
+        None,
+    )
+    .await;
+    let response = client
+        .post(format!("{}/accounts/login", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let error_response = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(error_response.code, "account_locked");
+    let response = client
+        .post(format!("{}/accounts/login", &test_state.server_url))
+        .json(&TestSignupBody {
+            email: signup_body.email.clone(),
+            password: Faker.fake::<TestSignupBody>().password,
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = client
+        .get(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let error_response = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(error_response.code, "account_locked");
+
+    // An ended lock is lifted
+    set_account_state_columns(
+        &pool,
+        &signup_body.email,
+        Some(Utc::now() - TimeDelta::hours(1)),
+        None,
+    )
+    .await;
+    let response = client
+        .get(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // A deactivated account is rejected as an unknown account
+    set_account_state_columns(&pool, &signup_body.email, None, Some(Utc::now())).await;
+    let response = client
+        .post(format!("{}/accounts/login", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = client
+        .get(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_login_latency_does_not_tell_whether_the_email_is_registered() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let wrong_password = Faker.fake::<TestSignupBody>().password;
+    let unknown_email = Faker.fake::<TestSignupBody>().email;
+    // The first login of an unknown email hashes the dummy password, it is not measured
+    let mut elapsed: Vec<Duration> = vec![];
+    for email in [&unknown_email, &signup_body.email, &unknown_email] {
+        let start = Instant::now();
+        let response = client
+            .post(format!("{}/accounts/login", &test_state.server_url))
+            .json(&TestSignupBody {
+                email: email.clone(),
+                password: wrong_password.clone(),
+            })
+            .send()
+            .await
+            .unwrap();
+        elapsed.push(start.elapsed());
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let (wrong_password_elapsed, unknown_email_elapsed) = (elapsed[1], elapsed[2]);
+    assert!(
+        unknown_email_elapsed * 2 >= wrong_password_elapsed
+            && wrong_password_elapsed * 2 >= unknown_email_elapsed,
+        "{elapsed:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_token_creation_does_not_disclose_unknown_emails() {
+    let test_state = common::setup().await.unwrap();
+    let signup_body = common::signup_verified_account(&test_state).await;
+
+    let client = reqwest::Client::new();
+    let wrong_password = "Wrong-Password-42AB".to_string();
+    let mut responses = vec![];
+    for email in [
+        signup_body.email.clone(),
+        Faker.fake::<TestSignupBody>().email,
+    ] {
+        let response = client
+            .post(format!("{}/tokens", &test_state.server_url))
+            .json(&TestCreateAccessTokenBody {
+                email,
+                password: wrong_password.clone(),
+                name: "laptop".to_string(),
+                lifetime: 3600,
+            })
+            .send()
+            .await
+            .unwrap();
+        responses.push((response.status(), response.text().await.unwrap()));
+    }
+    assert_eq!(responses[0].0, StatusCode::UNAUTHORIZED);
+    assert_eq!(responses[0], responses[1]);
+}
+
+#[tokio::test]
+async fn test_logins_do_not_count_towards_the_access_token_limit() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Logging in beyond the session limit ends the oldest session
+    let mut access_tokens = vec![];
+    for _ in 0..=MAX_ACTIVE_SESSIONS {
+        let response = client
+            .post(format!("{}/accounts/login", &test_state.server_url))
+            .json(&signup_body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        access_tokens.push(
+            response
+                .json::<TestAccessTokenCreatedResponse>()
+                .await
+                .unwrap()
+                .access_token,
+        );
+    }
+    for (i, access_token) in access_tokens.iter().enumerate() {
+        let response = client
+            .get(format!("{}/tokens/verify", &test_state.server_url))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .unwrap();
+        let expected_status = if i == 0 {
+            StatusCode::UNAUTHORIZED
+        } else {
+            StatusCode::OK
+        };
+        assert_eq!(response.status(), expected_status, "session {i}");
+    }
+
+    // The sessions leave room for the access tokens created explicitly
+    for i in 0..MAX_ACTIVE_TOKENS {
+        let response = client
+            .post(format!("{}/tokens", &test_state.server_url))
+            .json(&TestCreateAccessTokenBody {
+                email: signup_body.email.clone(),
+                password: signup_body.password.clone(),
+                name: format!("token-{i}"),
+                lifetime: DEFAULT_LIFETIME,
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+}
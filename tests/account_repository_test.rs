@@ -0,0 +1,268 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use fake::{Fake, Faker};
+use soko::{
+    cleanup::cleanup_verification_tickets,
+    database::{connect_options, pool_options},
+    newtypes::Email,
+    routes::accounts::{
+        AccountRepository, DEFAULT_VERIFICATION_TICKET_LIFETIME, PostgresAccountRepository,
+        RESEND_VERIFICATION_INTERVAL, ResendVerificationError, ResendVerificationRequest,
+        VerifyAccountError,
+    },
+};
+
+use crate::common::TestSignupBody;
+
+mod common;
+
+#[tokio::test]
+async fn test_verify_account_twice() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+    let account_repository = PostgresAccountRepository::from(pool.clone());
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    reqwest::Client::new()
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let account_id: uuid::Uuid =
+        sqlx::query_scalar(r#"SELECT "id" FROM "account" WHERE "email" = $1"#)
+            .bind(&signup_body.email)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    let ticket_id: uuid::Uuid = sqlx::query_scalar(
+        r#"SELECT "id" FROM "account_verification_ticket" WHERE "account_id" = $1 AND "status" = 'active'"#,
+    )
+    .bind(account_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    let account = account_repository
+        .verify_account(account_id, ticket_id)
+        .await
+        .unwrap();
+    assert!(account.verified);
+
+    let err = account_repository
+        .verify_account(account_id, ticket_id)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        VerifyAccountError::AccountAlreadyVerified { account_id: id } if id == account_id
+    ));
+
+    // The confirmed ticket is left untouched by the second verification
+    let statuses: Vec<String> = sqlx::query_scalar(
+        r#"SELECT "status"::text FROM "account_verification_ticket" WHERE "account_id" = $1"#,
+    )
+    .bind(account_id)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(statuses, vec!["confirmed".to_string()]);
+}
+
+#[tokio::test]
+async fn test_regenerate_verification_ticket() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+    let account_repository = PostgresAccountRepository::from(pool.clone());
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    reqwest::Client::new()
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let account = account_repository
+        .get_account_by_email(&Email::new(&signup_body.email).unwrap())
+        .await
+        .unwrap();
+
+    // The throttle window is checked again by the repository, e.g. against a concurrent resend
+    let request = ResendVerificationRequest::try_from_account(
+        account.clone(),
+        None,
+        Utc::now(),
+        RESEND_VERIFICATION_INTERVAL,
+    )
+    .unwrap();
+    // The ticket created by the signup is within the throttle window
+    let err = account_repository
+        .regenerate_verification_ticket(&request, RESEND_VERIFICATION_INTERVAL)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, ResendVerificationError::TooSoon { .. }));
+
+    let rotated_account = account_repository
+        .regenerate_verification_ticket(&request, Duration::ZERO)
+        .await
+        .unwrap();
+    assert_eq!(rotated_account.password_hash, account.password_hash);
+
+    // The password hash is left untouched while the previous ticket is replaced by a new active one
+    let password_hash: String =
+        sqlx::query_scalar(r#"SELECT "password_hash" FROM "account" WHERE "id" = $1"#)
+            .bind(account.id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(password_hash, account.password_hash);
+    let active_cyphertexts: Vec<String> = sqlx::query_scalar(
+        r#"SELECT "cyphertext" FROM "account_verification_ticket" WHERE "account_id" = $1 AND "status" = 'active'"#,
+    )
+    .bind(account.id)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        active_cyphertexts,
+        vec![request.verification_cyphertext.clone()]
+    );
+    let cancelled_tickets: i64 = sqlx::query_scalar(
+        r#"SELECT COUNT(*) FROM "account_verification_ticket" WHERE "account_id" = $1 AND "status" = 'cancelled'"#,
+    )
+    .bind(account.id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(cancelled_tickets, 1);
+
+    // The replaced ticket can no longer verify the account, the account update is rolled back
+    let cancelled_ticket_id: uuid::Uuid = sqlx::query_scalar(
+        r#"SELECT "id" FROM "account_verification_ticket" WHERE "account_id" = $1 AND "status" = 'cancelled'"#,
+    )
+    .bind(account.id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    let err = account_repository
+        .verify_account(account.id, cancelled_ticket_id)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        VerifyAccountError::NoActiveVerificationTicket { account_id } if account_id == account.id
+    ));
+    let verified: bool = sqlx::query_scalar(r#"SELECT "verified" FROM "account" WHERE "id" = $1"#)
+        .bind(account.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(!verified);
+
+    // A verified account has no ticket to rotate
+    let active_ticket_id: uuid::Uuid = sqlx::query_scalar(
+        r#"SELECT "id" FROM "account_verification_ticket" WHERE "account_id" = $1 AND "status" = 'active'"#,
+    )
+    .bind(account.id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    account_repository
+        .verify_account(account.id, active_ticket_id)
+        .await
+        .unwrap();
+    let err = account_repository
+        .regenerate_verification_ticket(&request, Duration::ZERO)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        ResendVerificationError::AccountAlreadyVerified { account_id } if account_id == account.id
+    ));
+}
+
+#[tokio::test]
+async fn test_cleanup_verification_tickets_in_batches() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+    let account_repository = PostgresAccountRepository::from(pool.clone());
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    reqwest::Client::new()
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let account_id: uuid::Uuid =
+        sqlx::query_scalar(r#"SELECT "id" FROM "account" WHERE "email" = $1"#)
+            .bind(&signup_body.email)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+    // Two and a half batches of stale tickets, the active ticket of the signup and the confirmed tickets are kept
+    let batch_size = 10;
+    sqlx::query(
+        r#"
+        INSERT INTO "account_verification_ticket" ("account_id", "cyphertext", "status", "created_at", "updated_at")
+        SELECT $1, 'stale-cyphertext', 'cancelled', now() - interval '3 days', now() - interval '3 days'
+        FROM generate_series(1, $2)
+    "#,
+    )
+    .bind(account_id)
+    .bind(batch_size * 5 / 2)
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        r#"
+        INSERT INTO "account_verification_ticket" ("account_id", "cyphertext", "status", "created_at", "updated_at")
+        VALUES ($1, 'confirmed-cyphertext', 'confirmed', now() - interval '3 days', now() - interval '3 days')
+    "#,
+    )
+    .bind(account_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let deleted = cleanup_verification_tickets(
+        &account_repository,
+        Utc::now() - DEFAULT_VERIFICATION_TICKET_LIFETIME,
+        batch_size as u32,
+    )
+    .await
+    .unwrap();
+    assert!(deleted >= 25, "{deleted}");
+
+    let statuses: Vec<String> = sqlx::query_scalar(
+        r#"SELECT "status"::TEXT FROM "account_verification_ticket" WHERE "account_id" = $1 ORDER BY 1"#,
+    )
+    .bind(account_id)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        statuses,
+        vec!["active".to_string(), "confirmed".to_string()]
+    );
+}
@@ -1,6 +1,21 @@
+use chrono::{TimeDelta, Utc};
 use fake::{Fake, Faker};
+use hmac::{Hmac, Mac};
 use reqwest::StatusCode;
-use soko::routes::accounts::AccountResponse;
+use serde_json::json;
+use sha3::Sha3_256;
+use soko::{
+    Config,
+    database::{connect_options, pool_options},
+    routes::{
+        ErrorResponse,
+        accounts::{
+            AccountResponse, DEFAULT_MAX_VERIFICATION_ATTEMPTS, MAX_DISPLAY_NAME_LENGTH,
+            RESEND_VERIFICATION_INTERVAL,
+        },
+        tokens::MAX_ACTIVE_TOKENS,
+    },
+};
 
 use crate::common::{TestSignupBody, TestVerifyAccountBody};
 
@@ -20,15 +35,9 @@ async fn test_account_signup() {
         .await
         .unwrap();
     assert_eq!(response.status(), StatusCode::CREATED);
-    assert_eq!(
-        response
-            .json::<AccountResponse>()
-            .await
-            .unwrap()
-            .email
-            .as_str(),
-        signup_body.email.to_lowercase()
-    );
+    let account = response.json::<AccountResponse>().await.unwrap();
+    assert_eq!(account.email.as_str(), signup_body.email.to_lowercase());
+    assert!(!account.verified);
 }
 
 #[tokio::test]
@@ -60,6 +69,70 @@ async fn test_account_email_verification() {
         .await
         .unwrap();
     assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.json::<AccountResponse>().await.unwrap().verified);
+}
+
+#[tokio::test]
+async fn test_account_sms_verification() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    let phone_number = "+33612345678";
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&json!({
+            "email": &signup_body.email,
+            "password": &signup_body.password,
+            "channel": "sms",
+            "phoneNumber": phone_number,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert!(
+        test_state
+            .mailing_service
+            .get_verification_secret(&signup_body.email)
+            .unwrap()
+            .is_none()
+    );
+
+    let response = client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .sms_service
+                .get_verification_secret(phone_number)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_account_sms_channel_without_phone_number_must_fail() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&json!({
+            "email": &signup_body.email,
+            "password": &signup_body.password,
+            "channel": "sms",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 }
 
 #[tokio::test]
@@ -89,16 +162,13 @@ async fn test_forbidden_signup_once_verified() {
         .await
         .unwrap();
 
+    // Replaying the verification succeeds, see `test_retried_email_verification`, an unrelated secret is rejected
     assert_eq!(
         client
             .post(format!("{}/accounts/verify-email", &test_state.server_url))
             .json(&TestVerifyAccountBody {
                 email: signup_body.email.clone(),
-                secret: test_state
-                    .mailing_service
-                    .get_verification_secret(&signup_body.email)
-                    .unwrap()
-                    .unwrap(),
+                secret: "unrelated-secret".to_string(),
             })
             .send()
             .await
@@ -122,6 +192,128 @@ async fn test_forbidden_signup_once_verified() {
     )
 }
 
+/// Submit wrong secrets until the verification ticket is invalidated, then check that the right secret is rejected
+async fn exhaust_verification_attempts(test_state: &common::TestState, max_attempts: u32) {
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    for remaining_attempts in (0..max_attempts).rev() {
+        let response = client
+            .post(format!("{}/accounts/verify-email", &test_state.server_url))
+            .json(&TestVerifyAccountBody {
+                email: signup_body.email.clone(),
+                secret: "wrong-secret".to_string(),
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = response.json::<serde_json::Value>().await.unwrap();
+        let error = &body["fields"]["secret"][0];
+        assert_eq!(
+            error["params"]["remaining_attempts"],
+            json!(remaining_attempts),
+            "{body}"
+        );
+        let expected_code = if remaining_attempts == 0 {
+            // The invalidation points to the resend of a new secret
+            assert!(
+                error["message"]
+                    .as_str()
+                    .unwrap()
+                    .contains("POST /accounts/resend-verification"),
+                "{body}"
+            );
+            "secret-invalidated"
+        } else {
+            "secret-validity"
+        };
+        assert_eq!(error["code"], json!(expected_code), "{body}");
+    }
+    // The right secret is rejected once the verification has been invalidated
+    let response = client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_failed_email_verifications_until_invalidation() {
+    let test_state = common::setup().await.unwrap();
+    exhaust_verification_attempts(&test_state, DEFAULT_MAX_VERIFICATION_ATTEMPTS).await;
+}
+
+#[tokio::test]
+async fn test_configured_max_verification_attempts() {
+    let test_state = common::setup_with_config(Config {
+        verification_max_attempts: 2,
+        ..common::test_config()
+    })
+    .await
+    .unwrap();
+    exhaust_verification_attempts(&test_state, 2).await;
+}
+
+#[tokio::test]
+async fn test_email_verification_does_not_tell_whether_the_email_is_registered() {
+    let mut config = common::test_config();
+    config.security.verify_nonenumeration = true;
+    let test_state = common::setup_with_config(config).await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let verified_signup_body = common::signup_verified_account(&test_state).await;
+
+    // Unverified, verified and unknown emails fail the same way with a wrong secret
+    let mut responses = vec![];
+    for email in [
+        signup_body.email.clone(),
+        verified_signup_body.email.clone(),
+        format!("unknown-{}", signup_body.email),
+    ] {
+        let response = client
+            .post(format!("{}/accounts/verify-email", &test_state.server_url))
+            .json(&TestVerifyAccountBody {
+                email,
+                secret: "unrelated-secret".to_string(),
+            })
+            .send()
+            .await
+            .unwrap();
+        responses.push((response.status(), response.text().await.unwrap()));
+    }
+    assert_eq!(responses[0].0, StatusCode::BAD_REQUEST);
+    assert_eq!(responses[0], responses[1]);
+    assert_eq!(responses[0], responses[2]);
+}
+
 #[tokio::test]
 async fn test_account_signup_two_successive_times() {
     let test_state = common::setup().await.unwrap();
@@ -148,8 +340,14 @@ async fn test_account_signup_two_successive_times() {
         .unwrap();
     assert_eq!(update_response.status(), StatusCode::CREATED);
 
-    let account = response.json::<AccountResponse>().await.unwrap();
-    let updated_account = update_response.json::<AccountResponse>().await.unwrap();
+    // The second signup tells that the verification secret has been sent again
+    let signup_response = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(signup_response["resent"], false);
+    let update_signup_response = update_response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(update_signup_response["resent"], true);
+
+    let account: AccountResponse = serde_json::from_value(signup_response).unwrap();
+    let updated_account: AccountResponse = serde_json::from_value(update_signup_response).unwrap();
     assert_eq!(account.created_at, updated_account.created_at);
     assert!(
         account.updated_at.timestamp_micros() < updated_account.updated_at.timestamp_micros(),
@@ -158,3 +356,1213 @@ async fn test_account_signup_two_successive_times() {
         updated_account.updated_at
     );
 }
+
+#[tokio::test]
+async fn test_retried_email_verification() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let verify_account_body = TestVerifyAccountBody {
+        email: signup_body.email.clone(),
+        secret: test_state
+            .mailing_service
+            .get_verification_secret(&signup_body.email)
+            .unwrap()
+            .unwrap(),
+    };
+    for _ in 0..2 {
+        let response = client
+            .post(format!("{}/accounts/verify-email", &test_state.server_url))
+            .json(&verify_account_body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response
+                .json::<AccountResponse>()
+                .await
+                .unwrap()
+                .email
+                .as_str(),
+            signup_body.email.to_lowercase()
+        );
+    }
+
+    // An unrelated secret is still rejected
+    let response = client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: "unrelated-secret".to_string(),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_concurrent_email_verifications() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let verify_account_body = TestVerifyAccountBody {
+        email: signup_body.email.clone(),
+        secret: test_state
+            .mailing_service
+            .get_verification_secret(&signup_body.email)
+            .unwrap()
+            .unwrap(),
+    };
+    let (first_response, second_response) = tokio::join!(
+        client
+            .post(format!("{}/accounts/verify-email", &test_state.server_url))
+            .json(&verify_account_body)
+            .send(),
+        client
+            .post(format!("{}/accounts/verify-email", &test_state.server_url))
+            .json(&verify_account_body)
+            .send()
+    );
+    let statuses = [
+        first_response.unwrap().status(),
+        second_response.unwrap().status(),
+    ];
+
+    assert_eq!(
+        statuses.iter().filter(|s| **s == StatusCode::OK).count(),
+        1,
+        "expected exactly one successful verification, got {statuses:?}"
+    );
+    assert!(
+        statuses.iter().all(|s| [
+            StatusCode::OK,
+            StatusCode::CONFLICT,
+            StatusCode::BAD_REQUEST
+        ]
+        .contains(s)),
+        "unexpected statuses {statuses:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_concurrent_email_verifications_issue_a_single_access_token() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let verify_account_body = json!({
+        "email": signup_body.email,
+        "secret": test_state
+            .mailing_service
+            .get_verification_secret(&signup_body.email)
+            .unwrap()
+            .unwrap(),
+        "issueToken": true,
+    });
+    let (first_response, second_response) = tokio::join!(
+        client
+            .post(format!("{}/accounts/verify-email", &test_state.server_url))
+            .json(&verify_account_body)
+            .send(),
+        client
+            .post(format!("{}/accounts/verify-email", &test_state.server_url))
+            .json(&verify_account_body)
+            .send()
+    );
+
+    // The loser either sees the verified account, i.e. a replay without access token, or conflicts with the winner
+    let mut issued_tokens = 0;
+    for response in [first_response.unwrap(), second_response.unwrap()] {
+        let status = response.status();
+        let body = response.json::<serde_json::Value>().await.unwrap();
+        match status {
+            StatusCode::OK => {
+                if !body["accessToken"].is_null() {
+                    issued_tokens += 1;
+                }
+            }
+            StatusCode::CONFLICT => {
+                assert_eq!(
+                    body["fields"]["email"][0]["code"],
+                    json!("email-verified"),
+                    "{body}"
+                );
+            }
+            _ => panic!("unexpected status {status}: {body}"),
+        }
+    }
+    assert_eq!(issued_tokens, 1);
+
+    let minted_tokens = sqlx::query_scalar::<_, i64>(
+        r#"
+        SELECT COUNT(*) FROM "access_token"
+        JOIN "account" ON "account"."id" = "access_token"."account_id"
+        WHERE lower("account"."email") = lower($1)
+        "#,
+    )
+    .bind(&signup_body.email)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(minted_tokens, 1);
+}
+
+#[tokio::test]
+async fn test_update_display_name() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let verify_account_response = client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&json!({
+            "email": signup_body.email,
+            "secret": test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+            "issueToken": true,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    let access_token = verify_account_response["accessToken"]["accessToken"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let response = client
+        .patch(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&access_token)
+        .json(&json!({ "displayName": "  Jane Doe " }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let account_response = response.json::<AccountResponse>().await.unwrap();
+    assert_eq!(account_response.display_name.as_deref(), Some("Jane Doe"));
+
+    // Absent fields are left untouched
+    let account_response = client
+        .patch(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&access_token)
+        .json(&json!({}))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<AccountResponse>()
+        .await
+        .unwrap();
+    assert_eq!(account_response.display_name.as_deref(), Some("Jane Doe"));
+
+    let response = client
+        .patch(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&access_token)
+        .json(&json!({ "displayName": "a".repeat(MAX_DISPLAY_NAME_LENGTH + 1) }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let error_response = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(
+        error_response["message"],
+        format!(
+            "displayName: display name must not be empty and must be at most {MAX_DISPLAY_NAME_LENGTH} characters long"
+        )
+    );
+    assert_eq!(
+        error_response["fields"]["displayName"][0]["code"],
+        "invalid-length"
+    );
+
+    // Null fields are cleared
+    let account_response = client
+        .patch(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&access_token)
+        .json(&json!({ "displayName": null }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<AccountResponse>()
+        .await
+        .unwrap();
+    assert_eq!(account_response.display_name, None);
+
+    let response = client
+        .patch(format!("{}/accounts/me", &test_state.server_url))
+        .json(&json!({ "displayName": "Jane Doe" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_conditional_profile_update() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let verify_account_response = client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&json!({
+            "email": signup_body.email,
+            "secret": test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+            "issueToken": true,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    let access_token = verify_account_response["accessToken"]["accessToken"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let response = client
+        .patch(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&access_token)
+        .json(&json!({ "displayName": "Jane Doe" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let stale_etag = response.headers()["etag"].to_str().unwrap().to_string();
+
+    let response = client
+        .patch(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&access_token)
+        .header("if-match", &stale_etag)
+        .json(&json!({ "displayName": "Jane Smith" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let fresh_etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_ne!(fresh_etag, stale_etag);
+
+    // The update based on the first version of the account must not clobber the second update
+    let response = client
+        .patch(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&access_token)
+        .header("if-match", &stale_etag)
+        .json(&json!({ "displayName": "Jane Doe" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().code,
+        "precondition_failed"
+    );
+
+    let response = client
+        .patch(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&access_token)
+        .header("if-match", &fresh_etag)
+        .json(&json!({ "displayName": "Jane Doe" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response
+            .json::<AccountResponse>()
+            .await
+            .unwrap()
+            .display_name
+            .as_deref(),
+        Some("Jane Doe")
+    );
+}
+
+#[tokio::test]
+async fn test_profile_update_requires_a_recent_authentication() {
+    let sensitive_action_window = std::time::Duration::from_secs(2);
+    let mut config = common::test_config();
+    config.security.sensitive_action_window = Some(sensitive_action_window);
+    let test_state = common::setup_with_config(config).await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let verify_account_response = client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&json!({
+            "email": signup_body.email,
+            "secret": test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+            "issueToken": true,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    let stale_access_token = verify_account_response["accessToken"]["accessToken"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let response = client
+        .patch(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&stale_access_token)
+        .json(&json!({ "displayName": "Jane Doe" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    tokio::time::sleep(sensitive_action_window).await;
+
+    // The access token is still active but too old for a sensitive action
+    let response = client
+        .patch(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&stale_access_token)
+        .json(&json!({ "displayName": "John Doe" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(
+        response.headers()["www-authenticate"]
+            .to_str()
+            .unwrap()
+            .contains(r#"error="insufficient_user_authentication""#)
+    );
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().code,
+        "reauthentication_required"
+    );
+    let response = client
+        .post(format!("{}/tokens/whoami", &test_state.server_url))
+        .bearer_auth(&stale_access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // A new login grants a fresh access token
+    let fresh_access_token = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&json!({
+            "email": signup_body.email,
+            "password": signup_body.password,
+            "name": "fresh-access-token",
+            "lifetime": 3600,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap()["accessToken"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let response = client
+        .patch(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&fresh_access_token)
+        .json(&json!({ "displayName": "John Doe" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let account_response = response.json::<AccountResponse>().await.unwrap();
+    assert_eq!(account_response.display_name.as_deref(), Some("John Doe"));
+}
+
+#[tokio::test]
+async fn test_update_profile_with_unverified_account() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Unverified accounts can not create access tokens, one is inserted directly
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+    let access_token = format!("soko__{}", uuid::Uuid::new_v4());
+    let mut hmac =
+        Hmac::<Sha3_256>::new_from_slice(config.access_token_secret.extract_inner()).unwrap();
+    hmac.update(access_token.as_bytes());
+    sqlx::query(
+        r#"
+            INSERT INTO "access_token" ("account_id", "name", "mac", "expires_at")
+            SELECT "id", 'unverified-token', $2, $3
+            FROM "account"
+            WHERE "email" = $1
+        "#,
+    )
+    .bind(&signup_body.email)
+    .bind(hmac.finalize().into_bytes().to_vec())
+    .bind(Utc::now() + TimeDelta::hours(1))
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let response = client
+        .patch(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&access_token)
+        .json(&json!({ "displayName": "Alice" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let error_response = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(error_response.code, "account_not_verified");
+}
+
+#[tokio::test]
+async fn test_signup_with_imported_mixed_case_email() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    let (local_part, domain) = signup_body.email.split_once('@').unwrap();
+    let imported_email = format!("{}@{}", local_part.to_uppercase(), domain);
+    sqlx::query(r#"INSERT INTO "account" ("email", "password_hash") VALUES ($1, 'imported')"#)
+        .bind(&imported_email)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // Inserting the same email with another case is rejected by the database
+    let result =
+        sqlx::query(r#"INSERT INTO "account" ("email", "password_hash") VALUES ($1, 'imported')"#)
+            .bind(imported_email.to_lowercase())
+            .execute(&pool)
+            .await;
+    assert!(result.is_err());
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    let emails: Vec<String> =
+        sqlx::query_scalar(r#"SELECT "email" FROM "account" WHERE lower("email") = lower($1)"#)
+            .bind(&signup_body.email)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(emails, vec![signup_body.email.to_lowercase()]);
+}
+
+#[tokio::test]
+async fn test_email_verification_is_rolled_back_on_access_token_failure() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // The account already reached its limit of active access tokens, the token creation fails after the verification
+    for i in 0..MAX_ACTIVE_TOKENS {
+        sqlx::query(
+            r#"
+                INSERT INTO "access_token" ("account_id", "name", "mac", "expires_at")
+                SELECT "id", 'existing-token-' || $4, $2, $3
+                FROM "account"
+                WHERE "email" = $1
+            "#,
+        )
+        .bind(&signup_body.email)
+        .bind(rand::random::<[u8; 32]>().to_vec())
+        .bind(Utc::now() + TimeDelta::hours(1))
+        .bind(i.to_string())
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+
+    let secret = test_state
+        .mailing_service
+        .get_verification_secret(&signup_body.email)
+        .unwrap()
+        .unwrap();
+    let response = client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&json!({
+            "email": signup_body.email,
+            "secret": secret,
+            "issueToken": true,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let verified: bool =
+        sqlx::query_scalar(r#"SELECT "verified" FROM "account" WHERE "email" = $1"#)
+            .bind(&signup_body.email)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert!(!verified);
+
+    // The verification ticket is still active
+    let response = client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret,
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_double_submitted_signup() {
+    let mut config = common::test_config();
+    config.security.signup_debounce = std::time::Duration::from_secs(5);
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let verification_secret = test_state
+        .mailing_service
+        .get_verification_secret(&signup_body.email)
+        .unwrap()
+        .unwrap();
+
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response
+        .headers()
+        .get("retry-after")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=5).contains(&retry_after));
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().code,
+        "duplicate_signup"
+    );
+
+    // The first signup is left untouched: a single ticket and the same emailed secret
+    let tickets: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM "account_verification_ticket" t
+        JOIN "account" a ON a."id" = t."account_id"
+        WHERE a."email" = $1
+    "#,
+    )
+    .bind(&signup_body.email)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(tickets, 1);
+    assert_eq!(
+        test_state
+            .mailing_service
+            .get_verification_secret(&signup_body.email)
+            .unwrap()
+            .unwrap(),
+        verification_secret
+    );
+}
+
+#[tokio::test]
+async fn test_concurrent_repeated_signups_are_debounced() {
+    let signup_debounce = std::time::Duration::from_secs(2);
+    let mut config = common::test_config();
+    config.security.signup_debounce = signup_debounce;
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    tokio::time::sleep(signup_debounce).await;
+
+    // Every repeated signup passes the debounce check of the handler, a single one resets the account creation
+    let signups: Vec<_> = (0..3)
+        .map(|_| {
+            let client = client.clone();
+            let server_url = test_state.server_url.clone();
+            let signup_body = signup_body.clone();
+            tokio::spawn(async move {
+                client
+                    .post(format!("{server_url}/accounts/signup"))
+                    .json(&signup_body)
+                    .send()
+                    .await
+                    .unwrap()
+                    .status()
+            })
+        })
+        .collect();
+    let mut statuses = vec![];
+    for signup in signups {
+        statuses.push(signup.await.unwrap());
+    }
+    assert_eq!(
+        statuses
+            .iter()
+            .filter(|status| **status == StatusCode::CREATED)
+            .count(),
+        1,
+        "{statuses:?}"
+    );
+    assert!(
+        statuses
+            .iter()
+            .all(|status| [StatusCode::CREATED, StatusCode::TOO_MANY_REQUESTS].contains(status)),
+        "{statuses:?}"
+    );
+
+    let tickets: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM "account_verification_ticket" t
+        JOIN "account" a ON a."id" = t."account_id"
+        WHERE a."email" = $1
+    "#,
+    )
+    .bind(signup_body.email.to_lowercase())
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(tickets, 2);
+}
+
+/// Move the creation of the active verification ticket of an account out of the resend throttle window
+async fn backdate_verification_ticket(pool: &sqlx::PgPool, email: &str) {
+    sqlx::query(
+        r#"
+        UPDATE "account_verification_ticket"
+        SET "created_at" = "created_at" - $2::interval
+        WHERE "status" = 'active' AND "account_id" = (SELECT "id" FROM "account" WHERE lower("email") = lower($1))
+    "#,
+    )
+    .bind(email.to_lowercase())
+    .bind(format!(
+        "{} seconds",
+        RESEND_VERIFICATION_INTERVAL.as_secs()
+    ))
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_resend_verification() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let first_secret = test_state
+        .mailing_service
+        .get_verification_secret(&signup_body.email)
+        .unwrap()
+        .unwrap();
+    backdate_verification_ticket(&pool, &signup_body.email).await;
+
+    let response = client
+        .post(format!(
+            "{}/accounts/resend-verification",
+            &test_state.server_url
+        ))
+        .json(&json!({ "email": signup_body.email }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let second_secret = test_state
+        .mailing_service
+        .get_verification_secret(&signup_body.email)
+        .unwrap()
+        .unwrap();
+    assert_ne!(first_secret, second_secret);
+
+    // The previous secret is no longer valid
+    let response = client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: first_secret,
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: second_secret.clone(),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The password is left untouched by the resend
+    let response = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&json!({
+            "email": signup_body.email,
+            "password": signup_body.password,
+            "name": "resend",
+            "lifetime": 3600,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    // Nothing is sent to a verified account, the response does not tell it apart from an unknown email
+    let unknown_email = Faker.fake::<TestSignupBody>().email;
+    for email in [signup_body.email.clone(), unknown_email.clone()] {
+        let response = client
+            .post(format!(
+                "{}/accounts/resend-verification",
+                &test_state.server_url
+            ))
+            .json(&json!({ "email": email }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.json::<serde_json::Value>().await.unwrap(),
+            json!({})
+        );
+    }
+    assert_eq!(
+        test_state
+            .mailing_service
+            .get_verification_secret(&signup_body.email)
+            .unwrap()
+            .unwrap(),
+        second_secret
+    );
+    assert!(
+        test_state
+            .mailing_service
+            .get_verification_secret(&unknown_email)
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn test_resend_verification_throttle() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let first_secret = test_state
+        .mailing_service
+        .get_verification_secret(&signup_body.email)
+        .unwrap()
+        .unwrap();
+
+    // Within the throttle window of the secret sent at signup, the resend is silently ignored
+    let response = client
+        .post(format!(
+            "{}/accounts/resend-verification",
+            &test_state.server_url
+        ))
+        .json(&json!({ "email": signup_body.email }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        test_state
+            .mailing_service
+            .get_verification_secret(&signup_body.email)
+            .unwrap()
+            .unwrap(),
+        first_secret
+    );
+
+    // Out of the throttle window, the new secret opens a new window
+    backdate_verification_ticket(&pool, &signup_body.email).await;
+    let mut secrets = vec![];
+    for _ in 0..2 {
+        let response = client
+            .post(format!(
+                "{}/accounts/resend-verification",
+                &test_state.server_url
+            ))
+            .json(&json!({ "email": signup_body.email }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        secrets.push(
+            test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        );
+    }
+    assert_ne!(secrets[0], first_secret);
+    assert_eq!(secrets[1], secrets[0]);
+}
+
+#[tokio::test]
+async fn test_resend_verification_keeps_the_sms_channel() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    let phone_number = "+33612345678";
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&json!({
+            "email": &signup_body.email,
+            "password": &signup_body.password,
+            "channel": "sms",
+            "phoneNumber": phone_number,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let first_secret = test_state
+        .sms_service
+        .get_verification_secret(phone_number)
+        .unwrap()
+        .unwrap();
+
+    backdate_verification_ticket(&pool, &signup_body.email).await;
+    let response = client
+        .post(format!(
+            "{}/accounts/resend-verification",
+            &test_state.server_url
+        ))
+        .json(&json!({ "email": signup_body.email }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let resent_secret = test_state
+        .sms_service
+        .get_verification_secret(phone_number)
+        .unwrap()
+        .unwrap();
+    assert_ne!(resent_secret, first_secret);
+    assert!(
+        test_state
+            .mailing_service
+            .get_verification_secret(&signup_body.email)
+            .unwrap()
+            .is_none()
+    );
+
+    let response = client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: resent_secret,
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_signup_latency_does_not_tell_whether_the_email_is_registered() {
+    let signup_min_duration = std::time::Duration::from_secs(3);
+    let mut config = common::test_config();
+    config.security.signup_min_duration = signup_min_duration;
+    let test_state = common::setup_with_config(config).await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    let client = reqwest::Client::new();
+    let mut elapsed = vec![];
+    // A signup for a new email, then a signup resetting the creation of the unverified account
+    for _ in 0..2 {
+        let start = std::time::Instant::now();
+        let response = client
+            .post(format!("{}/accounts/signup", &test_state.server_url))
+            .json(&signup_body)
+            .send()
+            .await
+            .unwrap();
+        elapsed.push(start.elapsed());
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+
+    for v in &elapsed {
+        assert!(*v >= signup_min_duration, "{elapsed:?}");
+    }
+    assert!(
+        elapsed[0].abs_diff(elapsed[1]) < std::time::Duration::from_secs(1),
+        "{elapsed:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_get_current_account() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let verify_account_response = client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&json!({
+            "email": signup_body.email,
+            "secret": test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+            "issueToken": true,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    let access_token = verify_account_response["accessToken"]["accessToken"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let response = client
+        .get(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    let account_response = response.json::<AccountResponse>().await.unwrap();
+    assert_eq!(
+        account_response.email.as_str(),
+        signup_body.email.to_lowercase()
+    );
+    assert!(account_response.verified);
+
+    // The ETag allows a conditional profile update
+    let response = client
+        .patch(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&access_token)
+        .header("If-Match", etag)
+        .json(&json!({ "displayName": "Jane Doe" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Missing and malformed access tokens
+    let response = client
+        .get(format!("{}/accounts/me", &test_state.server_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    for token in ["not-a-token", "soko__unknown"] {
+        let response = client
+            .get(format!("{}/accounts/me", &test_state.server_url))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{token}");
+    }
+}
@@ -0,0 +1,428 @@
+use fake::{Fake, Faker};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::json;
+use soko::{
+    Config,
+    database::{connect_options, pool_options},
+    newtypes::Opaque,
+    routes::tokens::MAX_LIFETIME,
+};
+
+use crate::common::{TestCreateAccessTokenBody, TestSignupBody, TestVerifyAccountBody};
+
+mod common;
+
+const ADMIN_API_KEY: &str = "integration-tests-admin-api-key-0123456789";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TestWhoamiResponse {
+    pub account_id: uuid::Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TestAccountMergeResponse {
+    pub account_id: uuid::Uuid,
+    pub moved_access_tokens: u64,
+}
+
+/// ID of the account signed up with `signup_body`
+async fn account_id_of(pool: &sqlx::PgPool, signup_body: &TestSignupBody) -> uuid::Uuid {
+    sqlx::query_scalar(r#"SELECT "id" FROM "account" WHERE "email" = $1"#)
+        .bind(signup_body.email.to_lowercase())
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_account_token_lifetime_policy() {
+    let config = Config {
+        admin_api_key: Some(Opaque::new(ADMIN_API_KEY.to_string())),
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let account_id: uuid::Uuid =
+        sqlx::query_scalar(r#"SELECT "id" FROM "account" WHERE "email" = $1"#)
+            .bind(&signup_body.email)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+    // 7 days for the account, the global maximum is 90 days
+    let policy_url = format!(
+        "{}/admin/accounts/{account_id}/token-lifetime-policy",
+        &test_state.server_url
+    );
+    let response = client
+        .put(&policy_url)
+        .header("x-api-key", ADMIN_API_KEY)
+        .json(&json!({ "maxTokenLifetimeSecs": 7 * 24 * 60 * 60 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&TestCreateAccessTokenBody {
+            email: signup_body.email.clone(),
+            password: signup_body.password.clone(),
+            name: "contractor".to_string(),
+            lifetime: 30 * 24 * 60 * 60,
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&TestCreateAccessTokenBody {
+            email: signup_body.email.clone(),
+            password: signup_body.password.clone(),
+            name: "contractor".to_string(),
+            lifetime: 7 * 24 * 60 * 60,
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    // Removing the account policy restores the global maximum
+    let response = client
+        .put(&policy_url)
+        .header("x-api-key", ADMIN_API_KEY)
+        .json(&json!({ "maxTokenLifetimeSecs": null }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&TestCreateAccessTokenBody {
+            email: signup_body.email.clone(),
+            password: signup_body.password.clone(),
+            name: "employee".to_string(),
+            lifetime: MAX_LIFETIME,
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn test_admin_routes_require_the_admin_api_key() {
+    let config = Config {
+        admin_api_key: Some(Opaque::new(ADMIN_API_KEY.to_string())),
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config).await.unwrap();
+
+    let policy_url = format!(
+        "{}/admin/accounts/{}/token-lifetime-policy",
+        &test_state.server_url,
+        uuid::Uuid::new_v4()
+    );
+    let client = reqwest::Client::new();
+    for headers in [vec![], vec![("x-api-key", "wrong-admin-api-key")]] {
+        let mut request = client
+            .put(&policy_url)
+            .json(&json!({ "maxTokenLifetimeSecs": 3600 }));
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        let response = request.send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let response = client
+        .put(&policy_url)
+        .header("x-api-key", ADMIN_API_KEY)
+        .json(&json!({ "maxTokenLifetimeSecs": 3600 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_admin_routes_are_disabled_without_admin_api_key() {
+    let test_state = common::setup().await.unwrap();
+
+    let response = reqwest::Client::new()
+        .put(format!(
+            "{}/admin/accounts/{}/token-lifetime-policy",
+            &test_state.server_url,
+            uuid::Uuid::new_v4()
+        ))
+        .header("x-api-key", ADMIN_API_KEY)
+        .json(&json!({ "maxTokenLifetimeSecs": 3600 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_merge_accounts() {
+    let config = Config {
+        admin_api_key: Some(Opaque::new(ADMIN_API_KEY.to_string())),
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let (source_signup_body, source_access_token) =
+        common::signup_with_access_token(&test_state).await;
+    let (target_signup_body, _) = common::signup_with_access_token(&test_state).await;
+    let source_account_id = account_id_of(&pool, &source_signup_body).await;
+    let target_account_id = account_id_of(&pool, &target_signup_body).await;
+
+    let merge_url = format!("{}/admin/accounts/merge", &test_state.server_url);
+    let client = reqwest::Client::new();
+    let response = client
+        .post(&merge_url)
+        .header("x-api-key", ADMIN_API_KEY)
+        .json(&json!({
+            "sourceAccountId": source_account_id,
+            "targetAccountId": source_account_id,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client
+        .post(&merge_url)
+        .header("x-api-key", ADMIN_API_KEY)
+        .json(&json!({
+            "sourceAccountId": uuid::Uuid::new_v4(),
+            "targetAccountId": target_account_id,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = client
+        .post(&merge_url)
+        .header("x-api-key", ADMIN_API_KEY)
+        .json(&json!({
+            "sourceAccountId": source_account_id,
+            "targetAccountId": target_account_id,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let account_merge = response.json::<TestAccountMergeResponse>().await.unwrap();
+    assert_eq!(account_merge.account_id, target_account_id);
+    assert_eq!(account_merge.moved_access_tokens, 1);
+
+    // The target account owns the access tokens of both accounts, the moved one is renamed as both are named `default`
+    let target_access_token_names: Vec<String> = sqlx::query_scalar(
+        r#"SELECT "name" FROM "access_token" WHERE "account_id" = $1 ORDER BY "name""#,
+    )
+    .bind(target_account_id)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(target_access_token_names.len(), 2);
+    assert_eq!(target_access_token_names[0], "default");
+    assert!(
+        target_access_token_names[1].starts_with("default-"),
+        "{target_access_token_names:?}"
+    );
+    let source_accounts: i64 =
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM "account" WHERE "id" = $1"#)
+            .bind(source_account_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(source_accounts, 0);
+
+    let whoami = client
+        .post(format!("{}/tokens/whoami", &test_state.server_url))
+        .bearer_auth(&source_access_token.access_token)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json::<TestWhoamiResponse>()
+        .await
+        .unwrap();
+    assert_eq!(whoami.account_id, target_account_id);
+}
+
+#[tokio::test]
+async fn test_merge_accounts_enforces_the_active_token_limit() {
+    let config = Config {
+        admin_api_key: Some(Opaque::new(ADMIN_API_KEY.to_string())),
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    // The source account reaches the limit of active access tokens, the target account has one
+    let (source_signup_body, _) = common::signup_with_access_token(&test_state).await;
+    let (target_signup_body, _) = common::signup_with_access_token(&test_state).await;
+    let client = reqwest::Client::new();
+    for name in ["laptop", "phone"] {
+        client
+            .post(format!("{}/tokens", &test_state.server_url))
+            .json(&TestCreateAccessTokenBody {
+                email: source_signup_body.email.clone(),
+                password: source_signup_body.password.clone(),
+                name: name.to_string(),
+                lifetime: 3600,
+            })
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+    let source_account_id = account_id_of(&pool, &source_signup_body).await;
+    let target_account_id = account_id_of(&pool, &target_signup_body).await;
+
+    let merge_body = json!({
+        "sourceAccountId": source_account_id,
+        "targetAccountId": target_account_id,
+    });
+    let response = client
+        .post(format!("{}/admin/accounts/merge", &test_state.server_url))
+        .header("x-api-key", ADMIN_API_KEY)
+        .json(&merge_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(
+        body["fields"]["sourceAccountId"][0]["code"],
+        json!("too-many-tokens")
+    );
+
+    // Nothing has been merged
+    let source_access_tokens: i64 =
+        sqlx::query_scalar(r#"SELECT COUNT(*) FROM "access_token" WHERE "account_id" = $1"#)
+            .bind(source_account_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(source_access_tokens, 3);
+
+    // Revoked access tokens do not count towards the limit
+    sqlx::query(
+        r#"UPDATE "access_token" SET "revoked_at" = NOW() WHERE "account_id" = $1 AND "name" = 'phone'"#,
+    )
+    .bind(source_account_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let response = client
+        .post(format!("{}/admin/accounts/merge", &test_state.server_url))
+        .header("x-api-key", ADMIN_API_KEY)
+        .json(&merge_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_admin_routes_accept_larger_bodies() {
+    let config = Config {
+        admin_api_key: Some(Opaque::new(ADMIN_API_KEY.to_string())),
+        max_body_bytes: 1024,
+        admin_max_body_bytes: 1024 * 1024,
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+
+    // Unknown fields are ignored, the padding only inflates the body
+    let padding = "a".repeat(64 * 1024);
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&json!({
+            "email": signup_body.email,
+            "password": signup_body.password,
+            "padding": padding,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let response = client
+        .post(format!("{}/admin/accounts/merge", &test_state.server_url))
+        .header("x-api-key", ADMIN_API_KEY)
+        .json(&json!({
+            "sourceAccountId": uuid::Uuid::new_v4(),
+            "targetAccountId": uuid::Uuid::new_v4(),
+            "padding": padding,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // The admin limit still applies
+    let response = client
+        .post(format!("{}/admin/accounts/merge", &test_state.server_url))
+        .header("x-api-key", ADMIN_API_KEY)
+        .json(&json!({
+            "sourceAccountId": uuid::Uuid::new_v4(),
+            "targetAccountId": uuid::Uuid::new_v4(),
+            "padding": "a".repeat(2 * 1024 * 1024),
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
@@ -0,0 +1,96 @@
+use fake::{Fake, Faker};
+use reqwest::StatusCode;
+use soko::{
+    Config,
+    routes::{
+        accounts::AccountResponse,
+        tokens::{MAX_LIFETIME, MAX_NAME_LENGTH},
+    },
+};
+
+use crate::common::{TestCreateAccessTokenBody, TestSignupBody};
+
+mod common;
+
+#[tokio::test]
+async fn test_signup_on_autoverify_domain() {
+    let test_state = common::setup_with_config(Config {
+        verification_autoverify_domains: vec!["example.test".to_string()],
+        ..common::test_config()
+    })
+    .await
+    .unwrap();
+
+    let signup_body = TestSignupBody {
+        email: format!("{}@example.test", uuid::Uuid::new_v4()),
+        ..Faker.fake()
+    };
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    response.json::<AccountResponse>().await.unwrap();
+
+    // No verification email is sent
+    assert!(
+        test_state
+            .mailing_service
+            .get_verification_secret(&signup_body.email)
+            .unwrap()
+            .is_none()
+    );
+
+    let response = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&TestCreateAccessTokenBody {
+            email: signup_body.email.clone(),
+            password: signup_body.password.clone(),
+            name: (1..MAX_NAME_LENGTH).fake(),
+            lifetime: (1..MAX_LIFETIME).fake(),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn test_signup_on_other_domain_requires_verification() {
+    let test_state = common::setup_with_config(Config {
+        verification_autoverify_domains: vec!["example.test".to_string()],
+        ..common::test_config()
+    })
+    .await
+    .unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let response = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&TestCreateAccessTokenBody {
+            email: signup_body.email.clone(),
+            password: signup_body.password.clone(),
+            name: (1..MAX_NAME_LENGTH).fake(),
+            lifetime: (1..MAX_LIFETIME).fake(),
+        })
+        .send()
+        .await
+        .unwrap();
+    // An unverified account fails as an unknown email, see `POST /tokens`
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,39 @@
+use axum::http::StatusCode;
+use soko::{Config, routes::system::GetHealthcheckResponse};
+
+mod common;
+
+#[tokio::test]
+async fn test_base_path() {
+    let test_state = common::setup_with_config(Config {
+        base_path: Some("/auth".to_string()),
+        ..common::test_config()
+    })
+    .await
+    .unwrap();
+
+    let response = reqwest::get(format!("{}/auth/health", &test_state.server_url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.json::<GetHealthcheckResponse>().await.unwrap().ok);
+
+    let response = reqwest::get(format!("{}/health", &test_state.server_url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = reqwest::get(format!("{}/auth/unknown-route", &test_state.server_url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Accounts routes live under the prefix as well, an empty body is rejected by the signup handler
+    let response = reqwest::Client::new()
+        .post(format!("{}/auth/accounts/signup", &test_state.server_url))
+        .json(&serde_json::json!({}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,190 @@
+use fake::{Fake, Faker};
+use reqwest::StatusCode;
+use serde_json::json;
+
+use crate::common::{TestSignupBody, TestState};
+
+mod common;
+
+async fn change_password(
+    test_state: &TestState,
+    client: &reqwest::Client,
+    access_token: &str,
+    body: serde_json::Value,
+) -> reqwest::Response {
+    client
+        .put(format!("{}/accounts/password", &test_state.server_url))
+        .bearer_auth(access_token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_change_password_revokes_the_tokens() {
+    let test_state = common::setup().await.unwrap();
+    let client = reqwest::Client::new();
+    let signup_body = common::signup_verified_account(&test_state).await;
+    let access_token = common::login(&test_state, &signup_body.email, &signup_body.password)
+        .await
+        .unwrap()
+        .access_token;
+    let other_access_token = common::login(&test_state, &signup_body.email, &signup_body.password)
+        .await
+        .unwrap()
+        .access_token;
+
+    let new_password = Faker.fake::<TestSignupBody>().password;
+    let response = change_password(
+        &test_state,
+        &client,
+        &access_token,
+        json!({ "currentPassword": signup_body.password, "newPassword": new_password }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    for access_token in [&access_token, &other_access_token] {
+        assert_eq!(
+            common::verify_status(&test_state, access_token).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+    assert!(
+        common::login(&test_state, &signup_body.email, &signup_body.password)
+            .await
+            .is_none()
+    );
+    let access_token = common::login(&test_state, &signup_body.email, &new_password)
+        .await
+        .unwrap()
+        .access_token;
+    assert_eq!(
+        common::verify_status(&test_state, &access_token).await,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn test_change_password_without_revoking_the_tokens() {
+    let test_state = common::setup().await.unwrap();
+    let client = reqwest::Client::new();
+    let signup_body = common::signup_verified_account(&test_state).await;
+    let access_token = common::login(&test_state, &signup_body.email, &signup_body.password)
+        .await
+        .unwrap()
+        .access_token;
+
+    let new_password = Faker.fake::<TestSignupBody>().password;
+    let response = change_password(
+        &test_state,
+        &client,
+        &access_token,
+        json!({
+            "currentPassword": signup_body.password,
+            "newPassword": new_password,
+            "revokeTokens": false
+        }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    assert_eq!(
+        common::verify_status(&test_state, &access_token).await,
+        StatusCode::OK
+    );
+    assert!(
+        common::login(&test_state, &signup_body.email, &signup_body.password)
+            .await
+            .is_none()
+    );
+    assert!(
+        common::login(&test_state, &signup_body.email, &new_password)
+            .await
+            .is_some()
+    );
+}
+
+#[tokio::test]
+async fn test_change_password_with_wrong_current_password_must_fail() {
+    let test_state = common::setup().await.unwrap();
+    let client = reqwest::Client::new();
+    let signup_body = common::signup_verified_account(&test_state).await;
+    let access_token = common::login(&test_state, &signup_body.email, &signup_body.password)
+        .await
+        .unwrap()
+        .access_token;
+
+    let response = change_password(
+        &test_state,
+        &client,
+        &access_token,
+        json!({
+            "currentPassword": Faker.fake::<TestSignupBody>().password,
+            "newPassword": Faker.fake::<TestSignupBody>().password
+        }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Nothing has been changed nor revoked
+    assert_eq!(
+        common::verify_status(&test_state, &access_token).await,
+        StatusCode::OK
+    );
+    assert!(
+        common::login(&test_state, &signup_body.email, &signup_body.password)
+            .await
+            .is_some()
+    );
+}
+
+#[tokio::test]
+async fn test_change_password_to_the_same_password_must_fail() {
+    let test_state = common::setup().await.unwrap();
+    let client = reqwest::Client::new();
+    let signup_body = common::signup_verified_account(&test_state).await;
+    let access_token = common::login(&test_state, &signup_body.email, &signup_body.password)
+        .await
+        .unwrap()
+        .access_token;
+
+    let response = change_password(
+        &test_state,
+        &client,
+        &access_token,
+        json!({
+            "currentPassword": signup_body.password,
+            "newPassword": signup_body.password
+        }),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(
+        body["fields"]["newPassword"][0]["code"],
+        json!("same-password"),
+        "{body}"
+    );
+    assert_eq!(
+        common::verify_status(&test_state, &access_token).await,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn test_change_password_requires_an_access_token() {
+    let test_state = common::setup().await.unwrap();
+
+    let response = reqwest::Client::new()
+        .put(format!("{}/accounts/password", &test_state.server_url))
+        .json(&json!({
+            "currentPassword": Faker.fake::<TestSignupBody>().password,
+            "newPassword": Faker.fake::<TestSignupBody>().password
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
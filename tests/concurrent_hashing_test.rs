@@ -0,0 +1,78 @@
+use fake::{Fake, Faker};
+use reqwest::StatusCode;
+use soko::{
+    Config,
+    database::{connect_options, pool_options},
+};
+
+use crate::common::TestSignupBody;
+
+mod common;
+
+#[tokio::test]
+async fn test_concurrent_signups_are_queued_with_a_single_hashing_slot() {
+    let config = Config {
+        max_concurrent_hashes: 1,
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let signup_bodies: Vec<TestSignupBody> = (0..2).map(|_| Faker.fake()).collect();
+    let signups: Vec<_> = signup_bodies
+        .iter()
+        .map(|signup_body| {
+            let client = client.clone();
+            let server_url = test_state.server_url.clone();
+            let signup_body = signup_body.clone();
+            tokio::spawn(async move {
+                client
+                    .post(format!("{server_url}/accounts/signup"))
+                    .json(&signup_body)
+                    .send()
+                    .await
+                    .unwrap()
+                    .status()
+            })
+        })
+        .collect();
+
+    // The second signup waits for the hashing slot instead of being rejected
+    for signup in signups {
+        assert_eq!(signup.await.unwrap(), StatusCode::CREATED);
+    }
+
+    // Every queued signup has created its account with a single active verification ticket
+    let emails: Vec<String> = signup_bodies
+        .iter()
+        .map(|signup_body| signup_body.email.to_lowercase())
+        .collect();
+    let active_tickets: Vec<i64> = sqlx::query_scalar(
+        r#"
+        SELECT COUNT("ticket"."id")
+        FROM "account"
+        LEFT JOIN "account_verification_ticket" AS "ticket"
+            ON "ticket"."account_id" = "account"."id" AND "ticket"."status" = 'active'
+        WHERE "account"."email" = ANY($1)
+        GROUP BY "account"."id"
+    "#,
+    )
+    .bind(&emails)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(active_tickets, vec![1, 1]);
+    for signup_body in &signup_bodies {
+        assert!(
+            test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .is_some()
+        );
+    }
+}
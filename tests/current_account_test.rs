@@ -0,0 +1,83 @@
+use reqwest::StatusCode;
+use serde_json::json;
+use soko::database::{connect_options, pool_options};
+
+mod common;
+
+#[tokio::test]
+async fn test_current_account_from_bearer_token() {
+    let config = common::test_config();
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+    let client = reqwest::Client::new();
+
+    // Valid access token
+    let (signup_body, access_token) = common::signup_with_access_token(&test_state).await;
+    let response = client
+        .get(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&access_token.access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["email"], json!(signup_body.email.to_lowercase()));
+
+    // Revoked access token
+    let (_, revoked_token) = common::signup_with_access_token(&test_state).await;
+    sqlx::query(r#"UPDATE "access_token" SET "revoked_at" = NOW() WHERE "id" = $1"#)
+        .bind(revoked_token.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // Expired access token, beyond the clock skew tolerance
+    let (_, expired_token) = common::signup_with_access_token(&test_state).await;
+    sqlx::query(
+        r#"UPDATE "access_token" SET "expires_at" = NOW() - INTERVAL '1 day' WHERE "id" = $1"#,
+    )
+    .bind(expired_token.id)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // Active access token of a deactivated account
+    let (_, deactivated_account_token) = common::signup_with_access_token(&test_state).await;
+    sqlx::query(
+        r#"UPDATE "account" SET "deactivated_at" = NOW() FROM "access_token" WHERE "access_token"."account_id" = "account"."id" AND "access_token"."id" = $1"#,
+    )
+    .bind(deactivated_account_token.id)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let invalid_token_challenge = r#"Bearer error="invalid_token", error_description="The access token is malformed, unknown or revoked""#;
+    let expired_token_challenge =
+        r#"Bearer error="invalid_token", error_description="The access token has expired""#;
+    for (token, expected_challenge) in [
+        ("garbage".to_string(), invalid_token_challenge),
+        ("soko__unknown".to_string(), invalid_token_challenge),
+        (revoked_token.access_token, invalid_token_challenge),
+        (expired_token.access_token, expired_token_challenge),
+        (
+            deactivated_account_token.access_token,
+            invalid_token_challenge,
+        ),
+    ] {
+        let response = client
+            .get(format!("{}/accounts/me", &test_state.server_url))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{token}");
+        assert_eq!(
+            response.headers()["www-authenticate"],
+            expected_challenge,
+            "{token}"
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use soko::{
+    Config,
+    database::{application_name, connect_options, pool_options},
+};
+
+mod common;
+
+#[tokio::test]
+async fn test_long_statement_is_aborted() {
+    let config = Config {
+        database_statement_timeout: Duration::from_millis(50),
+        ..common::test_config()
+    };
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let result = sqlx::query("SELECT pg_sleep(1)").execute(&pool).await;
+
+    let err = result.unwrap_err();
+    let database_error = err.as_database_error().unwrap();
+    // `query_canceled` error code
+    assert_eq!(database_error.code().as_deref(), Some("57014"));
+
+    sqlx::query("SELECT pg_sleep(0.01)")
+        .execute(&pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_application_name() {
+    let config = common::test_config();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let current_application_name: String =
+        sqlx::query_scalar("SELECT current_setting('application_name')")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+    assert_eq!(current_application_name, application_name(&config));
+    assert!(current_application_name.starts_with("soko@"));
+    assert!(current_application_name.ends_with("/integration-tests"));
+}
+
+#[tokio::test]
+async fn test_connections_use_utc() {
+    let config = common::test_config();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let time_zone: String = sqlx::query_scalar("SELECT current_setting('TimeZone')")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+    assert_eq!(time_zone, "UTC");
+}
@@ -0,0 +1,71 @@
+use fake::{Fake, Faker};
+use reqwest::StatusCode;
+use soko::Config;
+
+use crate::common::{TestSignupBody, TestVerifyAccountBody};
+
+mod common;
+
+#[tokio::test]
+async fn test_signup_returns_verification_secret_in_dev_mode() {
+    let test_state = common::setup_with_config(Config {
+        dev_return_verification_secret: true,
+        ..common::test_config()
+    })
+    .await
+    .unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let signup_response = response.json::<serde_json::Value>().await.unwrap();
+    let secret = signup_response["verificationSecret"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert_eq!(
+        Some(secret.clone()),
+        test_state
+            .mailing_service
+            .get_verification_secret(&signup_body.email)
+            .unwrap()
+    );
+
+    client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret,
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_signup_does_not_return_verification_secret_by_default() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let signup_response = response.json::<serde_json::Value>().await.unwrap();
+    assert!(signup_response.get("verificationSecret").is_none());
+    assert!(signup_response.get("email").is_some());
+}
@@ -0,0 +1,85 @@
+use metrics_util::{
+    CompositeKey,
+    debugging::{DebugValue, DebuggingRecorder},
+};
+use reqwest::StatusCode;
+use soko::observability::{
+    EMAIL_SEND_COUNTER, EMAIL_SEND_DURATION_HISTOGRAM, FAILURE_OUTCOME, OUTCOME_LABEL,
+    SUCCESS_OUTCOME,
+};
+
+use crate::common::TestSignupBody;
+use fake::{Fake, Faker};
+
+mod common;
+
+/// Value of the metric `name` with the given outcome, the histograms are given as their number of samples
+fn metric_value(snapshot: &[(CompositeKey, DebugValue)], name: &str, outcome: &str) -> u64 {
+    snapshot
+        .iter()
+        .find_map(|(key, value)| {
+            let key = key.key();
+            if key.name() != name
+                || !key
+                    .labels()
+                    .any(|l| l.key() == OUTCOME_LABEL && l.value() == outcome)
+            {
+                return None;
+            }
+            match value {
+                DebugValue::Counter(v) => Some(*v),
+                DebugValue::Histogram(samples) => Some(samples.len() as u64),
+                _ => None,
+            }
+        })
+        .unwrap_or(0)
+}
+
+// The recorder is global, this file must contain a single test
+#[tokio::test]
+async fn test_email_send_metrics() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    recorder.install().unwrap();
+
+    let test_state = common::setup().await.unwrap();
+    let client = reqwest::Client::new();
+
+    // A failed email does not fail the signup
+    test_state.mailing_service.set_failing(true);
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&Faker.fake::<TestSignupBody>())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    test_state.mailing_service.set_failing(false);
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&Faker.fake::<TestSignupBody>())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    let snapshot: Vec<(CompositeKey, DebugValue)> = snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .map(|(key, _, _, value)| (key, value))
+        .collect();
+    for outcome in [FAILURE_OUTCOME, SUCCESS_OUTCOME] {
+        assert_eq!(
+            metric_value(&snapshot, EMAIL_SEND_COUNTER, outcome),
+            1,
+            "{outcome}"
+        );
+        assert_eq!(
+            metric_value(&snapshot, EMAIL_SEND_DURATION_HISTOGRAM, outcome),
+            1,
+            "{outcome}"
+        );
+    }
+}
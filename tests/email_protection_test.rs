@@ -0,0 +1,216 @@
+use fake::{Fake, Faker};
+use reqwest::StatusCode;
+use soko::{
+    Config,
+    database::{connect_options, pool_options, run_migrations},
+    newtypes::{Email, Opaque},
+    routes::accounts::{AccountRepository, EmailProtection, PostgresAccountRepository},
+};
+
+use crate::common::{TestSignupBody, TestVerifyAccountBody};
+
+mod common;
+
+#[tokio::test]
+async fn test_signup_and_login_with_protected_emails() {
+    let email_protection_key = Opaque::new(rand::random());
+    let email_protection = EmailProtection::new(&email_protection_key);
+    let config = Config {
+        email_protection_key: Some(email_protection_key),
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config.clone()).await.unwrap();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    let uppercase_signup_body = TestSignupBody {
+        email: signup_body.email.to_uppercase(),
+        ..signup_body.clone()
+    };
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // The unverified account is found by its lookup value regardless of the case of the email
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&uppercase_signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let email_lookup = email_protection.lookup(&Email::new(&signup_body.email).unwrap());
+    let stored_email = sqlx::query_scalar::<_, String>(
+        r#"SELECT "email" FROM "account" WHERE "email_lookup" = $1"#,
+    )
+    .bind(&email_lookup)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert!(
+        stored_email.starts_with("enc:v1:"),
+        "email is not encrypted: {stored_email}"
+    );
+    assert!(
+        !stored_email
+            .to_lowercase()
+            .contains(&signup_body.email.to_lowercase())
+    );
+    let accounts_with_email = sqlx::query_scalar::<_, i64>(
+        r#"SELECT COUNT(*) FROM "account" WHERE lower("email") = lower($1)"#,
+    )
+    .bind(&signup_body.email)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(accounts_with_email, 0);
+
+    client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // A verified email can not be registered again
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&uppercase_signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["fields"]["email"][0]["code"], "existing-email");
+
+    let response = client
+        .post(format!("{}/accounts/login", &test_state.server_url))
+        .json(&uppercase_signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let access_token = response.json::<serde_json::Value>().await.unwrap()["accessToken"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    // The email is decrypted when the account is read
+    let response = client
+        .get(format!("{}/accounts/me", &test_state.server_url))
+        .bearer_auth(&access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(
+        body["email"].as_str().unwrap().to_lowercase(),
+        signup_body.email.to_lowercase()
+    );
+}
+
+#[tokio::test]
+async fn test_protect_plaintext_emails() {
+    let config = common::test_config();
+    let pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap())
+        .await
+        .unwrap();
+    // The backfill encrypts every plaintext email, it runs against its own database so that the other tests keep theirs
+    let database = format!("soko_protect_emails_{}", uuid::Uuid::new_v4().simple());
+    sqlx::query(&format!(r#"CREATE DATABASE "{database}""#))
+        .execute(&pool)
+        .await
+        .unwrap();
+    let protected_pool = pool_options(&config)
+        .connect_with(connect_options(&config).unwrap().database(&database))
+        .await
+        .unwrap();
+    run_migrations(&protected_pool).await.unwrap();
+
+    let emails = ["jane@example.com", "John@Example.com", "jim@example.com"];
+    for email in emails {
+        sqlx::query(r#"INSERT INTO "account" ("email", "password_hash") VALUES ($1, 'hash')"#)
+            .bind(email)
+            .execute(&protected_pool)
+            .await
+            .unwrap();
+    }
+
+    let email_protection = EmailProtection::new(&Opaque::new(rand::random()));
+    let account_repository = PostgresAccountRepository::from(protected_pool.clone())
+        .with_email_protection(Some(email_protection));
+    assert_eq!(
+        account_repository.count_plaintext_emails().await.unwrap(),
+        3
+    );
+    // Without the backfill, the plaintext accounts can not be found
+    assert!(
+        account_repository
+            .get_account_by_email(&Email::new(emails[0]).unwrap())
+            .await
+            .is_err()
+    );
+
+    let protected = account_repository
+        .protect_plaintext_emails(2)
+        .await
+        .unwrap();
+    assert_eq!(protected, 3);
+    assert_eq!(
+        account_repository.count_plaintext_emails().await.unwrap(),
+        0
+    );
+    let stored_emails = sqlx::query_scalar::<_, String>(r#"SELECT "email" FROM "account""#)
+        .fetch_all(&protected_pool)
+        .await
+        .unwrap();
+    assert!(
+        stored_emails
+            .iter()
+            .all(|email| email.starts_with("enc:v1:")),
+        "{stored_emails:?}"
+    );
+    for email in emails {
+        let account = account_repository
+            .get_account_by_email(&Email::new(&email.to_lowercase()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(account.email.as_str(), email);
+    }
+    assert_eq!(
+        account_repository
+            .protect_plaintext_emails(2)
+            .await
+            .unwrap(),
+        0
+    );
+
+    protected_pool.close().await;
+    sqlx::query(&format!(r#"DROP DATABASE "{database}" WITH (FORCE)"#))
+        .execute(&pool)
+        .await
+        .unwrap();
+}
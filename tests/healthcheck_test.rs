@@ -1,5 +1,5 @@
 use axum::http::StatusCode;
-use soko::routes::GetHealthcheckResponse;
+use soko::routes::system::{GetDeepHealthcheckResponse, GetHealthcheckResponse};
 
 mod common;
 
@@ -13,3 +13,64 @@ async fn test_healthcheck() {
     assert_eq!(response.status(), StatusCode::OK);
     assert!(response.json::<GetHealthcheckResponse>().await.unwrap().ok);
 }
+
+#[tokio::test]
+async fn test_readiness() {
+    let test_state = common::setup().await.unwrap();
+
+    // The first health check runs in the background right after startup
+    let mut response = None;
+    for _ in 0..20 {
+        let r = reqwest::get(format!("{}/health/ready", &test_state.server_url))
+            .await
+            .unwrap();
+        if r.status() == StatusCode::OK {
+            response = Some(r);
+            break;
+        }
+        assert_eq!(r.status(), StatusCode::SERVICE_UNAVAILABLE);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    let response = response.expect("application never became ready");
+    assert!(response.json::<GetHealthcheckResponse>().await.unwrap().ok);
+}
+
+#[tokio::test]
+async fn test_deep_healthcheck() {
+    let test_state = common::setup().await.unwrap();
+
+    let response = reqwest::get(format!("{}/health/deep", &test_state.server_url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let health = response.json::<GetDeepHealthcheckResponse>().await.unwrap();
+    assert!(health.ok);
+    for subsystem in ["database", "mailing", "sms", "hashing"] {
+        assert!(health.subsystems[subsystem].ok, "{subsystem}");
+    }
+    assert!(health.subsystems["database"].required);
+    assert!(health.subsystems["mailing"].required);
+
+    test_state.mailing_service.set_unreachable(true);
+
+    let response = reqwest::get(format!("{}/health/deep", &test_state.server_url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let health = response.json::<GetDeepHealthcheckResponse>().await.unwrap();
+    assert!(!health.ok);
+    assert!(!health.subsystems["mailing"].ok);
+    assert!(health.subsystems["database"].ok);
+}
+
+#[tokio::test]
+async fn test_database_readiness() {
+    let test_state = common::setup().await.unwrap();
+
+    // Unlike `/health/ready`, the database is queried by the request itself
+    let response = reqwest::get(format!("{}/ready", &test_state.server_url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.json::<GetHealthcheckResponse>().await.unwrap().ok);
+}
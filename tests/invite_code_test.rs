@@ -0,0 +1,103 @@
+use fake::{Fake, Faker};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::json;
+use soko::{Config, newtypes::Opaque};
+
+use crate::common::TestSignupBody;
+
+mod common;
+
+const ADMIN_API_KEY: &str = "integration-tests-admin-api-key-0123456789";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TestInviteCodeResponse {
+    pub code: String,
+}
+
+#[tokio::test]
+async fn test_single_use_invite_code() {
+    let config = Config {
+        signup_require_invite: true,
+        admin_api_key: Some(Opaque::new(ADMIN_API_KEY.to_string())),
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config).await.unwrap();
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/admin/invite-codes", &test_state.server_url))
+        .header("x-api-key", ADMIN_API_KEY)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let invite_code = response
+        .json::<TestInviteCodeResponse>()
+        .await
+        .unwrap()
+        .code;
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&json!({
+            "email": &signup_body.email,
+            "password": &signup_body.password,
+            "inviteCode": &invite_code,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    // The invite code has been used by the first signup
+    let other_signup_body = Faker.fake::<TestSignupBody>();
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&json!({
+            "email": &other_signup_body.email,
+            "password": &other_signup_body.password,
+            "inviteCode": &invite_code,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let error_response = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(
+        error_response["fields"]["inviteCode"][0]["code"],
+        "invalid-invite-code"
+    );
+}
+
+#[tokio::test]
+async fn test_unknown_invite_code() {
+    let config = Config {
+        signup_require_invite: true,
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config).await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    let response = reqwest::Client::new()
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&json!({
+            "email": &signup_body.email,
+            "password": &signup_body.password,
+            "inviteCode": "unknown-invite-code",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
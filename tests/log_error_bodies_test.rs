@@ -0,0 +1,48 @@
+use reqwest::StatusCode;
+use serde_json::json;
+use soko::Config;
+
+use crate::common::test_support::CapturedLogs;
+
+mod common;
+
+#[tokio::test]
+async fn test_error_body_is_logged() {
+    // The subscriber is installed before the setup so that it captures the logs of the server
+    let logs = CapturedLogs::default();
+    tracing_subscriber::fmt()
+        .with_ansi(false)
+        .with_writer(logs.clone())
+        .init();
+
+    let test_state = common::setup_with_config(Config {
+        log_error_bodies: true,
+        ..common::test_config()
+    })
+    .await
+    .unwrap();
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&json!({ "email": "john.doe@example.com", "secret": "" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["fields"]["secret"][0]["code"], "length");
+
+    let logs = logs.contents();
+    let logged_body = logs
+        .lines()
+        .find(|line| line.contains("400 Bad Request response body"))
+        .expect("error body has not been logged");
+    assert!(
+        logged_body.contains(r#""message":"secret: length""#),
+        "{logged_body}"
+    );
+    assert!(
+        logged_body.contains(r#""secret":"******""#),
+        "{logged_body}"
+    );
+}
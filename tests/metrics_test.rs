@@ -0,0 +1,97 @@
+use metrics_util::{
+    CompositeKey,
+    debugging::{DebugValue, DebuggingRecorder},
+};
+use reqwest::StatusCode;
+use soko::observability::{
+    FAILED_PASSWORD_COUNTER, FAILURE_OUTCOME, OUTCOME_LABEL, TOKEN_CREATION_COUNTER,
+};
+
+use crate::common::{TestCreateAccessTokenBody, TestSignupBody, TestVerifyAccountBody};
+use fake::{Fake, Faker};
+
+mod common;
+
+fn counter_value(
+    snapshot: &[(CompositeKey, DebugValue)],
+    name: &str,
+    outcome: Option<&str>,
+) -> u64 {
+    snapshot
+        .iter()
+        .find_map(|(key, value)| {
+            let key = key.key();
+            let matches_outcome = outcome.is_none_or(|outcome| {
+                key.labels()
+                    .any(|l| l.key() == OUTCOME_LABEL && l.value() == outcome)
+            });
+            match value {
+                DebugValue::Counter(v) if key.name() == name && matches_outcome => Some(*v),
+                _ => None,
+            }
+        })
+        .unwrap_or(0)
+}
+
+// The recorder is global, this file must contain a single test
+#[tokio::test]
+async fn test_failed_password_metrics() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    recorder.install().unwrap();
+
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let response = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&TestCreateAccessTokenBody {
+            email: signup_body.email.clone(),
+            password: Faker.fake::<TestSignupBody>().password,
+            name: "my-token".to_string(),
+            lifetime: 3_600,
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Counters are reset when taking a snapshot
+    let snapshot: Vec<(CompositeKey, DebugValue)> = snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .map(|(key, _, _, value)| (key, value))
+        .collect();
+    assert_eq!(counter_value(&snapshot, FAILED_PASSWORD_COUNTER, None), 1);
+    assert_eq!(
+        counter_value(&snapshot, TOKEN_CREATION_COUNTER, Some(FAILURE_OUTCOME)),
+        1
+    );
+}
@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+mod common;
+
+#[tokio::test]
+async fn test_migrate_command_runs_the_migrations_and_exits() {
+    let config = common::test_config();
+
+    // The server would keep running, the command must exit on its own
+    let output = tokio::time::timeout(
+        Duration::from_secs(60),
+        tokio::process::Command::new(env!("CARGO_BIN_EXE_soko"))
+            .arg("migrate")
+            // Only the database URL is required, no `.env` file is loaded from the temporary directory
+            .env_clear()
+            .env("DATABASE_URL", config.database_url.extract_inner())
+            .current_dir(std::env::temp_dir())
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .expect("migrate command did not exit")
+    .unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("Successfully ran migrations"), "{stdout}");
+    assert!(!stdout.contains("TCP listener"), "{stdout}");
+}
+
+#[tokio::test]
+async fn test_unexpected_arguments_are_rejected() {
+    let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_soko"))
+        .arg("serve-everything")
+        .kill_on_drop(true)
+        .output()
+        .await
+        .unwrap();
+    assert!(!output.status.success());
+}
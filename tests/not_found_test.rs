@@ -1,4 +1,5 @@
 use axum::http::StatusCode;
+use soko::routes::ErrorResponse;
 mod common;
 
 #[tokio::test]
@@ -9,5 +10,41 @@ async fn test_not_found() {
         .await
         .unwrap();
     assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let error_response = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(error_response.code, "not_found");
+    assert_eq!(error_response.message, "Not found");
+}
+
+#[tokio::test]
+async fn test_not_found_json() {
+    let test_state = common::setup().await.unwrap();
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/unknown-route", &test_state.server_url))
+        .header("accept", "application/json")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        response.headers()["content-type"].to_str().unwrap(),
+        "application/json"
+    );
+    let error_response = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(error_response.code, "not_found");
+    assert_eq!(error_response.message, "Not found");
+}
+
+#[tokio::test]
+async fn test_not_found_plain_text() {
+    let test_state = common::setup().await.unwrap();
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/unknown-route", &test_state.server_url))
+        .header("accept", "text/plain")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
     assert_eq!(response.text().await.unwrap(), "Not found");
 }
@@ -0,0 +1,157 @@
+use fake::{Fake, Faker};
+use reqwest::StatusCode;
+use serde_json::json;
+use soko::{
+    Config,
+    routes::{
+        CharacterClass, MAX_PASSWORD_LENGTH, MIN_PASSWORD_LENGTH, PasswordPolicy,
+        accounts::{CharacterClassRequirement, PasswordPolicyResponse},
+    },
+};
+
+use crate::common::TestSignupBody;
+
+mod common;
+
+#[tokio::test]
+async fn test_password_policy_matches_enforced_policy() {
+    let password_policy = PasswordPolicy {
+        requirements: vec![(CharacterClass::Lowercase, 1), (CharacterClass::Digit, 3)],
+    };
+    let test_state = common::setup_with_config(Config {
+        password_policy: password_policy.clone(),
+        ..common::test_config()
+    })
+    .await
+    .unwrap();
+
+    let response = reqwest::Client::new()
+        .get(format!(
+            "{}/accounts/password-policy",
+            &test_state.server_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let policy = response.json::<PasswordPolicyResponse>().await.unwrap();
+    assert_eq!(policy.min_length, MIN_PASSWORD_LENGTH);
+    assert_eq!(policy.max_length, MAX_PASSWORD_LENGTH);
+    let expected_requirements: Vec<_> = password_policy
+        .requirements
+        .into_iter()
+        .map(|(class, min_count)| CharacterClassRequirement { class, min_count })
+        .collect();
+    assert_eq!(policy.requirements, expected_requirements);
+    assert!(!policy.prehash);
+}
+
+#[tokio::test]
+async fn test_password_policy_with_prehash() {
+    let test_state = common::setup_with_config(Config {
+        password_prehash: true,
+        ..common::test_config()
+    })
+    .await
+    .unwrap();
+
+    let response = reqwest::Client::new()
+        .get(format!(
+            "{}/accounts/password-policy",
+            &test_state.server_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let policy = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(policy["prehash"], true);
+    assert_eq!(policy["requirements"][0]["class"], "uppercase");
+    assert_eq!(policy["requirements"][0]["minCount"], 2);
+}
+
+#[tokio::test]
+async fn test_password_is_checked_against_the_configured_policy() {
+    let test_state = common::setup_with_config(Config {
+        password_policy: "lowercase:1,digit:1".parse().unwrap(),
+        ..common::test_config()
+    })
+    .await
+    .unwrap();
+    let client = reqwest::Client::new();
+
+    // The default policy is not enforced anymore
+    let mut signup_body = Faker.fake::<TestSignupBody>();
+    signup_body.password = "abcdefghi1".to_string();
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    let mut signup_body = Faker.fake::<TestSignupBody>();
+    signup_body.password = "AB12{&ABCDEF".to_string();
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(
+        body["fields"]["password"][0]["code"],
+        json!("invalid-password"),
+        "{body}"
+    );
+}
+
+#[tokio::test]
+async fn test_password_containing_the_email_is_rejected() {
+    let test_state = common::setup_with_config(Config {
+        password_reject_email: true,
+        ..common::test_config()
+    })
+    .await
+    .unwrap();
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(format!(
+            "{}/accounts/password-policy",
+            &test_state.server_url
+        ))
+        .send()
+        .await
+        .unwrap();
+    let policy = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(policy["rejectEmail"], true);
+
+    for password in ["AB12{&john", "AB12{&JOHN", "john@x.comAB12{&"] {
+        let response = client
+            .post(format!("{}/accounts/signup", &test_state.server_url))
+            .json(&json!({ "email": "john@x.com", "password": password }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{password}");
+        let body = response.json::<serde_json::Value>().await.unwrap();
+        assert_eq!(
+            body["fields"]["password"][0]["code"],
+            json!("invalid-password"),
+            "{body}"
+        );
+    }
+
+    let mut signup_body = Faker.fake::<TestSignupBody>();
+    signup_body.password = "AB12{&abcdef".to_string();
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+}
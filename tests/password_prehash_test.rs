@@ -0,0 +1,114 @@
+use crate::common::{TestCreateAccessTokenBody, TestSignupBody, TestVerifyAccountBody};
+use fake::{Fake, Faker};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use soko::{
+    Config,
+    routes::tokens::{MAX_LIFETIME, MAX_NAME_LENGTH},
+};
+
+mod common;
+
+fn prehash(password: &str) -> String {
+    Sha256::digest(password.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_prehashed_signup_and_access_token_creation() {
+    let test_state = common::setup_with_config(Config {
+        password_prehash: true,
+        ..common::test_config()
+    })
+    .await
+    .unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    let prehashed_password = prehash(&signup_body.password);
+
+    let client = reqwest::Client::new();
+
+    // Plaintext password is rejected
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&TestSignupBody {
+            email: signup_body.email.clone(),
+            password: prehashed_password.clone(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let response = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&TestCreateAccessTokenBody {
+            email: signup_body.email.clone(),
+            password: prehashed_password.clone(),
+            name: (1..MAX_NAME_LENGTH).fake(),
+            lifetime: (1..MAX_LIFETIME).fake(),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    // Digest of another password is rejected
+    let response = client
+        .post(format!("{}/tokens", &test_state.server_url))
+        .json(&TestCreateAccessTokenBody {
+            email: signup_body.email.clone(),
+            password: prehash(&Faker.fake::<TestSignupBody>().password),
+            name: (1..MAX_NAME_LENGTH).fake(),
+            lifetime: (1..MAX_LIFETIME).fake(),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_prehashed_signup_rejected_when_disabled() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&TestSignupBody {
+            email: signup_body.email.clone(),
+            password: prehash(&signup_body.password),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,296 @@
+use fake::{Fake, Faker};
+use reqwest::StatusCode;
+use serde_json::json;
+use soko::{Config, newtypes::Opaque};
+
+use crate::common::{TestSignupBody, TestState};
+
+mod common;
+
+const ADMIN_API_KEY: &str = "integration-tests-admin-api-key-0123456789";
+
+async fn request_password_reset(
+    test_state: &TestState,
+    client: &reqwest::Client,
+    email: &str,
+) -> StatusCode {
+    client
+        .post(format!(
+            "{}/accounts/request-password-reset",
+            &test_state.server_url
+        ))
+        .json(&json!({ "email": email }))
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+async fn reset_password(
+    test_state: &TestState,
+    client: &reqwest::Client,
+    email: &str,
+    code: &str,
+    new_password: &str,
+) -> reqwest::Response {
+    client
+        .post(format!(
+            "{}/accounts/reset-password",
+            &test_state.server_url
+        ))
+        .json(&json!({ "email": email, "code": code, "newPassword": new_password }))
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_reset_password() {
+    let test_state = common::setup().await.unwrap();
+    let client = reqwest::Client::new();
+    let signup_body = common::signup_verified_account(&test_state).await;
+
+    assert_eq!(
+        request_password_reset(&test_state, &client, &signup_body.email).await,
+        StatusCode::OK
+    );
+    let code = test_state
+        .mailing_service
+        .get_password_reset_code(&signup_body.email)
+        .unwrap()
+        .unwrap();
+
+    let new_password = Faker.fake::<TestSignupBody>().password;
+    let response = reset_password(
+        &test_state,
+        &client,
+        &signup_body.email,
+        &code,
+        &new_password,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    assert!(
+        common::login(&test_state, &signup_body.email, &signup_body.password)
+            .await
+            .is_none()
+    );
+    assert!(
+        common::login(&test_state, &signup_body.email, &new_password)
+            .await
+            .is_some()
+    );
+
+    // The code can only be used once
+    let response = reset_password(
+        &test_state,
+        &client,
+        &signup_body.email,
+        &code,
+        &signup_body.password,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_reset_password_revokes_the_tokens() {
+    let test_state = common::setup().await.unwrap();
+    let client = reqwest::Client::new();
+    let (signup_body, issued_access_token) = common::signup_with_access_token(&test_state).await;
+
+    assert_eq!(
+        request_password_reset(&test_state, &client, &signup_body.email).await,
+        StatusCode::OK
+    );
+    let code = test_state
+        .mailing_service
+        .get_password_reset_code(&signup_body.email)
+        .unwrap()
+        .unwrap();
+    let response = reset_password(
+        &test_state,
+        &client,
+        &signup_body.email,
+        &code,
+        &Faker.fake::<TestSignupBody>().password,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The token issued before the reset is revoked
+    assert_eq!(
+        common::verify_status(&test_state, &issued_access_token.access_token).await,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[tokio::test]
+async fn test_request_password_reset_does_not_disclose_the_email_registration() {
+    let test_state = common::setup().await.unwrap();
+    let client = reqwest::Client::new();
+
+    let unknown_email = Faker.fake::<TestSignupBody>().email;
+    assert_eq!(
+        request_password_reset(&test_state, &client, &unknown_email).await,
+        StatusCode::OK
+    );
+    assert!(
+        test_state
+            .mailing_service
+            .get_password_reset_code(&unknown_email)
+            .unwrap()
+            .is_none()
+    );
+
+    // Unverified accounts are not sent any code
+    let signup_body = Faker.fake::<TestSignupBody>();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(
+        request_password_reset(&test_state, &client, &signup_body.email).await,
+        StatusCode::OK
+    );
+    assert!(
+        test_state
+            .mailing_service
+            .get_password_reset_code(&signup_body.email)
+            .unwrap()
+            .is_none()
+    );
+
+    // An unknown email fails as a wrong code
+    let response = reset_password(
+        &test_state,
+        &client,
+        &unknown_email,
+        "unknown-code",
+        &signup_body.password,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(
+        body["fields"]["code"][0]["code"],
+        json!("code-validity"),
+        "{body}"
+    );
+}
+
+#[tokio::test]
+async fn test_reset_password_with_wrong_code_must_fail() {
+    let test_state = common::setup().await.unwrap();
+    let client = reqwest::Client::new();
+    let signup_body = common::signup_verified_account(&test_state).await;
+
+    assert_eq!(
+        request_password_reset(&test_state, &client, &signup_body.email).await,
+        StatusCode::OK
+    );
+    let code = test_state
+        .mailing_service
+        .get_password_reset_code(&signup_body.email)
+        .unwrap()
+        .unwrap();
+
+    // The verification secret of the account is not a reset code
+    let verification_secret = test_state
+        .mailing_service
+        .get_verification_secret(&signup_body.email)
+        .unwrap()
+        .unwrap();
+    let new_password = Faker.fake::<TestSignupBody>().password;
+    for wrong_code in [verification_secret, "wrong-code".to_string()] {
+        let response = reset_password(
+            &test_state,
+            &client,
+            &signup_body.email,
+            &wrong_code,
+            &new_password,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = response.json::<serde_json::Value>().await.unwrap();
+        assert_eq!(
+            body["fields"]["code"][0]["code"],
+            json!("code-validity"),
+            "{body}"
+        );
+    }
+    assert!(
+        common::login(&test_state, &signup_body.email, &signup_body.password)
+            .await
+            .is_some()
+    );
+
+    // The wrong codes do not invalidate the right one
+    let response = reset_password(
+        &test_state,
+        &client,
+        &signup_body.email,
+        &code,
+        &new_password,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_reset_password_with_expired_ticket_must_fail() {
+    let config = Config {
+        test_clock: true,
+        admin_api_key: Some(Opaque::new(ADMIN_API_KEY.to_string())),
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+    let signup_body = common::signup_verified_account(&test_state).await;
+
+    assert_eq!(
+        request_password_reset(&test_state, &client, &signup_body.email).await,
+        StatusCode::OK
+    );
+    let code = test_state
+        .mailing_service
+        .get_password_reset_code(&signup_body.email)
+        .unwrap()
+        .unwrap();
+
+    // Password reset tickets expire after 15 minutes
+    let response = client
+        .post(format!("{}/admin/advance-clock", &test_state.server_url))
+        .header("x-api-key", ADMIN_API_KEY)
+        .json(&json!({ "seconds": 16 * 60 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = reset_password(
+        &test_state,
+        &client,
+        &signup_body.email,
+        &code,
+        &Faker.fake::<TestSignupBody>().password,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(
+        body["fields"]["code"][0]["code"],
+        json!("code-expired"),
+        "{body}"
+    );
+    assert!(
+        common::login(&test_state, &signup_body.email, &signup_body.password)
+            .await
+            .is_some()
+    );
+}
@@ -0,0 +1,112 @@
+use axum::http::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+use soko::{
+    Config,
+    newtypes::Opaque,
+    observability::{HTTP_REQUESTS_COUNTER, SIGNUP_COUNTER},
+};
+
+use crate::common::TestSignupBody;
+use fake::{Fake, Faker};
+
+mod common;
+
+const ADMIN_API_KEY: &str = "integration-tests-admin-api-key-0123456789";
+
+/// Lines of the rendered metrics for the metric `name`, samples of histograms included
+fn metric_lines<'a>(metrics: &'a str, name: &str) -> Vec<&'a str> {
+    metrics
+        .lines()
+        .filter(|line| line.starts_with(name))
+        .collect()
+}
+
+// The recorder is global, this file must contain a single test
+#[tokio::test]
+async fn test_prometheus_metrics() {
+    let test_state = common::setup_with_config(Config {
+        metrics_enabled: true,
+        admin_api_key: Some(Opaque::new(ADMIN_API_KEY.to_string())),
+        ..common::test_config()
+    })
+    .await
+    .unwrap();
+    let client = reqwest::Client::new();
+
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&Faker.fake::<TestSignupBody>())
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    // Scraped twice so that a recorded scrape would show up
+    for _ in 0..2 {
+        let response = client
+            .get(format!("{}/metrics", &test_state.server_url))
+            .header("x-api-key", ADMIN_API_KEY)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(
+            response.headers()[CONTENT_TYPE]
+                .to_str()
+                .unwrap()
+                .starts_with("text/plain")
+        );
+    }
+    let metrics = client
+        .get(format!("{}/metrics", &test_state.server_url))
+        .header("x-api-key", ADMIN_API_KEY)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    let requests = metric_lines(&metrics, HTTP_REQUESTS_COUNTER);
+    assert!(
+        requests
+            .iter()
+            .any(|line| line.contains(r#"path="/accounts/signup""#)
+                && line.contains(r#"method="POST""#)
+                && line.contains(r#"status="201""#)),
+        "{metrics}"
+    );
+    assert!(
+        !metrics.contains(r#"path="/metrics""#),
+        "the scrapes must not be recorded: {metrics}"
+    );
+    assert!(
+        metric_lines(&metrics, "soko_http_request_duration_seconds_bucket")
+            .iter()
+            .any(|line| line.contains(r#"path="/accounts/signup""#)),
+        "{metrics}"
+    );
+    assert!(
+        !metric_lines(&metrics, SIGNUP_COUNTER).is_empty(),
+        "{metrics}"
+    );
+
+    // The route requires the admin API key
+    for key in [None, Some("wrong-admin-api-key")] {
+        let mut request = client.get(format!("{}/metrics", &test_state.server_url));
+        if let Some(key) = key {
+            request = request.header("x-api-key", key);
+        }
+        let response = request.send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{key:?}");
+    }
+
+    // The route is not served if the metrics are disabled
+    let test_state = common::setup().await.unwrap();
+    let response = client
+        .get(format!("{}/metrics", &test_state.server_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
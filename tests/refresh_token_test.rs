@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use serde_json::json;
+use soko::routes::tokens::{REFRESH_TOKEN_PREFIX, SESSION_ACCESS_TOKEN_LIFETIME};
+
+use crate::common::{TestSessionCreatedResponse, TestState};
+
+mod common;
+
+async fn setup_with_refresh_tokens() -> TestState {
+    let mut config = common::test_config();
+    config.refresh_token_lifetime = Some(Duration::from_secs(24 * 60 * 60));
+    common::setup_with_config(config).await.unwrap()
+}
+
+async fn refresh(
+    test_state: &TestState,
+    client: &reqwest::Client,
+    refresh_token: &str,
+) -> reqwest::Response {
+    client
+        .post(format!("{}/tokens/refresh", &test_state.server_url))
+        .json(&json!({ "refreshToken": refresh_token }))
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_refresh_tokens_are_disabled_by_default() {
+    let test_state = common::setup().await.unwrap();
+    let client = reqwest::Client::new();
+
+    let signup_body = common::signup_verified_account(&test_state).await;
+    let session = common::login(&test_state, &signup_body.email, &signup_body.password)
+        .await
+        .unwrap();
+    assert!(session.refresh_token.is_none());
+    assert!(session.refresh_token_expires_at.is_none());
+
+    let response = refresh(
+        &test_state,
+        &client,
+        &format!("{REFRESH_TOKEN_PREFIX}unknown"),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_refresh_access_token() {
+    let test_state = setup_with_refresh_tokens().await;
+    let client = reqwest::Client::new();
+
+    let signup_body = common::signup_verified_account(&test_state).await;
+    let session = common::login(&test_state, &signup_body.email, &signup_body.password)
+        .await
+        .unwrap();
+    assert_eq!(
+        session.lifetime_secs,
+        i64::from(SESSION_ACCESS_TOKEN_LIFETIME)
+    );
+    assert!(session.refresh_token_expires_at.is_some());
+    let refresh_token = session.refresh_token.unwrap();
+    assert!(refresh_token.starts_with(REFRESH_TOKEN_PREFIX));
+
+    // A refresh token is not an access token
+    assert_eq!(
+        common::verify_status(&test_state, &refresh_token).await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let response = refresh(&test_state, &client, &refresh_token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let refreshed = response.json::<TestSessionCreatedResponse>().await.unwrap();
+    assert_eq!(
+        refreshed.lifetime_secs,
+        i64::from(SESSION_ACCESS_TOKEN_LIFETIME)
+    );
+    assert_ne!(refreshed.access_token, session.access_token);
+    assert_eq!(
+        common::verify_status(&test_state, &refreshed.access_token).await,
+        StatusCode::OK
+    );
+
+    // Unknown and malformed refresh tokens are rejected
+    for refresh_token in [
+        format!("{REFRESH_TOKEN_PREFIX}unknown"),
+        session.access_token.clone(),
+    ] {
+        let response = refresh(&test_state, &client, &refresh_token).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[tokio::test]
+async fn test_refresh_rotates_the_refresh_token() {
+    let test_state = setup_with_refresh_tokens().await;
+    let client = reqwest::Client::new();
+
+    let signup_body = common::signup_verified_account(&test_state).await;
+    let session = common::login(&test_state, &signup_body.email, &signup_body.password)
+        .await
+        .unwrap();
+    let mut refresh_token = session.refresh_token.unwrap();
+    let mut access_token = session.access_token;
+
+    // The active access tokens do not pile up, each refresh revokes the previous access token
+    for _ in 0..5 {
+        let response = refresh(&test_state, &client, &refresh_token).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let refreshed = response.json::<TestSessionCreatedResponse>().await.unwrap();
+        let rotated_refresh_token = refreshed.refresh_token.unwrap();
+        assert_ne!(rotated_refresh_token, refresh_token);
+
+        assert_eq!(
+            common::verify_status(&test_state, &access_token).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            common::verify_status(&test_state, &refreshed.access_token).await,
+            StatusCode::OK
+        );
+
+        refresh_token = rotated_refresh_token;
+        access_token = refreshed.access_token;
+    }
+}
+
+#[tokio::test]
+async fn test_reused_refresh_token_revokes_its_family() {
+    let test_state = setup_with_refresh_tokens().await;
+    let client = reqwest::Client::new();
+
+    let signup_body = common::signup_verified_account(&test_state).await;
+    let session = common::login(&test_state, &signup_body.email, &signup_body.password)
+        .await
+        .unwrap();
+    let stolen_refresh_token = session.refresh_token.unwrap();
+    // The families of the other sessions are left untouched
+    let other_signup_body = common::signup_verified_account(&test_state).await;
+    let other_session = common::login(
+        &test_state,
+        &other_signup_body.email,
+        &other_signup_body.password,
+    )
+    .await
+    .unwrap();
+
+    let response = refresh(&test_state, &client, &stolen_refresh_token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let refreshed = response.json::<TestSessionCreatedResponse>().await.unwrap();
+    let rotated_refresh_token = refreshed.refresh_token.unwrap();
+
+    // The rotated refresh token is presented again, the family is revoked
+    let response = refresh(&test_state, &client, &stolen_refresh_token).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    assert_eq!(
+        common::verify_status(&test_state, &refreshed.access_token).await,
+        StatusCode::UNAUTHORIZED
+    );
+    let response = refresh(&test_state, &client, &rotated_refresh_token).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    assert_eq!(
+        common::verify_status(&test_state, &other_session.access_token).await,
+        StatusCode::OK
+    );
+    let response = refresh(&test_state, &client, &other_session.refresh_token.unwrap()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
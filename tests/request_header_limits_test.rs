@@ -0,0 +1,47 @@
+use axum::http::StatusCode;
+use soko::{Config, routes::ErrorResponse};
+mod common;
+
+#[tokio::test]
+async fn test_oversized_request_headers_are_rejected() {
+    let config = Config {
+        max_request_header_bytes: 1024,
+        max_request_headers: 16,
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(format!("{}/health", &test_state.server_url))
+        .header("x-padding", "a".repeat(512))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = client
+        .get(format!("{}/health", &test_state.server_url))
+        .header("x-padding", "a".repeat(2048))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        response.status(),
+        StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+    );
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().code,
+        "request_header_fields_too_large"
+    );
+
+    let mut request = client.get(format!("{}/health", &test_state.server_url));
+    for i in 0..16 {
+        request = request.header(format!("x-header-{i}"), "value");
+    }
+    let response = request.send().await.unwrap();
+    assert_eq!(
+        response.status(),
+        StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+    );
+}
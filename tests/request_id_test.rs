@@ -0,0 +1,55 @@
+use axum::http::{HeaderName, StatusCode};
+use soko::{Config, observability::DEFAULT_REQUEST_ID_HEADER};
+mod common;
+
+#[tokio::test]
+async fn test_request_id_with_custom_header() {
+    let config = Config {
+        request_id_header: Some(HeaderName::from_static("x-correlation-id")),
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config).await.unwrap();
+    let client = reqwest::Client::new();
+
+    // A given request ID is echoed
+    let response = client
+        .get(format!("{}/health", &test_state.server_url))
+        .header("X-Correlation-ID", "correlation-id-123")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["x-correlation-id"].to_str().unwrap(),
+        "correlation-id-123"
+    );
+    assert!(response.headers().get(DEFAULT_REQUEST_ID_HEADER).is_none());
+
+    // A request ID is generated if absent
+    let response = client
+        .get(format!("{}/health", &test_state.server_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let request_id = response.headers()["x-correlation-id"].to_str().unwrap();
+    assert!(uuid::Uuid::parse_str(request_id).is_ok(), "{request_id}");
+}
+
+#[tokio::test]
+async fn test_request_id_disabled() {
+    let config = Config {
+        request_id_header: None,
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config).await.unwrap();
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/health", &test_state.server_url))
+        .header(DEFAULT_REQUEST_ID_HEADER, "request-id-123")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get(DEFAULT_REQUEST_ID_HEADER).is_none());
+}
@@ -0,0 +1,53 @@
+use std::time::{Duration, Instant};
+
+use soko::{
+    newtypes::Email,
+    third_party::{MailingService, SmtpConfig, SmtpMailingService},
+};
+use tokio::net::TcpListener;
+
+fn smtp_config(port: u16) -> SmtpConfig {
+    SmtpConfig {
+        host: "127.0.0.1".to_string(),
+        port,
+        credentials: None,
+        from: "Soko <no-reply@example.com>".parse().unwrap(),
+        templates_dir: None,
+    }
+}
+
+#[tokio::test]
+async fn test_send_email_fails_if_the_relay_is_unreachable() {
+    // The port is released before the send, nothing listens on it
+    let port = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mailing_service = SmtpMailingService::try_from(&smtp_config(port)).unwrap();
+
+    let err = mailing_service
+        .send_email(&Email::new("jane@example.com").unwrap(), "secret")
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("failed to send email"), "{err:?}");
+    assert!(mailing_service.health_check().await.is_err());
+}
+
+#[tokio::test]
+async fn test_send_email_times_out_if_the_relay_does_not_answer() {
+    // The connection is accepted by the OS but the relay never sends its greeting
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let mailing_service = SmtpMailingService::try_from(&smtp_config(port)).unwrap();
+
+    let started_at = Instant::now();
+    let result = mailing_service
+        .send_email(&Email::new("jane@example.com").unwrap(), "secret")
+        .await;
+    assert!(result.is_err());
+    // Below the timeout of the requests, see `REQUEST_TIMEOUT`
+    assert!(started_at.elapsed() < Duration::from_secs(10));
+    drop(listener);
+}
@@ -0,0 +1,210 @@
+use fake::{Fake, Faker};
+use reqwest::StatusCode;
+use serde_json::json;
+use soko::{Config, newtypes::Opaque};
+use std::time::Duration;
+
+use crate::common::{TestSignupBody, TestVerifyAccountBody};
+
+mod common;
+
+const ADMIN_API_KEY: &str = "integration-tests-admin-api-key-0123456789";
+
+#[tokio::test]
+async fn test_verification_ticket_expires_once_the_clock_is_advanced() {
+    let config = Config {
+        test_clock: true,
+        admin_api_key: Some(Opaque::new(ADMIN_API_KEY.to_string())),
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config).await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Verification tickets expire after 15 minutes by default
+    let response = client
+        .post(format!("{}/admin/advance-clock", &test_state.server_url))
+        .header("x-api-key", ADMIN_API_KEY)
+        .json(&json!({ "seconds": 16 * 60 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // A wrong secret does not disclose the expiration
+    let response = client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: "wrong-secret".to_string(),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(
+        body["fields"]["secret"][0]["code"],
+        json!("secret-validity"),
+        "{body}"
+    );
+
+    let response = client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(
+        body["fields"]["secret"][0]["code"],
+        json!("secret-expired"),
+        "{body}"
+    );
+}
+
+#[tokio::test]
+async fn test_verification_ticket_lifetime_is_configurable() {
+    let config = Config {
+        test_clock: true,
+        admin_api_key: Some(Opaque::new(ADMIN_API_KEY.to_string())),
+        verification_ticket_lifetime: Duration::from_secs(60 * 60),
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config).await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Beyond the default lifetime but within the configured one
+    let response = client
+        .post(format!("{}/admin/advance-clock", &test_state.server_url))
+        .header("x-api-key", ADMIN_API_KEY)
+        .json(&json!({ "seconds": 16 * 60 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&TestVerifyAccountBody {
+            email: signup_body.email.clone(),
+            secret: test_state
+                .mailing_service
+                .get_verification_secret(&signup_body.email)
+                .unwrap()
+                .unwrap(),
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_access_token_expires_once_the_clock_is_advanced() {
+    let config = Config {
+        test_clock: true,
+        admin_api_key: Some(Opaque::new(ADMIN_API_KEY.to_string())),
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config).await.unwrap();
+    let (_, access_token) = common::signup_with_access_token(&test_state).await;
+    let client = reqwest::Client::new();
+
+    // Access tokens issued at verification live for 7 days by default
+    let response = client
+        .post(format!("{}/admin/advance-clock", &test_state.server_url))
+        .header("x-api-key", ADMIN_API_KEY)
+        .json(&json!({ "seconds": 6 * 24 * 60 * 60 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = client
+        .get(format!("{}/tokens", &test_state.server_url))
+        .bearer_auth(&access_token.access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let tokens = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(tokens[0]["expired"], json!(false));
+    let response = client
+        .get(format!("{}/tokens/verify", &test_state.server_url))
+        .bearer_auth(&access_token.access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let expires_in_secs = response.json::<serde_json::Value>().await.unwrap()["expiresInSecs"]
+        .as_i64()
+        .unwrap();
+    assert!(expires_in_secs <= 24 * 60 * 60, "{expires_in_secs}");
+
+    let response = client
+        .post(format!("{}/admin/advance-clock", &test_state.server_url))
+        .header("x-api-key", ADMIN_API_KEY)
+        .json(&json!({ "seconds": 2 * 24 * 60 * 60 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = client
+        .get(format!("{}/tokens/verify", &test_state.server_url))
+        .bearer_auth(&access_token.access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        response.headers()["www-authenticate"],
+        r#"Bearer error="invalid_token", error_description="The access token has expired""#
+    );
+}
+
+#[tokio::test]
+async fn test_clock_can_not_be_advanced_without_test_clock() {
+    let config = Config {
+        admin_api_key: Some(Opaque::new(ADMIN_API_KEY.to_string())),
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config).await.unwrap();
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/admin/advance-clock", &test_state.server_url))
+        .header("x-api-key", ADMIN_API_KEY)
+        .json(&json!({ "seconds": 16 * 60 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,46 @@
+use axum::http::StatusCode;
+use soko::{Config, routes::system::GetHealthcheckResponse, server::TlsConfig};
+mod common;
+
+#[tokio::test]
+async fn test_health_over_https() {
+    let certified_key =
+        rcgen::generate_simple_self_signed(vec!["localhost".to_string(), "127.0.0.1".to_string()])
+            .unwrap();
+    let directory = std::env::temp_dir().join(format!("soko-tls-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let cert_path = directory.join("cert.pem");
+    let key_path = directory.join("key.pem");
+    std::fs::write(&cert_path, certified_key.cert.pem()).unwrap();
+    std::fs::write(&key_path, certified_key.signing_key.serialize_pem()).unwrap();
+
+    let config = Config {
+        tls: Some(TlsConfig {
+            cert_path,
+            key_path,
+        }),
+        ..common::test_config()
+    };
+    let test_state = common::setup_with_config(config).await.unwrap();
+    assert!(test_state.server_url.starts_with("https://"));
+
+    let client = reqwest::Client::builder()
+        .add_root_certificate(
+            reqwest::Certificate::from_pem(certified_key.cert.pem().as_bytes()).unwrap(),
+        )
+        .build()
+        .unwrap();
+    let response = client
+        .get(format!("{}/health", &test_state.server_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.json::<GetHealthcheckResponse>().await.unwrap().ok);
+
+    // Plain HTTP is not served
+    let plain_url = test_state.server_url.replacen("https://", "http://", 1);
+    assert!(reqwest::get(format!("{plain_url}/health")).await.is_err());
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
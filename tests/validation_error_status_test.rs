@@ -0,0 +1,95 @@
+use axum::http::StatusCode;
+use fake::{Fake, Faker};
+use serde_json::json;
+use soko::Config;
+
+use crate::common::TestSignupBody;
+
+mod common;
+
+#[tokio::test]
+async fn test_validation_error_status_defaults_to_bad_request() {
+    let test_state = common::setup().await.unwrap();
+
+    let client = reqwest::Client::new();
+
+    // Password does not satisfy the password policy
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&TestSignupBody {
+            password: "password".to_string(),
+            ..Faker.fake()
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Malformed JSON body
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .header("content-type", "application/json")
+        .body("{\"email\":")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_validation_error_status_unprocessable_entity() {
+    let test_state = common::setup_with_config(Config {
+        validation_error_status: StatusCode::UNPROCESSABLE_ENTITY,
+        ..common::test_config()
+    })
+    .await
+    .unwrap();
+
+    let client = reqwest::Client::new();
+
+    // Password does not satisfy the password policy
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&TestSignupBody {
+            password: "password".to_string(),
+            ..Faker.fake()
+        })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Empty verification secret fails the body validation
+    let response = client
+        .post(format!("{}/accounts/verify-email", &test_state.server_url))
+        .json(&json!({
+            "email": Faker.fake::<TestSignupBody>().email,
+            "secret": "",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // A valid body rejected by the handler keeps its status
+    let signup_body = common::signup_verified_account(&test_state).await;
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body["fields"]["email"][0]["code"], "existing-email");
+
+    // Malformed JSON body
+    let response = client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .header("content-type", "application/json")
+        .body("{\"email\":")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,126 @@
+use std::{net::SocketAddr, time::Duration};
+
+use axum::{Router, body::Bytes, http::HeaderMap, routing::post};
+use chrono::Utc;
+use fake::{Fake, Faker};
+use reqwest::StatusCode;
+use soko::{
+    newtypes::Opaque,
+    third_party::{
+        AccountVerifiedEvent, HttpWebhookNotifier, WEBHOOK_SIGNATURE_HEADER,
+        WEBHOOK_TIMESTAMP_HEADER, WebhookConfig, WebhookNotifier, sign_webhook_body,
+    },
+};
+use tokio::sync::mpsc;
+
+use crate::common::{TestSignupBody, TestVerifyAccountBody};
+
+mod common;
+
+#[tokio::test]
+async fn test_verification_webhook_is_notified_once() {
+    let test_state = common::setup().await.unwrap();
+
+    let signup_body = Faker.fake::<TestSignupBody>();
+
+    let client = reqwest::Client::new();
+    client
+        .post(format!("{}/accounts/signup", &test_state.server_url))
+        .json(&signup_body)
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert!(
+        test_state
+            .webhook_notifier
+            .get_account_verified_events(&signup_body.email)
+            .await
+            .is_empty()
+    );
+
+    let verify_account_body = TestVerifyAccountBody {
+        email: signup_body.email.clone(),
+        secret: test_state
+            .mailing_service
+            .get_verification_secret(&signup_body.email)
+            .unwrap()
+            .unwrap(),
+    };
+    // The second verification is a replay, the account is already verified
+    for _ in 0..2 {
+        let response = client
+            .post(format!("{}/accounts/verify-email", &test_state.server_url))
+            .json(&verify_account_body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    // The webhook is notified in the background
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let events = test_state
+        .webhook_notifier
+        .get_account_verified_events(&signup_body.email)
+        .await;
+    assert_eq!(events.len(), 1, "{events:?}");
+    assert_eq!(events[0].email.as_str(), signup_body.email.to_lowercase());
+}
+
+#[tokio::test]
+async fn test_http_webhook_notifier_signs_the_body() {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let webhook_router = Router::new().route(
+        "/webhooks/soko",
+        post(move |headers: HeaderMap, body: Bytes| async move {
+            sender.send((headers, body)).unwrap();
+            StatusCode::NO_CONTENT
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+        .await
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, webhook_router).await.unwrap() });
+
+    let secret = Opaque::new("webhook-secret".to_string());
+    let notifier = HttpWebhookNotifier::from(Some(WebhookConfig {
+        url: format!("http://{addr}/webhooks/soko").parse().unwrap(),
+        secret: secret.clone(),
+    }));
+    let event = AccountVerifiedEvent {
+        account_id: uuid::Uuid::new_v4(),
+        email: Faker.fake(),
+        verified_at: Utc::now(),
+    };
+    notifier.notify_account_verified(&event).await.unwrap();
+
+    let (headers, body) = receiver.recv().await.unwrap();
+    // The timestamp of the delivery is signed along the body
+    let timestamp = headers[WEBHOOK_TIMESTAMP_HEADER]
+        .to_str()
+        .unwrap()
+        .parse::<i64>()
+        .unwrap();
+    assert!((Utc::now().timestamp() - timestamp).abs() <= 5);
+    assert_eq!(
+        headers[WEBHOOK_SIGNATURE_HEADER].to_str().unwrap(),
+        sign_webhook_body(timestamp, &body, &secret).unwrap()
+    );
+    assert_ne!(
+        headers[WEBHOOK_SIGNATURE_HEADER].to_str().unwrap(),
+        sign_webhook_body(timestamp - 1, &body, &secret).unwrap()
+    );
+    assert_eq!(
+        serde_json::from_slice::<AccountVerifiedEvent>(&body).unwrap(),
+        event
+    );
+
+    // Without configured webhook, nothing is sent
+    HttpWebhookNotifier::from(None)
+        .notify_account_verified(&event)
+        .await
+        .unwrap();
+}